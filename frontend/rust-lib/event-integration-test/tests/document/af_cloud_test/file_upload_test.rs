@@ -23,7 +23,7 @@ async fn af_cloud_upload_big_file_test() {
   let (file_path, upload_data) = generate_file_with_bytes_len(15 * 1024 * 1024).await;
   let (created_upload, rx) = test
     .storage_manager
-    .storage_service
+    .storage_service()
     .create_upload(&workspace_id, parent_dir, &file_path, false)
     .await
     .unwrap();
@@ -91,7 +91,7 @@ async fn af_cloud_upload_6_files_test() {
       .0;
     let (created_upload, rx) = test
       .storage_manager
-      .storage_service
+      .storage_service()
       .create_upload(&workspace_id, "temp_test", &file_path, false)
       .await
       .unwrap();
@@ -106,16 +106,19 @@ async fn af_cloud_upload_6_files_test() {
 
   for mut receiver in receivers {
     let cloned_uploads = uploads.clone();
-    let state = test.storage_manager.get_file_state(&receiver.file_id).await;
+    let state = test
+      .storage_manager
+      .get_file_state("temp_test", &receiver.file_id)
+      .await;
     let handle = tokio::spawn(async move {
-      if let Some(FileUploadState::Finished { file_id }) = state {
+      if let Some(FileUploadState::Finished { file_id, .. }) = state {
         cloned_uploads
           .lock()
           .await
           .retain(|upload| upload.file_id != file_id);
       }
       while let Ok(value) = receiver.recv().await {
-        if let FileUploadState::Finished { file_id } = value {
+        if let FileUploadState::Finished { file_id, .. } = value {
           cloned_uploads
             .lock()
             .await
@@ -10,6 +10,7 @@ use collab_entity::CollabType;
 use flowy_search_pub::cloud::SearchCloudService;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
@@ -36,7 +37,10 @@ use flowy_folder_pub::cloud::{
 };
 use flowy_folder_pub::entities::PublishPayload;
 use flowy_server_pub::af_cloud_config::AFCloudConfiguration;
-use flowy_storage_pub::cloud::{ObjectIdentity, ObjectValue, StorageCloudService};
+use flowy_storage_pub::cloud::{
+  ObjectIdentity, ObjectPage, ObjectRange, ObjectValue, PartChecksum, StorageCapabilities,
+  StorageCloudService,
+};
 use flowy_storage_pub::storage::{CompletedPartRequest, CreateUploadResponse, UploadPartResponse};
 use flowy_user_pub::cloud::{UserCloudService, UserCloudServiceProvider};
 use flowy_user_pub::entities::{Authenticator, UserTokenState};
@@ -100,6 +104,79 @@ impl StorageCloudService for ServerProvider {
       .await
   }
 
+  async fn get_object_url_v2(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<String> {
+    let server = self.get_server()?;
+    let storage = server.file_storage().ok_or(FlowyError::internal())?;
+    storage
+      .get_object_url_v2(workspace_id, parent_dir, file_id)
+      .await
+  }
+
+  async fn parse_object_url_v2(&self, url: &str) -> Option<(String, String, String)> {
+    self
+      .get_server()
+      .ok()?
+      .file_storage()?
+      .parse_object_url_v2(url)
+      .await
+  }
+
+  async fn object_exists(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<bool> {
+    let server = self.get_server()?;
+    let storage = server.file_storage().ok_or(FlowyError::internal())?;
+    storage
+      .object_exists(workspace_id, parent_dir, file_id)
+      .await
+  }
+
+  fn min_part_size(&self) -> usize {
+    self
+      .get_server()
+      .ok()
+      .and_then(|server| server.file_storage())
+      .map(|storage| storage.min_part_size())
+      .unwrap_or_default()
+  }
+
+  fn capabilities(&self) -> StorageCapabilities {
+    self
+      .get_server()
+      .ok()
+      .and_then(|server| server.file_storage())
+      .map(|storage| storage.capabilities())
+      .unwrap_or_default()
+  }
+
+  async fn get_object_range(&self, url: String, range: Range<u64>) -> FlowyResult<ObjectRange> {
+    let server = self.get_server()?;
+    let storage = server.file_storage().ok_or(FlowyError::internal())?;
+    storage.get_object_range(url, range).await
+  }
+
+  async fn abort_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    upload_id: &str,
+    file_id: &str,
+  ) -> FlowyResult<()> {
+    let server = self.get_server()?;
+    let storage = server.file_storage().ok_or(FlowyError::internal())?;
+    storage
+      .abort_upload(workspace_id, parent_dir, upload_id, file_id)
+      .await
+  }
+
   async fn create_upload(
     &self,
     workspace_id: &str,
@@ -114,6 +191,91 @@ impl StorageCloudService for ServerProvider {
       .await
   }
 
+  async fn create_upload_with_metadata(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    content_type: &str,
+    metadata: &HashMap<String, String>,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    let server = self.get_server();
+    let storage = server?.file_storage().ok_or(FlowyError::internal())?;
+    storage
+      .create_upload_with_metadata(workspace_id, parent_dir, file_id, content_type, metadata)
+      .await
+  }
+
+  async fn create_upload_with_storage_class(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    content_type: &str,
+    metadata: &HashMap<String, String>,
+    storage_class: &str,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    let server = self.get_server();
+    let storage = server?.file_storage().ok_or(FlowyError::internal())?;
+    storage
+      .create_upload_with_storage_class(
+        workspace_id,
+        parent_dir,
+        file_id,
+        content_type,
+        metadata,
+        storage_class,
+      )
+      .await
+  }
+
+  async fn create_upload_if_not_exists(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    content_type: &str,
+    metadata: &HashMap<String, String>,
+    storage_class: &str,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    let server = self.get_server();
+    let storage = server?.file_storage().ok_or(FlowyError::internal())?;
+    storage
+      .create_upload_if_not_exists(
+        workspace_id,
+        parent_dir,
+        file_id,
+        content_type,
+        metadata,
+        storage_class,
+      )
+      .await
+  }
+
+  async fn object_metadata(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<HashMap<String, String>> {
+    let server = self.get_server()?;
+    let storage = server.file_storage().ok_or(FlowyError::internal())?;
+    storage
+      .object_metadata(workspace_id, parent_dir, file_id)
+      .await
+  }
+
+  async fn list_objects(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    cursor: Option<&str>,
+  ) -> FlowyResult<ObjectPage> {
+    let server = self.get_server()?;
+    let storage = server.file_storage().ok_or(FlowyError::internal())?;
+    storage.list_objects(workspace_id, parent_dir, cursor).await
+  }
+
   async fn upload_part(
     &self,
     workspace_id: &str,
@@ -137,6 +299,46 @@ impl StorageCloudService for ServerProvider {
       .await
   }
 
+  #[allow(clippy::too_many_arguments)]
+  async fn upload_part_with_checksum(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    upload_id: &str,
+    file_id: &str,
+    part_number: i32,
+    body: Vec<u8>,
+    checksum: &PartChecksum,
+  ) -> Result<UploadPartResponse, FlowyError> {
+    let server = self.get_server();
+    let storage = server?.file_storage().ok_or(FlowyError::internal())?;
+    storage
+      .upload_part_with_checksum(
+        workspace_id,
+        parent_dir,
+        upload_id,
+        file_id,
+        part_number,
+        body,
+        checksum,
+      )
+      .await
+  }
+
+  async fn part_checksums(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    upload_id: &str,
+    file_id: &str,
+  ) -> FlowyResult<HashMap<i32, PartChecksum>> {
+    let server = self.get_server()?;
+    let storage = server.file_storage().ok_or(FlowyError::internal())?;
+    storage
+      .part_checksums(workspace_id, parent_dir, upload_id, file_id)
+      .await
+  }
+
   async fn complete_upload(
     &self,
     workspace_id: &str,
@@ -165,7 +165,7 @@ impl AppFlowyCore {
         Arc::downgrade(&authenticate_user),
         server_provider.clone(),
         store_preference.clone(),
-        Arc::downgrade(&storage_manager.storage_service),
+        Arc::downgrade(&storage_manager.storage_service()),
       );
 
       let database_manager = DatabaseDepsResolver::resolve(
@@ -183,7 +183,7 @@ impl AppFlowyCore {
         &database_manager,
         collab_builder.clone(),
         server_provider.clone(),
-        Arc::downgrade(&storage_manager.storage_service),
+        Arc::downgrade(&storage_manager.storage_service()),
       );
 
       let folder_indexer = Arc::new(FolderIndexManagerImpl::new(Some(Arc::downgrade(
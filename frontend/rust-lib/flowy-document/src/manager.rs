@@ -431,7 +431,7 @@ impl DocumentManager {
 
   pub async fn delete_file(&self, local_file_path: String, url: String) -> FlowyResult<()> {
     let storage_service = self.storage_service_upgrade()?;
-    storage_service.delete_object(url, local_file_path)?;
+    storage_service.delete_object(url, None, local_file_path)?;
     Ok(())
   }
 
@@ -186,7 +186,12 @@ pub struct DocumentTestFileStorageService;
 
 #[async_trait]
 impl StorageService for DocumentTestFileStorageService {
-  fn delete_object(&self, _url: String, _local_file_path: String) -> FlowyResult<()> {
+  fn delete_object(
+    &self,
+    _url: String,
+    _parent_dir: Option<String>,
+    _local_file_path: String,
+  ) -> FlowyResult<()> {
     todo!()
   }
 
@@ -224,6 +229,15 @@ impl StorageService for DocumentTestFileStorageService {
   ) -> Result<Option<FileProgressReceiver>, FlowyError> {
     todo!()
   }
+
+  async fn cancel_upload(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    _file_id: &str,
+  ) -> Result<(), FlowyError> {
+    todo!()
+  }
 }
 
 struct DefaultCollabStorageProvider();
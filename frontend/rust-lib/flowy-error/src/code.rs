@@ -356,6 +356,54 @@ pub enum ErrorCode {
 
   #[error("Requested namespace has one or more invalid characters")]
   CustomNamespaceInvalidCharacter = 122,
+
+  #[error("Upload was cancelled")]
+  UploadCancelled = 123,
+
+  #[error("The local file of the upload was removed")]
+  UploadFileMissing = 124,
+
+  #[error("Workspace id of the upload is empty")]
+  UploadWorkspaceIdIsEmpty = 125,
+
+  #[error("Parent dir of the upload is empty")]
+  UploadParentDirIsEmpty = 126,
+
+  #[error("Local file path of the upload is empty")]
+  UploadFilePathIsEmpty = 127,
+
+  #[error("Failed to create the temp file of the upload")]
+  UploadTempFileError = 128,
+
+  #[error("Upload expired")]
+  UploadExpired = 129,
+
+  #[error("Invalid upload record")]
+  InvalidUploadRecord = 130,
+
+  #[error("The active workspace changed since the storage was opened")]
+  StorageWorkspaceChanged = 131,
+
+  #[error("The file needs more parts than the storage accepts")]
+  UploadTooManyParts = 132,
+
+  #[error("The storage doesn't support the storage class")]
+  UnsupportedStorageClass = 133,
+
+  #[error("Too many uploads are pending, retry later")]
+  TooManyPendingUploads = 134,
+
+  #[error("The uploaded parts are invalid, the file must be uploaded again")]
+  UploadPartsInvalid = 135,
+
+  #[error("The parent dir of the upload is invalid")]
+  InvalidParentDir = 136,
+
+  #[error("The object already exists")]
+  ObjectAlreadyExists = 137,
+
+  #[error("The upload exceeds a storage quota of the workspace or its folder")]
+  StorageQuotaExceeded = 138,
 }
 
 impl ErrorCode {
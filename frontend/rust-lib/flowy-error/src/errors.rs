@@ -76,14 +76,27 @@ impl FlowyError {
     self.code == ErrorCode::FileStorageLimitExceeded
   }
 
+  /// The server rejected the uploaded parts when completing the upload, so they can't be reused.
+  pub fn is_upload_parts_invalid(&self) -> bool {
+    self.code == ErrorCode::UploadPartsInvalid
+  }
+
   pub fn is_single_file_limit_exceeded(&self) -> bool {
     self.code == ErrorCode::SingleUploadLimitExceeded
   }
 
+  pub fn is_upload_cancelled(&self) -> bool {
+    self.code == ErrorCode::UploadCancelled
+  }
+
   pub fn should_retry_upload(&self) -> bool {
     !matches!(
       self.code,
-      ErrorCode::FileStorageLimitExceeded | ErrorCode::SingleUploadLimitExceeded
+      ErrorCode::FileStorageLimitExceeded
+        | ErrorCode::SingleUploadLimitExceeded
+        | ErrorCode::UploadCancelled
+        | ErrorCode::UploadFileMissing
+        | ErrorCode::ObjectAlreadyExists
     )
   }
 
@@ -141,6 +154,8 @@ impl FlowyError {
   static_flowy_error!(local_ai_unavailable, ErrorCode::LocalAIUnavailable);
   static_flowy_error!(response_timeout, ErrorCode::ResponseTimeout);
   static_flowy_error!(file_storage_limit, ErrorCode::FileStorageLimitExceeded);
+  static_flowy_error!(upload_cancelled, ErrorCode::UploadCancelled);
+  static_flowy_error!(upload_file_missing, ErrorCode::UploadFileMissing);
 }
 
 impl std::convert::From<ErrorCode> for FlowyError {
@@ -1,6 +1,7 @@
 use crate::af_cloud::AFServer;
 use client_api::entity::{CompleteUploadRequest, CreateUploadRequest};
 use flowy_error::FlowyError;
+use flowy_storage_pub::chunked_byte::MIN_CHUNK_SIZE;
 use flowy_storage_pub::cloud::{ObjectIdentity, ObjectValue, StorageCloudService};
 use flowy_storage_pub::storage::{CompletedPartRequest, CreateUploadResponse, UploadPartResponse};
 use lib_infra::async_trait::async_trait;
@@ -64,6 +65,11 @@ where
     Some(value)
   }
 
+  fn min_part_size(&self) -> usize {
+    // The objects are stored in S3, which requires every part except the last to be at least 5MB.
+    MIN_CHUNK_SIZE
+  }
+
   async fn create_upload(
     &self,
     workspace_id: &str,
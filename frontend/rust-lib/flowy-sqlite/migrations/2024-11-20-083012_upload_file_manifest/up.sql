@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE upload_file_manifest (
+    workspace_id TEXT NOT NULL,
+    parent_dir TEXT NOT NULL,
+    file_id TEXT NOT NULL,
+    manifest TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (workspace_id, parent_dir, file_id)
+);
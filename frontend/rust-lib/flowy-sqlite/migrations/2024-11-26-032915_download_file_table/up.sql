@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE download_file_table (
+    url TEXT NOT NULL PRIMARY KEY,
+    local_file_path TEXT NOT NULL,
+    priority INTEGER NOT NULL,
+    created_at BIGINT NOT NULL
+);
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE upload_file_failure (
+    workspace_id TEXT NOT NULL,
+    parent_dir TEXT NOT NULL,
+    file_id TEXT NOT NULL,
+    error TEXT NOT NULL,
+    failed_at BIGINT NOT NULL,
+    pinned BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (workspace_id, parent_dir, file_id)
+);
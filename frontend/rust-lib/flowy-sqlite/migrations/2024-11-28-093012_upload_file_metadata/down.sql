@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE upload_file_table DROP COLUMN metadata;
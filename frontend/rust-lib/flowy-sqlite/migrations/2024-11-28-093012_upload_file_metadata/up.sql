@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE upload_file_table ADD COLUMN metadata TEXT NOT NULL DEFAULT '';
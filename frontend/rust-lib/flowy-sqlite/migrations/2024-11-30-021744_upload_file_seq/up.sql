@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE upload_file_table ADD COLUMN seq BIGINT NOT NULL DEFAULT 0;
+-- The rowids follow the insertion order of the existing records.
+UPDATE upload_file_table SET seq = rowid;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE upload_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    workspace_id TEXT NOT NULL,
+    parent_dir TEXT NOT NULL,
+    file_id TEXT NOT NULL,
+    event TEXT NOT NULL,
+    detail TEXT NOT NULL DEFAULT '',
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX upload_log_file_id_idx ON upload_log (file_id);
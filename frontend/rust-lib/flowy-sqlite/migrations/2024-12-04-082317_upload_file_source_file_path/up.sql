@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE upload_file_table ADD COLUMN source_file_path TEXT NOT NULL DEFAULT '';
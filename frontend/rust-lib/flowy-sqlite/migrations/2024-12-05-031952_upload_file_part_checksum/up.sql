@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE upload_file_part ADD COLUMN checksum TEXT NOT NULL DEFAULT '';
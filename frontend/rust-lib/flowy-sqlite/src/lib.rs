@@ -40,13 +40,19 @@ pub fn init<P: AsRef<Path>>(storage_path: P) -> Result<Database, io::Error> {
   let pool_config = PoolConfig::default();
   let database = Database::new(storage_path, DB_NAME, pool_config).map_err(as_io_error)?;
   let mut conn = database.get_connection().map_err(as_io_error)?;
-  (*conn)
-    .run_pending_migrations(MIGRATIONS)
-    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+  run_migrations(&mut conn)?;
 
   Ok(database)
 }
 
+/// Runs the migrations that were not applied to the database yet.
+pub fn run_migrations(conn: &mut SqliteConnection) -> Result<(), io::Error> {
+  conn
+    .run_pending_migrations(MIGRATIONS)
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+  Ok(())
+}
+
 fn as_io_error<E>(e: E) -> io::Error
 where
   E: Into<crate::sqlite_impl::Error> + Debug,
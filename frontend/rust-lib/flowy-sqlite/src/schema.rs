@@ -45,11 +45,42 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    download_file_table (url) {
+        url -> Text,
+        local_file_path -> Text,
+        priority -> Integer,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    upload_file_failure (workspace_id, parent_dir, file_id) {
+        workspace_id -> Text,
+        parent_dir -> Text,
+        file_id -> Text,
+        error -> Text,
+        failed_at -> BigInt,
+        pinned -> Bool,
+    }
+}
+
+diesel::table! {
+    upload_file_manifest (workspace_id, parent_dir, file_id) {
+        workspace_id -> Text,
+        parent_dir -> Text,
+        file_id -> Text,
+        manifest -> Text,
+        created_at -> BigInt,
+    }
+}
+
 diesel::table! {
     upload_file_part (upload_id, e_tag) {
         upload_id -> Text,
         e_tag -> Text,
         part_num -> Integer,
+        checksum -> Text,
     }
 }
 
@@ -65,6 +96,24 @@ diesel::table! {
         upload_id -> Text,
         created_at -> BigInt,
         is_finish -> Bool,
+        metadata -> Text,
+        storage_class -> Text,
+        seq -> BigInt,
+        overwrite -> Bool,
+        linked_parent_dir -> Text,
+        source_file_path -> Text,
+    }
+}
+
+diesel::table! {
+    upload_log (id) {
+        id -> BigInt,
+        workspace_id -> Text,
+        parent_dir -> Text,
+        file_id -> Text,
+        event -> Text,
+        detail -> Text,
+        created_at -> BigInt,
     }
 }
 
@@ -122,8 +171,12 @@ diesel::allow_tables_to_appear_in_same_query!(
   chat_message_table,
   chat_table,
   collab_snapshot,
+  download_file_table,
+  upload_file_failure,
+  upload_file_manifest,
   upload_file_part,
   upload_file_table,
+  upload_log,
   user_data_migration_records,
   user_table,
   user_workspace_table,
@@ -1,11 +1,15 @@
 use anyhow::anyhow;
 use bytes::Bytes;
 use std::fmt::Display;
+use std::ops::Deref;
 use std::path::Path;
+use std::sync::Arc;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tokio::io::SeekFrom;
 use tokio::io::{self, AsyncSeekExt};
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
 
 /// In Amazon S3, the minimum chunk size for multipart uploads is 5 MB,except for the last part,
 /// which can be smaller.(https://docs.aws.amazon.com/AmazonS3/latest/userguide/qfacts.html)
@@ -24,11 +28,9 @@ impl ChunkedBytes {
     file_path: P,
     chunk_size: usize,
   ) -> Result<Self, anyhow::Error> {
-    if chunk_size < MIN_CHUNK_SIZE {
-      return Err(anyhow!(
-        "Chunk size should be greater than or equal to {} bytes",
-        MIN_CHUNK_SIZE
-      ));
+    // The minimum part size depends on the backend, see `StorageCloudService::min_part_size`.
+    if chunk_size == 0 {
+      return Err(anyhow!("Chunk size should be greater than zero"));
     }
 
     let file = File::open(file_path).await?;
@@ -42,6 +44,14 @@ impl ChunkedBytes {
     })
   }
 
+  /// Returns the size of the next chunk, zero at the end of the file.
+  pub fn next_chunk_len(&self) -> usize {
+    self
+      .file_size
+      .saturating_sub(self.current_offset)
+      .min(self.chunk_size as u64) as usize
+  }
+
   /// Read the next chunk from the file.
   pub async fn next_chunk(&mut self) -> Option<Result<Bytes, io::Error>> {
     if self.current_offset >= self.file_size {
@@ -91,6 +101,11 @@ impl ChunkedBytes {
   pub fn current_offset(&self) -> u64 {
     self.current_offset
   }
+
+  /// Get the size of the file when it was opened.
+  pub fn file_size(&self) -> u64 {
+    self.file_size
+  }
 }
 
 impl Display for ChunkedBytes {
@@ -106,6 +121,156 @@ impl Display for ChunkedBytes {
   }
 }
 
+/// [ByteBudget] caps the bytes of the chunks held in memory by all the [ChunkReader]s sharing it.
+/// The share of a chunk is taken before the chunk is read, and given back when the [Chunk] is
+/// dropped.
+#[derive(Clone, Debug)]
+pub struct ByteBudget {
+  semaphore: Arc<Semaphore>,
+  capacity: usize,
+}
+
+impl ByteBudget {
+  pub fn new(capacity: usize) -> Self {
+    let capacity = capacity.clamp(1, (u32::MAX as usize).min(Semaphore::MAX_PERMITS));
+    Self {
+      semaphore: Arc::new(Semaphore::new(capacity)),
+      capacity,
+    }
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  /// Returns the bytes of the chunks currently held.
+  pub fn in_use(&self) -> usize {
+    self.capacity - self.semaphore.available_permits()
+  }
+
+  /// Waits until the bytes fit in the budget. A chunk larger than the whole budget takes all of it,
+  /// so that it's read alone instead of never.
+  async fn acquire(&self, bytes: usize) -> OwnedSemaphorePermit {
+    let permits = bytes.clamp(1, self.capacity) as u32;
+    self
+      .semaphore
+      .clone()
+      .acquire_many_owned(permits)
+      .await
+      .expect("the semaphore of the budget is never closed")
+  }
+}
+
+/// A chunk read by a [ChunkReader]. It holds its share of the [ByteBudget] of the reader, if any,
+/// until it's dropped.
+#[derive(Debug)]
+pub struct Chunk {
+  bytes: Bytes,
+  _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Deref for Chunk {
+  type Target = Bytes;
+
+  fn deref(&self) -> &Self::Target {
+    &self.bytes
+  }
+}
+
+/// [ChunkReader] reads the chunks of a [ChunkedBytes].
+///
+/// With a prefetch depth greater than zero, the next chunks are read from disk in a background task
+/// while the current chunk is consumed, which hides the disk latency behind the network upload. At
+/// most `depth` chunks are read ahead, so the extra memory is bounded to `depth * chunk_size`. With
+/// a [ByteBudget], the chunks read ahead count against the budget as well.
+pub enum ChunkReader {
+  Sequential {
+    chunked_bytes: ChunkedBytes,
+    budget: Option<ByteBudget>,
+  },
+  Prefetch {
+    rx: mpsc::Receiver<Result<Chunk, io::Error>>,
+    handle: JoinHandle<()>,
+  },
+}
+
+impl ChunkReader {
+  pub fn new(
+    mut chunked_bytes: ChunkedBytes,
+    prefetch_depth: usize,
+    budget: Option<ByteBudget>,
+  ) -> Self {
+    if prefetch_depth == 0 {
+      return ChunkReader::Sequential {
+        chunked_bytes,
+        budget,
+      };
+    }
+
+    let (tx, rx) = mpsc::channel(prefetch_depth);
+    let handle = tokio::spawn(async move {
+      loop {
+        // Reserve the slot before reading, so that the chunks held in memory never exceed the depth.
+        let permit = match tx.reserve().await {
+          Ok(permit) => permit,
+          Err(_) => break,
+        };
+        match read_chunk(&mut chunked_bytes, budget.as_ref()).await {
+          Some(Ok(chunk)) => permit.send(Ok(chunk)),
+          Some(Err(err)) => {
+            permit.send(Err(err));
+            break;
+          },
+          None => break,
+        }
+      }
+    });
+    ChunkReader::Prefetch { rx, handle }
+  }
+
+  /// Read the next chunk from the file.
+  pub async fn next_chunk(&mut self) -> Option<Result<Chunk, io::Error>> {
+    match self {
+      ChunkReader::Sequential {
+        chunked_bytes,
+        budget,
+      } => read_chunk(chunked_bytes, budget.as_ref()).await,
+      ChunkReader::Prefetch { rx, .. } => rx.recv().await,
+    }
+  }
+}
+
+/// Reads the next chunk, once its share of the budget is taken.
+async fn read_chunk(
+  chunked_bytes: &mut ChunkedBytes,
+  budget: Option<&ByteBudget>,
+) -> Option<Result<Chunk, io::Error>> {
+  let len = chunked_bytes.next_chunk_len();
+  if len == 0 {
+    return None;
+  }
+  let permit = match budget {
+    Some(budget) => Some(budget.acquire(len).await),
+    None => None,
+  };
+  let bytes = match chunked_bytes.next_chunk().await? {
+    Ok(bytes) => bytes,
+    Err(err) => return Some(Err(err)),
+  };
+  Some(Ok(Chunk {
+    bytes,
+    _permit: permit,
+  }))
+}
+
+impl Drop for ChunkReader {
+  fn drop(&mut self) {
+    if let ChunkReader::Prefetch { handle, .. } = self {
+      handle.abort();
+    }
+  }
+}
+
 // Function to split input bytes into several chunks and return offsets
 pub fn split_into_chunks(data: &Bytes, chunk_size: usize) -> Vec<(usize, usize)> {
   calculate_offsets(data.len(), chunk_size)
@@ -414,4 +579,78 @@ mod tests {
 
     tokio::fs::remove_file(file_path).await.unwrap();
   }
+
+  #[tokio::test]
+  async fn test_prefetch_chunks() {
+    // Create a file of 15 MB (3 chunks of 5 MB)
+    let mut file_path = temp_dir();
+    file_path.push("test_prefetch_chunks");
+
+    let mut file = File::create(&file_path).await.unwrap();
+    file.write_all(&vec![0; 15 * 1024 * 1024]).await.unwrap(); // 15 MB
+    file.flush().await.unwrap();
+
+    let chunked_bytes = ChunkedBytes::from_file(&file_path, MIN_CHUNK_SIZE)
+      .await
+      .unwrap();
+    let mut reader = ChunkReader::new(chunked_bytes, 2, None);
+
+    let chunk = reader.next_chunk().await.unwrap().unwrap();
+    assert_eq!(chunk.len(), 5 * 1024 * 1024);
+
+    // Simulate uploading the first chunk. The next chunks are read from disk in the meantime, so
+    // they are available right away.
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    for _ in 0..2 {
+      let chunk = tokio::time::timeout(std::time::Duration::from_millis(10), reader.next_chunk())
+        .await
+        .expect("chunk should be prefetched")
+        .unwrap()
+        .unwrap();
+      assert_eq!(chunk.len(), 5 * 1024 * 1024);
+    }
+    assert!(reader.next_chunk().await.is_none());
+
+    tokio::fs::remove_file(file_path).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_prefetch_chunks_with_budget() {
+    // Create a file of 15 MB (3 chunks of 5 MB)
+    let mut file_path = temp_dir();
+    file_path.push("test_prefetch_chunks_with_budget");
+
+    let mut file = File::create(&file_path).await.unwrap();
+    file.write_all(&vec![0; 15 * 1024 * 1024]).await.unwrap(); // 15 MB
+    file.flush().await.unwrap();
+
+    let chunked_bytes = ChunkedBytes::from_file(&file_path, MIN_CHUNK_SIZE)
+      .await
+      .unwrap();
+    let budget = ByteBudget::new(MIN_CHUNK_SIZE);
+    let mut reader = ChunkReader::new(chunked_bytes, 2, Some(budget.clone()));
+
+    // The budget only fits one chunk, nothing is read ahead while the first chunk is held.
+    let chunk = reader.next_chunk().await.unwrap().unwrap();
+    assert_eq!(budget.in_use(), MIN_CHUNK_SIZE);
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(budget.in_use(), MIN_CHUNK_SIZE);
+    assert!(
+      tokio::time::timeout(std::time::Duration::from_millis(10), reader.next_chunk())
+        .await
+        .is_err()
+    );
+    drop(chunk);
+
+    for _ in 0..2 {
+      let chunk = reader.next_chunk().await.unwrap().unwrap();
+      assert_eq!(chunk.len(), 5 * 1024 * 1024);
+      assert!(budget.in_use() <= budget.capacity());
+    }
+    assert!(reader.next_chunk().await.is_none());
+    drop(reader);
+    assert_eq!(budget.in_use(), 0);
+
+    tokio::fs::remove_file(file_path).await.unwrap();
+  }
 }
@@ -3,6 +3,10 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use flowy_error::{FlowyError, FlowyResult};
 use mime::Mime;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::ops::Range;
+use std::str::FromStr;
 
 #[async_trait]
 pub trait StorageCloudService: Send + Sync {
@@ -54,6 +58,81 @@ pub trait StorageCloudService: Send + Sync {
 
   async fn parse_object_url_v1(&self, url: &str) -> Option<(String, String, String)>;
 
+  /// Returns the url of the object in the v2 format.
+  ///
+  /// # Returns
+  /// - `Ok(String)`: The url of the object.
+  /// - `Err(Error)`: The backend doesn't support the v2 format, or an error occurred during the operation.
+  async fn get_object_url_v2(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    _file_id: &str,
+  ) -> FlowyResult<String> {
+    Err(FlowyError::not_support())
+  }
+
+  /// Parses the workspace id, parent dir and file id of a url in the v2 format. Returns None when
+  /// the url is not a v2 url, or the backend doesn't support the v2 format.
+  async fn parse_object_url_v2(&self, _url: &str) -> Option<(String, String, String)> {
+    None
+  }
+
+  /// Checks whether the object exists on the server.
+  ///
+  /// # Returns
+  /// - `Ok(bool)`: Whether the object exists.
+  /// - `Err(Error)`: The backend doesn't support the check, or an error occurred during the operation.
+  async fn object_exists(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    _file_id: &str,
+  ) -> FlowyResult<bool> {
+    Err(FlowyError::not_support())
+  }
+
+  /// The minimum size of each part of a multipart upload, except the last part which can be
+  /// smaller. The parts are never cut smaller than this size.
+  fn min_part_size(&self) -> usize {
+    0
+  }
+
+  /// The optional features the backend supports, so that the callers pick a code path up front
+  /// instead of falling back after a failed request. By default, only the required features are
+  /// supported.
+  fn capabilities(&self) -> StorageCapabilities {
+    StorageCapabilities {
+      min_part_size: self.min_part_size(),
+      ..Default::default()
+    }
+  }
+
+  /// Fetches a range of the bytes of the object. Only called when the backend reports
+  /// [StorageCapabilities::range_downloads].
+  ///
+  /// # Returns
+  /// - `Ok(ObjectRange)`: The bytes of the range, which can be shorter than the range at the end of
+  ///   the object, and the size of the whole object.
+  /// - `Err(Error)`: The backend doesn't support range requests, or an error occurred during the
+  ///   operation.
+  async fn get_object_range(&self, _url: String, _range: Range<u64>) -> FlowyResult<ObjectRange> {
+    Err(FlowyError::not_support())
+  }
+
+  /// Aborts the multipart upload, so that the server can release the uploaded parts.
+  ///
+  /// Backends that don't support aborting an upload ignore it.
+  async fn abort_upload(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    _upload_id: &str,
+    _file_id: &str,
+  ) -> FlowyResult<()> {
+    Ok(())
+  }
+
   async fn create_upload(
     &self,
     workspace_id: &str,
@@ -62,6 +141,94 @@ pub trait StorageCloudService: Send + Sync {
     content_type: &str,
   ) -> Result<CreateUploadResponse, FlowyError>;
 
+  /// Same as [Self::create_upload], with metadata stored along with the object, e.g. the original
+  /// file name. Only called with metadata when the backend reports
+  /// [StorageCapabilities::object_metadata], the metadata is ignored by default.
+  async fn create_upload_with_metadata(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    content_type: &str,
+    _metadata: &HashMap<String, String>,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    self
+      .create_upload(workspace_id, parent_dir, file_id, content_type)
+      .await
+  }
+
+  /// Same as [Self::create_upload_with_metadata], with the object stored in the given storage
+  /// class, e.g. a cheaper tier for archived files. Only called with a class the backend lists in
+  /// [StorageCapabilities::storage_classes], the class is ignored by default.
+  async fn create_upload_with_storage_class(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    content_type: &str,
+    metadata: &HashMap<String, String>,
+    _storage_class: &str,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    self
+      .create_upload_with_metadata(workspace_id, parent_dir, file_id, content_type, metadata)
+      .await
+  }
+
+  /// Same as [Self::create_upload_with_storage_class], on the condition that the object doesn't
+  /// exist yet, e.g. with an `If-None-Match: *` precondition. An empty storage class is the default
+  /// class of the backend. Only called when the backend reports
+  /// [StorageCapabilities::conditional_create].
+  ///
+  /// # Returns
+  /// - `Ok(CreateUploadResponse)`: The object doesn't exist, the upload is created.
+  /// - `Err(Error)`: [flowy_error::ErrorCode::ObjectAlreadyExists] when the object exists, or an
+  ///   error occurred during the operation.
+  async fn create_upload_if_not_exists(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    _file_id: &str,
+    _content_type: &str,
+    _metadata: &HashMap<String, String>,
+    _storage_class: &str,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    Err(FlowyError::not_support())
+  }
+
+  /// Returns the metadata stored along with the object by [Self::create_upload_with_metadata].
+  ///
+  /// # Returns
+  /// - `Ok(HashMap)`: The metadata of the object, empty when it has none.
+  /// - `Err(Error)`: The backend doesn't support metadata, or an error occurred during the
+  ///   operation.
+  async fn object_metadata(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    _file_id: &str,
+  ) -> FlowyResult<HashMap<String, String>> {
+    Err(FlowyError::not_support())
+  }
+
+  /// Lists the objects under the parent dir, a page at a time.
+  ///
+  /// # Parameters
+  /// - `cursor`: `None` for the first page, then the [ObjectPage::next_cursor] of the previous
+  ///   page.
+  ///
+  /// # Returns
+  /// - `Ok(ObjectPage)`: The objects of the page, ordered by file id.
+  /// - `Err(Error)`: The backend doesn't support listing, or an error occurred during the
+  ///   operation.
+  async fn list_objects(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    _cursor: Option<&str>,
+  ) -> FlowyResult<ObjectPage> {
+    Err(FlowyError::not_support())
+  }
+
   async fn upload_part(
     &self,
     workspace_id: &str,
@@ -72,6 +239,50 @@ pub trait StorageCloudService: Send + Sync {
     body: Vec<u8>,
   ) -> Result<UploadPartResponse, FlowyError>;
 
+  /// Same as [Self::upload_part], with the checksum of the body for the backend to validate the
+  /// part on receipt. Only called when the backend reports [StorageCapabilities::part_checksum],
+  /// the checksum is ignored by default.
+  #[allow(clippy::too_many_arguments)]
+  async fn upload_part_with_checksum(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    upload_id: &str,
+    file_id: &str,
+    part_number: i32,
+    body: Vec<u8>,
+    _checksum: &PartChecksum,
+  ) -> Result<UploadPartResponse, FlowyError> {
+    self
+      .upload_part(
+        workspace_id,
+        parent_dir,
+        upload_id,
+        file_id,
+        part_number,
+        body,
+      )
+      .await
+  }
+
+  /// Returns the checksums of the parts the server stored, keyed by part number, computed with
+  /// the algorithm of [StorageCapabilities::part_checksum]. They're compared with the checksums of
+  /// the sent parts before completing the upload. A part missing from the map isn't verified.
+  ///
+  /// # Returns
+  /// - `Ok(HashMap)`: The checksums of the stored parts.
+  /// - `Err(Error)`: The backend doesn't report the checksums, or an error occurred during the
+  ///   operation.
+  async fn part_checksums(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    _upload_id: &str,
+    _file_id: &str,
+  ) -> FlowyResult<HashMap<i32, PartChecksum>> {
+    Err(FlowyError::not_support())
+  }
+
   async fn complete_upload(
     &self,
     workspace_id: &str,
@@ -82,6 +293,125 @@ pub trait StorageCloudService: Send + Sync {
   ) -> Result<(), FlowyError>;
 }
 
+/// The optional features of a [StorageCloudService].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageCapabilities {
+  /// Whether the objects can be fetched by ranges, see [StorageCloudService::get_object_range].
+  pub range_downloads: bool,
+  /// Whether the object urls are presigned, so they can be fetched without the user credentials.
+  pub presigned_urls: bool,
+  /// Whether an unfinished multipart upload can be aborted, see
+  /// [StorageCloudService::abort_upload].
+  pub abort_upload: bool,
+  /// Whether an object can be moved to another parent dir without uploading it again.
+  pub move_object: bool,
+  /// Whether the existence of an object can be checked, see [StorageCloudService::object_exists].
+  pub head_object: bool,
+  /// Whether metadata can be stored along with an object, see
+  /// [StorageCloudService::create_upload_with_metadata].
+  pub object_metadata: bool,
+  /// The storage classes an object can be stored in, see
+  /// [StorageCloudService::create_upload_with_storage_class]. Empty when the backend doesn't
+  /// support choosing one.
+  pub storage_classes: Vec<String>,
+  /// Whether the objects under a parent dir can be listed, see [StorageCloudService::list_objects].
+  pub list_objects: bool,
+  /// Whether an upload can be created on the condition that the object doesn't exist yet, see
+  /// [StorageCloudService::create_upload_if_not_exists].
+  pub conditional_create: bool,
+  /// Whether the backend requires the file id to be the hash of the content, e.g. to verify the
+  /// uploaded object. The file ids are then always computed from the content.
+  pub content_addressed: bool,
+  /// See [StorageCloudService::min_part_size].
+  pub min_part_size: usize,
+  /// The maximum number of parts of a multipart upload, `None` when unlimited.
+  pub max_parts: Option<usize>,
+  /// The checksum algorithm the backend validates the parts with, see
+  /// [StorageCloudService::upload_part_with_checksum] and [StorageCloudService::part_checksums].
+  /// `None` when it doesn't, the parts are then only checksummed with
+  /// [ChecksumAlgorithm::Crc32] for the local record.
+  pub part_checksum: Option<ChecksumAlgorithm>,
+}
+
+/// The algorithm of a [PartChecksum].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChecksumAlgorithm {
+  Crc32,
+  Sha256,
+}
+
+impl ChecksumAlgorithm {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ChecksumAlgorithm::Crc32 => "crc32",
+      ChecksumAlgorithm::Sha256 => "sha256",
+    }
+  }
+}
+
+/// The checksum of an uploaded part, see [StorageCloudService::upload_part_with_checksum].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartChecksum {
+  pub algorithm: ChecksumAlgorithm,
+  /// The base64 of the digest.
+  pub value: String,
+}
+
+impl Display for PartChecksum {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}:{}", self.algorithm.as_str(), self.value)
+  }
+}
+
+impl FromStr for PartChecksum {
+  type Err = FlowyError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (algorithm, value) = s
+      .split_once(':')
+      .ok_or_else(|| FlowyError::invalid_data().with_context(format!("invalid checksum: {}", s)))?;
+    let algorithm = match algorithm {
+      "crc32" => ChecksumAlgorithm::Crc32,
+      "sha256" => ChecksumAlgorithm::Sha256,
+      _ => {
+        return Err(
+          FlowyError::invalid_data()
+            .with_context(format!("unknown checksum algorithm: {}", algorithm)),
+        )
+      },
+    };
+    Ok(Self {
+      algorithm,
+      value: value.to_string(),
+    })
+  }
+}
+
+/// A range of the bytes of an object, see [StorageCloudService::get_object_range].
+pub struct ObjectRange {
+  pub raw: Bytes,
+  /// The size of the whole object.
+  pub total_size: u64,
+}
+
+/// An object listed by [StorageCloudService::list_objects].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectInfo {
+  pub file_id: String,
+  /// The size of the object in bytes, `None` when the backend doesn't report it.
+  pub size: Option<u64>,
+  /// The content type of the object, `None` when the backend doesn't report it.
+  pub content_type: Option<String>,
+}
+
+/// A page of the objects listed by [StorageCloudService::list_objects].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ObjectPage {
+  pub objects: Vec<ObjectInfo>,
+  /// The cursor of the next page, `None` on the last page.
+  pub next_cursor: Option<String>,
+}
+
 pub struct ObjectIdentity {
   pub workspace_id: String,
   pub file_id: String,
@@ -168,3 +498,24 @@ impl StorageObject {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn part_checksum_round_trip_test() {
+    for algorithm in [ChecksumAlgorithm::Crc32, ChecksumAlgorithm::Sha256] {
+      let checksum = PartChecksum {
+        algorithm,
+        value: "3q2+7w==".to_string(),
+      };
+      assert_eq!(
+        checksum.to_string().parse::<PartChecksum>().unwrap(),
+        checksum
+      );
+    }
+    assert!("md5:abc".parse::<PartChecksum>().is_err());
+    assert!("abc".parse::<PartChecksum>().is_err());
+  }
+}
@@ -5,11 +5,21 @@ use lib_infra::box_any::BoxAny;
 use serde::Serialize;
 use std::fmt::Display;
 use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+use tokio::io::AsyncWrite;
 use tokio::sync::broadcast;
+pub use tokio_util::sync::CancellationToken;
 
 #[async_trait]
 pub trait StorageService: Send + Sync {
-  fn delete_object(&self, url: String, local_file_path: String) -> FlowyResult<()>;
+  /// Deletes the object of the url along with its local copy. `parent_dir` is the location the
+  /// object is deleted from, the parent dir of the url when `None`.
+  fn delete_object(
+    &self,
+    url: String,
+    parent_dir: Option<String>,
+    local_file_path: String,
+  ) -> FlowyResult<()>;
 
   fn download_object(&self, url: String, local_file_path: String) -> FlowyResult<()>;
 
@@ -21,6 +31,38 @@ pub trait StorageService: Send + Sync {
     upload_immediately: bool,
   ) -> Result<(CreatedUpload, Option<FileProgressReceiver>), FlowyError>;
 
+  /// Same as [StorageService::create_upload], but the preparation of the upload can be aborted
+  /// with the `cancel_token` before the upload is queued. Implementations that can't abort the
+  /// preparation ignore the token.
+  async fn create_upload_with_cancel(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    local_file_path: &str,
+    upload_immediately: bool,
+    _cancel_token: CancellationToken,
+  ) -> Result<(CreatedUpload, Option<FileProgressReceiver>), FlowyError> {
+    self
+      .create_upload(
+        workspace_id,
+        parent_dir,
+        local_file_path,
+        upload_immediately,
+      )
+      .await
+  }
+
+  /// Downloads the object into the writer instead of a local file, e.g. to pipe it into a decoder,
+  /// and returns the number of bytes written. Dropping the returned future cancels the download.
+  /// Implementations that can't stream a download return an unsupported error.
+  async fn download_to_writer(
+    &self,
+    _url: &str,
+    _writer: &mut (dyn AsyncWrite + Send + Unpin),
+  ) -> FlowyResult<u64> {
+    Err(FlowyError::not_support())
+  }
+
   async fn start_upload(&self, record: &BoxAny) -> Result<(), FlowyError>;
 
   async fn resume_upload(
@@ -35,6 +77,13 @@ pub trait StorageService: Send + Sync {
     parent_idr: &str,
     file_id: &str,
   ) -> Result<Option<FileProgressReceiver>, FlowyError>;
+
+  async fn cancel_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> Result<(), FlowyError>;
 }
 
 pub struct FileProgressReceiver {
@@ -59,36 +108,139 @@ impl DerefMut for FileProgressReceiver {
 #[derive(Clone, Debug)]
 pub enum FileUploadState {
   NotStarted,
-  Uploading { progress: f64 },
-  Finished { file_id: String },
+  /// The upload waits in the queue for a free upload slot.
+  Queued,
+  Uploading {
+    progress: f64,
+  },
+  /// The upload waits until the reasons clear, e.g. the storage quota is exceeded. The reasons are
+  /// the bits of the `PauseReasons` of the storage manager.
+  Paused {
+    reasons: u8,
+  },
+  /// All the parts are uploaded, but completing the upload failed with a transient error. The
+  /// parts are kept, completing the upload is retried.
+  CompletionPending {
+    error: String,
+  },
+  Finished {
+    file_id: String,
+    /// The size of the uploaded file in bytes. `None` when the upload had already finished
+    /// before the state was requested.
+    total_bytes: Option<u64>,
+    /// The time spent uploading the file in this session, from the start of the upload to its
+    /// completion. `None` when the upload had already finished before the state was requested.
+    duration: Option<Duration>,
+  },
+}
+
+/// The version of the serialized [FileProgress]. Bump it when the fields of the payload change,
+/// so that the consumers of the progress stream can tell the schemas apart.
+pub const FILE_PROGRESS_SCHEMA_VERSION: u32 = 7;
+
+/// The direction of the transfer a [FileProgress] reports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferDirection {
+  #[default]
+  Upload,
+  Download,
 }
 
 #[derive(Clone, Debug, Serialize)]
 pub struct FileProgress {
+  pub version: u32,
+  /// The position of the progress in the progress stream, assigned when it's broadcast. It
+  /// increases with each progress of any file, so a consumer ignores a progress whose seq isn't
+  /// above the last one it received for the same file.
+  pub seq: u64,
   pub file_url: String,
   pub file_id: String,
+  /// The workspace of the file, so that the consumers can filter the stream without parsing the
+  /// url. Set on all the progress of the transfers.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub workspace_id: Option<String>,
   pub progress: f64,
   pub error: Option<String>,
+  pub direction: TransferDirection,
+  /// The size of the file in bytes, only set on the final progress of a completed upload.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub total_bytes: Option<u64>,
+  /// The time spent uploading the file in milliseconds, only set on the final progress of a
+  /// completed upload.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub duration_ms: Option<u64>,
+  /// The reasons the upload is paused, see [FileUploadState::Paused]. Only set while it's paused.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub paused_reasons: Option<u8>,
+  /// Set on the error of a transient completion failure, see [FileUploadState::CompletionPending].
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub completion_pending: bool,
 }
 
 impl FileProgress {
   pub fn new_progress(file_url: String, file_id: String, progress: f64) -> Self {
     FileProgress {
+      version: FILE_PROGRESS_SCHEMA_VERSION,
+      seq: 0,
       file_url,
       file_id,
+      workspace_id: None,
       progress: (progress * 10.0).round() / 10.0,
       error: None,
+      direction: TransferDirection::Upload,
+      total_bytes: None,
+      duration_ms: None,
+      paused_reasons: None,
+      completion_pending: false,
     }
   }
 
   pub fn new_error(file_url: String, file_id: String, error: String) -> Self {
     FileProgress {
+      version: FILE_PROGRESS_SCHEMA_VERSION,
+      seq: 0,
       file_url,
       file_id,
+      workspace_id: None,
       progress: 0.0,
       error: Some(error),
+      direction: TransferDirection::Upload,
+      total_bytes: None,
+      duration_ms: None,
+      paused_reasons: None,
+      completion_pending: false,
     }
   }
+
+  pub fn with_direction(mut self, direction: TransferDirection) -> Self {
+    self.direction = direction;
+    self
+  }
+
+  pub fn with_workspace_id(mut self, workspace_id: String) -> Self {
+    self.workspace_id = Some(workspace_id);
+    self
+  }
+
+  /// Marks the upload as paused for the reasons.
+  pub fn with_paused_reasons(mut self, reasons: u8) -> Self {
+    self.paused_reasons = Some(reasons);
+    self
+  }
+
+  /// Marks the error as a transient completion failure, the upload is completed later.
+  pub fn with_completion_pending(mut self) -> Self {
+    self.completion_pending = true;
+    self
+  }
+
+  /// Attaches the summary of a completed transfer.
+  pub fn with_summary(mut self, total_bytes: u64, duration: Duration) -> Self {
+    self.total_bytes = Some(total_bytes);
+    self.duration_ms = Some(duration.as_millis() as u64);
+    self
+  }
 }
 
 impl Display for FileProgress {
@@ -97,11 +249,122 @@ impl Display for FileProgress {
   }
 }
 
+/// The copy of the object a [DeleteProgress] reports on. The local file and the cloud object are
+/// deleted independently, so each one reports its own state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteTarget {
+  Local,
+  Cloud,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum DeleteState {
+  Deleting,
+  Deleted,
+  DeleteFailed { error: String },
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DeleteProgress {
+  pub version: u32,
+  pub file_url: String,
+  pub target: DeleteTarget,
+  #[serde(flatten)]
+  pub state: DeleteState,
+}
+
+impl DeleteProgress {
+  pub fn new(file_url: String, target: DeleteTarget, state: DeleteState) -> Self {
+    DeleteProgress {
+      version: FILE_PROGRESS_SCHEMA_VERSION,
+      file_url,
+      target,
+      state,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum DownloadState {
+  /// The download waits for its turn. Only reported by the per-file receivers, see
+  /// `StorageManager::download_objects`.
+  Queued,
+  Downloading,
+  /// The attempt failed with a transient error, the download is retried after a backoff.
+  Retrying {
+    attempt: u32,
+    error: String,
+  },
+  Downloaded,
+  DownloadFailed {
+    error: String,
+  },
+  /// The download was cancelled, its partial file is removed.
+  Cancelled,
+}
+
+impl DownloadState {
+  pub fn is_finished(&self) -> bool {
+    matches!(
+      self,
+      DownloadState::Downloaded | DownloadState::DownloadFailed { .. } | DownloadState::Cancelled
+    )
+  }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DownloadProgress {
+  pub version: u32,
+  pub file_url: String,
+  pub local_file_path: String,
+  #[serde(flatten)]
+  pub state: DownloadState,
+  /// The size of the downloaded file in bytes, only set on [DownloadState::Downloaded].
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub total_bytes: Option<u64>,
+  /// The file id the downloaded file was verified against, only set on
+  /// [DownloadState::Downloaded] when the file was verified.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub verified_file_id: Option<String>,
+}
+
+impl DownloadProgress {
+  pub fn new(file_url: String, local_file_path: String, state: DownloadState) -> Self {
+    DownloadProgress {
+      version: FILE_PROGRESS_SCHEMA_VERSION,
+      file_url,
+      local_file_path,
+      state,
+      total_bytes: None,
+      verified_file_id: None,
+    }
+  }
+
+  /// The completion of the download of `total_bytes` bytes to the local file.
+  pub fn downloaded(
+    file_url: String,
+    local_file_path: String,
+    total_bytes: u64,
+    verified_file_id: Option<String>,
+  ) -> Self {
+    DownloadProgress {
+      total_bytes: Some(total_bytes),
+      verified_file_id,
+      ..Self::new(file_url, local_file_path, DownloadState::Downloaded)
+    }
+  }
+}
+
 #[derive(Debug)]
 pub struct ProgressNotifier {
   file_id: String,
   tx: broadcast::Sender<FileUploadState>,
   pub current_value: Option<FileUploadState>,
+  /// The seq of the last progress passed to [Self::notify_in_order].
+  last_seq: u64,
 }
 
 impl ProgressNotifier {
@@ -111,6 +374,7 @@ impl ProgressNotifier {
       file_id,
       tx,
       current_value: None,
+      last_seq: 0,
     }
   }
 
@@ -121,13 +385,113 @@ impl ProgressNotifier {
     }
   }
 
+  /// The number of live receivers subscribed to this notifier.
+  pub fn subscriber_count(&self) -> usize {
+    self.tx.receiver_count()
+  }
+
   pub async fn notify(&mut self, progress: FileUploadState) {
     self.current_value = Some(progress.clone());
     let _ = self.tx.send(progress);
   }
+
+  /// Notifies the state of the progress with the given [FileProgress::seq], unless a progress with
+  /// a higher seq was already notified, or the upload already finished. The progress may reach the
+  /// notifier out of order, this keeps the state moving forward. Returns whether it was notified.
+  pub async fn notify_in_order(&mut self, seq: u64, progress: FileUploadState) -> bool {
+    let is_finished = matches!(self.current_value, Some(FileUploadState::Finished { .. }));
+    if seq <= self.last_seq || is_finished {
+      return false;
+    }
+    self.last_seq = seq;
+    self.notify(progress).await;
+    true
+  }
 }
 
 pub struct CreatedUpload {
   pub url: String,
   pub file_id: String,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn file_progress_direction_serialization_test() {
+    let upload = FileProgress::new_progress("url".to_string(), "file_id".to_string(), 0.5);
+    let json = serde_json::to_value(&upload).unwrap();
+    assert_eq!(json["version"], FILE_PROGRESS_SCHEMA_VERSION);
+    assert_eq!(json["seq"], 0);
+    assert_eq!(json["direction"], "upload");
+    assert_eq!(json["progress"], 0.5);
+
+    let download = FileProgress::new_error("url".to_string(), "file_id".to_string(), "err".into())
+      .with_direction(TransferDirection::Download);
+    let json = serde_json::to_value(&download).unwrap();
+    assert_eq!(json["version"], FILE_PROGRESS_SCHEMA_VERSION);
+    assert_eq!(json["direction"], "download");
+    assert_eq!(json["error"], "err");
+    assert!(json.get("total_bytes").is_none());
+    assert!(json.get("paused_reasons").is_none());
+    assert!(json.get("completion_pending").is_none());
+
+    let paused = FileProgress::new_progress("url".to_string(), "file_id".to_string(), 0.5)
+      .with_paused_reasons(2);
+    let json = serde_json::to_value(&paused).unwrap();
+    assert_eq!(json["paused_reasons"], 2);
+
+    let pending = FileProgress::new_error("url".to_string(), "file_id".to_string(), "err".into())
+      .with_completion_pending();
+    let json = serde_json::to_value(&pending).unwrap();
+    assert_eq!(json["error"], "err");
+    assert_eq!(json["completion_pending"], true);
+
+    let finished = FileProgress::new_progress("url".to_string(), "file_id".to_string(), 1.0)
+      .with_summary(1024, Duration::from_millis(1500));
+    let json = serde_json::to_value(&finished).unwrap();
+    assert_eq!(json["total_bytes"], 1024);
+    assert_eq!(json["duration_ms"], 1500);
+  }
+
+  #[test]
+  fn download_progress_serialization_test() {
+    let downloading = DownloadProgress::new(
+      "url".to_string(),
+      "path".to_string(),
+      DownloadState::Downloading,
+    );
+    let json = serde_json::to_value(&downloading).unwrap();
+    assert_eq!(json["version"], FILE_PROGRESS_SCHEMA_VERSION);
+    assert_eq!(json["state"], "downloading");
+    assert!(json.get("total_bytes").is_none());
+    assert!(json.get("verified_file_id").is_none());
+
+    let downloaded = DownloadProgress::downloaded(
+      "url".to_string(),
+      "path".to_string(),
+      1024,
+      Some("file_id".to_string()),
+    );
+    let json = serde_json::to_value(&downloaded).unwrap();
+    assert_eq!(json["state"], "downloaded");
+    assert_eq!(json["local_file_path"], "path");
+    assert_eq!(json["total_bytes"], 1024);
+    assert_eq!(json["verified_file_id"], "file_id");
+  }
+
+  #[test]
+  fn file_progress_workspace_id_serialization_test() {
+    let progress = FileProgress::new_progress("url".to_string(), "file_id".to_string(), 0.5)
+      .with_workspace_id("workspace_id".to_string());
+    let json = serde_json::to_value(&progress).unwrap();
+    assert_eq!(json["version"], FILE_PROGRESS_SCHEMA_VERSION);
+    assert_eq!(json["workspace_id"], "workspace_id");
+    assert_eq!(json["file_id"], "file_id");
+
+    let progress = FileProgress::new_progress("url".to_string(), "file_id".to_string(), 0.5);
+    let json = serde_json::to_value(&progress).unwrap();
+    assert!(json.get("workspace_id").is_none());
+  }
+}
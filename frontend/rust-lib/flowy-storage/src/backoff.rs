@@ -0,0 +1,14 @@
+use std::time::Duration;
+
+/// The longest wait between two attempts, however many attempts failed before.
+pub const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Returns the wait before the retry numbered `attempt`, starting at 1: the `delay` doubled for
+/// each previous retry, capped at [MAX_RETRY_BACKOFF]. A `delay` above the cap is kept as is. It
+/// never overflows, whatever number of attempts the config allows.
+pub fn retry_backoff(delay: Duration, attempt: u32) -> Duration {
+  let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+  delay
+    .saturating_mul(factor)
+    .min(MAX_RETRY_BACKOFF.max(delay))
+}
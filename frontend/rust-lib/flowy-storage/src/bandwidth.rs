@@ -0,0 +1,183 @@
+use crate::clock::Clock;
+use dashmap::DashMap;
+use flowy_storage_pub::chunked_byte::ByteBudget;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// [BandwidthLimiter] paces the uploaded bytes to stay under a rate.
+///
+/// Each call to [BandwidthLimiter::reserve] books a time slot proportional to the number of bytes,
+/// and returns how long the caller has to wait before sending them. A rate of zero means unlimited.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+  bytes_per_sec: AtomicU64,
+  next_available: Mutex<Instant>,
+}
+
+impl BandwidthLimiter {
+  pub fn new(bytes_per_sec: Option<u64>, now: Instant) -> Self {
+    Self {
+      bytes_per_sec: AtomicU64::new(bytes_per_sec.unwrap_or(0)),
+      next_available: Mutex::new(now),
+    }
+  }
+
+  /// Updates the rate. The new rate applies to the next reservation.
+  pub fn set_limit(&self, bytes_per_sec: Option<u64>) {
+    self
+      .bytes_per_sec
+      .store(bytes_per_sec.unwrap_or(0), Ordering::SeqCst);
+  }
+
+  pub fn limit(&self) -> Option<u64> {
+    match self.bytes_per_sec.load(Ordering::SeqCst) {
+      0 => None,
+      bytes_per_sec => Some(bytes_per_sec),
+    }
+  }
+
+  /// Reserves the bandwidth for the given number of bytes and returns the delay, from now, before
+  /// they can be sent.
+  pub fn reserve(&self, bytes: u64, now: Instant) -> Duration {
+    let bytes_per_sec = match self.limit() {
+      None => return Duration::ZERO,
+      Some(bytes_per_sec) => bytes_per_sec,
+    };
+
+    let mut next_available = self.next_available.lock().unwrap();
+    let start = (*next_available).max(now);
+    *next_available = start + Duration::from_secs_f64(bytes as f64 / bytes_per_sec as f64);
+    start - now
+  }
+}
+
+/// Waits until the bytes can be sent under all the given limiters. The limiters compose, so the
+/// effective rate is the lowest of them.
+pub async fn acquire_bandwidth(clock: &dyn Clock, limiters: &[&BandwidthLimiter], bytes: u64) {
+  let now = clock.now();
+  let delay = limiters
+    .iter()
+    .map(|limiter| limiter.reserve(bytes, now))
+    .max()
+    .unwrap_or_default();
+  if !delay.is_zero() {
+    clock.sleep(delay).await;
+  }
+}
+
+/// [ThroughputMeter] measures the rate of the sent bytes over a sliding window. The samples older
+/// than the window are dropped, so the rate decays to zero once nothing is sent.
+#[derive(Debug)]
+pub struct ThroughputMeter {
+  window: Duration,
+  samples: Mutex<VecDeque<(Instant, u64)>>,
+}
+
+impl ThroughputMeter {
+  pub fn new(window: Duration) -> Self {
+    Self {
+      window,
+      samples: Mutex::new(VecDeque::new()),
+    }
+  }
+
+  /// Records the bytes sent at the given time.
+  pub fn record(&self, bytes: u64, now: Instant) {
+    let mut samples = self.samples.lock().unwrap();
+    samples.push_back((now, bytes));
+    self.drop_expired(&mut samples, now);
+  }
+
+  /// Returns the bytes per second sent over the window ending at the given time.
+  pub fn bytes_per_sec(&self, now: Instant) -> u64 {
+    let mut samples = self.samples.lock().unwrap();
+    self.drop_expired(&mut samples, now);
+    if self.window.is_zero() {
+      return 0;
+    }
+    let bytes = samples.iter().map(|(_, bytes)| bytes).sum::<u64>();
+    (bytes as f64 / self.window.as_secs_f64()) as u64
+  }
+
+  fn drop_expired(&self, samples: &mut VecDeque<(Instant, u64)>, now: Instant) {
+    while let Some((sent_at, _)) = samples.front() {
+      if now.saturating_duration_since(*sent_at) < self.window {
+        break;
+      }
+      samples.pop_front();
+    }
+  }
+}
+
+/// The bandwidth of the uploads: a global limit shared by all the uploads, an optional limit for
+/// each file, the budget of the bytes in flight, and the measured throughput of all the uploads.
+#[derive(Debug)]
+pub(crate) struct UploadBandwidth {
+  clock: Arc<dyn Clock>,
+  global: BandwidthLimiter,
+  files: DashMap<String, Arc<BandwidthLimiter>>,
+  throughput: ThroughputMeter,
+  in_flight: Option<ByteBudget>,
+}
+
+impl UploadBandwidth {
+  pub(crate) fn new(
+    global_limit: Option<u64>,
+    throughput_window: Duration,
+    max_in_flight_bytes: Option<usize>,
+    clock: Arc<dyn Clock>,
+  ) -> Self {
+    Self {
+      global: BandwidthLimiter::new(global_limit, clock.now()),
+      clock,
+      files: DashMap::new(),
+      throughput: ThroughputMeter::new(throughput_window),
+      in_flight: max_in_flight_bytes.map(ByteBudget::new),
+    }
+  }
+
+  /// Returns the budget shared by the chunk readers of the uploads, see
+  /// [crate::config::StorageManagerConfig::max_in_flight_bytes].
+  pub(crate) fn in_flight_budget(&self) -> Option<ByteBudget> {
+    self.in_flight.clone()
+  }
+
+  /// Records the bytes of an uploaded part.
+  pub(crate) fn record_sent(&self, bytes: u64) {
+    self.throughput.record(bytes, self.clock.now());
+  }
+
+  /// Returns the upload rate of all the uploads, in bytes per second.
+  pub(crate) fn throughput_bps(&self) -> u64 {
+    self.throughput.bytes_per_sec(self.clock.now())
+  }
+
+  pub(crate) fn set_global_limit(&self, bytes_per_sec: Option<u64>) {
+    self.global.set_limit(bytes_per_sec);
+  }
+
+  pub(crate) fn set_file_limit(&self, file_id: &str, bytes_per_sec: Option<u64>) {
+    self.file_limiter(file_id).set_limit(bytes_per_sec);
+  }
+
+  pub(crate) fn remove_file_limit(&self, file_id: &str) {
+    self.files.remove(file_id);
+  }
+
+  /// Returns the limiter of the file. The limiter is kept for the file, so that a limit set while
+  /// uploading applies to the remaining parts.
+  pub(crate) fn file_limiter(&self, file_id: &str) -> Arc<BandwidthLimiter> {
+    self
+      .files
+      .entry(file_id.to_string())
+      .or_insert_with(|| Arc::new(BandwidthLimiter::new(None, self.clock.now())))
+      .clone()
+  }
+
+  /// Waits until the bytes of the file can be sent under both the global and the file limit.
+  pub(crate) async fn acquire(&self, file_limiter: &BandwidthLimiter, bytes: u64) {
+    acquire_bandwidth(self.clock.as_ref(), &[&self.global, file_limiter], bytes).await;
+  }
+}
@@ -0,0 +1,14 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flowy_storage_pub::cloud::{ChecksumAlgorithm, PartChecksum};
+use sha2::{Digest, Sha256};
+
+/// Computes the checksum of the part with the algorithm. The value is the base64 of the digest,
+/// the CRC32 digest in big-endian order.
+pub fn part_checksum(algorithm: ChecksumAlgorithm, body: &[u8]) -> PartChecksum {
+  let value = match algorithm {
+    ChecksumAlgorithm::Crc32 => STANDARD.encode(crc32fast::hash(body).to_be_bytes()),
+    ChecksumAlgorithm::Sha256 => STANDARD.encode(Sha256::digest(body)),
+  };
+  PartChecksum { algorithm, value }
+}
@@ -0,0 +1,125 @@
+use futures_util::future::BoxFuture;
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::oneshot;
+
+/// [Clock] is the source of time of the storage manager: the retry backoffs, the pauses between
+/// the server requests, the bandwidth pacing, the measured durations and the stored timestamps all
+/// go through it. The tests inject a [MockClock] to control the time instead of sleeping. Only the
+/// backoff of acquiring a sqlite connection waits in real time, as the pool frees up in real time,
+/// and [crate::file_cache::HashTempFileNaming] salts the names with the real time, which no
+/// timing depends on.
+pub trait Clock: Debug + Send + Sync {
+  /// The current monotonic time, used to measure durations.
+  fn now(&self) -> Instant;
+  /// The current wall-clock time, used to timestamp events.
+  fn system_now(&self) -> SystemTime;
+  /// Waits until the duration elapsed.
+  fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// The real time, backed by the tokio timer.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> Instant {
+    Instant::now()
+  }
+
+  fn system_now(&self) -> SystemTime {
+    SystemTime::now()
+  }
+
+  fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+    Box::pin(tokio::time::sleep(duration))
+  }
+}
+
+/// A clock that only moves when [MockClock::advance] is called. A sleep completes once the clock
+/// was advanced past its deadline, so anything sleeping on it waits until the test advances it.
+#[derive(Debug)]
+pub struct MockClock {
+  start: Instant,
+  system_start: SystemTime,
+  state: Mutex<MockClockState>,
+}
+
+#[derive(Debug, Default)]
+struct MockClockState {
+  elapsed: Duration,
+  /// The pending sleeps, as their deadline and the sender that wakes them up.
+  sleepers: Vec<(Duration, oneshot::Sender<()>)>,
+  /// The durations of all the requested sleeps, in order.
+  sleeps: Vec<Duration>,
+}
+
+impl Default for MockClock {
+  fn default() -> Self {
+    Self {
+      start: Instant::now(),
+      system_start: SystemTime::now(),
+      state: Default::default(),
+    }
+  }
+}
+
+impl MockClock {
+  /// Moves the clock forward and wakes up the sleeps whose deadline passed.
+  pub fn advance(&self, duration: Duration) {
+    let mut state = self.state.lock().unwrap();
+    state.elapsed += duration;
+    let elapsed = state.elapsed;
+    let (due, pending) = std::mem::take(&mut state.sleepers)
+      .into_iter()
+      .partition(|(deadline, _)| *deadline <= elapsed);
+    state.sleepers = pending;
+    for (_, sender) in due {
+      let _ = sender.send(());
+    }
+  }
+
+  /// The time the clock was advanced by since it was created.
+  pub fn elapsed(&self) -> Duration {
+    self.state.lock().unwrap().elapsed
+  }
+
+  /// The durations of all the sleeps requested so far, in order.
+  pub fn sleeps(&self) -> Vec<Duration> {
+    self.state.lock().unwrap().sleeps.clone()
+  }
+
+  /// The number of sleeps waiting for the clock to advance.
+  pub fn pending_sleeps(&self) -> usize {
+    let mut state = self.state.lock().unwrap();
+    // A dropped sleep is no longer waiting.
+    state.sleepers.retain(|(_, sender)| !sender.is_closed());
+    state.sleepers.len()
+  }
+}
+
+impl Clock for MockClock {
+  fn now(&self) -> Instant {
+    self.start + self.elapsed()
+  }
+
+  fn system_now(&self) -> SystemTime {
+    self.system_start + self.elapsed()
+  }
+
+  fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+    let mut state = self.state.lock().unwrap();
+    state.sleeps.push(duration);
+    if duration.is_zero() {
+      return Box::pin(async {});
+    }
+    let (sender, receiver) = oneshot::channel();
+    let deadline = state.elapsed + duration;
+    state.sleepers.push((deadline, sender));
+    Box::pin(async move {
+      // The sender is only dropped with the clock, the sleep is over then.
+      let _ = receiver.await;
+    })
+  }
+}
@@ -0,0 +1,524 @@
+use crate::clock::{Clock, SystemClock};
+use crate::downloader::{DownloadPathFormat, WorkspaceDownloadPathFormat};
+use crate::file_cache::{
+  FsTempFileRemover, HashTempFileNaming, TempFileNaming, TempFileRemover, DEFAULT_COPY_BUFFER_SIZE,
+};
+use crate::file_id::FileIdStrategy;
+use crate::quota::UploadQuota;
+use crate::spawner::{Spawner, TokioSpawner};
+use flowy_storage_pub::chunked_byte::MIN_CHUNK_SIZE;
+use mime_guess::mime::{self, Mime};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How the progress of the uploads reaches the per-file notifiers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProgressFanOut {
+  /// A relay task forwards the progress of the global stream to the per-file notifiers.
+  #[default]
+  Relay,
+  /// The uploads notify the per-file notifiers directly. The global stream only serves the
+  /// consumers of the progress port.
+  Direct,
+}
+
+/// What happens to the temp file of an upload once the upload completed. The kept files count
+/// against no quota, the download cache isn't bounded yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TempFilePolicy {
+  /// Deletes the temp file.
+  #[default]
+  Delete,
+  /// Keeps the temp file where it is, as the local copy of the uploaded object. Downloading the
+  /// object then uses it instead of fetching it.
+  KeepAsCache,
+  /// Moves the temp file to the download cache, where
+  /// [crate::manager::StorageManager::default_download_path] places the object.
+  MoveToCache,
+}
+
+/// [StorageManagerConfig] controls the behavior of the [crate::manager::StorageManager].
+#[derive(Debug, Clone)]
+pub struct StorageManagerConfig {
+  /// When true, a completed upload is only reused after checking that its object still exists on
+  /// the server. Otherwise, the local record is trusted.
+  pub verify_completed_upload: bool,
+  /// The number of parts read ahead from disk while the current part is uploading. Each upload
+  /// holds up to `prefetch_depth` extra parts in memory. Zero disables the prefetching.
+  pub prefetch_depth: usize,
+  /// The maximum upload rate in bytes per second shared by all the uploads. `None` means unlimited.
+  pub bandwidth_limit: Option<u64>,
+  /// The window over which [crate::manager::StorageManager::current_throughput_bps] averages the
+  /// uploaded bytes.
+  pub throughput_window: Duration,
+  /// How often the local upload records are reconciled with the server. `None` disables the
+  /// reconciliation.
+  pub reconcile_interval: Option<Duration>,
+  /// When true, the background loops are driven by the caller instead of running on their own: the
+  /// progress relay only forwards the progress on
+  /// [crate::manager::StorageManager::tick_relay], the reconciliation only runs on
+  /// [crate::manager::StorageManager::reconcile_uploads], and
+  /// [crate::manager::StorageManager::initialize] leaves the unfinished uploads to
+  /// [crate::manager::StorageManager::run_startup_resume]. It's meant for the tests that need to
+  /// control when they run.
+  pub manual_background_tasks: bool,
+  /// The number of records checked by each reconciliation pass.
+  pub reconcile_batch_size: usize,
+  /// The delay between two server requests of a reconciliation pass.
+  pub reconcile_request_interval: Duration,
+  /// The maximum number of uploads running at the same time. The other uploads wait in the queue.
+  pub max_concurrent_uploads: usize,
+  /// The maximum bytes of the parts held in memory across all the uploads, including the parts read
+  /// ahead by [Self::prefetch_depth]. A part is only read once it fits, so the uploads wait for
+  /// each other when it's reached. A part larger than the budget is read alone. `None` means
+  /// unbounded.
+  pub max_in_flight_bytes: Option<usize>,
+  /// The maximum number of uploads not completed yet, across all the workspaces. Creating an
+  /// upload beyond it fails with [crate::error::StorageError::TooManyPendingUploads], for the
+  /// caller to retry later. `None` means unbounded.
+  pub max_pending_uploads: Option<usize>,
+  /// Decides whether an upload can be created, e.g. against the limits of the folders. Creating an
+  /// upload it vetoes fails with [crate::error::StorageError::QuotaExceeded]. `None` accepts all
+  /// the uploads, only the storage limit of the server applies.
+  pub upload_quota: Option<Arc<dyn UploadQuota>>,
+  /// How long a failed upload is kept for the user to retry it. Past it, the upload is purged along
+  /// with its parts and temp file, unless the user pinned it. `None` keeps the failed uploads.
+  pub failed_upload_retention: Option<Duration>,
+  /// The failures of the uploads following the first one within the window are coalesced into a
+  /// single notification, see
+  /// [crate::notification::StorageNotification::UploadFailuresChanged]. `None` notifies every
+  /// failure.
+  pub failure_notification_window: Option<Duration>,
+  /// The maximum number of rows kept in the upload log, see
+  /// [crate::manager::StorageManager::upload_log]. The oldest rows are pruned first.
+  pub upload_log_max_rows: usize,
+  /// How long the rows of the upload log are kept. `None` only bounds the log by
+  /// `upload_log_max_rows`.
+  pub upload_log_max_age: Option<Duration>,
+  /// The number of recent progress events replayed to a consumer attaching to the progress stream.
+  /// Zero disables the replay.
+  pub progress_history_size: usize,
+  /// The minimum time between two progress updates of an upload, so that fast uploads don't flood
+  /// the consumers. The terminal states, finished or failed, are always emitted right away.
+  pub progress_min_interval: Duration,
+  /// The progress change, between 0 and 1, that emits an update before `progress_min_interval`
+  /// elapsed. `None` means the updates are only paced by the interval.
+  pub progress_min_delta: Option<f64>,
+  /// The progress, between 0 and 0.9, emitted as soon as an upload is queued and again when it
+  /// starts, so that the bar moves before the first part is uploaded. The following progress never
+  /// goes below it. Zero emits no progress until the first part.
+  pub progress_floor: f64,
+  /// The minimum time between two progress updates of a file sent by
+  /// [crate::manager::StorageManager::query_file_state], so that querying the files on each render
+  /// doesn't flood the progress stream. A changed state is always emitted right away.
+  pub file_state_emit_interval: Duration,
+  /// How long [crate::manager::StorageManager::subscribe_queue_depth] waits after a change of the
+  /// queue before emitting the status, so that a burst of changes emits a single status.
+  pub queue_status_debounce: Duration,
+  /// How long [crate::manager::StorageManager::uploads_stream] collects the changes of the uploads
+  /// before emitting them, so that an upload changing several times emits its last state only.
+  pub uploads_stream_debounce: Duration,
+  /// The size of the parts of a new upload. It's raised to the minimum part size of the backend
+  /// when smaller.
+  pub chunk_size: usize,
+  /// The maximum number of attempts of each part of an upload. A transient failure of a part sends
+  /// the part again after a backoff, the upload only fails once the attempts are exhausted. The
+  /// parts already uploaded are never sent again.
+  pub part_max_attempts: u32,
+  /// The delay before the first retry of a part, doubled for each following retry up to
+  /// [crate::backoff::MAX_RETRY_BACKOFF].
+  pub part_retry_delay: Duration,
+  /// The number of times an upload created with `upload_immediately` is queued again after a
+  /// failure, once its part attempts are exhausted. It's lower than the other kinds so that a
+  /// failing interactive upload reports its failure soon.
+  pub immediate_task_retries: u8,
+  /// The number of times an upload created without `upload_immediately` is queued again after a
+  /// failure.
+  pub task_retries: u8,
+  /// The number of times a resumed upload is queued again after a failure.
+  pub background_task_retries: u8,
+  /// The upper bound of the size of the parts of a new upload, `None` means unbounded. A part is
+  /// only recorded once it's fully uploaded, so an interrupted upload sends the in-progress part
+  /// again when it resumes. Bounding the part size bounds the bytes sent again, at the cost of more
+  /// part requests. It never goes below the minimum part size of the backend.
+  pub max_part_size: Option<usize>,
+  /// When true, a manifest listing the uploaded parts is stored for each completed upload, see
+  /// [crate::manifest::UploadManifest].
+  pub upload_manifest: bool,
+  /// When true, the manifest is also uploaded next to the object. Only used when
+  /// `upload_manifest` is enabled.
+  pub upload_manifest_sidecar: bool,
+  /// When true, an upload of content already uploaded under another parent dir of the workspace
+  /// links to that object instead of uploading the bytes again. The url of the link is the url of
+  /// the shared object, and deleting it only drops one of the references until the last one.
+  pub link_duplicate_uploads: bool,
+  /// What happens to the temp file of a completed upload.
+  pub temp_file_policy: TempFilePolicy,
+  /// The maximum number of attempts to delete the temp file of a completed upload. A file that
+  /// still can't be deleted is left to the orphan sweep of
+  /// [crate::manager::StorageManager::repair_storage].
+  pub temp_delete_max_attempts: u32,
+  /// The delay before the first retry of a temp file deletion, doubled for each following retry up
+  /// to [crate::backoff::MAX_RETRY_BACKOFF].
+  pub temp_delete_retry_delay: Duration,
+  /// How the progress reaches the per-file notifiers.
+  pub progress_fan_out: ProgressFanOut,
+  /// The maximum number of downloads running at the same time. The other downloads wait for a
+  /// running one to finish.
+  pub max_concurrent_downloads: usize,
+  /// The maximum number of attempts of a download. The transient failures are retried with an
+  /// exponential backoff, a missing object fails right away.
+  pub download_max_attempts: u32,
+  /// The delay before the first retry of a download, doubled for each following retry up to
+  /// [crate::backoff::MAX_RETRY_BACKOFF].
+  pub download_retry_delay: Duration,
+  /// The size of the ranges of a download, when the backend supports range requests. Otherwise
+  /// the object is fetched in a single request.
+  pub download_range_size: usize,
+  /// The maximum number of ranges of a download fetched at the same time.
+  pub download_range_concurrency: usize,
+  /// The number of ranges read by [crate::manager::StorageManager::read_range] kept in memory,
+  /// zero disables the cache.
+  pub range_cache_capacity: usize,
+  /// Only the ranges of at most this many bytes are cached, the larger ones are always fetched.
+  pub range_cache_max_len: u64,
+  /// The content type of an uploaded file whose type can't be guessed, neither from its extension
+  /// nor from its leading bytes.
+  pub fallback_content_type: Mime,
+  /// The size of the buffer used to copy a file to upload to the temporary storage. A larger
+  /// buffer speeds up the copy of large files on fast disks, a smaller one saves memory. It's
+  /// raised to [crate::file_cache::MIN_COPY_BUFFER_SIZE] when smaller.
+  pub temp_copy_buffer_size: usize,
+  /// Names the temporary copies of the files to upload.
+  pub temp_file_naming: Arc<dyn TempFileNaming>,
+  /// How the ids of the files to upload are derived. The content hash by default, see
+  /// [FileIdStrategy::Metadata] for the faster alternative and its tradeoff.
+  pub file_id_strategy: FileIdStrategy,
+  /// Removes the temporary copies of the files to upload.
+  pub temp_file_remover: Arc<dyn TempFileRemover>,
+  /// Places the downloads in the download cache, see
+  /// [crate::manager::StorageManager::default_download_path].
+  pub download_path_format: Arc<dyn DownloadPathFormat>,
+  /// The source of time of the delays and the measured durations.
+  pub clock: Arc<dyn Clock>,
+  /// Runs the background tasks, on the tokio runtime by default.
+  pub spawner: Arc<dyn Spawner>,
+}
+
+impl Default for StorageManagerConfig {
+  fn default() -> Self {
+    Self {
+      verify_completed_upload: false,
+      prefetch_depth: 0,
+      bandwidth_limit: None,
+      throughput_window: Duration::from_secs(5),
+      reconcile_interval: Some(Duration::from_secs(30 * 60)),
+      manual_background_tasks: false,
+      reconcile_batch_size: 20,
+      reconcile_request_interval: Duration::from_millis(200),
+      max_concurrent_uploads: 3,
+      max_in_flight_bytes: None,
+      max_pending_uploads: Some(5_000),
+      upload_quota: None,
+      failed_upload_retention: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+      failure_notification_window: Some(Duration::from_secs(5)),
+      upload_log_max_rows: 10_000,
+      upload_log_max_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+      progress_history_size: 50,
+      progress_min_interval: Duration::from_millis(100),
+      progress_min_delta: None,
+      progress_floor: 0.0,
+      file_state_emit_interval: Duration::from_secs(1),
+      queue_status_debounce: Duration::from_millis(100),
+      uploads_stream_debounce: Duration::from_millis(100),
+      chunk_size: MIN_CHUNK_SIZE,
+      part_max_attempts: 3,
+      part_retry_delay: Duration::from_millis(500),
+      immediate_task_retries: 2,
+      task_retries: 5,
+      background_task_retries: 5,
+      max_part_size: None,
+      upload_manifest: false,
+      upload_manifest_sidecar: false,
+      link_duplicate_uploads: false,
+      temp_file_policy: TempFilePolicy::default(),
+      temp_delete_max_attempts: 3,
+      temp_delete_retry_delay: Duration::from_millis(100),
+      progress_fan_out: ProgressFanOut::default(),
+      fallback_content_type: mime::APPLICATION_OCTET_STREAM,
+      temp_copy_buffer_size: DEFAULT_COPY_BUFFER_SIZE,
+      temp_file_naming: Arc::new(HashTempFileNaming::default()),
+      file_id_strategy: FileIdStrategy::default(),
+      temp_file_remover: Arc::new(FsTempFileRemover),
+      download_path_format: Arc::new(WorkspaceDownloadPathFormat),
+      clock: Arc::new(SystemClock),
+      spawner: Arc::new(TokioSpawner),
+      max_concurrent_downloads: 3,
+      download_max_attempts: 3,
+      download_retry_delay: Duration::from_secs(1),
+      download_range_size: 8 * 1024 * 1024,
+      download_range_concurrency: 4,
+      range_cache_capacity: 0,
+      range_cache_max_len: 256 * 1024,
+    }
+  }
+}
+
+impl StorageManagerConfig {
+  pub fn verify_completed_upload(mut self, verify_completed_upload: bool) -> Self {
+    self.verify_completed_upload = verify_completed_upload;
+    self
+  }
+
+  pub fn prefetch_depth(mut self, prefetch_depth: usize) -> Self {
+    self.prefetch_depth = prefetch_depth;
+    self
+  }
+
+  pub fn bandwidth_limit(mut self, bytes_per_sec: Option<u64>) -> Self {
+    self.bandwidth_limit = bytes_per_sec;
+    self
+  }
+
+  pub fn throughput_window(mut self, throughput_window: Duration) -> Self {
+    self.throughput_window = throughput_window;
+    self
+  }
+
+  pub fn reconcile_interval(mut self, interval: Option<Duration>) -> Self {
+    self.reconcile_interval = interval;
+    self
+  }
+
+  pub fn manual_background_tasks(mut self, manual: bool) -> Self {
+    self.manual_background_tasks = manual;
+    self
+  }
+
+  pub fn reconcile_batch_size(mut self, batch_size: usize) -> Self {
+    self.reconcile_batch_size = batch_size;
+    self
+  }
+
+  pub fn reconcile_request_interval(mut self, interval: Duration) -> Self {
+    self.reconcile_request_interval = interval;
+    self
+  }
+
+  pub fn max_concurrent_uploads(mut self, max_concurrent_uploads: usize) -> Self {
+    self.max_concurrent_uploads = max_concurrent_uploads;
+    self
+  }
+
+  pub fn max_in_flight_bytes(mut self, max_in_flight_bytes: Option<usize>) -> Self {
+    self.max_in_flight_bytes = max_in_flight_bytes;
+    self
+  }
+
+  pub fn max_pending_uploads(mut self, max_pending_uploads: Option<usize>) -> Self {
+    self.max_pending_uploads = max_pending_uploads;
+    self
+  }
+
+  pub fn upload_quota(mut self, upload_quota: Option<Arc<dyn UploadQuota>>) -> Self {
+    self.upload_quota = upload_quota;
+    self
+  }
+
+  pub fn failed_upload_retention(mut self, retention: Option<Duration>) -> Self {
+    self.failed_upload_retention = retention;
+    self
+  }
+
+  pub fn failure_notification_window(mut self, window: Option<Duration>) -> Self {
+    self.failure_notification_window = window;
+    self
+  }
+
+  pub fn upload_log_max_rows(mut self, max_rows: usize) -> Self {
+    self.upload_log_max_rows = max_rows;
+    self
+  }
+
+  pub fn upload_log_max_age(mut self, max_age: Option<Duration>) -> Self {
+    self.upload_log_max_age = max_age;
+    self
+  }
+
+  pub fn progress_history_size(mut self, progress_history_size: usize) -> Self {
+    self.progress_history_size = progress_history_size;
+    self
+  }
+
+  pub fn progress_min_interval(mut self, interval: Duration) -> Self {
+    self.progress_min_interval = interval;
+    self
+  }
+
+  pub fn progress_min_delta(mut self, delta: Option<f64>) -> Self {
+    self.progress_min_delta = delta;
+    self
+  }
+
+  /// The 0.1 above the floor is reserved for completing the upload.
+  pub fn progress_floor(mut self, floor: f64) -> Self {
+    self.progress_floor = floor.clamp(0.0, 0.9);
+    self
+  }
+
+  pub fn file_state_emit_interval(mut self, interval: Duration) -> Self {
+    self.file_state_emit_interval = interval;
+    self
+  }
+
+  pub fn queue_status_debounce(mut self, debounce: Duration) -> Self {
+    self.queue_status_debounce = debounce;
+    self
+  }
+
+  pub fn uploads_stream_debounce(mut self, debounce: Duration) -> Self {
+    self.uploads_stream_debounce = debounce;
+    self
+  }
+
+  pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+    self.chunk_size = chunk_size;
+    self
+  }
+
+  pub fn part_max_attempts(mut self, max_attempts: u32) -> Self {
+    self.part_max_attempts = max_attempts;
+    self
+  }
+
+  pub fn part_retry_delay(mut self, delay: Duration) -> Self {
+    self.part_retry_delay = delay;
+    self
+  }
+
+  pub fn immediate_task_retries(mut self, retries: u8) -> Self {
+    self.immediate_task_retries = retries;
+    self
+  }
+
+  pub fn task_retries(mut self, retries: u8) -> Self {
+    self.task_retries = retries;
+    self
+  }
+
+  pub fn background_task_retries(mut self, retries: u8) -> Self {
+    self.background_task_retries = retries;
+    self
+  }
+
+  pub fn max_part_size(mut self, max_part_size: Option<usize>) -> Self {
+    self.max_part_size = max_part_size;
+    self
+  }
+
+  pub fn upload_manifest(mut self, upload_manifest: bool) -> Self {
+    self.upload_manifest = upload_manifest;
+    self
+  }
+
+  pub fn upload_manifest_sidecar(mut self, upload_manifest_sidecar: bool) -> Self {
+    self.upload_manifest_sidecar = upload_manifest_sidecar;
+    self
+  }
+
+  pub fn link_duplicate_uploads(mut self, link_duplicate_uploads: bool) -> Self {
+    self.link_duplicate_uploads = link_duplicate_uploads;
+    self
+  }
+
+  pub fn temp_file_policy(mut self, temp_file_policy: TempFilePolicy) -> Self {
+    self.temp_file_policy = temp_file_policy;
+    self
+  }
+
+  pub fn temp_delete_max_attempts(mut self, max_attempts: u32) -> Self {
+    self.temp_delete_max_attempts = max_attempts;
+    self
+  }
+
+  pub fn temp_delete_retry_delay(mut self, delay: Duration) -> Self {
+    self.temp_delete_retry_delay = delay;
+    self
+  }
+
+  pub fn progress_fan_out(mut self, progress_fan_out: ProgressFanOut) -> Self {
+    self.progress_fan_out = progress_fan_out;
+    self
+  }
+
+  pub fn max_concurrent_downloads(mut self, max_concurrent_downloads: usize) -> Self {
+    self.max_concurrent_downloads = max_concurrent_downloads;
+    self
+  }
+
+  pub fn download_max_attempts(mut self, max_attempts: u32) -> Self {
+    self.download_max_attempts = max_attempts;
+    self
+  }
+
+  pub fn download_retry_delay(mut self, delay: Duration) -> Self {
+    self.download_retry_delay = delay;
+    self
+  }
+
+  pub fn download_range_size(mut self, range_size: usize) -> Self {
+    self.download_range_size = range_size;
+    self
+  }
+
+  pub fn download_range_concurrency(mut self, concurrency: usize) -> Self {
+    self.download_range_concurrency = concurrency;
+    self
+  }
+
+  pub fn range_cache(mut self, capacity: usize, max_len: u64) -> Self {
+    self.range_cache_capacity = capacity;
+    self.range_cache_max_len = max_len;
+    self
+  }
+
+  pub fn fallback_content_type(mut self, content_type: Mime) -> Self {
+    self.fallback_content_type = content_type;
+    self
+  }
+
+  pub fn temp_copy_buffer_size(mut self, buffer_size: usize) -> Self {
+    self.temp_copy_buffer_size = buffer_size;
+    self
+  }
+
+  pub fn temp_file_naming(mut self, naming: Arc<dyn TempFileNaming>) -> Self {
+    self.temp_file_naming = naming;
+    self
+  }
+
+  pub fn file_id_strategy(mut self, strategy: FileIdStrategy) -> Self {
+    self.file_id_strategy = strategy;
+    self
+  }
+
+  pub fn temp_file_remover(mut self, remover: Arc<dyn TempFileRemover>) -> Self {
+    self.temp_file_remover = remover;
+    self
+  }
+
+  pub fn download_path_format(mut self, format: Arc<dyn DownloadPathFormat>) -> Self {
+    self.download_path_format = format;
+    self
+  }
+
+  pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  pub fn spawner(mut self, spawner: Arc<dyn Spawner>) -> Self {
+    self.spawner = spawner;
+    self
+  }
+}
@@ -0,0 +1,98 @@
+#[cfg(feature = "diagnostics")]
+use dashmap::DashMap;
+use std::time::{Duration, SystemTime};
+
+/// The timing of an uploaded part, see [crate::manager::StorageManager::upload_timing].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartTiming {
+  pub part_number: i32,
+  /// The size of the part in bytes.
+  pub size: usize,
+  pub started_at: SystemTime,
+  pub finished_at: SystemTime,
+  /// False when the part request failed or was cancelled.
+  pub succeeded: bool,
+}
+
+impl PartTiming {
+  pub fn duration(&self) -> Duration {
+    self
+      .finished_at
+      .duration_since(self.started_at)
+      .unwrap_or_default()
+  }
+}
+
+/// When an upload was queued, started and finished, see
+/// [crate::manager::StorageManager::upload_detail]. It tells the time spent waiting in the queue
+/// apart from the time spent uploading.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UploadDetail {
+  pub queued_at: SystemTime,
+  /// When the last attempt started. A retried upload waits again in the queue, so the wait of a
+  /// retried upload includes its failed attempts.
+  pub started_at: Option<SystemTime>,
+  /// When the last attempt succeeded.
+  pub finished_at: Option<SystemTime>,
+}
+
+impl UploadDetail {
+  pub fn new(queued_at: SystemTime) -> Self {
+    Self {
+      queued_at,
+      started_at: None,
+      finished_at: None,
+    }
+  }
+
+  /// The time spent in the queue before the last attempt started. `None` until it starts.
+  pub fn queue_wait(&self) -> Option<Duration> {
+    let started_at = self.started_at?;
+    Some(
+      started_at
+        .duration_since(self.queued_at)
+        .unwrap_or_default(),
+    )
+  }
+
+  /// The time spent uploading in the last attempt. `None` until it finishes.
+  pub fn upload_duration(&self) -> Option<Duration> {
+    let finished_at = self.finished_at?;
+    Some(
+      finished_at
+        .duration_since(self.started_at?)
+        .unwrap_or_default(),
+    )
+  }
+}
+
+/// [PartTimings] keeps the timings of the uploaded parts, keyed by file id. It only records them
+/// with the `diagnostics` feature, otherwise it does nothing.
+#[derive(Default)]
+pub struct PartTimings {
+  #[cfg(feature = "diagnostics")]
+  timings: DashMap<String, Vec<PartTiming>>,
+}
+
+impl PartTimings {
+  #[cfg_attr(not(feature = "diagnostics"), allow(unused_variables))]
+  pub fn record(&self, file_id: &str, timing: PartTiming) {
+    #[cfg(feature = "diagnostics")]
+    self
+      .timings
+      .entry(file_id.to_string())
+      .or_default()
+      .push(timing);
+  }
+
+  /// Returns the timings of the parts of the file, in the order they were uploaded. A part that
+  /// was uploaded again, e.g. after a failure, appears once per attempt.
+  #[cfg(feature = "diagnostics")]
+  pub fn timings(&self, file_id: &str) -> Vec<PartTiming> {
+    self
+      .timings
+      .get(file_id)
+      .map(|timings| timings.clone())
+      .unwrap_or_default()
+  }
+}
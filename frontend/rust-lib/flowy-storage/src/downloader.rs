@@ -0,0 +1,828 @@
+use crate::backoff::retry_backoff;
+use crate::config::StorageManagerConfig;
+use crate::file_id::verify_file_id;
+use crate::manager::{
+  acquire_sqlite_connection, parse_object_url, unix_timestamp, StorageUserService,
+};
+use crate::spawner::Spawner;
+use crate::sqlite_sql::{delete_download_file, upsert_download_file, DownloadFileTable};
+use bytes::{Bytes, BytesMut};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use flowy_error::{FlowyError, FlowyResult};
+use flowy_storage_pub::cloud::StorageCloudService;
+use flowy_storage_pub::storage::{DownloadProgress, DownloadState};
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{broadcast, watch, OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, trace, warn};
+
+pub(crate) type DownloadNotifier = broadcast::Sender<DownloadProgress>;
+
+/// Receives the state of a download. It starts with [DownloadState::Queued] and ends with either
+/// [DownloadState::Downloaded] or [DownloadState::DownloadFailed].
+pub type DownloadStateReceiver = watch::Receiver<DownloadState>;
+
+/// The priority of a queued download. The queued downloads start by priority, then in the order
+/// they were queued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DownloadPriority {
+  /// A download the user may need soon, e.g. the attachments of an opened page. It yields to the
+  /// user-initiated downloads.
+  Prefetch = 0,
+  /// A download the user is waiting for.
+  UserInitiated = 1,
+}
+
+impl From<i32> for DownloadPriority {
+  fn from(value: i32) -> Self {
+    match value {
+      0 => DownloadPriority::Prefetch,
+      _ => DownloadPriority::UserInitiated,
+    }
+  }
+}
+
+/// What a download does when its local file already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExistingFilePolicy {
+  /// Keeps the existing file without checking it.
+  Skip,
+  /// Downloads the object again and replaces the existing file.
+  Overwrite,
+  /// Keeps the existing file if its content matches the file id of the url, and downloads the
+  /// object again otherwise. The file is kept unchecked when the url carries no file id.
+  #[default]
+  VerifyThenSkip,
+}
+
+/// [DownloadPathFormat] decides where the object of a url lands in the download cache, see
+/// [crate::manager::StorageManager::default_download_path]. The path is relative to the cache
+/// directory, and must stay the same for the same object so that it's only downloaded once.
+pub trait DownloadPathFormat: Debug + Send + Sync {
+  fn relative_path(&self, workspace_id: &str, parent_dir: &str, file_id: &str) -> PathBuf;
+}
+
+/// Places the object under directories named after its workspace and parent dir, so that the same
+/// file id in another workspace or parent dir doesn't collide.
+#[derive(Debug, Default)]
+pub struct WorkspaceDownloadPathFormat;
+
+impl DownloadPathFormat for WorkspaceDownloadPathFormat {
+  fn relative_path(&self, workspace_id: &str, parent_dir: &str, file_id: &str) -> PathBuf {
+    [workspace_id, parent_dir, file_id]
+      .iter()
+      .map(|component| path_component(component))
+      .collect()
+  }
+}
+
+/// Returns the component as is when it's a plain file name, or its hash otherwise, so that a
+/// component can't escape the cache directory or hold characters some platforms reject.
+fn path_component(component: &str) -> String {
+  let is_plain = !component.is_empty()
+    && component != "."
+    && component != ".."
+    && component
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+  if is_plain {
+    component.to_string()
+  } else {
+    format!("{:016x}", fxhash::hash64(component))
+  }
+}
+
+/// A running download, keyed by its url so that the concurrent requests of the same url share it.
+struct ActiveDownload {
+  /// The seq of the [DownloadTask] of the download.
+  seq: u64,
+  local_file_path: String,
+  state: watch::Sender<DownloadState>,
+  cancel_token: CancellationToken,
+}
+
+struct DownloadTask {
+  url: String,
+  local_file_path: String,
+  priority: DownloadPriority,
+  existing_file_policy: ExistingFilePolicy,
+  /// Keeps the downloads of the same priority in the order they were queued.
+  seq: u64,
+  state: watch::Sender<DownloadState>,
+  cancel_token: CancellationToken,
+}
+
+impl Eq for DownloadTask {}
+
+impl PartialEq for DownloadTask {
+  fn eq(&self, other: &Self) -> bool {
+    self.url == other.url
+  }
+}
+
+impl PartialOrd for DownloadTask {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for DownloadTask {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self
+      .priority
+      .cmp(&other.priority)
+      .then_with(|| other.seq.cmp(&self.seq))
+  }
+}
+
+/// [FileDownloader] runs the downloads. They wait in a priority queue, and at most
+/// [StorageManagerConfig::max_concurrent_downloads] of them run at the same time. The queued
+/// downloads are recorded in sqlite, so they resume after a restart, see
+/// [FileDownloader::restore_tasks].
+pub(crate) struct FileDownloader {
+  config: Arc<StorageManagerConfig>,
+  cloud_service: Arc<dyn StorageCloudService>,
+  user_service: Arc<dyn StorageUserService>,
+  notifier: DownloadNotifier,
+  queue: Mutex<BinaryHeap<DownloadTask>>,
+  next_seq: AtomicU64,
+  /// Each running download holds a permit.
+  download_permits: Arc<Semaphore>,
+  /// The queued and running downloads, keyed by their url.
+  active_downloads: DashMap<String, ActiveDownload>,
+}
+
+impl FileDownloader {
+  pub fn new(
+    config: Arc<StorageManagerConfig>,
+    cloud_service: Arc<dyn StorageCloudService>,
+    user_service: Arc<dyn StorageUserService>,
+    notifier: DownloadNotifier,
+  ) -> Self {
+    let max_downloads = config.max_concurrent_downloads.max(1);
+    Self {
+      config,
+      cloud_service,
+      user_service,
+      notifier,
+      queue: Default::default(),
+      next_seq: Default::default(),
+      download_permits: Arc::new(Semaphore::new(max_downloads)),
+      active_downloads: Default::default(),
+    }
+  }
+
+  /// Queues the download of the object to the local file, unless the url is already downloading. A
+  /// request for the same url and another local file waits for the running download and copies its
+  /// file. A queued download gets the higher priority of the requests of its url. The policy tells
+  /// what to do when the local file already exists, it's ignored when joining a running download.
+  pub fn download(
+    self: &Arc<Self>,
+    url: String,
+    local_file_path: String,
+    priority: DownloadPriority,
+    existing_file_policy: ExistingFilePolicy,
+  ) -> DownloadStateReceiver {
+    self.queue_download(
+      url,
+      local_file_path,
+      priority,
+      existing_file_policy,
+      unix_timestamp(self.config.clock.system_now()),
+    )
+  }
+
+  /// Queues the downloads recorded in sqlite that are not queued yet, e.g. the downloads that were
+  /// interrupted by a restart. They use the default [ExistingFilePolicy].
+  pub fn restore_tasks(self: &Arc<Self>, records: Vec<DownloadFileTable>) {
+    let mut restored = 0;
+    for record in records {
+      if self.active_downloads.contains_key(&record.url) {
+        continue;
+      }
+      self.queue_download(
+        record.url,
+        record.local_file_path,
+        DownloadPriority::from(record.priority),
+        ExistingFilePolicy::default(),
+        record.created_at,
+      );
+      restored += 1;
+    }
+    info!("[File] prepare download task: {}", restored);
+  }
+
+  fn queue_download(
+    self: &Arc<Self>,
+    url: String,
+    local_file_path: String,
+    priority: DownloadPriority,
+    existing_file_policy: ExistingFilePolicy,
+    created_at: i64,
+  ) -> DownloadStateReceiver {
+    let seq = self
+      .next_seq
+      .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    let cancel_token = CancellationToken::new();
+    let (state_tx, state) = match self.active_downloads.entry(url.clone()) {
+      Entry::Occupied(entry) => {
+        let active = entry.get();
+        let state = active.state.subscribe();
+        let source_path = active.local_file_path.clone();
+        drop(entry);
+        self.raise_priority(&url, priority);
+        if source_path == local_file_path {
+          return state;
+        }
+        return copy_downloaded_file(
+          self.config.spawner.as_ref(),
+          state,
+          source_path,
+          local_file_path,
+        );
+      },
+      Entry::Vacant(entry) => {
+        let (state_tx, state) = watch::channel(DownloadState::Queued);
+        entry.insert(ActiveDownload {
+          seq,
+          local_file_path: local_file_path.clone(),
+          state: state_tx.clone(),
+          cancel_token: cancel_token.clone(),
+        });
+        (state_tx, state)
+      },
+    };
+
+    if let Err(err) = self.insert_record(&DownloadFileTable {
+      url: url.clone(),
+      local_file_path: local_file_path.clone(),
+      priority: priority as i32,
+      created_at,
+    }) {
+      // The download still runs, it's just not resumed after a restart.
+      warn!("[File] record download {} failed: {}", url, err);
+    }
+
+    let task = DownloadTask {
+      url,
+      local_file_path,
+      priority,
+      existing_file_policy,
+      seq,
+      state: state_tx,
+      cancel_token,
+    };
+    trace!("[File] Queued download: {}", task.url);
+    self.queue.lock().unwrap().push(task);
+    self.process_next();
+    state
+  }
+
+  fn raise_priority(&self, url: &str, priority: DownloadPriority) {
+    let mut queue = self.queue.lock().unwrap();
+    if queue
+      .iter()
+      .any(|task| task.url == url && task.priority < priority)
+    {
+      let mut tasks = std::mem::take(&mut *queue).into_vec();
+      for task in tasks.iter_mut().filter(|task| task.url == url) {
+        task.priority = priority;
+      }
+      *queue = BinaryHeap::from(tasks);
+    }
+  }
+
+  /// Starts the next queued download if a download slot is free. Each finished download calls it
+  /// again, so the queue drains as the slots free up.
+  fn process_next(self: &Arc<Self>) {
+    let permit = match self.download_permits.clone().try_acquire_owned() {
+      Ok(permit) => permit,
+      Err(_) => {
+        trace!("[File] max downloads reached");
+        return;
+      },
+    };
+    let task = match self.queue.lock().unwrap().pop() {
+      Some(task) => task,
+      None => return,
+    };
+
+    let downloader = self.clone();
+    self.config.spawner.spawn(Box::pin(async move {
+      downloader.run(task, permit).await;
+      downloader.process_next();
+    }));
+  }
+
+  /// Cancels the queued or running download of the url. The running download is aborted and its
+  /// partial file is removed. The download record is removed in both cases. Returns false if the
+  /// url is not downloading.
+  pub async fn cancel(&self, url: &str) -> FlowyResult<bool> {
+    let active = match self.active_downloads.remove(url) {
+      Some((_, active)) => active,
+      None => return Ok(false),
+    };
+    let was_queued = {
+      let mut queue = self.queue.lock().unwrap();
+      let len = queue.len();
+      queue.retain(|task| task.seq != active.seq);
+      queue.len() < len
+    };
+    if was_queued {
+      info!("[File] cancelled queued download: {}", url);
+      notify_download_state(
+        &self.notifier,
+        &active.state,
+        url,
+        &active.local_file_path,
+        DownloadState::Cancelled,
+      );
+    } else {
+      // The running download removes its partial file once it stops.
+      info!("[File] cancelling running download: {}", url);
+      active.cancel_token.cancel();
+    }
+    self.delete_record(url).await?;
+    Ok(true)
+  }
+
+  async fn run(&self, task: DownloadTask, _permit: OwnedSemaphorePermit) {
+    let state_tx = &task.state;
+    if let Some(verified_file_id) = self.keep_existing_file(&task).await {
+      let total_bytes = tokio::fs::metadata(&task.local_file_path)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or_default();
+      notify_downloaded(
+        &self.notifier,
+        state_tx,
+        &task.url,
+        &task.local_file_path,
+        total_bytes,
+        verified_file_id,
+      );
+    } else {
+      tokio::select! {
+        _ = download_object(
+          &self.config,
+          &self.cloud_service,
+          &self.notifier,
+          state_tx,
+          task.url.clone(),
+          task.local_file_path.clone(),
+        ) => {},
+        _ = task.cancel_token.cancelled() => {
+          remove_part_file(&part_file_path(&task.local_file_path)).await;
+          notify_download_state(
+            &self.notifier,
+            state_tx,
+            &task.url,
+            &task.local_file_path,
+            DownloadState::Cancelled,
+          );
+          // The download and its record were removed by the cancel.
+          return;
+        },
+      }
+    }
+
+    if let Err(err) = self.delete_record(&task.url).await {
+      warn!("[File] delete download record {} failed: {}", task.url, err);
+    }
+    self
+      .active_downloads
+      .remove_if(&task.url, |_, active| active.seq == task.seq);
+  }
+
+  /// Returns `Some` if the local file of the task already exists and is kept as the downloaded
+  /// file, according to the [ExistingFilePolicy] of the task. It holds the file id the file was
+  /// verified against, if it was.
+  async fn keep_existing_file(&self, task: &DownloadTask) -> Option<Option<String>> {
+    if tokio::fs::metadata(&task.local_file_path).await.is_err() {
+      return None;
+    }
+    match task.existing_file_policy {
+      ExistingFilePolicy::Skip => {
+        warn!(
+          "file already exist in user local disk: {}",
+          task.local_file_path
+        );
+        Some(None)
+      },
+      ExistingFilePolicy::Overwrite => {
+        info!("[File] overwrite existing file: {}", task.local_file_path);
+        None
+      },
+      ExistingFilePolicy::VerifyThenSkip => {
+        let file_id = match parse_object_url(&self.cloud_service, &task.url).await {
+          Some((_, _, file_id)) => file_id,
+          None => {
+            warn!(
+              "file already exist in user local disk: {}, no file id to verify it",
+              task.local_file_path
+            );
+            return Some(None);
+          },
+        };
+        match verify_file_id(Path::new(&task.local_file_path), &file_id).await {
+          Ok(true) => {
+            info!(
+              "[File] existing file {} matches {}, skip downloading",
+              task.local_file_path, file_id
+            );
+            Some(Some(file_id))
+          },
+          Ok(false) => {
+            warn!(
+              "[File] existing file {} doesn't match {}, download it again",
+              task.local_file_path, file_id
+            );
+            None
+          },
+          Err(err) => {
+            warn!(
+              "[File] verify existing file {} failed: {}, download it again",
+              task.local_file_path, err
+            );
+            None
+          },
+        }
+      },
+    }
+  }
+
+  /// Downloads the object into the writer and returns the number of bytes written. It neither
+  /// waits in the queue nor is recorded, the caller consumes the object right away. The progress is
+  /// sent to the download notifier with an empty local file path. Dropping the returned future
+  /// cancels the download.
+  pub async fn download_to_writer(
+    &self,
+    url: &str,
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+  ) -> FlowyResult<u64> {
+    let (state_tx, _) = watch::channel(DownloadState::Queued);
+    let notify = |state: DownloadState| {
+      notify_download_state(&self.notifier, &state_tx, url, "", state);
+    };
+
+    notify(DownloadState::Downloading);
+    let result = async {
+      let object = fetch_object_with_retry(&self.config, &self.cloud_service, url, &notify).await?;
+      writer.write_all(&object).await?;
+      writer.flush().await?;
+      Ok::<_, FlowyError>(object.len() as u64)
+    }
+    .await;
+    match &result {
+      Ok(written) => {
+        info!("[File] downloaded {} bytes of {} to writer", written, url);
+        notify_downloaded(&self.notifier, &state_tx, url, "", *written, None);
+      },
+      Err(err) => {
+        error!("[File] download {} to writer failed: {}", url, err);
+        notify(DownloadState::DownloadFailed {
+          error: err.msg.clone(),
+        });
+      },
+    }
+    result
+  }
+
+  fn insert_record(&self, record: &DownloadFileTable) -> FlowyResult<()> {
+    let uid = self.user_service.user_id()?;
+    let mut conn = self.user_service.sqlite_connection(uid)?;
+    upsert_download_file(&mut conn, record)
+  }
+
+  async fn delete_record(&self, url: &str) -> FlowyResult<()> {
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    delete_download_file(&mut conn, url)
+  }
+}
+
+fn notify_download_state(
+  download_notifier: &DownloadNotifier,
+  state_tx: &watch::Sender<DownloadState>,
+  url: &str,
+  local_file_path: &str,
+  state: DownloadState,
+) {
+  state_tx.send_replace(state.clone());
+  // No receivers is fine, nobody is watching the download.
+  let _ = download_notifier.send(DownloadProgress::new(
+    url.to_string(),
+    local_file_path.to_string(),
+    state,
+  ));
+}
+
+/// Notifies the completion of the download, along with the size of the downloaded file.
+fn notify_downloaded(
+  download_notifier: &DownloadNotifier,
+  state_tx: &watch::Sender<DownloadState>,
+  url: &str,
+  local_file_path: &str,
+  total_bytes: u64,
+  verified_file_id: Option<String>,
+) {
+  state_tx.send_replace(DownloadState::Downloaded);
+  let _ = download_notifier.send(DownloadProgress::downloaded(
+    url.to_string(),
+    local_file_path.to_string(),
+    total_bytes,
+    verified_file_id,
+  ));
+}
+
+/// The file the object is written to while downloading. It's renamed to the local file once
+/// complete, so a cancelled or failed download never leaves a truncated local file.
+fn part_file_path(local_file_path: &str) -> String {
+  format!("{}.part", local_file_path)
+}
+
+async fn remove_part_file(part_file_path: &str) {
+  if let Err(err) = tokio::fs::remove_file(part_file_path).await {
+    if err.kind() != std::io::ErrorKind::NotFound {
+      warn!(
+        "[File] remove partial file {} failed: {}",
+        part_file_path, err
+      );
+    }
+  }
+}
+
+/// Downloads the object to the local file. The transient failures are retried with an exponential
+/// backoff, up to [StorageManagerConfig::download_max_attempts] attempts. A missing object fails
+/// right away. Each retry fetches the whole object again.
+async fn download_object(
+  config: &StorageManagerConfig,
+  cloud_service: &Arc<dyn StorageCloudService>,
+  download_notifier: &DownloadNotifier,
+  state_tx: &watch::Sender<DownloadState>,
+  url: String,
+  local_file_path: String,
+) {
+  let notify = |state: DownloadState| {
+    notify_download_state(download_notifier, state_tx, &url, &local_file_path, state);
+  };
+
+  notify(DownloadState::Downloading);
+  let part_file_path = part_file_path(&local_file_path);
+  if let Err(err) = tokio::fs::File::create(&part_file_path).await {
+    error!("[File] create file {} failed: {}", part_file_path, err);
+    notify(DownloadState::DownloadFailed {
+      error: err.to_string(),
+    });
+    return;
+  }
+
+  let object = match fetch_object_with_retry(config, cloud_service, &url, &notify).await {
+    Ok(object) => object,
+    Err(err) => {
+      error!("[File] download {} failed: {}", url, err);
+      remove_part_file(&part_file_path).await;
+      notify(DownloadState::DownloadFailed { error: err.msg });
+      return;
+    },
+  };
+
+  let written = match tokio::fs::write(&part_file_path, &object).await {
+    Ok(_) => tokio::fs::rename(&part_file_path, &local_file_path).await,
+    Err(err) => Err(err),
+  };
+  match written {
+    Ok(_) => {
+      info!(
+        "[File] downloaded {} bytes to file: {}",
+        object.len(),
+        local_file_path
+      );
+      notify_downloaded(
+        download_notifier,
+        state_tx,
+        &url,
+        &local_file_path,
+        object.len() as u64,
+        None,
+      );
+    },
+    Err(err) => {
+      error!("[File] write file {} failed: {}", local_file_path, err);
+      remove_part_file(&part_file_path).await;
+      notify(DownloadState::DownloadFailed {
+        error: err.to_string(),
+      });
+    },
+  }
+}
+
+/// Fetches the object, retrying the transient failures with an exponential backoff up to
+/// [StorageManagerConfig::download_max_attempts] attempts. A missing object fails right away.
+/// Each retry is reported to `notify` as [DownloadState::Retrying].
+async fn fetch_object_with_retry(
+  config: &StorageManagerConfig,
+  cloud_service: &Arc<dyn StorageCloudService>,
+  url: &str,
+  notify: impl Fn(DownloadState),
+) -> FlowyResult<Bytes> {
+  let mut attempt = 1;
+  loop {
+    match fetch_object(config, cloud_service, url).await {
+      Ok(object) => return Ok(object),
+      Err(err) if !err.is_record_not_found() && attempt < config.download_max_attempts => {
+        warn!(
+          "[File] download {} failed: {}, retry: {}",
+          url, err, attempt
+        );
+        notify(DownloadState::Retrying {
+          attempt,
+          error: err.msg.clone(),
+        });
+        config
+          .clock
+          .sleep(retry_backoff(config.download_retry_delay, attempt))
+          .await;
+        attempt += 1;
+      },
+      Err(err) => return Err(err),
+    }
+  }
+}
+
+/// Fetches the object. When the backend supports range requests, the object is fetched by ranges
+/// of [StorageManagerConfig::download_range_size], several at a time. Otherwise it's fetched in a
+/// single request.
+async fn fetch_object(
+  config: &StorageManagerConfig,
+  cloud_service: &Arc<dyn StorageCloudService>,
+  url: &str,
+) -> FlowyResult<Bytes> {
+  if !cloud_service.capabilities().range_downloads {
+    return Ok(cloud_service.get_object(url.to_string()).await?.raw);
+  }
+
+  // The first range tells the size of the object.
+  let range_size = config.download_range_size.max(1) as u64;
+  let first = cloud_service
+    .get_object_range(url.to_string(), 0..range_size)
+    .await?;
+  let total_size = first.total_size;
+  if first.raw.len() as u64 >= total_size {
+    return Ok(first.raw);
+  }
+
+  let ranges = (range_size..total_size)
+    .step_by(range_size as usize)
+    .map(|start| start..(start + range_size).min(total_size));
+  let rest = stream::iter(ranges)
+    .map(|range| cloud_service.get_object_range(url.to_string(), range))
+    .buffered(config.download_range_concurrency.max(1))
+    .try_collect::<Vec<_>>()
+    .await?;
+  trace!("[File] fetched {} in {} ranges", url, rest.len() + 1);
+  let mut object = BytesMut::with_capacity(total_size as usize);
+  object.extend_from_slice(&first.raw);
+  for range in rest {
+    object.extend_from_slice(&range.raw);
+  }
+  Ok(object.freeze())
+}
+
+/// Follows the download of the same url to another local file, and copies its file once it's
+/// downloaded instead of fetching the object again.
+fn copy_downloaded_file(
+  spawner: &dyn Spawner,
+  mut source: DownloadStateReceiver,
+  source_path: String,
+  local_file_path: String,
+) -> DownloadStateReceiver {
+  let (state_tx, state) = watch::channel(source.borrow().clone());
+  spawner.spawn(Box::pin(async move {
+    let source_state = loop {
+      let source_state = source.borrow_and_update().clone();
+      if source_state.is_finished() || source.changed().await.is_err() {
+        break source_state;
+      }
+      state_tx.send_replace(source_state);
+    };
+    let state = match source_state {
+      DownloadState::Downloaded => match tokio::fs::copy(&source_path, &local_file_path).await {
+        Ok(_) => DownloadState::Downloaded,
+        Err(err) => {
+          error!(
+            "[File] copy {} to {} failed: {}",
+            source_path, local_file_path, err
+          );
+          DownloadState::DownloadFailed {
+            error: err.to_string(),
+          }
+        },
+      },
+      DownloadState::DownloadFailed { .. } | DownloadState::Cancelled => source_state,
+      _ => DownloadState::DownloadFailed {
+        error: "the download stopped before finishing".to_string(),
+      },
+    };
+    state_tx.send_replace(state);
+  }));
+  state
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DownloadBatchProgress {
+  pub total: usize,
+  pub downloaded: usize,
+  pub failed: usize,
+  pub cancelled: usize,
+}
+
+impl DownloadBatchProgress {
+  pub fn is_finished(&self) -> bool {
+    self.finished() >= self.total
+  }
+
+  /// The share of the finished downloads, between 0 and 1.
+  pub fn progress(&self) -> f64 {
+    if self.total == 0 {
+      return 1.0;
+    }
+    self.finished() as f64 / self.total as f64
+  }
+
+  fn finished(&self) -> usize {
+    self.downloaded + self.failed + self.cancelled
+  }
+}
+
+/// [DownloadBatchHandle] tracks the downloads started by
+/// [crate::manager::StorageManager::download_objects], both one by one and as a whole.
+pub struct DownloadBatchHandle {
+  files: Vec<(String, String, DownloadStateReceiver)>,
+  progress: watch::Receiver<DownloadBatchProgress>,
+}
+
+impl DownloadBatchHandle {
+  pub(crate) fn new(
+    files: Vec<(String, String, DownloadStateReceiver)>,
+    spawner: &dyn Spawner,
+  ) -> Self {
+    let (progress_tx, progress) = watch::channel(DownloadBatchProgress {
+      total: files.len(),
+      ..Default::default()
+    });
+    for (_, _, state) in &files {
+      let mut state = state.clone();
+      let progress_tx = progress_tx.clone();
+      spawner.spawn(Box::pin(async move {
+        let state = state
+          .wait_for(DownloadState::is_finished)
+          .await
+          .map(|state| state.clone());
+        progress_tx.send_modify(|progress| match state {
+          Ok(DownloadState::Downloaded) => progress.downloaded += 1,
+          Ok(DownloadState::Cancelled) => progress.cancelled += 1,
+          _ => progress.failed += 1,
+        });
+      }));
+    }
+    Self { files, progress }
+  }
+
+  /// Subscribes to the aggregate progress of the batch.
+  pub fn progress(&self) -> watch::Receiver<DownloadBatchProgress> {
+    self.progress.clone()
+  }
+
+  /// Returns the url, the local file path and the state receiver of each download, in the order of
+  /// the requests.
+  pub fn files(&self) -> &[(String, String, DownloadStateReceiver)] {
+    &self.files
+  }
+
+  /// Returns the state receiver of the download of the url to the local file.
+  pub fn receiver(&self, url: &str, local_file_path: &str) -> Option<DownloadStateReceiver> {
+    self
+      .files
+      .iter()
+      .find(|(file_url, file_path, _)| file_url == url && file_path == local_file_path)
+      .map(|(_, _, state)| state.clone())
+  }
+
+  /// Waits until all the downloads of the batch are finished.
+  pub async fn wait(&self) -> DownloadBatchProgress {
+    let mut progress = self.progress.clone();
+    let finished = progress
+      .wait_for(DownloadBatchProgress::is_finished)
+      .await
+      .map(|progress| *progress);
+    finished.unwrap_or_else(|_| *self.progress.borrow())
+  }
+}
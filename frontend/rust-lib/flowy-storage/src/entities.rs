@@ -1,4 +1,4 @@
-use flowy_derive::ProtoBuf;
+use flowy_derive::{ProtoBuf, ProtoBuf_Enum};
 
 #[derive(Default, ProtoBuf, Clone, Debug)]
 pub struct RegisterStreamPB {
@@ -20,3 +20,95 @@ pub struct FileStatePB {
   #[pb(index = 2)]
   pub is_finish: bool,
 }
+
+#[derive(Default, ProtoBuf, Clone, Debug)]
+pub struct ReconcileSummaryPB {
+  #[pb(index = 1)]
+  pub checked: i64,
+
+  #[pb(index = 2)]
+  pub marked_finished: i64,
+
+  #[pb(index = 3)]
+  pub requeued: i64,
+
+  #[pb(index = 4)]
+  pub dropped: i64,
+}
+
+#[derive(Default, ProtoBuf, Clone, Debug)]
+pub struct StorageWriteAccessPB {
+  #[pb(index = 1)]
+  pub is_enabled: bool,
+}
+
+/// The payload of the error returned when the storage limit blocks an upload, so the UI can tell
+/// how much space the upload needs.
+#[derive(Default, ProtoBuf, Clone, Debug, PartialEq, Eq)]
+pub struct StorageLimitExceededPB {
+  /// The bytes used in the workspace, zero when the usage is unknown, see
+  /// [crate::manager::StorageManager::update_storage_usage].
+  #[pb(index = 1)]
+  pub used_bytes: i64,
+
+  /// The bytes the workspace can store, zero when the usage is unknown.
+  #[pb(index = 2)]
+  pub total_bytes: i64,
+
+  /// The size of the file to upload.
+  #[pb(index = 3)]
+  pub required_bytes: i64,
+}
+
+#[derive(Default, ProtoBuf, Clone, Debug)]
+pub struct UploadPauseReasonsPB {
+  /// The bits of the [crate::pause::PauseReasons], zero when the uploads are not paused.
+  #[pb(index = 1)]
+  pub reasons: i32,
+}
+
+#[derive(ProtoBuf_Enum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateUploadStatePB {
+  /// The same file is already being uploaded to the same place.
+  #[default]
+  InProgress = 0,
+  /// The same file was already uploaded to the same place.
+  Completed = 1,
+}
+
+#[derive(Default, ProtoBuf, Clone, Debug)]
+pub struct DuplicateUploadPB {
+  #[pb(index = 1)]
+  pub url: String,
+
+  #[pb(index = 2)]
+  pub file_id: String,
+
+  #[pb(index = 3)]
+  pub state: DuplicateUploadStatePB,
+}
+
+#[derive(ProtoBuf_Enum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UploadFailuresStatePB {
+  /// Uploads failed, `failed_count` of them since the previous notification.
+  #[default]
+  Failed = 0,
+  /// An upload succeeded after the failures.
+  Recovered = 1,
+}
+
+#[derive(Default, ProtoBuf, Clone, Debug)]
+pub struct UploadFailuresPB {
+  #[pb(index = 1)]
+  pub workspace_id: String,
+
+  #[pb(index = 2)]
+  pub state: UploadFailuresStatePB,
+
+  #[pb(index = 3)]
+  pub failed_count: i64,
+
+  /// Describes the failures, e.g. "3 uploads failed: network error". Empty once recovered.
+  #[pb(index = 4)]
+  pub message: String,
+}
@@ -0,0 +1,94 @@
+use crate::quota::QuotaExceeded;
+use flowy_error::{ErrorCode, FlowyError};
+
+/// Errors returned by the storage service. Each variant maps to a stable [ErrorCode] so callers
+/// can tell the failures apart without parsing the message.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StorageError {
+  #[error("workspace id is empty")]
+  EmptyWorkspaceId,
+
+  #[error("parent dir is empty")]
+  EmptyParentDir,
+
+  #[error("invalid parent dir: {0}")]
+  InvalidParentDir(String),
+
+  #[error("local file path is empty")]
+  EmptyFilePath,
+
+  #[error("create temp file for upload file failed: {0}")]
+  TempFileCreation(String),
+
+  #[error("upload record not found: {0}")]
+  RecordNotFound(String),
+
+  #[error("invalid upload record: {0}")]
+  InvalidRecord(String),
+
+  #[error("upload expired: {0}")]
+  UploadExpired(String),
+
+  #[error("upload was cancelled")]
+  Cancelled,
+
+  #[error("local file of the upload was removed: {0}")]
+  FileMissing(String),
+
+  #[error("file storage limit exceeded")]
+  OverQuota,
+
+  #[error("the storage was opened for workspace {opened}, but the active workspace is {current}")]
+  WorkspaceChanged { opened: String, current: String },
+
+  #[error("the file needs {parts} parts, but the storage accepts at most {max_parts}")]
+  TooManyParts { parts: usize, max_parts: usize },
+
+  #[error("unsupported storage class: {0}")]
+  UnsupportedStorageClass(String),
+
+  #[error("{pending} uploads are pending, the storage accepts at most {max_pending}")]
+  TooManyPendingUploads { pending: usize, max_pending: usize },
+
+  #[error("the object already exists: {0}")]
+  AlreadyExists(String),
+
+  #[error("{0}")]
+  QuotaExceeded(QuotaExceeded),
+
+  /// The server stored a part that differs from the sent one. The parts can't be reused, so it
+  /// shares the code of the invalid parts.
+  #[error("the checksum of part {part_number} doesn't match the checksum of the sent part")]
+  PartChecksumMismatch { part_number: i32 },
+}
+
+impl StorageError {
+  pub fn code(&self) -> ErrorCode {
+    match self {
+      StorageError::EmptyWorkspaceId => ErrorCode::UploadWorkspaceIdIsEmpty,
+      StorageError::EmptyParentDir => ErrorCode::UploadParentDirIsEmpty,
+      StorageError::InvalidParentDir(_) => ErrorCode::InvalidParentDir,
+      StorageError::EmptyFilePath => ErrorCode::UploadFilePathIsEmpty,
+      StorageError::TempFileCreation(_) => ErrorCode::UploadTempFileError,
+      StorageError::RecordNotFound(_) => ErrorCode::RecordNotFound,
+      StorageError::InvalidRecord(_) => ErrorCode::InvalidUploadRecord,
+      StorageError::UploadExpired(_) => ErrorCode::UploadExpired,
+      StorageError::Cancelled => ErrorCode::UploadCancelled,
+      StorageError::FileMissing(_) => ErrorCode::UploadFileMissing,
+      StorageError::OverQuota => ErrorCode::FileStorageLimitExceeded,
+      StorageError::WorkspaceChanged { .. } => ErrorCode::StorageWorkspaceChanged,
+      StorageError::TooManyParts { .. } => ErrorCode::UploadTooManyParts,
+      StorageError::UnsupportedStorageClass(_) => ErrorCode::UnsupportedStorageClass,
+      StorageError::TooManyPendingUploads { .. } => ErrorCode::TooManyPendingUploads,
+      StorageError::AlreadyExists(_) => ErrorCode::ObjectAlreadyExists,
+      StorageError::QuotaExceeded(_) => ErrorCode::StorageQuotaExceeded,
+      StorageError::PartChecksumMismatch { .. } => ErrorCode::UploadPartsInvalid,
+    }
+  }
+}
+
+impl From<StorageError> for FlowyError {
+  fn from(err: StorageError) -> Self {
+    FlowyError::new(err.code(), err)
+  }
+}
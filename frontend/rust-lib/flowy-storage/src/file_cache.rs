@@ -1,45 +1,198 @@
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::fmt::Debug;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs::{self, File};
 use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
 use tracing::error;
 
+/// The default size of the buffer used to copy a file to the temporary storage.
+pub const DEFAULT_COPY_BUFFER_SIZE: usize = 1024 * 1024;
+/// The smallest copy buffer, smaller buffers make the copy needlessly slow.
+pub const MIN_COPY_BUFFER_SIZE: usize = 4 * 1024;
+/// Extensions longer than this are dropped from the temporary file name.
+const MAX_EXTENSION_LEN: usize = 16;
+
+/// [TempFileNaming] decides the name of the temporary copy of a file. The upload record keeps the
+/// path of the copy, so the name doesn't need to encode anything about the original file.
+pub trait TempFileNaming: Debug + Send + Sync {
+  fn temp_file_name(&self, existing_file_path: &Path) -> String;
+}
+
+/// Names the temporary file after a short hash, keeping the extension of the original file.
+/// The name stays short and portable however long or odd the original path is.
+#[derive(Debug, Default)]
+pub struct HashTempFileNaming {
+  counter: AtomicU64,
+}
+
+impl TempFileNaming for HashTempFileNaming {
+  fn temp_file_name(&self, existing_file_path: &Path) -> String {
+    let nanos = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_nanos())
+      .unwrap_or_default();
+    // The counter keeps the names of the copies of the same file apart.
+    let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+    let hash = fxhash::hash64(&(existing_file_path, nanos, counter));
+    match temp_file_extension(existing_file_path) {
+      Some(ext) => format!("{:016x}.{}", hash, ext),
+      None => format!("{:016x}", hash),
+    }
+  }
+}
+
+/// Returns the extension of the file when it's safe to use in a file name on all the platforms.
+fn temp_file_extension(path: &Path) -> Option<String> {
+  let ext = path.extension()?.to_str()?;
+  if ext.is_empty()
+    || ext.len() > MAX_EXTENSION_LEN
+    || !ext.chars().all(|c| c.is_ascii_alphanumeric())
+  {
+    return None;
+  }
+  Some(ext.to_lowercase())
+}
+
+/// [TempFileRemover] removes the temporary files. The removal may fail transiently, e.g. while
+/// another process holds the file open on Windows.
+#[async_trait]
+pub trait TempFileRemover: Debug + Send + Sync {
+  async fn remove_temp_file(&self, path: &Path) -> io::Result<()>;
+}
+
+/// Removes the temporary files from the file system.
+#[derive(Debug, Default)]
+pub struct FsTempFileRemover;
+
+#[async_trait]
+impl TempFileRemover for FsTempFileRemover {
+  async fn remove_temp_file(&self, path: &Path) -> io::Result<()> {
+    fs::remove_file(path).await
+  }
+}
+
 /// [FileTempStorage] is used to store the temporary files for uploading. After the file is uploaded,
-/// the file will be deleted.
+/// the file will be deleted. Each user has its own temp dir under the root dir, so that the files
+/// of one account never show up in the session of another.
 pub struct FileTempStorage {
-  storage_dir: PathBuf,
+  root_dir: PathBuf,
+  /// The user whose temp dir is used, see [Self::switch_user]. The root dir itself is used until a
+  /// user is set.
+  uid: RwLock<Option<i64>>,
+  naming: Arc<dyn TempFileNaming>,
+  copy_buffer_size: usize,
+  remover: Arc<dyn TempFileRemover>,
+  /// The temp files whose deletion failed, see [Self::mark_undeleted].
+  undeleted: Mutex<HashSet<PathBuf>>,
 }
 
 impl FileTempStorage {
-  /// Creates a new `FileTempStorage` with the specified temporary directory.
-  pub fn new(storage_dir: PathBuf) -> Self {
-    if !storage_dir.exists() {
-      if let Err(err) = std::fs::create_dir_all(&storage_dir) {
+  /// Creates a new `FileTempStorage` with the specified root directory. The files are copied
+  /// with a buffer of `copy_buffer_size` bytes, raised to [MIN_COPY_BUFFER_SIZE] when smaller.
+  pub fn new(root_dir: PathBuf, naming: Arc<dyn TempFileNaming>, copy_buffer_size: usize) -> Self {
+    if !root_dir.exists() {
+      if let Err(err) = std::fs::create_dir_all(&root_dir) {
         error!("Failed to create temporary storage directory: {:?}", err);
       }
     }
 
-    FileTempStorage { storage_dir }
+    FileTempStorage {
+      root_dir,
+      uid: RwLock::new(None),
+      naming,
+      copy_buffer_size: copy_buffer_size.max(MIN_COPY_BUFFER_SIZE),
+      remover: Arc::new(FsTempFileRemover),
+      undeleted: Mutex::new(HashSet::new()),
+    }
+  }
+
+  /// Removes the temporary files with the remover instead of [FsTempFileRemover].
+  pub fn with_remover(mut self, remover: Arc<dyn TempFileRemover>) -> Self {
+    self.remover = remover;
+    self
+  }
+
+  /// The size of the buffer used to copy the files, see [Self::new].
+  pub fn copy_buffer_size(&self) -> usize {
+    self.copy_buffer_size
+  }
+
+  /// Returns the temp dir of the user.
+  pub fn user_dir(&self, uid: i64) -> PathBuf {
+    self.root_dir.join(uid.to_string())
+  }
+
+  /// Returns the temp dir of the current user.
+  pub fn storage_dir(&self) -> PathBuf {
+    match *self.uid.read().unwrap() {
+      Some(uid) => self.user_dir(uid),
+      None => self.root_dir.clone(),
+    }
+  }
+
+  /// Makes the temp dir of the user the current one. Returns the previous user when it was another
+  /// one, whose temp files are no longer listed.
+  pub fn switch_user(&self, uid: i64) -> Option<i64> {
+    let previous = self.uid.write().unwrap().replace(uid);
+    previous.filter(|previous| *previous != uid)
+  }
+
+  /// Removes the temp dir of the user along with its files.
+  pub async fn clear_user(&self, uid: i64) -> io::Result<()> {
+    match fs::remove_dir_all(self.user_dir(uid)).await {
+      Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+      _ => Ok(()),
+    }
+  }
+
+  /// Checks that the temporary files can be written, creating the storage directory if needed.
+  pub async fn validate(&self) -> io::Result<()> {
+    let storage_dir = self.storage_dir();
+    fs::create_dir_all(&storage_dir).await?;
+    let probe_path = storage_dir.join(".write_probe");
+    File::create(&probe_path).await?;
+    fs::remove_file(&probe_path).await
   }
 
   /// Generates a temporary file path using the given file name.
   fn generate_temp_file_path_with_name(&self, file_name: &str) -> PathBuf {
-    self.storage_dir.join(file_name)
+    self.storage_dir().join(file_name)
   }
 
-  /// Creates a temporary file from an existing local file path.
-  #[allow(dead_code)]
+  /// Creates a temporary file from an existing local file path. The copy stops when the
+  /// `cancel_token` is cancelled, and the partial temporary file is removed.
   pub async fn create_temp_file_from_existing(
     &self,
     existing_file_path: &Path,
+    cancel_token: &CancellationToken,
   ) -> io::Result<String> {
-    let file_name = existing_file_path
-      .file_name()
-      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid file name"))?
-      .to_str()
-      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid file name"))?;
+    if existing_file_path.file_name().is_none() {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Invalid file name",
+      ));
+    }
 
-    let temp_file_path = self.generate_temp_file_path_with_name(file_name);
-    fs::copy(existing_file_path, &temp_file_path).await?;
+    let file_name = self.naming.temp_file_name(existing_file_path);
+    let temp_file_path = self.generate_temp_file_path_with_name(&file_name);
+    // The temp dir of the user is created with its first file.
+    fs::create_dir_all(self.storage_dir()).await?;
+    if let Err(err) = copy_file(
+      existing_file_path,
+      &temp_file_path,
+      self.copy_buffer_size,
+      cancel_token,
+    )
+    .await
+    {
+      let _ = fs::remove_file(&temp_file_path).await;
+      return Err(err);
+    }
     Ok(
       temp_file_path
         .to_str()
@@ -51,6 +204,31 @@ impl FileTempStorage {
     )
   }
 
+  /// Copies the existing file to the temporary file path, e.g. to restore an evicted temporary
+  /// file. The partial copy is removed when the copy fails or is cancelled.
+  pub async fn restore_temp_file(
+    &self,
+    existing_file_path: &Path,
+    temp_file_path: &Path,
+    cancel_token: &CancellationToken,
+  ) -> io::Result<()> {
+    if let Some(parent) = temp_file_path.parent() {
+      fs::create_dir_all(parent).await?;
+    }
+    if let Err(err) = copy_file(
+      existing_file_path,
+      temp_file_path,
+      self.copy_buffer_size,
+      cancel_token,
+    )
+    .await
+    {
+      let _ = fs::remove_file(temp_file_path).await;
+      return Err(err);
+    }
+    Ok(())
+  }
+
   /// Creates a temporary file from bytes and a specified file name.
   #[allow(dead_code)]
   pub async fn create_temp_file_from_bytes(
@@ -81,9 +259,65 @@ impl FileTempStorage {
     Ok(data)
   }
 
+  /// Lists the paths of the temporary files of the current user.
+  pub async fn list_temp_files(&self) -> io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    let mut entries = match fs::read_dir(self.storage_dir()).await {
+      Ok(entries) => entries,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(paths),
+      Err(err) => return Err(err),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+      if entry.file_type().await?.is_file() {
+        paths.push(entry.path());
+      }
+    }
+    Ok(paths)
+  }
+
   /// Deletes the specified temporary file.
   pub async fn delete_temp_file<T: AsRef<Path>>(&self, file_path: T) -> io::Result<()> {
-    fs::remove_file(file_path).await?;
-    Ok(())
+    let file_path = file_path.as_ref();
+    let result = self.remover.remove_temp_file(file_path).await;
+    if matches!(&result, Err(err) if err.kind() != io::ErrorKind::NotFound) {
+      return result;
+    }
+    self.undeleted.lock().unwrap().remove(file_path);
+    result
+  }
+
+  /// Records a temp file that couldn't be deleted, so that the orphan sweep deletes it later even
+  /// though its upload record still refers to it. The file is forgotten once it's deleted.
+  pub fn mark_undeleted(&self, file_path: PathBuf) {
+    self.undeleted.lock().unwrap().insert(file_path);
+  }
+
+  /// Returns whether the deletion of the temp file failed, see [Self::mark_undeleted].
+  pub fn is_undeleted(&self, file_path: &Path) -> bool {
+    self.undeleted.lock().unwrap().contains(file_path)
+  }
+}
+
+/// Copies the file in chunks of up to `buffer_size` bytes, so that the `cancel_token` is checked
+/// while copying large files. Each chunk is written directly, the files aren't buffered again.
+async fn copy_file(
+  from: &Path,
+  to: &Path,
+  buffer_size: usize,
+  cancel_token: &CancellationToken,
+) -> io::Result<()> {
+  let mut reader = File::open(from).await?;
+  let mut writer = File::create(to).await?;
+  let mut buf = vec![0; buffer_size];
+  loop {
+    if cancel_token.is_cancelled() {
+      return Err(io::Error::new(io::ErrorKind::Interrupted, "copy cancelled"));
+    }
+    let n = reader.read(&mut buf).await?;
+    if n == 0 {
+      break;
+    }
+    writer.write_all(&buf[..n]).await?;
   }
+  writer.flush().await
 }
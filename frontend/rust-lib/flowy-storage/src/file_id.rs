@@ -0,0 +1,141 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The extension of the file id when the file has none.
+const DEFAULT_EXTENSION: &str = "blob";
+
+/// How the id of a file to upload is derived. The id keys the upload record and the object, so
+/// two files with the same id are uploaded once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileIdStrategy {
+  /// The hash of the content, see [compute_file_id_from_reader]. The whole file is read, which
+  /// takes a while for large files, but the same content always gets the same id wherever it
+  /// comes from, and the downloads can be verified against it.
+  #[default]
+  ContentHash,
+  /// The hash of the name, size and modification time of the file, see [file_id_from_metadata].
+  /// The content is never read, so it's meant for the large files of a trusted source that don't
+  /// change in place. The same content under another name or with another modification time is
+  /// uploaded again, a content changed without touching the size and the modification time keeps
+  /// the id of the previous content, and the downloads can't be verified against the id. Only
+  /// used when the backend isn't content addressed, see
+  /// [flowy_storage_pub::cloud::StorageCapabilities::content_addressed].
+  Metadata,
+}
+
+/// Computes the id of the content read from the reader: the url-safe base64 of its sha256,
+/// followed by the extension. The ids are the same as the ones of
+/// [collab_importer::util::FileId::from_path], so the uploads are deduplicated by content whatever
+/// their source.
+pub async fn compute_file_id_from_reader<R>(
+  mut reader: R,
+  extension: Option<&str>,
+) -> io::Result<String>
+where
+  R: AsyncRead + Unpin,
+{
+  let mut hasher = Sha256::new();
+  let mut buffer = vec![0u8; 64 * 1024];
+  loop {
+    let n = reader.read(&mut buffer).await?;
+    if n == 0 {
+      break;
+    }
+    hasher.update(&buffer[..n]);
+  }
+  let extension = extension.unwrap_or(DEFAULT_EXTENSION);
+  Ok(format!(
+    "{}.{}",
+    URL_SAFE_NO_PAD.encode(hasher.finalize()),
+    extension
+  ))
+}
+
+/// Computes the id of the file, see [compute_file_id_from_reader].
+pub async fn file_id_from_path(path: &Path) -> io::Result<String> {
+  let file = tokio::fs::File::open(path).await?;
+  let extension = path.extension().and_then(|extension| extension.to_str());
+  compute_file_id_from_reader(file, extension).await
+}
+
+/// Computes the id of the file at the path with the strategy, see [compute_file_id].
+pub async fn file_id_with_strategy(path: &Path, strategy: FileIdStrategy) -> io::Result<String> {
+  let file = tokio::fs::File::open(path).await?;
+  let metadata = file.metadata().await?;
+  compute_file_id(file, path, &metadata, strategy).await
+}
+
+/// Computes the id of the file with the strategy. The content is read from the reader with
+/// [FileIdStrategy::ContentHash] only, [FileIdStrategy::Metadata] uses the path and the metadata.
+pub async fn compute_file_id<R>(
+  reader: R,
+  path: &Path,
+  metadata: &Metadata,
+  strategy: FileIdStrategy,
+) -> io::Result<String>
+where
+  R: AsyncRead + Unpin,
+{
+  let extension = path.extension().and_then(|extension| extension.to_str());
+  match strategy {
+    FileIdStrategy::ContentHash => compute_file_id_from_reader(reader, extension).await,
+    FileIdStrategy::Metadata => Ok(file_id_from_metadata(path, metadata)),
+  }
+}
+
+/// Computes the id of the file from its name, size and modification time: the url-safe base64 of
+/// their sha256, followed by the extension. See [FileIdStrategy::Metadata] for the tradeoff.
+pub fn file_id_from_metadata(path: &Path, metadata: &Metadata) -> String {
+  let modified_nanos = metadata
+    .modified()
+    .ok()
+    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+    .map(|duration| duration.as_nanos())
+    .unwrap_or_default();
+  let mut hasher = Sha256::new();
+  // Prefixed, so that the hash never matches the hash of a content.
+  hasher.update(b"metadata:");
+  hasher.update(
+    path
+      .file_name()
+      .map(|name| name.to_string_lossy())
+      .unwrap_or_default()
+      .as_bytes(),
+  );
+  hasher.update(metadata.len().to_le_bytes());
+  hasher.update(modified_nanos.to_le_bytes());
+  let extension = path
+    .extension()
+    .and_then(|extension| extension.to_str())
+    .unwrap_or(DEFAULT_EXTENSION);
+  format!(
+    "{}.{}",
+    URL_SAFE_NO_PAD.encode(hasher.finalize()),
+    extension
+  )
+}
+
+/// Returns true if the content of the file matches the file id. The extension of the file id is
+/// used, so the file is verified whatever its own extension.
+pub async fn verify_file_id(path: &Path, file_id: &str) -> io::Result<bool> {
+  let file = tokio::fs::File::open(path).await?;
+  let extension = Path::new(file_id)
+    .extension()
+    .and_then(|extension| extension.to_str());
+  Ok(compute_file_id_from_reader(file, extension).await? == file_id)
+}
+
+/// Computes the id of the in-memory content, see [compute_file_id_from_reader]. It's the id of a
+/// file with the same content and extension.
+pub async fn file_id_from_bytes(bytes: &[u8], extension: Option<&str>) -> String {
+  // Reading from a slice never fails.
+  compute_file_id_from_reader(bytes, extension)
+    .await
+    .expect("read in-memory content")
+}
@@ -1,9 +1,31 @@
-mod entities;
+pub mod backoff;
+mod bandwidth;
+pub mod checksum;
+pub mod clock;
+pub mod config;
+pub mod diagnostics;
+pub mod downloader;
+pub mod entities;
+pub mod error;
 mod event_handler;
 pub mod event_map;
-mod file_cache;
+pub mod file_cache;
+pub mod file_id;
 pub mod manager;
-mod notification;
+pub mod manifest;
+mod metadata;
+mod mime_sniff;
+pub mod notification;
+pub mod parent_dir;
+pub mod pause;
+mod progress;
 mod protobuf;
+pub mod quota;
+mod range_cache;
+pub mod snapshot;
+pub mod spawner;
 pub mod sqlite_sql;
+pub mod upload_log;
 mod uploader;
+#[cfg(feature = "webdav")]
+pub mod webdav;
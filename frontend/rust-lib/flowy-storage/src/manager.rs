@@ -1,34 +1,75 @@
-use crate::entities::FileStatePB;
+use crate::backoff::retry_backoff;
+use crate::bandwidth::UploadBandwidth;
+use crate::checksum::part_checksum;
+use crate::clock::Clock;
+use crate::config::{ProgressFanOut, StorageManagerConfig, TempFilePolicy};
+use crate::diagnostics::{PartTiming, PartTimings, UploadDetail};
+use crate::downloader::{
+  DownloadBatchHandle, DownloadNotifier, DownloadPriority, DownloadStateReceiver,
+  ExistingFilePolicy, FileDownloader,
+};
+use crate::entities::{
+  DuplicateUploadPB, DuplicateUploadStatePB, FileStatePB, ReconcileSummaryPB,
+  StorageLimitExceededPB, StorageWriteAccessPB,
+};
+use crate::error::StorageError;
 use crate::file_cache::FileTempStorage;
-use crate::notification::{make_notification, StorageNotification};
+use crate::file_id::{
+  file_id_from_metadata, file_id_with_strategy, verify_file_id, FileIdStrategy,
+};
+use crate::manifest::{manifest_sidecar_id, UploadManifest};
+use crate::metadata::{metadata_from_record, metadata_to_record, validate_metadata};
+use crate::mime_sniff::{is_valid_content_type, sniff_mime, SNIFF_LEN};
+use crate::notification::{make_notification, StorageNotification, UploadFailureNotifier};
+use crate::parent_dir::canonical_parent_dir;
+use crate::pause::PauseReasons;
+use crate::progress::{state_progress, upload_state, ProgressBroadcaster, ProgressThrottle};
+use crate::range_cache::RangeCache;
+use crate::snapshot::UploadSnapshot;
 use crate::sqlite_sql::{
-  batch_select_upload_file, delete_all_upload_parts, delete_upload_file, insert_upload_file,
-  insert_upload_part, is_upload_completed, select_upload_file, select_upload_parts,
-  update_upload_file_completed, update_upload_file_upload_id, UploadFilePartTable, UploadFileTable,
+  batch_select_upload_file, count_unfinished_upload_files, count_upload_files_by_upload_id,
+  delete_all_upload_parts, delete_upload_failure, delete_upload_file,
+  delete_upload_file_by_file_id, insert_upload_file, insert_upload_part, is_upload_completed,
+  select_download_files, select_expired_upload_failures, select_linkable_upload_file,
+  select_upload_failures, select_upload_file, select_upload_files, select_upload_manifest,
+  select_upload_part_upload_ids, select_upload_parts, select_workspace_upload_files,
+  update_upload_failure_pinned, update_upload_file_completed,
+  update_upload_file_completed_by_file_id, update_upload_file_local_path,
+  update_upload_file_unfinished_by_file_id, update_upload_file_upload_id, upsert_upload_failure,
+  upsert_upload_manifest, UploadFileFailureTable, UploadFileManifestTable, UploadFilePartTable,
+  UploadFileTable, UploadLogTable,
 };
+use crate::upload_log::{UploadLog, UploadLogEvent};
 use crate::uploader::{FileUploader, FileUploaderRunner, Signal, UploadTask, UploadTaskQueue};
 use allo_isolate::Isolate;
 use async_trait::async_trait;
-use collab_importer::util::FileId;
+use bytes::Bytes;
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use flowy_error::{ErrorCode, FlowyError, FlowyResult};
 use flowy_sqlite::DBConnection;
-use flowy_storage_pub::chunked_byte::{calculate_offsets, ChunkedBytes, MIN_CHUNK_SIZE};
-use flowy_storage_pub::cloud::StorageCloudService;
+use flowy_storage_pub::chunked_byte::{calculate_offsets, ChunkReader, ChunkedBytes};
+use flowy_storage_pub::cloud::{
+  ChecksumAlgorithm, ObjectPage, ObjectValue, PartChecksum, StorageCloudService,
+};
 use flowy_storage_pub::storage::{
-  CompletedPartRequest, CreatedUpload, FileProgress, FileProgressReceiver, FileUploadState,
-  ProgressNotifier, StorageService, UploadPartResponse,
+  CompletedPartRequest, CreatedUpload, DeleteProgress, DeleteState, DeleteTarget, DownloadProgress,
+  DownloadState, FileProgress, FileProgressReceiver, FileUploadState, ProgressNotifier,
+  StorageService, TransferDirection, UploadPartResponse,
 };
+use futures_util::stream::{self, Stream};
 use lib_infra::box_any::BoxAny;
 use lib_infra::isolate_stream::{IsolateSink, SinkExt};
-use lib_infra::util::timestamp;
+use std::collections::{HashMap, VecDeque};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::io::AsyncWriteExt;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWrite};
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
 use tokio::sync::{broadcast, watch};
-use tracing::{debug, error, info, instrument, trace};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace, warn};
 
 pub trait StorageUserService: Send + Sync + 'static {
   fn user_id(&self) -> Result<i64, FlowyError>;
@@ -37,14 +78,33 @@ pub trait StorageUserService: Send + Sync + 'static {
   fn get_application_root_dir(&self) -> &str;
 }
 
-type GlobalNotifier = broadcast::Sender<FileProgress>;
+/// The number of times to retry acquiring a sqlite connection before giving up. The pool can be
+/// exhausted temporarily when many uploads run concurrently.
+const SQLITE_CONNECTION_MAX_RETRIES: u32 = 3;
+
+/// How often [StorageManager::wait_until_idle] checks whether the uploads settled.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+type GlobalNotifier = Arc<ProgressBroadcaster>;
+type DeleteNotifier = broadcast::Sender<DeleteProgress>;
 pub struct StorageManager {
-  pub storage_service: Arc<dyn StorageService>,
+  storage_service: Arc<dyn StorageService>,
+  service: Arc<StorageServiceImpl>,
   cloud_service: Arc<dyn StorageCloudService>,
   user_service: Arc<dyn StorageUserService>,
   uploader: Arc<FileUploader>,
   progress_notifiers: Arc<DashMap<String, ProgressNotifier>>,
   global_notifier: GlobalNotifier,
+  delete_notifier: DeleteNotifier,
+  download_notifier: DownloadNotifier,
+  bandwidth: Arc<UploadBandwidth>,
+  /// Paces the progress updates sent by [Self::query_file_state], keyed by [upload_key].
+  file_state_throttles: DashMap<String, ProgressThrottle>,
+  /// Serializes the [notify_pause_state] calls.
+  pause_state_lock: Arc<tokio::sync::Mutex<()>>,
+  /// The receiver of the progress relay driven by [Self::tick_relay], only set with
+  /// [StorageManagerConfig::manual_background_tasks].
+  manual_relay: tokio::sync::Mutex<Option<broadcast::Receiver<FileProgress>>>,
 }
 
 impl Drop for StorageManager {
@@ -57,18 +117,69 @@ impl StorageManager {
   pub fn new(
     cloud_service: Arc<dyn StorageCloudService>,
     user_service: Arc<dyn StorageUserService>,
+  ) -> Self {
+    Self::new_with_config(cloud_service, user_service, StorageManagerConfig::default())
+  }
+
+  pub fn new_with_config(
+    cloud_service: Arc<dyn StorageCloudService>,
+    user_service: Arc<dyn StorageUserService>,
+    config: StorageManagerConfig,
   ) -> Self {
     let is_exceed_storage_limit = Arc::new(AtomicBool::new(false));
     let temp_storage_path = PathBuf::from(format!(
       "{}/cache_files",
       user_service.get_application_root_dir()
     ));
-    let (global_notifier, _) = broadcast::channel(2000);
-    let temp_storage = Arc::new(FileTempStorage::new(temp_storage_path));
-    let (notifier, notifier_rx) = watch::channel(Signal::Proceed);
-    let task_queue = Arc::new(UploadTaskQueue::new(notifier));
     let progress_notifiers = Arc::new(DashMap::new());
+    let progress_fan_out = config.progress_fan_out;
+    let global_notifier = ProgressBroadcaster::new(2000, config.progress_history_size);
+    let global_notifier = match progress_fan_out {
+      ProgressFanOut::Relay => Arc::new(global_notifier),
+      ProgressFanOut::Direct => {
+        Arc::new(global_notifier.with_file_notifiers(Arc::downgrade(&progress_notifiers)))
+      },
+    };
+    let (delete_notifier, _) = broadcast::channel(100);
+    let (download_notifier, _) = broadcast::channel(100);
+    let temp_storage = Arc::new(
+      FileTempStorage::new(
+        temp_storage_path,
+        config.temp_file_naming.clone(),
+        config.temp_copy_buffer_size,
+      )
+      .with_remover(config.temp_file_remover.clone()),
+    );
+    if let Ok(uid) = user_service.user_id() {
+      temp_storage.switch_user(uid);
+    }
+    let (notifier, notifier_rx) = watch::channel(Signal::Proceed);
+    let task_queue = Arc::new(UploadTaskQueue::new(notifier, config.clock.clone()));
+    let bandwidth = Arc::new(UploadBandwidth::new(
+      config.bandwidth_limit,
+      config.throughput_window,
+      config.max_in_flight_bytes,
+      config.clock.clone(),
+    ));
+    let reconcile_interval = config.reconcile_interval;
+    let manual_background_tasks = config.manual_background_tasks;
+    let max_concurrent_uploads = config.max_concurrent_uploads;
+    let config = Arc::new(config);
+    let upload_log = Arc::new(UploadLog::new(config.clone(), user_service.clone()));
+    let downloader = Arc::new(FileDownloader::new(
+      config.clone(),
+      cloud_service.clone(),
+      user_service.clone(),
+      download_notifier.clone(),
+    ));
+    let range_cache = RangeCache::new(config.range_cache_capacity, config.range_cache_max_len);
+    let failure_notifier = UploadFailureNotifier::new(
+      config.failure_notification_window,
+      config.clock.clone(),
+      config.spawner.clone(),
+    );
     let storage_service = Arc::new(StorageServiceImpl {
+      config,
       cloud_service: cloud_service.clone(),
       user_service: user_service.clone(),
       temp_storage,
@@ -76,408 +187,3500 @@ impl StorageManager {
       is_exceed_storage_limit: is_exceed_storage_limit.clone(),
       progress_notifiers: progress_notifiers.clone(),
       global_notifier: global_notifier.clone(),
+      delete_notifier: delete_notifier.clone(),
+      downloader,
+      active_uploads: Default::default(),
+      bandwidth: bandwidth.clone(),
+      part_timings: Default::default(),
+      upload_log: upload_log.clone(),
+      range_cache,
+      reconcile_cursor: Default::default(),
+      opened_workspace_id: Default::default(),
+      storage_usage: Default::default(),
+      failure_notifier,
     });
 
     let uploader = Arc::new(FileUploader::new(
       storage_service.clone(),
       task_queue,
       is_exceed_storage_limit,
+      max_concurrent_uploads,
+      upload_log,
     ));
-    tokio::spawn(FileUploaderRunner::run(
+    let spawner = storage_service.config.spawner.clone();
+    spawner.spawn(Box::pin(FileUploaderRunner::run(
       Arc::downgrade(&uploader),
       notifier_rx,
-    ));
+      spawner.clone(),
+    )));
 
-    let weak_uploader = Arc::downgrade(&uploader);
-    let cloned_user_service = user_service.clone();
-    tokio::spawn(async move {
-      // Start uploading after 20 seconds
-      tokio::time::sleep(Duration::from_secs(20)).await;
-      if let Some(uploader) = weak_uploader.upgrade() {
-        if let Err(err) = prepare_upload_task(uploader, cloned_user_service).await {
-          error!("prepare upload task failed: {}", err);
-        }
-      }
-    });
+    let mut manual_relay = None;
+    if let Some(interval) = reconcile_interval.filter(|_| !manual_background_tasks) {
+      spawner.spawn(Box::pin(run_reconciliation(
+        interval,
+        storage_service.config.clock.clone(),
+        Arc::downgrade(&storage_service),
+        Arc::downgrade(&uploader),
+      )));
+    }
 
-    let mut rx = global_notifier.subscribe();
-    let weak_notifier = Arc::downgrade(&progress_notifiers);
-    tokio::spawn(async move {
-      while let Ok(progress) = rx.recv().await {
-        if let Some(notifiers) = weak_notifier.upgrade() {
-          if let Some(mut notifier) = notifiers.get_mut(&progress.file_id) {
-            if progress.progress >= 1.0 {
-              let finish = FileUploadState::Finished {
-                file_id: progress.file_id,
-              };
-              notifier.notify(finish).await;
-            } else {
-              let progress = FileUploadState::Uploading {
-                progress: progress.progress,
-              };
-              notifier.notify(progress).await;
-            }
-          }
-        } else {
-          info!("progress notifiers is dropped");
-          break;
-        }
+    if progress_fan_out == ProgressFanOut::Relay {
+      if manual_background_tasks {
+        manual_relay = Some(global_notifier.subscribe());
+      } else {
+        spawner.spawn(Box::pin(run_progress_relay(
+          global_notifier.subscribe(),
+          Arc::downgrade(&progress_notifiers),
+          cloud_service.clone(),
+        )));
       }
-    });
+    }
 
     Self {
-      storage_service,
+      storage_service: storage_service.clone(),
+      service: storage_service,
       cloud_service,
       user_service,
       uploader,
       progress_notifiers,
       global_notifier,
+      delete_notifier,
+      download_notifier,
+      bandwidth,
+      file_state_throttles: Default::default(),
+      pause_state_lock: Default::default(),
+      manual_relay: tokio::sync::Mutex::new(manual_relay),
+    }
+  }
+
+  /// Forwards the progress sent since the last call to the per-file notifiers, in place of the
+  /// relay task with [StorageManagerConfig::manual_background_tasks]. Returns the number of the
+  /// forwarded progress, always zero otherwise.
+  pub async fn tick_relay(&self) -> usize {
+    let mut manual_relay = self.manual_relay.lock().await;
+    let rx = match manual_relay.as_mut() {
+      Some(rx) => rx,
+      None => return 0,
+    };
+    let mut relayed = 0;
+    loop {
+      match rx.try_recv() {
+        Ok(progress) => {
+          relay_progress(&progress, &self.progress_notifiers, &self.cloud_service).await;
+          relayed += 1;
+        },
+        Err(TryRecvError::Lagged(skipped)) => {
+          warn!("[File] progress relay lagged, skipped {} events", skipped);
+        },
+        Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => return relayed,
+      }
     }
   }
 
+  /// Queues the unfinished uploads of the current workspace, which
+  /// [Self::initialize] leaves to this call with [StorageManagerConfig::manual_background_tasks].
+  pub async fn run_startup_resume(&self) -> FlowyResult<()> {
+    prepare_upload_task(&self.service, &self.uploader).await
+  }
+
+  /// Returns the service behind the manager, for the callers that need a lower-level control over
+  /// the uploads and downloads.
+  ///
+  /// The service shares the state of the manager: the uploads it creates go through the same
+  /// queue, limits and progress notifiers. It's kept alive as long as it's referenced, but stops
+  /// uploading once the manager is dropped, since the manager owns the uploader.
+  pub fn storage_service(&self) -> Arc<dyn StorageService> {
+    self.storage_service.clone()
+  }
+
   pub async fn register_file_progress_stream(&self, port: i64) {
     info!("register file progress stream: {}", port);
     let mut sink = IsolateSink::new(Isolate::new(port));
-    let mut rx = self.global_notifier.subscribe();
-    tokio::spawn(async move {
-      while let Ok(progress) = rx.recv().await {
+    let (history, mut rx) = self.global_notifier.subscribe_with_history();
+    self.service.config.spawner.spawn(Box::pin(async move {
+      // Replay the recent progress before streaming the new one.
+      for progress in history {
         if let Ok(s) = serde_json::to_string(&progress) {
           if let Err(err) = sink.send(s).await {
             error!("[File]: send file progress failed: {}", err);
           }
         }
       }
-    });
+
+      loop {
+        match rx.recv().await {
+          Ok(progress) => {
+            if let Ok(s) = serde_json::to_string(&progress) {
+              if let Err(err) = sink.send(s).await {
+                error!("[File]: send file progress failed: {}", err);
+              }
+            }
+          },
+          Err(RecvError::Lagged(skipped)) => {
+            warn!(
+              "[File]: file progress stream lagged, skipped {} events",
+              skipped
+            );
+          },
+          Err(RecvError::Closed) => {
+            info!("[File]: file progress stream stopped, the storage manager was dropped");
+            break;
+          },
+        }
+      }
+    }));
   }
 
-  pub async fn query_file_state(&self, url: &str) -> Option<FileStatePB> {
-    let (workspace_id, parent_dir, file_id) = self.cloud_service.parse_object_url_v1(url).await?;
-    let current_workspace_id = self.user_service.workspace_id().ok()?;
-    if workspace_id != current_workspace_id {
-      return None;
-    }
+  /// Subscribes to the progress of the transfers. The recent progress, oldest first, is returned
+  /// along with the receiver, so that a consumer attaching mid-session can show the recent
+  /// activity. The receiver only yields the progress sent after the returned history.
+  pub fn subscribe_events(&self) -> (Vec<FileProgress>, broadcast::Receiver<FileProgress>) {
+    self.global_notifier.subscribe_with_history()
+  }
 
-    let uid = self.user_service.user_id().ok()?;
-    let mut conn = self.user_service.sqlite_connection(uid).ok()?;
-    let is_finish = is_upload_completed(&mut conn, &workspace_id, &parent_dir, &file_id).ok()?;
+  /// Subscribes to the progress of the deletes started by [StorageService::delete_object].
+  pub fn subscribe_delete_progress(&self) -> broadcast::Receiver<DeleteProgress> {
+    self.delete_notifier.subscribe()
+  }
 
-    if let Err(err) = self.global_notifier.send(FileProgress::new_progress(
-      url.to_string(),
-      file_id.clone(),
-      if is_finish { 1.0 } else { 0.0 },
-    )) {
-      error!("[File] send global notifier failed: {}", err);
+  /// Downloads the objects to their local files. The downloads share the
+  /// [StorageManagerConfig::max_concurrent_downloads] limit with the other downloads. A url that's
+  /// already downloading isn't fetched again: the request joins the running download, and copies
+  /// its file when the local file path differs.
+  pub fn download_objects(&self, requests: Vec<(String, String)>) -> DownloadBatchHandle {
+    self.download_objects_with_priority(requests, DownloadPriority::UserInitiated)
+  }
+
+  /// Downloads the objects ahead of time, e.g. the attachments of an opened page. The prefetch
+  /// downloads yield to the user-initiated ones.
+  pub fn prefetch_objects(&self, requests: Vec<(String, String)>) -> DownloadBatchHandle {
+    self.download_objects_with_priority(requests, DownloadPriority::Prefetch)
+  }
+
+  fn download_objects_with_priority(
+    &self,
+    requests: Vec<(String, String)>,
+    priority: DownloadPriority,
+  ) -> DownloadBatchHandle {
+    let downloader = &self.service.downloader;
+    let files = requests
+      .into_iter()
+      .map(|(url, local_file_path)| {
+        let state = downloader.download(
+          url.clone(),
+          local_file_path.clone(),
+          priority,
+          ExistingFilePolicy::default(),
+        );
+        (url, local_file_path, state)
+      })
+      .collect();
+    DownloadBatchHandle::new(files, self.service.config.spawner.as_ref())
+  }
+
+  /// Downloads the objects of the urls into the download cache ahead of time, e.g. for the pages
+  /// made available offline. The downloads are queued at the [DownloadPriority::Prefetch]
+  /// priority, so they yield to the user-initiated ones, and share the
+  /// [StorageManagerConfig::max_concurrent_downloads] limit. An object already in the cache, or
+  /// kept by its upload, isn't fetched again and counts as downloaded in the progress of the batch.
+  /// A url that can't be mapped to the cache counts as failed.
+  pub async fn prefetch(&self, urls: Vec<String>) -> DownloadBatchHandle {
+    let mut files = Vec::with_capacity(urls.len());
+    for url in urls {
+      let (local_file_path, state) = match self.prefetch_url(&url).await {
+        Ok(file) => file,
+        Err(err) => {
+          let (_, state) = watch::channel(DownloadState::DownloadFailed {
+            error: err.to_string(),
+          });
+          (String::new(), state)
+        },
+      };
+      files.push((url, local_file_path, state));
     }
+    DownloadBatchHandle::new(files, self.service.config.spawner.as_ref())
+  }
 
-    Some(FileStatePB { file_id, is_finish })
+  async fn prefetch_url(&self, url: &str) -> FlowyResult<(String, DownloadStateReceiver)> {
+    let cached_path = match self.cached_upload_path(url).await {
+      Some(path) => path,
+      None => self.default_download_path(url).await?,
+    };
+    let local_file_path = cached_path.to_string_lossy().into_owned();
+    // The downloads land in a `.part` file first, so a file in the cache is complete.
+    if tokio::fs::metadata(&cached_path)
+      .await
+      .map(|metadata| metadata.is_file())
+      .unwrap_or(false)
+    {
+      let (_, state) = watch::channel(DownloadState::Downloaded);
+      return Ok((local_file_path, state));
+    }
+    if let Some(parent) = cached_path.parent() {
+      tokio::fs::create_dir_all(parent).await?;
+    }
+    let state = self.service.downloader.download(
+      url.to_string(),
+      local_file_path.clone(),
+      DownloadPriority::Prefetch,
+      ExistingFilePolicy::default(),
+    );
+    Ok((local_file_path, state))
   }
 
-  pub async fn initialize(&self, _workspace_id: &str) {
-    self.enable_storage_write_access();
+  /// Downloads the object to the local file, like [StorageService::download_object], with the given
+  /// policy for an already existing local file. The other downloads use
+  /// [ExistingFilePolicy::VerifyThenSkip].
+  pub fn download_object_with_policy(
+    &self,
+    url: String,
+    local_file_path: String,
+    existing_file_policy: ExistingFilePolicy,
+  ) -> DownloadStateReceiver {
+    self.service.downloader.download(
+      url,
+      local_file_path,
+      DownloadPriority::UserInitiated,
+      existing_file_policy,
+    )
   }
 
-  pub fn update_network_reachable(&self, reachable: bool) {
-    if reachable {
-      self.uploader.resume();
-    } else {
-      self.uploader.pause();
+  /// Returns the path of the download cache where the object of the url lands, as placed by
+  /// [StorageManagerConfig::download_path_format]. The path is the same for the same object, so
+  /// downloading it again finds the existing file.
+  pub async fn default_download_path(&self, url: &str) -> FlowyResult<PathBuf> {
+    let (workspace_id, parent_dir, file_id) = parse_object_url(&self.cloud_service, url)
+      .await
+      .ok_or_else(|| FlowyError::invalid_data().with_context(format!("invalid url: {}", url)))?;
+    Ok(download_file_path(
+      &self.service.config,
+      &self.user_service,
+      &workspace_id,
+      &parent_dir,
+      &file_id,
+    ))
+  }
+
+  /// Returns the local copy of the object the upload of the url kept, see
+  /// [StorageManagerConfig::temp_file_policy]. `None` when the upload isn't completed or its copy
+  /// was removed.
+  pub async fn cached_upload_path(&self, url: &str) -> Option<PathBuf> {
+    let (workspace_id, parent_dir, file_id) = parse_object_url(&self.cloud_service, url).await?;
+    let record = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await.ok()?;
+      select_upload_file(&mut conn, &workspace_id, &parent_dir, &file_id).ok()??
+    };
+    let path = PathBuf::from(record.local_file_path);
+    (record.is_finish && path.is_file()).then_some(path)
+  }
+
+  /// Downloads the object of the url to its [Self::default_download_path]. The local copy kept by
+  /// the upload of the object is returned instead when it exists, see [Self::cached_upload_path].
+  pub async fn download(&self, url: &str) -> FlowyResult<(PathBuf, DownloadStateReceiver)> {
+    if let Some(path) = self.cached_upload_path(url).await {
+      let (_, state) = watch::channel(DownloadState::Downloaded);
+      return Ok((path, state));
+    }
+    let local_file_path = self.default_download_path(url).await?;
+    if let Some(parent) = local_file_path.parent() {
+      tokio::fs::create_dir_all(parent).await?;
     }
+    let state = self.service.downloader.download(
+      url.to_string(),
+      local_file_path.to_string_lossy().into_owned(),
+      DownloadPriority::UserInitiated,
+      ExistingFilePolicy::default(),
+    );
+    Ok((local_file_path, state))
   }
 
-  pub fn disable_storage_write_access(&self) {
-    // when storage is purchased, resume the uploader
-    self.uploader.disable_storage_write();
+  /// Same as [StorageService::create_upload], for a path that isn't valid UTF-8, which some file
+  /// systems allow. Only the temp copy of the file, which is always named in UTF-8, is recorded, so
+  /// the upload resumes like any other.
+  pub async fn create_upload_from_path(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    local_file_path: &Path,
+    upload_immediately: bool,
+  ) -> Result<(CreatedUpload, Option<FileProgressReceiver>), FlowyError> {
+    self
+      .service
+      .create_upload_with_options(
+        workspace_id,
+        parent_dir,
+        local_file_path,
+        upload_immediately,
+        CreateUploadOptions::default(),
+      )
+      .await
   }
 
-  pub fn enable_storage_write_access(&self) {
-    // when storage is purchased, resume the uploader
-    self.uploader.enable_storage_write();
+  /// Same as [StorageService::create_upload], with the [CreateUploadOptions] of the upload, e.g.
+  /// its content type, metadata and storage class. When [CreateUploadOptions::on_progress] is set,
+  /// the progress is passed to the callback and no receiver is returned.
+  pub async fn create_upload_with(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    local_file_path: &str,
+    upload_immediately: bool,
+    mut options: CreateUploadOptions,
+  ) -> Result<(CreatedUpload, Option<FileProgressReceiver>), FlowyError> {
+    let on_progress = options.on_progress.take();
+    let (created_upload, receiver) = self
+      .service
+      .create_upload_with_options(
+        workspace_id,
+        parent_dir,
+        Path::new(local_file_path),
+        upload_immediately,
+        options,
+      )
+      .await?;
+    let Some(on_progress) = on_progress else {
+      return Ok((created_upload, receiver));
+    };
+    let receiver = match receiver {
+      Some(receiver) => Some(receiver),
+      None => {
+        self
+          .service
+          .subscribe_file_progress(parent_dir, &created_upload.file_id)
+          .await?
+      },
+    };
+    let mut receiver = match receiver {
+      Some(receiver) => receiver,
+      None => return Ok((created_upload, None)),
+    };
+
+    let file_url = created_upload.url.clone();
+    let workspace_id = workspace_id.to_string();
+    self.service.config.spawner.spawn(Box::pin(async move {
+      let mut last_progress = 0.0;
+      loop {
+        match receiver.recv().await {
+          Ok(state) => {
+            let is_finished = matches!(state, FileUploadState::Finished { .. });
+            if let Some(progress) =
+              state_progress(&file_url, &receiver.file_id, state, last_progress)
+                .map(|progress| progress.with_workspace_id(workspace_id.clone()))
+            {
+              last_progress = progress.progress;
+              on_progress(progress);
+            }
+            if is_finished {
+              break;
+            }
+          },
+          Err(RecvError::Lagged(skipped)) => {
+            trace!(
+              "[File] progress callback of {} lagged, skipped {} states",
+              receiver.file_id,
+              skipped
+            );
+          },
+          // The upload was cancelled or removed.
+          Err(RecvError::Closed) => break,
+        }
+      }
+    }));
+    Ok((created_upload, None))
   }
 
-  pub async fn subscribe_file_state(
+  /// Returns the metadata of the object of the url, see [CreateUploadOptions::metadata]. It's
+  /// read from the server when it supports
+  /// [flowy_storage_pub::cloud::StorageCapabilities::object_metadata], otherwise from the local
+  /// upload record.
+  pub async fn object_metadata(&self, url: &str) -> FlowyResult<HashMap<String, String>> {
+    let (workspace_id, parent_dir, file_id) = parse_object_url(&self.cloud_service, url)
+      .await
+      .ok_or_else(|| FlowyError::invalid_data().with_context(format!("invalid url: {}", url)))?;
+    if self.cloud_service.capabilities().object_metadata {
+      return self
+        .cloud_service
+        .object_metadata(&workspace_id, &parent_dir, &file_id)
+        .await;
+    }
+    let record = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_upload_file(&mut conn, &workspace_id, &parent_dir, &file_id)?
+    }
+    .ok_or_else(|| FlowyError::from(StorageError::RecordNotFound(file_id.clone())))?;
+    metadata_from_record(&record.metadata)
+  }
+
+  /// Reads `len` bytes of the object of the url from `start`, e.g. for a media player seeking in
+  /// the file. The bytes are shorter than `len` at the end of the object, and empty past it. Fails
+  /// with [ErrorCode::NotSupportYet] when the server doesn't support
+  /// [flowy_storage_pub::cloud::StorageCapabilities::range_downloads]. The small ranges are
+  /// cached, see [StorageManagerConfig::range_cache_capacity].
+  pub async fn read_range(&self, url: &str, start: u64, len: u64) -> FlowyResult<Bytes> {
+    if !self.cloud_service.capabilities().range_downloads {
+      return Err(FlowyError::not_support().with_context("ranged reads are not supported"));
+    }
+    if len == 0 {
+      return Ok(Bytes::new());
+    }
+    if let Some(bytes) = self.service.range_cache.get(url, start, len) {
+      return Ok(bytes);
+    }
+    let range = self
+      .cloud_service
+      .get_object_range(url.to_string(), start..start.saturating_add(len))
+      .await?;
+    self
+      .service
+      .range_cache
+      .insert(url, start, len, range.raw.clone());
+    Ok(range.raw)
+  }
+
+  /// Lists a page of the objects the server stores under the parent dir, e.g. for a file browser.
+  /// Pass `None` as the cursor for the first page, then the
+  /// [ObjectPage::next_cursor] of the previous page. Fails with
+  /// [ErrorCode::NotSupportYet] when the server doesn't support
+  /// [flowy_storage_pub::cloud::StorageCapabilities::list_objects].
+  pub async fn list_objects(
     &self,
+    workspace_id: &str,
     parent_dir: &str,
-    file_id: &str,
-  ) -> Result<Option<FileProgressReceiver>, FlowyError> {
+    cursor: Option<&str>,
+  ) -> FlowyResult<ObjectPage> {
+    let parent_dir = &canonical_parent_dir(parent_dir)?;
+    if !self.cloud_service.capabilities().list_objects {
+      return Err(FlowyError::not_support().with_context("listing objects is not supported"));
+    }
     self
-      .storage_service
-      .subscribe_file_progress(parent_dir, file_id)
+      .cloud_service
+      .list_objects(workspace_id, parent_dir, cursor)
       .await
   }
 
-  pub async fn get_file_state(&self, file_id: &str) -> Option<FileUploadState> {
+  /// Runs the checks of [StorageService::create_upload] without creating the upload: no record, temp
+  /// file or task is created. It tells the UI right away whether the file would be uploaded, is
+  /// already uploaded or uploading, or would be rejected.
+  pub async fn validate_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    local_file_path: &str,
+  ) -> FlowyResult<UploadValidation> {
     self
-      .progress_notifiers
-      .get(file_id)
-      .and_then(|notifier| notifier.value().current_value.clone())
+      .service
+      .validate_upload(workspace_id, parent_dir, Path::new(local_file_path))
+      .await
   }
-}
 
-async fn prepare_upload_task(
-  uploader: Arc<FileUploader>,
-  user_service: Arc<dyn StorageUserService>,
-) -> FlowyResult<()> {
-  let uid = user_service.user_id()?;
-  let conn = user_service.sqlite_connection(uid)?;
-  let upload_files = batch_select_upload_file(conn, 100, false)?;
-  let tasks = upload_files
-    .into_iter()
-    .map(|upload_file| UploadTask::BackgroundTask {
-      workspace_id: upload_file.workspace_id,
-      file_id: upload_file.file_id,
-      parent_dir: upload_file.parent_dir,
-      created_at: upload_file.created_at,
-      retry_count: 0,
-    })
-    .collect::<Vec<_>>();
-  info!("[File] prepare upload task: {}", tasks.len());
-  uploader.queue_tasks(tasks).await;
-  Ok(())
-}
+  /// Subscribes to the progress of the downloads started by [StorageService::download_object] and
+  /// [Self::download_objects].
+  pub fn subscribe_download_progress(&self) -> broadcast::Receiver<DownloadProgress> {
+    self.download_notifier.subscribe()
+  }
 
-pub struct StorageServiceImpl {
-  cloud_service: Arc<dyn StorageCloudService>,
-  user_service: Arc<dyn StorageUserService>,
-  temp_storage: Arc<FileTempStorage>,
-  task_queue: Arc<UploadTaskQueue>,
-  is_exceed_storage_limit: Arc<AtomicBool>,
-  progress_notifiers: Arc<DashMap<String, ProgressNotifier>>,
-  global_notifier: GlobalNotifier,
-}
+  /// Returns the upload state of the file of the url. The url can be in the v1 or v2 format. The
+  /// state is also sent to the progress stream, at most once per
+  /// [StorageManagerConfig::file_state_emit_interval] for each file unless it changed.
+  pub async fn query_file_state(&self, url: &str) -> Option<FileStatePB> {
+    let (workspace_id, parent_dir, file_id) = parse_object_url(&self.cloud_service, url).await?;
+    let current_workspace_id = match self.service.current_workspace_id() {
+      Ok(workspace_id) => workspace_id,
+      Err(err) => {
+        warn!("[File] query file state of {} failed: {}", url, err);
+        return None;
+      },
+    };
+    if workspace_id != current_workspace_id {
+      return None;
+    }
 
-#[async_trait]
-impl StorageService for StorageServiceImpl {
-  fn delete_object(&self, url: String, local_file_path: String) -> FlowyResult<()> {
-    let cloud_service = self.cloud_service.clone();
-    tokio::spawn(async move {
-      match tokio::fs::remove_file(&local_file_path).await {
-        Ok(_) => {
-          debug!("[File] deleted file from local disk: {}", local_file_path)
-        },
-        Err(err) => {
-          error!("[File] delete file at {} failed: {}", local_file_path, err);
-        },
+    let is_finish = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await.ok()?;
+      is_upload_completed(&mut conn, &workspace_id, &parent_dir, &file_id).ok()?
+    };
+
+    // The synthetic state always reports an upload. It's only sent again once the interval elapsed
+    // or the upload finished, the files are queried on each render.
+    let key = upload_key(&workspace_id, &parent_dir, &file_id);
+    let progress = if is_finish { 1.0 } else { 0.0 };
+    let should_emit = self
+      .file_state_throttles
+      .entry(key.clone())
+      .or_insert_with(|| {
+        let config = &self.service.config;
+        ProgressThrottle::new(
+          config.file_state_emit_interval,
+          Some(1.0),
+          config.clock.clone(),
+        )
+      })
+      .should_emit(progress);
+    if should_emit {
+      let progress = FileProgress::new_progress(url.to_string(), file_id.clone(), progress)
+        .with_direction(TransferDirection::Upload)
+        .with_workspace_id(workspace_id);
+      if let Err(err) = self.global_notifier.send_upload(&key, progress).await {
+        error!("[File] send global notifier failed: {}", err);
       }
-      if let Err(e) = cloud_service.delete_object(&url).await {
-        // TODO: add WAL to log the delete operation.
-        // keep a list of files to be deleted, and retry later
-        error!("[File] delete file failed: {}", e);
+    }
+
+    Some(FileStatePB { file_id, is_finish })
+  }
+
+  /// Validates the storage and queues the unfinished uploads. It fails when the temporary files
+  /// can't be written or the upload tables can't be migrated, no upload can be created then. With
+  /// [StorageManagerConfig::manual_background_tasks], the unfinished uploads are left to
+  /// [Self::run_startup_resume].
+  ///
+  /// The operations that resolve the workspace from the [StorageUserService], like
+  /// [Self::subscribe_file_state], fail with [StorageError::WorkspaceChanged] when the active
+  /// workspace isn't the one initialized last, until the storage is initialized for it. The uploads
+  /// always target the workspace of their record, so the queued uploads of the previous workspace
+  /// keep uploading to it.
+  pub async fn initialize(&self, workspace_id: &str) -> FlowyResult<()> {
+    let uid = self.user_service.user_id()?;
+    if let Some(previous_uid) = self.service.temp_storage.switch_user(uid) {
+      // The temp files of the previous user must not be reachable from the session of this one.
+      if let Err(err) = self.clear_user_temp(previous_uid).await {
+        error!(
+          "[File] clear temp files of user {} failed: {}",
+          previous_uid, err
+        );
       }
-      debug!("[File] deleted file from cloud: {}", url);
-    });
+    }
+    self
+      .service
+      .temp_storage
+      .validate()
+      .await
+      .map_err(|err| FlowyError::from(StorageError::TempFileCreation(err.to_string())))?;
+    {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      flowy_sqlite::run_migrations(&mut conn)
+        .map_err(|err| FlowyError::internal().with_context(err))?;
+    }
+
+    *self.service.opened_workspace_id.write().unwrap() = Some(workspace_id.to_string());
+    self.enable_storage_write_access();
+    // Purged before queueing the unfinished uploads, so the expired ones aren't uploaded again.
+    if let Err(err) = self.service.purge_failed_uploads().await {
+      error!("[File] purge failed uploads failed: {}", err);
+    }
+    if !self.service.config.manual_background_tasks {
+      prepare_upload_task(&self.service, &self.uploader).await?;
+    }
+    prepare_download_task(&self.service).await?;
     Ok(())
   }
 
-  fn download_object(&self, url: String, local_file_path: String) -> FlowyResult<()> {
-    let cloud_service = self.cloud_service.clone();
-    tokio::spawn(async move {
-      if tokio::fs::metadata(&local_file_path).await.is_ok() {
-        tracing::warn!("file already exist in user local disk: {}", local_file_path);
-        return Ok(());
-      }
-      let object_value = cloud_service.get_object(url).await?;
-      let mut file = tokio::fs::OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(&local_file_path)
-        .await?;
+  /// Returns the dir of the temp files of the current user.
+  pub fn user_temp_dir(&self) -> PathBuf {
+    self.service.temp_storage.storage_dir()
+  }
 
-      match file.write(&object_value.raw).await {
-        Ok(n) => {
-          info!("downloaded {} bytes to file: {}", n, local_file_path);
-        },
-        Err(err) => {
-          error!("write file failed: {}", err);
-        },
-      }
-      Ok::<_, FlowyError>(())
-    });
+  /// Removes the temp files of the user, e.g. when the user signs out. The unfinished uploads of
+  /// the user lose their temp files, so they fail when resumed. They're also removed when another
+  /// user initializes the storage.
+  pub async fn clear_user_temp(&self, uid: i64) -> FlowyResult<()> {
+    self.service.temp_storage.clear_user(uid).await?;
     Ok(())
   }
 
-  async fn create_upload(
-    &self,
-    workspace_id: &str,
-    parent_dir: &str,
-    file_path: &str,
-    upload_immediately: bool,
-  ) -> Result<(CreatedUpload, Option<FileProgressReceiver>), FlowyError> {
-    if workspace_id.is_empty() {
-      return Err(FlowyError::internal().with_context("workspace id is empty"));
+  pub fn update_network_reachable(&self, reachable: bool) {
+    self.uploader.set_network_reachable(reachable);
+  }
+
+  /// Sets whether the connection is metered. On a metered connection, only the uploads the user
+  /// started with `upload_immediately` proceed, the other and the background uploads wait in the
+  /// queue until it's no longer metered. The running uploads finish.
+  pub fn set_metered(&self, metered: bool) {
+    if self.uploader.set_metered(metered) {
+      info!("[File] metered connection: {}", metered);
     }
+  }
+
+  pub fn is_metered(&self) -> bool {
+    self.uploader.is_metered()
+  }
 
-    if parent_dir.is_empty() {
-      return Err(FlowyError::internal().with_context("parent dir is empty"));
+  pub fn disable_storage_write_access(&self) {
+    if self.uploader.disable_storage_write() {
+      notify_storage_write_access(false);
+      self.notify_pause_state();
     }
+  }
 
-    if file_path.is_empty() {
-      return Err(FlowyError::internal().with_context("local file path is empty"));
+  pub fn enable_storage_write_access(&self) {
+    // when storage is purchased, resume the uploader
+    if self.uploader.enable_storage_write() {
+      notify_storage_write_access(true);
+      self.notify_pause_state();
     }
+  }
 
-    let workspace_id = workspace_id.to_string();
-    let parent_dir = parent_dir.to_string();
-    let file_path = file_path.to_string();
+  /// Tells the subscribers of the waiting uploads why they don't make progress, or that they
+  /// resumed, see [notify_pause_state].
+  fn notify_pause_state(&self) {
+    let service = self.service.clone();
+    let uploader = self.uploader.clone();
+    let lock = self.pause_state_lock.clone();
+    service.config.spawner.spawn(Box::pin(async move {
+      // The notifications read the state when they run, so the last one reflects the last change.
+      let _guard = lock.lock().await;
+      if let Err(err) = notify_pause_state(&service, &uploader).await {
+        error!("[File] notify pause state failed: {}", err);
+      }
+    }));
+  }
 
-    let is_exceed_limit = self
-      .is_exceed_storage_limit
-      .load(std::sync::atomic::Ordering::Relaxed);
-    if is_exceed_limit {
-      make_notification(StorageNotification::FileStorageLimitExceeded)
-        .payload(FlowyError::file_storage_limit())
-        .send();
+  /// Records the bytes used and the bytes the workspace can store, as reported by the server. They
+  /// are carried by the [StorageLimitExceededPB] payload of the error returned when the storage
+  /// limit blocks an upload.
+  pub fn update_storage_usage(&self, used_bytes: u64, total_bytes: u64) {
+    *self.service.storage_usage.write().unwrap() = Some((used_bytes, total_bytes));
+  }
 
-      return Err(FlowyError::file_storage_limit());
-    }
+  /// Returns true when the storage write access is enabled. The uploads also need the network to
+  /// be reachable to make progress.
+  pub fn is_storage_write_enabled(&self) -> bool {
+    self.uploader.is_storage_write_enabled()
+  }
+
+  /// Returns the reasons that keep the uploads of the current workspace from making progress. The
+  /// changes of the reasons pausing all the uploads are notified with
+  /// [StorageNotification::UploadPauseReasonsChanged].
+  pub fn effective_pause_reasons(&self) -> PauseReasons {
+    match self.service.current_workspace_id() {
+      Ok(workspace_id) => {
+        let mut reasons = self.uploader.effective_pause_reasons();
+        if self.uploader.is_workspace_paused(&workspace_id) {
+          reasons.insert(PauseReasons::WORKSPACE_PAUSED);
+        }
+        reasons
+      },
+      Err(_) => self.uploader.effective_pause_reasons(),
+    }
+  }
+
+  /// Returns the reasons that keep the upload of the file from making progress.
+  pub fn file_pause_reasons(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> PauseReasons {
+    self
+      .uploader
+      .pause_reasons_of(workspace_id, &lookup_parent_dir(parent_dir), file_id)
+  }
+
+  /// Pauses the uploads of the workspace. The running uploads finish, the queued ones wait until
+  /// [Self::resume_workspace_uploads] is called.
+  pub fn pause_workspace_uploads(&self, workspace_id: &str) {
+    self.uploader.pause_workspace(workspace_id);
+  }
+
+  pub fn resume_workspace_uploads(&self, workspace_id: &str) {
+    self.uploader.resume_workspace(workspace_id);
+  }
+
+  /// Pauses the upload of the file. A running upload finishes, a queued one waits until
+  /// [Self::resume_file_upload] is called.
+  pub fn pause_file_upload(&self, workspace_id: &str, parent_dir: &str, file_id: &str) {
+    self
+      .uploader
+      .pause_file(workspace_id, &lookup_parent_dir(parent_dir), file_id);
+  }
+
+  pub fn resume_file_upload(&self, workspace_id: &str, parent_dir: &str, file_id: &str) {
+    self
+      .uploader
+      .resume_file(workspace_id, &lookup_parent_dir(parent_dir), file_id);
+  }
+
+  pub async fn subscribe_file_state(
+    &self,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> Result<Option<FileProgressReceiver>, FlowyError> {
+    self
+      .storage_service
+      .subscribe_file_progress(parent_dir, file_id)
+      .await
+  }
+
+  /// Cancels the upload of the file. The running upload is aborted immediately, including its
+  /// in-flight part request, and the upload record, parts and temp file are removed. The
+  /// server-side multipart upload is aborted as well.
+  pub async fn cancel_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<()> {
+    self
+      .storage_service
+      .cancel_upload(workspace_id, parent_dir, file_id)
+      .await
+  }
+
+  /// Cancels the queued or running download of the url. The running download is aborted, and its
+  /// partial file and download record are removed. The subscribers receive a cancelled state.
+  /// Cancelling a url that's not downloading does nothing.
+  pub async fn cancel_download(&self, url: &str) -> FlowyResult<()> {
+    self.service.downloader.cancel(url).await?;
+    Ok(())
+  }
+
+  /// Queues the unfinished upload of the file again, e.g. when the user retries a failed upload.
+  /// With `immediate`, the upload jumps ahead of the other queued uploads. The queued tasks of the
+  /// file are replaced, so the file is only uploaded once.
+  pub async fn retry_failed_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    immediate: bool,
+  ) -> FlowyResult<()> {
+    self
+      .service
+      .retry_upload(workspace_id, parent_dir, file_id, immediate)
+      .await
+  }
+
+  /// Resumes the unfinished upload of the file with the strategy. [ResumeStrategy::Continue] reuses
+  /// the uploaded parts like [StorageService::resume_upload], [ResumeStrategy::Restart] uploads the
+  /// whole file again. Nothing is done when the file is already uploading.
+  pub async fn resume_upload_with_strategy(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    strategy: ResumeStrategy,
+  ) -> FlowyResult<()> {
+    self
+      .service
+      .resume_upload_with_strategy(workspace_id, parent_dir, file_id, strategy)
+      .await
+  }
+
+  /// Returns the failed uploads of the workspace, the most recent failure first. They're kept for
+  /// the user to retry them with [Self::retry_failed_upload] until
+  /// [StorageManagerConfig::failed_upload_retention] elapsed.
+  pub async fn list_failed_uploads(
+    &self,
+    workspace_id: &str,
+  ) -> FlowyResult<Vec<UploadFileFailureTable>> {
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    select_upload_failures(&mut conn, workspace_id)
+  }
+
+  /// Pins the failed upload so that it's never purged, or unpins it. Fails when the upload has no
+  /// failure.
+  pub async fn pin_failed_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    pinned: bool,
+  ) -> FlowyResult<()> {
+    let parent_dir = &canonical_parent_dir(parent_dir)?;
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    if update_upload_failure_pinned(&mut conn, workspace_id, parent_dir, file_id, pinned)? {
+      Ok(())
+    } else {
+      Err(StorageError::RecordNotFound(file_id.to_string()).into())
+    }
+  }
+
+  /// Purges the failed uploads that weren't retried within
+  /// [StorageManagerConfig::failed_upload_retention], along with their parts and temp files. The
+  /// pinned and running uploads are kept. Returns the number of purged uploads. It runs when the
+  /// storage is initialized and after each periodic reconciliation.
+  pub async fn purge_failed_uploads(&self) -> FlowyResult<usize> {
+    self.service.purge_failed_uploads().await
+  }
+
+  /// Returns the timings of the uploaded parts of the file, to tell a single slow part from a
+  /// uniformly slow upload. Only available with the `diagnostics` feature.
+  #[cfg(feature = "diagnostics")]
+  pub fn upload_timing(&self, file_id: &str) -> Vec<PartTiming> {
+    self.service.part_timings.timings(file_id)
+  }
+
+  /// Returns when the upload of the file was queued, started and finished, which tells the time
+  /// spent waiting for an upload slot apart from the time spent uploading. Only the uploads run by
+  /// the background uploader are tracked.
+  pub fn upload_detail(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> Option<UploadDetail> {
+    self
+      .service
+      .task_queue
+      .upload_detail(workspace_id, &lookup_parent_dir(parent_dir), file_id)
+  }
+
+  /// Returns the persisted lifecycle events of the uploads of the file, the oldest event first.
+  /// Unlike the progress stream, the log survives a restart. It's bounded by
+  /// [StorageManagerConfig::upload_log_max_rows] and [StorageManagerConfig::upload_log_max_age].
+  pub async fn upload_log(&self, file_id: &str) -> FlowyResult<Vec<UploadLogTable>> {
+    self.service.upload_log.file_logs(file_id).await
+  }
+
+  /// Returns the last `limit` events of the upload log of all the files, the oldest event first.
+  pub async fn upload_log_tail(&self, limit: usize) -> FlowyResult<Vec<UploadLogTable>> {
+    self.service.upload_log.tail(limit).await
+  }
+
+  /// Sets the maximum upload rate in bytes per second shared by all the uploads. `None` removes the
+  /// limit.
+  pub fn set_bandwidth_limit(&self, bytes_per_sec: Option<u64>) {
+    self.bandwidth.set_global_limit(bytes_per_sec);
+  }
+
+  /// Sets the maximum upload rate in bytes per second of the file. The file is also bound by the
+  /// global limit, so the lower of the two applies. It takes effect on the next uploaded part,
+  /// including for a running upload. `None` removes the limit.
+  pub fn set_file_bandwidth_limit(&self, file_id: &str, bytes_per_sec: Option<u64>) {
+    self.bandwidth.set_file_limit(file_id, bytes_per_sec);
+  }
+
+  /// Returns the number of uploads not completed yet, across all the workspaces. Callers can
+  /// throttle themselves before reaching [StorageManagerConfig::max_pending_uploads].
+  pub async fn pending_upload_count(&self) -> FlowyResult<usize> {
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    count_unfinished_upload_files(&mut conn)
+  }
+
+  /// Waits until no upload is queued or running, for at most `timeout`. It's meant for the tests
+  /// and before a shutdown, to let the uploads settle. The uploads that are queued but paused, by
+  /// the network, the storage quota or an explicit pause, never settle on their own, so it returns
+  /// [IdleOutcome::Paused] as soon as only those are left rather than waiting for the timeout.
+  pub async fn wait_until_idle(&self, timeout: Duration) -> IdleOutcome {
+    let clock = &self.service.config.clock;
+    let deadline = clock.now() + timeout;
+    loop {
+      let (queued, reasons) = self.uploader.queued_tasks().await;
+      // The uploads started outside of the uploader, e.g. a resume, are only in the active uploads.
+      let running = self
+        .uploader
+        .running_uploads()
+        .max(self.service.active_uploads.len());
+      if running == 0 {
+        if queued == 0 {
+          return IdleOutcome::Idle;
+        }
+        if !reasons.is_empty() {
+          return IdleOutcome::Paused { queued, reasons };
+        }
+      }
+      if clock.now() >= deadline {
+        return IdleOutcome::TimedOut { queued, running };
+      }
+      clock.sleep(IDLE_POLL_INTERVAL).await;
+    }
+  }
+
+  /// Returns the number of the queued and running uploads.
+  pub async fn queue_status(&self) -> QueueStatus {
+    queue_status(&self.uploader, &self.service.active_uploads).await
+  }
+
+  /// Returns a stream of the [QueueStatus], for a live sync indicator. The current status is
+  /// emitted right away, then the status after each upload queued, started, finished or failed.
+  /// The changes are debounced by [StorageManagerConfig::queue_status_debounce], and a status equal
+  /// to the last emitted one is skipped. The stream ends once the manager is dropped.
+  pub fn subscribe_queue_depth(&self) -> impl Stream<Item = QueueStatus> {
+    let changes = self.service.task_queue.subscribe_changes();
+    let uploader = Arc::downgrade(&self.uploader);
+    let active_uploads = self.service.active_uploads.clone();
+    let clock = self.service.config.clock.clone();
+    let debounce = self.service.config.queue_status_debounce;
+    stream::unfold(
+      (changes, None),
+      move |(mut changes, last): (watch::Receiver<()>, Option<QueueStatus>)| {
+        let uploader = uploader.clone();
+        let active_uploads = active_uploads.clone();
+        let clock = clock.clone();
+        async move {
+          let status = loop {
+            if last.is_some() {
+              changes.changed().await.ok()?;
+              clock.sleep(debounce).await;
+            }
+            changes.borrow_and_update();
+            let uploader = uploader.upgrade()?;
+            let status = queue_status(&uploader, &active_uploads).await;
+            if last != Some(status) {
+              break status;
+            }
+          };
+          Some((status, (changes, Some(status))))
+        }
+      },
+    )
+  }
+
+  /// Returns a stream of the uploads of the current workspace, the feed of a transfer manager. A
+  /// snapshot of every known upload is emitted first, in the creation order, then the snapshot of
+  /// an upload each time it changes. The changes are debounced by
+  /// [StorageManagerConfig::uploads_stream_debounce], an upload changing several times within it
+  /// only emits its last snapshot. The stream ends once the manager is dropped.
+  pub fn uploads_stream(&self) -> impl Stream<Item = UploadSnapshot> {
+    // Subscribe before listing the uploads, so that no change is missed in between.
+    let state = UploadsStreamState {
+      receiver: self.global_notifier.subscribe(),
+      workspace_id: None,
+      snapshots: HashMap::new(),
+      pending: VecDeque::new(),
+    };
+    let service = Arc::downgrade(&self.service);
+    let clock = self.service.config.clock.clone();
+    let debounce = self.service.config.uploads_stream_debounce;
+    stream::unfold(state, move |mut state| {
+      let service = service.clone();
+      let clock = clock.clone();
+      async move {
+        loop {
+          if let Some(snapshot) = state.pending.pop_front() {
+            return Some((snapshot, state));
+          }
+          let workspace_id = match &state.workspace_id {
+            Some(workspace_id) => workspace_id.clone(),
+            None => {
+              let service = service.upgrade()?;
+              let workspace_id = service.current_workspace_id().ok()?;
+              let snapshots = match service.upload_snapshots(&workspace_id).await {
+                Ok(snapshots) => snapshots,
+                Err(err) => {
+                  error!("[File] list the uploads failed: {}", err);
+                  return None;
+                },
+              };
+              for snapshot in snapshots {
+                state.snapshots.insert(
+                  upload_key(
+                    &snapshot.workspace_id,
+                    &snapshot.parent_dir,
+                    &snapshot.file_id,
+                  ),
+                  snapshot.clone(),
+                );
+                state.pending.push_back(snapshot);
+              }
+              state.workspace_id = Some(workspace_id);
+              continue;
+            },
+          };
+
+          let mut changes = vec![next_upload_progress(&mut state.receiver).await?];
+          clock.sleep(debounce).await;
+          loop {
+            match state.receiver.try_recv() {
+              Ok(progress) if progress.direction == TransferDirection::Upload => {
+                changes.push(progress)
+              },
+              Ok(_) | Err(TryRecvError::Lagged(_)) => {},
+              Err(_) => break,
+            }
+          }
+
+          let service = service.upgrade()?;
+          let mut changed_keys = vec![];
+          for progress in changes {
+            if progress.workspace_id.as_deref() != Some(workspace_id.as_str()) {
+              continue;
+            }
+            let Some((_, parent_dir, file_id)) =
+              parse_object_url(&service.cloud_service, &progress.file_url).await
+            else {
+              continue;
+            };
+            let key = upload_key(&workspace_id, &parent_dir, &file_id);
+            if !state.snapshots.contains_key(&key) {
+              // Created after the stream started.
+              match service
+                .upload_snapshot(&workspace_id, &parent_dir, &file_id)
+                .await
+              {
+                Ok(Some(snapshot)) => {
+                  state.snapshots.insert(key.clone(), snapshot);
+                },
+                Ok(None) => continue,
+                Err(err) => {
+                  warn!("[File] snapshot the upload {} failed: {}", key, err);
+                  continue;
+                },
+              }
+            }
+            if let Some(snapshot) = state.snapshots.get_mut(&key) {
+              snapshot.apply(&progress);
+            }
+            if !changed_keys.contains(&key) {
+              changed_keys.push(key);
+            }
+          }
+          state.pending.extend(
+            changed_keys
+              .iter()
+              .filter_map(|key| state.snapshots.get(key).cloned()),
+          );
+        }
+      }
+    })
+  }
+
+  /// Returns the current upload rate of all the uploads in bytes per second, averaged over
+  /// [StorageManagerConfig::throughput_window]. Only the uploaded parts count, so it drops to zero
+  /// once nothing was uploaded for the window.
+  pub fn current_throughput_bps(&self) -> u64 {
+    self.bandwidth.throughput_bps()
+  }
+
+  /// Reconciles a batch of the local upload records with the server. It runs periodically when
+  /// [StorageManagerConfig::reconcile_interval] is set, each call checks the next batch.
+  pub async fn reconcile_uploads(&self) -> FlowyResult<ReconcileSummary> {
+    reconcile_uploads(&self.service, &self.uploader).await
+  }
+
+  /// Audits the storage without changing it: the objects of the finished uploads exist on the
+  /// server, the unfinished uploads aren't on the server yet and their files exist locally, and no
+  /// uploaded parts or temp files are left without an upload record. The server checks are paced by
+  /// [StorageManagerConfig::reconcile_request_interval].
+  pub async fn verify_storage(&self) -> FlowyResult<StorageReport> {
+    verify_storage(&self.service).await
+  }
+
+  /// Fixes the issues found by [Self::verify_storage]:
+  /// - a finished upload without object is uploaded again when its file still exists, otherwise
+  ///   its record is dropped so that the file can be uploaded again.
+  /// - an uploaded file not marked as finished is marked as finished.
+  /// - an unfinished upload without local file is dropped, and its server-side upload aborted.
+  /// - the orphaned parts and temp files are deleted.
+  ///
+  /// The running uploads are skipped. It's safe to run it again with the same report, the issues
+  /// fixed already are skipped.
+  pub async fn repair_storage(&self, report: StorageReport) -> FlowyResult<RepairSummary> {
+    repair_storage(&self.service, &self.uploader, report).await
+  }
+
+  /// Cancels all the uploads of the workspace, for example when the workspace is removed or the
+  /// user signs out. The running uploads are aborted, the queued tasks are dropped, and the
+  /// unfinished upload records are removed along with their temp files and server-side uploads.
+  ///
+  /// It's safe to call it multiple times, the following calls return zero counts.
+  pub async fn cancel_workspace_uploads(
+    &self,
+    workspace_id: &str,
+  ) -> FlowyResult<CancelledUploads> {
+    self.service.cancel_workspace_uploads(workspace_id).await
+  }
+
+  /// Returns true if the file of the current workspace waits in the queue or is being uploaded. It
+  /// only looks up the in-memory state, so the UI can call it on each render. A failed upload
+  /// isn't uploading.
+  pub fn is_uploading(&self, parent_dir: &str, file_id: &str) -> bool {
+    let workspace_id = match self.service.current_workspace_id() {
+      Ok(workspace_id) => workspace_id,
+      Err(_) => return false,
+    };
+    let key = upload_key(&workspace_id, &lookup_parent_dir(parent_dir), file_id);
+    let state = self
+      .progress_notifiers
+      .get(&key)
+      .and_then(|notifier| notifier.value().current_value.clone());
+    match state {
+      Some(
+        FileUploadState::Queued
+        | FileUploadState::Paused { .. }
+        | FileUploadState::CompletionPending { .. },
+      ) => true,
+      // The upload stays registered as active for a moment after it finished.
+      Some(FileUploadState::Finished { .. }) => false,
+      // A queued upload carries its progress floor, see [StorageManagerConfig::progress_floor].
+      Some(FileUploadState::Uploading { .. })
+        if self
+          .service
+          .task_queue
+          .upload_detail(&workspace_id, &lookup_parent_dir(parent_dir), file_id)
+          .is_some_and(|detail| detail.started_at.is_none()) =>
+      {
+        true
+      },
+      _ => self.service.active_uploads.contains_key(&key),
+    }
+  }
+
+  pub async fn get_file_state(&self, parent_dir: &str, file_id: &str) -> Option<FileUploadState> {
+    let workspace_id = self.service.current_workspace_id().ok()?;
+    self
+      .progress_notifiers
+      .get(&upload_key(
+        &workspace_id,
+        &lookup_parent_dir(parent_dir),
+        file_id,
+      ))
+      .and_then(|notifier| notifier.value().current_value.clone())
+  }
+
+  /// Returns the manifest of the completed upload of the file in the current workspace. The
+  /// manifest is only generated when [StorageManagerConfig::upload_manifest] is enabled.
+  pub async fn upload_manifest(
+    &self,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<Option<UploadManifest>> {
+    let parent_dir = &canonical_parent_dir(parent_dir)?;
+    let workspace_id = self.service.current_workspace_id()?;
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    match select_upload_manifest(&mut conn, &workspace_id, parent_dir, file_id)? {
+      Some(record) => Ok(Some(serde_json::from_str(&record.manifest)?)),
+      None => Ok(None),
+    }
+  }
+}
+
+/// Relays the progress sent to the global notifier to the per-file notifiers.
+///
+/// The relay only stops when the storage manager is dropped, which closes the global notifier or
+/// drops the per-file notifiers. Lagging behind the global notifier skips the missed events but
+/// keeps the relay running, otherwise the subscribers would never receive any further progress.
+///
+/// The per-file notifiers are keyed by [upload_key], so the same file uploaded to different
+/// workspaces or parent dirs is tracked independently.
+async fn run_progress_relay(
+  mut rx: broadcast::Receiver<FileProgress>,
+  weak_notifiers: Weak<DashMap<String, ProgressNotifier>>,
+  cloud_service: Arc<dyn StorageCloudService>,
+) {
+  loop {
+    let progress = match rx.recv().await {
+      Ok(progress) => progress,
+      Err(RecvError::Lagged(skipped)) => {
+        warn!("[File] progress relay lagged, skipped {} events", skipped);
+        continue;
+      },
+      Err(RecvError::Closed) => {
+        info!("[File] progress relay stopped, the global notifier was dropped");
+        break;
+      },
+    };
+
+    let notifiers = match weak_notifiers.upgrade() {
+      Some(notifiers) => notifiers,
+      None => {
+        info!("[File] progress relay stopped, the progress notifiers were dropped");
+        break;
+      },
+    };
+    relay_progress(&progress, &notifiers, &cloud_service).await;
+  }
+}
+
+/// Notifies the per-file notifier of the file of the progress, if any.
+async fn relay_progress(
+  progress: &FileProgress,
+  notifiers: &DashMap<String, ProgressNotifier>,
+  cloud_service: &Arc<dyn StorageCloudService>,
+) {
+  let key = match parse_object_url(cloud_service, &progress.file_url).await {
+    Some((workspace_id, parent_dir, file_id)) => upload_key(&workspace_id, &parent_dir, &file_id),
+    None => {
+      warn!(
+        "[File] progress relay skipped invalid url: {}",
+        progress.file_url
+      );
+      return;
+    },
+  };
+  if let Some(mut notifier) = notifiers.get_mut(&key) {
+    notifier
+      .notify_in_order(progress.seq, upload_state(progress))
+      .await;
+  }
+}
+
+async fn run_reconciliation(
+  interval: Duration,
+  clock: Arc<dyn Clock>,
+  weak_service: Weak<StorageServiceImpl>,
+  weak_uploader: Weak<FileUploader>,
+) {
+  loop {
+    clock.sleep(interval).await;
+    let (service, uploader) = match (weak_service.upgrade(), weak_uploader.upgrade()) {
+      (Some(service), Some(uploader)) => (service, uploader),
+      _ => {
+        info!("[File] reconciliation stopped, the storage manager was dropped");
+        break;
+      },
+    };
+    if let Err(err) = reconcile_uploads(&service, &uploader).await {
+      error!("[File] reconcile uploads failed: {}", err);
+    }
+    if let Err(err) = service.purge_failed_uploads().await {
+      error!("[File] purge failed uploads failed: {}", err);
+    }
+  }
+}
+
+/// Checks a batch of the upload records against the server and fixes the local state:
+/// - an unfinished record whose object exists is marked as finished.
+/// - an unfinished record whose object doesn't exist is queued again, or dropped when its temp
+///   file is gone.
+/// - a finished record whose object doesn't exist is dropped, so that the file can be uploaded
+///   again.
+///
+/// The running uploads and the records the server can't check are skipped.
+async fn reconcile_uploads(
+  service: &StorageServiceImpl,
+  uploader: &FileUploader,
+) -> FlowyResult<ReconcileSummary> {
+  if !service.cloud_service.capabilities().head_object {
+    trace!("[File] skip reconciling, the backend can't check the objects");
+    return Ok(ReconcileSummary::default());
+  }
+  let batch_size = service.config.reconcile_batch_size as i64;
+  let offset = service.reconcile_cursor.load(Ordering::SeqCst);
+  let records = {
+    let mut conn = acquire_sqlite_connection(&service.user_service).await?;
+    select_upload_files(&mut conn, offset, batch_size)?
+  };
+  // Start over from the first record after reaching the last one.
+  if (records.len() as i64) < batch_size {
+    service.reconcile_cursor.store(0, Ordering::SeqCst);
+  } else {
+    service
+      .reconcile_cursor
+      .store(offset + batch_size, Ordering::SeqCst);
+  }
+
+  let mut summary = ReconcileSummary::default();
+  for (index, record) in records.into_iter().enumerate() {
+    let key = upload_key(&record.workspace_id, &record.parent_dir, &record.file_id);
+    if service.active_uploads.contains_key(&key) {
+      continue;
+    }
+
+    if index > 0 {
+      service
+        .config
+        .clock
+        .sleep(service.config.reconcile_request_interval)
+        .await;
+    }
+    let exists = match service
+      .cloud_service
+      .object_exists(
+        &record.workspace_id,
+        record.object_parent_dir(),
+        &record.file_id,
+      )
+      .await
+    {
+      Ok(exists) => exists,
+      Err(err) => {
+        trace!("[File] skip reconciling {}: {}", record.file_id, err);
+        continue;
+      },
+    };
+    summary.checked += 1;
+
+    match (record.is_finish, exists) {
+      (true, true) => {},
+      (true, false) => {
+        info!(
+          "[File] reconcile: drop finished upload without object: {}",
+          key
+        );
+        let conn = acquire_sqlite_connection(&service.user_service).await?;
+        delete_upload_file_by_file_id(
+          conn,
+          &record.workspace_id,
+          &record.parent_dir,
+          &record.file_id,
+        )?;
+        summary.dropped += 1;
+      },
+      (false, true) => {
+        info!("[File] reconcile: mark uploaded file as finished: {}", key);
+        let conn = acquire_sqlite_connection(&service.user_service).await?;
+        update_upload_file_completed_by_file_id(
+          conn,
+          &record.workspace_id,
+          &record.parent_dir,
+          &record.file_id,
+        )?;
+        if let Err(err) = service
+          .temp_storage
+          .delete_temp_file(&record.local_file_path)
+          .await
+        {
+          trace!("[File] delete temp file failed: {}", err);
+        }
+        let file_url = service
+          .cloud_service
+          .get_object_url_v1(&record.workspace_id, &record.parent_dir, &record.file_id)
+          .await?;
+        let progress = FileProgress::new_progress(file_url, record.file_id.clone(), 1.0)
+          .with_workspace_id(record.workspace_id.clone());
+        if let Err(err) = service
+          .global_notifier
+          .send_upload(&upload_file_key(&record), progress)
+          .await
+        {
+          error!("[File] send global notifier failed: {}", err);
+        }
+        summary.marked_finished += 1;
+      },
+      (false, false) => {
+        if record.has_local_file() {
+          if !service
+            .task_queue
+            .contains_task(&record.workspace_id, &record.parent_dir, &record.file_id)
+            .await
+          {
+            info!("[File] reconcile: queue unfinished upload: {}", key);
+            service.notify_queued_floor(&record).await;
+            uploader
+              .queue_tasks(vec![UploadTask::BackgroundTask {
+                workspace_id: record.workspace_id,
+                file_id: record.file_id,
+                parent_dir: record.parent_dir,
+                seq: record.seq,
+                retries_left: service.config.background_task_retries,
+              }])
+              .await;
+            summary.requeued += 1;
+          }
+        } else {
+          info!("[File] reconcile: drop upload without local file: {}", key);
+          let conn = acquire_sqlite_connection(&service.user_service).await?;
+          delete_upload_file_by_file_id(
+            conn,
+            &record.workspace_id,
+            &record.parent_dir,
+            &record.file_id,
+          )?;
+          abort_server_upload(&service.cloud_service, &record).await;
+          summary.dropped += 1;
+        }
+      },
+    }
+  }
+
+  info!("[File] reconcile uploads: {:?}", summary);
+  if summary.marked_finished + summary.requeued + summary.dropped > 0 {
+    make_notification(StorageNotification::UploadsReconciled)
+      .payload(ReconcileSummaryPB::from(summary.clone()))
+      .send();
+  }
+  Ok(summary)
+}
+
+/// Sends the pause state of the unfinished uploads that aren't running to their notifiers and the
+/// progress stream: [FileUploadState::Paused] with the reasons that keep them waiting, or their
+/// progress once nothing does. The running uploads report their own progress.
+async fn notify_pause_state(
+  service: &StorageServiceImpl,
+  uploader: &FileUploader,
+) -> FlowyResult<()> {
+  let records = select_all_upload_files(service).await?;
+  for record in records.into_iter().filter(|record| !record.is_finish) {
+    let key = upload_file_key(&record);
+    if service.active_uploads.contains_key(&key) {
+      continue;
+    }
+    let uploaded_parts = if record.upload_id.is_empty() {
+      0
+    } else {
+      let mut conn = acquire_sqlite_connection(&service.user_service).await?;
+      select_upload_parts(&mut conn, &record.upload_id)?.len()
+    };
+    let progress = floor_progress(
+      &service.config,
+      uploaded_parts as u64,
+      record.num_chunk as usize,
+    )
+    .unwrap_or(uploaded_parts as f64 / record.num_chunk.max(1) as f64);
+    let file_url = service
+      .cloud_service
+      .get_object_url_v1(&record.workspace_id, &record.parent_dir, &record.file_id)
+      .await?;
+    let mut progress = FileProgress::new_progress(file_url, record.file_id.clone(), progress)
+      .with_workspace_id(record.workspace_id.clone());
+    let reasons =
+      uploader.pause_reasons_of(&record.workspace_id, &record.parent_dir, &record.file_id);
+    if !reasons.is_empty() {
+      progress = progress.with_paused_reasons(reasons.bits());
+    }
+    if let Err(err) = service.global_notifier.send_upload(&key, progress).await {
+      error!("[File] send global notifier failed: {}", err);
+    }
+  }
+  Ok(())
+}
+
+/// Checks all the upload records, the uploaded parts and the temp files for inconsistencies, see
+/// [StorageManager::verify_storage].
+async fn verify_storage(service: &StorageServiceImpl) -> FlowyResult<StorageReport> {
+  let records = select_all_upload_files(service).await?;
+  let mut report = StorageReport {
+    checked_records: records.len(),
+    ..Default::default()
+  };
+  let can_check_objects = service.cloud_service.capabilities().head_object;
+  let mut checked_objects = 0;
+  for record in &records {
+    // A running upload is expected to be unfinished, and its object to be missing.
+    if service
+      .active_uploads
+      .contains_key(&upload_file_key(record))
+    {
+      continue;
+    }
+
+    let exists = if can_check_objects {
+      if checked_objects > 0 {
+        service
+          .config
+          .clock
+          .sleep(service.config.reconcile_request_interval)
+          .await;
+      }
+      checked_objects += 1;
+      match service
+        .cloud_service
+        .object_exists(
+          &record.workspace_id,
+          record.object_parent_dir(),
+          &record.file_id,
+        )
+        .await
+      {
+        Ok(exists) => Some(exists),
+        Err(err) => {
+          trace!("[File] skip verifying {}: {}", record.file_id, err);
+          None
+        },
+      }
+    } else {
+      None
+    };
+
+    match (record.is_finish, exists) {
+      (true, Some(true)) => {},
+      (true, Some(false)) => report.issues.push(StorageIssue::MissingObject {
+        workspace_id: record.workspace_id.clone(),
+        parent_dir: record.parent_dir.clone(),
+        file_id: record.file_id.clone(),
+      }),
+      (true, None) => report.unchecked_objects += 1,
+      (false, Some(true)) => report.issues.push(StorageIssue::UploadedButUnfinished {
+        workspace_id: record.workspace_id.clone(),
+        parent_dir: record.parent_dir.clone(),
+        file_id: record.file_id.clone(),
+      }),
+      (false, _) => {
+        if !record.has_local_file() {
+          report.issues.push(StorageIssue::MissingLocalFile {
+            workspace_id: record.workspace_id.clone(),
+            parent_dir: record.parent_dir.clone(),
+            file_id: record.file_id.clone(),
+            local_file_path: record.local_file_path.clone(),
+          });
+        }
+      },
+    }
+  }
+
+  let upload_ids = {
+    let mut conn = acquire_sqlite_connection(&service.user_service).await?;
+    select_upload_part_upload_ids(&mut conn)?
+  };
+  for upload_id in upload_ids {
+    if !records.iter().any(|record| record.upload_id == upload_id) {
+      report
+        .issues
+        .push(StorageIssue::OrphanedParts { upload_id });
+    }
+  }
+
+  for path in service.temp_storage.list_temp_files().await? {
+    // The temp file of a completed upload that couldn't be deleted is still referenced by its
+    // record.
+    if service.temp_storage.is_undeleted(&path)
+      || !records
+        .iter()
+        .any(|record| Path::new(&record.local_file_path) == path)
+    {
+      report.issues.push(StorageIssue::OrphanedTempFile {
+        path: path.to_string_lossy().into_owned(),
+      });
+    }
+  }
+
+  info!(
+    "[File] verify storage: {} records, {} issues",
+    report.checked_records,
+    report.issues.len()
+  );
+  Ok(report)
+}
+
+/// Fixes the issues of the report, see [StorageManager::repair_storage]. Each issue is checked
+/// against the current state before it's fixed, so an issue fixed already is skipped.
+async fn repair_storage(
+  service: &StorageServiceImpl,
+  uploader: &FileUploader,
+  report: StorageReport,
+) -> FlowyResult<RepairSummary> {
+  let mut summary = RepairSummary::default();
+  for issue in report.issues {
+    match issue {
+      StorageIssue::MissingObject {
+        workspace_id,
+        parent_dir,
+        file_id,
+      } => {
+        let record =
+          match select_repairable_record(service, &workspace_id, &parent_dir, &file_id).await? {
+            Some(record) if record.is_finish => record,
+            _ => continue,
+          };
+        if Path::new(&record.local_file_path).exists() {
+          info!("[File] repair: queue upload of missing object: {}", file_id);
+          let conn = acquire_sqlite_connection(&service.user_service).await?;
+          update_upload_file_unfinished_by_file_id(conn, &workspace_id, &parent_dir, &file_id)?;
+          uploader
+            .queue_tasks(vec![UploadTask::BackgroundTask {
+              workspace_id,
+              file_id,
+              parent_dir,
+              seq: record.seq,
+              retries_left: service.config.background_task_retries,
+            }])
+            .await;
+          summary.requeued += 1;
+        } else {
+          info!(
+            "[File] repair: drop finished upload without object: {}",
+            file_id
+          );
+          let conn = acquire_sqlite_connection(&service.user_service).await?;
+          delete_upload_file_by_file_id(conn, &workspace_id, &parent_dir, &file_id)?;
+          summary.dropped += 1;
+        }
+      },
+      StorageIssue::UploadedButUnfinished {
+        workspace_id,
+        parent_dir,
+        file_id,
+      } => {
+        let record =
+          match select_repairable_record(service, &workspace_id, &parent_dir, &file_id).await? {
+            Some(record) if !record.is_finish => record,
+            _ => continue,
+          };
+        info!("[File] repair: mark uploaded file as finished: {}", file_id);
+        let conn = acquire_sqlite_connection(&service.user_service).await?;
+        update_upload_file_completed_by_file_id(conn, &workspace_id, &parent_dir, &file_id)?;
+        if !record.upload_id.is_empty() {
+          let conn = acquire_sqlite_connection(&service.user_service).await?;
+          delete_all_upload_parts(conn, &record.upload_id)?;
+        }
+        if let Err(err) = service
+          .temp_storage
+          .delete_temp_file(&record.local_file_path)
+          .await
+        {
+          trace!("[File] delete temp file failed: {}", err);
+        }
+        summary.marked_finished += 1;
+      },
+      StorageIssue::MissingLocalFile {
+        workspace_id,
+        parent_dir,
+        file_id,
+        ..
+      } => {
+        let record =
+          match select_repairable_record(service, &workspace_id, &parent_dir, &file_id).await? {
+            Some(record) if !record.is_finish && !record.has_local_file() => record,
+            _ => continue,
+          };
+        info!("[File] repair: drop upload without local file: {}", file_id);
+        let conn = acquire_sqlite_connection(&service.user_service).await?;
+        delete_upload_file_by_file_id(conn, &workspace_id, &parent_dir, &file_id)?;
+        abort_server_upload(&service.cloud_service, &record).await;
+        summary.dropped += 1;
+      },
+      StorageIssue::OrphanedParts { upload_id } => {
+        let records = select_all_upload_files(service).await?;
+        if records.iter().any(|record| record.upload_id == upload_id) {
+          continue;
+        }
+        // The server-side upload can't be aborted without the file it belongs to, the server
+        // expires it.
+        info!(
+          "[File] repair: delete orphaned parts of upload: {}",
+          upload_id
+        );
+        let conn = acquire_sqlite_connection(&service.user_service).await?;
+        delete_all_upload_parts(conn, &upload_id)?;
+        summary.deleted_parts += 1;
+      },
+      StorageIssue::OrphanedTempFile { path } => {
+        let records = select_all_upload_files(service).await?;
+        if !service.temp_storage.is_undeleted(Path::new(&path))
+          && records.iter().any(|record| record.local_file_path == path)
+        {
+          continue;
+        }
+        match service.temp_storage.delete_temp_file(&path).await {
+          Ok(()) => {
+            info!("[File] repair: delete orphaned temp file: {}", path);
+            summary.deleted_temp_files += 1;
+          },
+          Err(err) => trace!("[File] delete orphaned temp file failed: {}", err),
+        }
+      },
+    }
+  }
+
+  info!("[File] repair storage: {:?}", summary);
+  Ok(summary)
+}
+
+/// Selects the upload record, `None` when it doesn't exist or its upload is running.
+async fn select_repairable_record(
+  service: &StorageServiceImpl,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+) -> FlowyResult<Option<UploadFileTable>> {
+  if service
+    .active_uploads
+    .contains_key(&upload_key(workspace_id, parent_dir, file_id))
+  {
+    return Ok(None);
+  }
+  let mut conn = acquire_sqlite_connection(&service.user_service).await?;
+  select_upload_file(&mut conn, workspace_id, parent_dir, file_id)
+}
+
+/// Selects all the upload records, one batch at a time.
+async fn select_all_upload_files(
+  service: &StorageServiceImpl,
+) -> FlowyResult<Vec<UploadFileTable>> {
+  let batch_size = service.config.reconcile_batch_size as i64;
+  let mut records = vec![];
+  loop {
+    let mut conn = acquire_sqlite_connection(&service.user_service).await?;
+    let batch = select_upload_files(&mut conn, records.len() as i64, batch_size)?;
+    let is_last = (batch.len() as i64) < batch_size;
+    records.extend(batch);
+    if is_last {
+      return Ok(records);
+    }
+  }
+}
+
+/// Queues the unfinished uploads restored from sqlite. The uploads already queued or running are
+/// skipped, so it's safe to call it each time the storage is initialized.
+async fn prepare_upload_task(
+  service: &StorageServiceImpl,
+  uploader: &FileUploader,
+) -> FlowyResult<()> {
+  let upload_files = {
+    let conn = acquire_sqlite_connection(&service.user_service).await?;
+    batch_select_upload_file(conn, 100, false)?
+  };
+  let mut tasks = vec![];
+  for upload_file in upload_files {
+    let key = upload_file_key(&upload_file);
+    if service.active_uploads.contains_key(&key)
+      || service
+        .task_queue
+        .contains_task(
+          &upload_file.workspace_id,
+          &upload_file.parent_dir,
+          &upload_file.file_id,
+        )
+        .await
+    {
+      continue;
+    }
+    service.notify_queued_floor(&upload_file).await;
+    tasks.push(UploadTask::BackgroundTask {
+      workspace_id: upload_file.workspace_id,
+      file_id: upload_file.file_id,
+      parent_dir: upload_file.parent_dir,
+      seq: upload_file.seq,
+      retries_left: service.config.background_task_retries,
+    });
+  }
+  info!("[File] prepare upload task: {}", tasks.len());
+  uploader.queue_tasks(tasks).await;
+  Ok(())
+}
+
+async fn prepare_download_task(service: &StorageServiceImpl) -> FlowyResult<()> {
+  let download_files = {
+    let mut conn = acquire_sqlite_connection(&service.user_service).await?;
+    select_download_files(&mut conn)?
+  };
+  service.downloader.restore_tasks(download_files);
+  Ok(())
+}
+
+pub struct StorageServiceImpl {
+  config: Arc<StorageManagerConfig>,
+  cloud_service: Arc<dyn StorageCloudService>,
+  user_service: Arc<dyn StorageUserService>,
+  temp_storage: Arc<FileTempStorage>,
+  task_queue: Arc<UploadTaskQueue>,
+  is_exceed_storage_limit: Arc<AtomicBool>,
+  progress_notifiers: Arc<DashMap<String, ProgressNotifier>>,
+  global_notifier: GlobalNotifier,
+  delete_notifier: DeleteNotifier,
+  downloader: Arc<FileDownloader>,
+  /// The cancellation tokens of the running uploads, keyed by [upload_key].
+  active_uploads: Arc<DashMap<String, CancellationToken>>,
+  bandwidth: Arc<UploadBandwidth>,
+  part_timings: PartTimings,
+  upload_log: Arc<UploadLog>,
+  range_cache: RangeCache,
+  /// The offset of the next batch of records to reconcile.
+  reconcile_cursor: AtomicI64,
+  /// The workspace the storage was last initialized for, see
+  /// [StorageServiceImpl::current_workspace_id].
+  opened_workspace_id: RwLock<Option<String>>,
+  /// The used and the total bytes of the workspace, see [StorageManager::update_storage_usage].
+  storage_usage: RwLock<Option<(u64, u64)>>,
+  failure_notifier: UploadFailureNotifier,
+}
+
+#[async_trait]
+impl StorageService for StorageServiceImpl {
+  fn delete_object(
+    &self,
+    url: String,
+    parent_dir: Option<String>,
+    local_file_path: String,
+  ) -> FlowyResult<()> {
+    self.range_cache.remove_object(&url);
+    let cloud_service = self.cloud_service.clone();
+    let user_service = self.user_service.clone();
+    let delete_notifier = self.delete_notifier.clone();
+    self.config.spawner.spawn(Box::pin(async move {
+      let notify = |target: DeleteTarget, state: DeleteState| {
+        // No receivers is fine, nobody is watching the delete.
+        let _ = delete_notifier.send(DeleteProgress::new(url.clone(), target, state));
+      };
+
+      notify(DeleteTarget::Local, DeleteState::Deleting);
+      match tokio::fs::remove_file(&local_file_path).await {
+        Ok(_) => {
+          debug!("[File] deleted file from local disk: {}", local_file_path);
+          notify(DeleteTarget::Local, DeleteState::Deleted);
+        },
+        Err(err) => {
+          error!("[File] delete file at {} failed: {}", local_file_path, err);
+          notify(
+            DeleteTarget::Local,
+            DeleteState::DeleteFailed {
+              error: err.to_string(),
+            },
+          );
+        },
+      }
+
+      notify(DeleteTarget::Cloud, DeleteState::Deleting);
+      match release_object_link(&cloud_service, &user_service, &url, parent_dir.as_deref()).await {
+        Ok(true) => {
+          info!(
+            "[File] keep the object shared with other locations: {}",
+            url
+          );
+          notify(DeleteTarget::Cloud, DeleteState::Deleted);
+          return;
+        },
+        Ok(false) => {},
+        Err(err) => warn!("[File] release the links of {} failed: {}", url, err),
+      }
+      match cloud_service.delete_object(&url).await {
+        Ok(_) => {
+          debug!("[File] deleted file from cloud: {}", url);
+          notify(DeleteTarget::Cloud, DeleteState::Deleted);
+        },
+        Err(err) => {
+          // TODO: add WAL to log the delete operation.
+          // keep a list of files to be deleted, and retry later
+          error!("[File] delete file failed: {}", err);
+          notify(
+            DeleteTarget::Cloud,
+            DeleteState::DeleteFailed {
+              error: err.msg.clone(),
+            },
+          );
+        },
+      }
+    }));
+    Ok(())
+  }
+
+  fn download_object(&self, url: String, local_file_path: String) -> FlowyResult<()> {
+    self.downloader.download(
+      url,
+      local_file_path,
+      DownloadPriority::UserInitiated,
+      ExistingFilePolicy::default(),
+    );
+    Ok(())
+  }
+
+  async fn create_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_path: &str,
+    upload_immediately: bool,
+  ) -> Result<(CreatedUpload, Option<FileProgressReceiver>), FlowyError> {
+    self
+      .create_upload_with_cancel(
+        workspace_id,
+        parent_dir,
+        file_path,
+        upload_immediately,
+        CancellationToken::new(),
+      )
+      .await
+  }
+
+  async fn create_upload_with_cancel(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_path: &str,
+    upload_immediately: bool,
+    cancel_token: CancellationToken,
+  ) -> Result<(CreatedUpload, Option<FileProgressReceiver>), FlowyError> {
+    self
+      .create_upload_with_options(
+        workspace_id,
+        parent_dir,
+        Path::new(file_path),
+        upload_immediately,
+        CreateUploadOptions::default().cancel_token(cancel_token),
+      )
+      .await
+  }
+
+  async fn download_to_writer(
+    &self,
+    url: &str,
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+  ) -> FlowyResult<u64> {
+    self.downloader.download_to_writer(url, writer).await
+  }
+
+  async fn start_upload(&self, record: &BoxAny) -> Result<(), FlowyError> {
+    let file_record = record.downcast_ref::<UploadFileTable>().ok_or_else(|| {
+      FlowyError::from(StorageError::InvalidRecord(
+        "failed to downcast record to UploadFileTable".to_string(),
+      ))
+    })?;
+
+    let active_upload = match self.register_active_upload(file_record) {
+      Some(active_upload) => active_upload,
+      None => {
+        info!(
+          "[File] {} is already uploading, skip start upload",
+          file_record.file_id
+        );
+        return Ok(());
+      },
+    };
+    // A requeued task carries the record it was first queued with. The stored record has the
+    // upload_id of the previous attempt, so that its uploaded parts are kept.
+    let stored_record = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_upload_file(
+        &mut conn,
+        &file_record.workspace_id,
+        &file_record.parent_dir,
+        &file_record.file_id,
+      )?
+    };
+    let file_record = &stored_record.unwrap_or_else(|| file_record.clone());
+    self
+      .upload_log
+      .record(
+        &file_record.workspace_id,
+        &file_record.parent_dir,
+        &file_record.file_id,
+        UploadLogEvent::Start,
+      )
+      .await;
+    let result = start_upload(
+      &self.config,
+      &self.cloud_service,
+      &self.user_service,
+      &self.temp_storage,
+      file_record,
+      self.global_notifier.clone(),
+      &active_upload.cancel_token,
+      &self.bandwidth,
+      &self.part_timings,
+      &self.upload_log,
+    )
+    .await;
+    self.record_upload_result(file_record, &result).await;
+    result
+  }
+
+  async fn resume_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> Result<(), FlowyError> {
+    self
+      .resume_upload_with_strategy(workspace_id, parent_dir, file_id, ResumeStrategy::Continue)
+      .await
+  }
+
+  async fn subscribe_file_progress(
+    &self,
+    parent_idr: &str,
+    file_id: &str,
+  ) -> Result<Option<FileProgressReceiver>, FlowyError> {
+    trace!("[File]: subscribe file progress: {}", file_id);
+
+    let parent_idr = &canonical_parent_dir(parent_idr)?;
+    let workspace_id = self.current_workspace_id()?;
+    if self
+      .is_upload_completed(&workspace_id, parent_idr, file_id)
+      .await?
+    {
+      return Ok(None);
+    }
+
+    let key = upload_key(&workspace_id, parent_idr, file_id);
+    let receiver = self
+      .progress_notifiers
+      .entry(key.clone())
+      .or_insert_with(|| ProgressNotifier::new(file_id.to_string()))
+      .subscribe();
+
+    // The upload might be completed between the check above and the notifier insertion. In that
+    // case the finish event was already sent, so deliver the terminal state to the new subscriber.
+    if self
+      .is_upload_completed(&workspace_id, parent_idr, file_id)
+      .await?
+    {
+      if let Some(mut notifier) = self.progress_notifiers.get_mut(&key) {
+        notifier
+          .notify(FileUploadState::Finished {
+            file_id: file_id.to_string(),
+            total_bytes: None,
+            duration: None,
+          })
+          .await;
+      }
+    }
+    Ok(Some(receiver))
+  }
+
+  async fn cancel_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> Result<(), FlowyError> {
+    let parent_dir = &canonical_parent_dir(parent_dir)?;
+    info!(
+      "[File] cancel upload: {}/{}/{}",
+      workspace_id, parent_dir, file_id
+    );
+    // Abort the running upload, including its in-flight part request.
+    if let Some(cancel_token) =
+      self
+        .active_uploads
+        .get(&upload_key(workspace_id, parent_dir, file_id))
+    {
+      cancel_token.cancel();
+    }
+    self
+      .task_queue
+      .remove_tasks(workspace_id, parent_dir, file_id)
+      .await;
+
+    let record = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_upload_file(&mut conn, workspace_id, parent_dir, file_id)?
+    };
+    if let Some(record) = record.filter(|record| !record.is_finish) {
+      let conn = acquire_sqlite_connection(&self.user_service).await?;
+      delete_upload_file_by_file_id(conn, workspace_id, parent_dir, file_id)?;
+      abort_server_upload(&self.cloud_service, &record).await;
+      if let Err(err) = self
+        .temp_storage
+        .delete_temp_file(&record.local_file_path)
+        .await
+      {
+        trace!("[File] delete temp file failed: {}", err);
+      }
+    }
+    {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      delete_upload_failure(&mut conn, workspace_id, parent_dir, file_id)?;
+    }
+    self
+      .release_progress_notifier(&upload_key(workspace_id, parent_dir, file_id))
+      .await;
+    self.bandwidth.remove_file_limit(file_id);
+    Ok(())
+  }
+}
+
+impl StorageServiceImpl {
+  /// Returns the snapshots of the uploads of the workspace, in the creation order.
+  async fn upload_snapshots(&self, workspace_id: &str) -> FlowyResult<Vec<UploadSnapshot>> {
+    let (mut records, errors) = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      let mut records = select_workspace_upload_files(&mut conn, workspace_id, false)?;
+      records.extend(select_workspace_upload_files(
+        &mut conn,
+        workspace_id,
+        true,
+      )?);
+      let errors = select_upload_failures(&mut conn, workspace_id)?
+        .into_iter()
+        .map(|failure| {
+          (
+            upload_key(&failure.workspace_id, &failure.parent_dir, &failure.file_id),
+            failure.error,
+          )
+        })
+        .collect::<HashMap<_, _>>();
+      (records, errors)
+    };
+    records.sort_by_key(|record| record.seq);
+
+    let mut snapshots = Vec::with_capacity(records.len());
+    for record in records {
+      let error = errors.get(&upload_file_key(&record)).cloned();
+      snapshots.push(self.snapshot_of_record(&record, error).await?);
+    }
+    Ok(snapshots)
+  }
+
+  /// Returns the snapshot of the upload, `None` when there's no such upload.
+  async fn upload_snapshot(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<Option<UploadSnapshot>> {
+    let record = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_upload_file(&mut conn, workspace_id, parent_dir, file_id)?
+    };
+    match record {
+      Some(record) => Ok(Some(self.snapshot_of_record(&record, None).await?)),
+      None => Ok(None),
+    }
+  }
+
+  async fn snapshot_of_record(
+    &self,
+    record: &UploadFileTable,
+    error: Option<String>,
+  ) -> FlowyResult<UploadSnapshot> {
+    let url = self
+      .cloud_service
+      .get_object_url_v1(
+        &record.workspace_id,
+        record.object_parent_dir(),
+        &record.file_id,
+      )
+      .await?;
+    let live_state = self
+      .progress_notifiers
+      .get(&upload_file_key(record))
+      .and_then(|notifier| notifier.value().current_value.clone());
+    Ok(UploadSnapshot::from_record(record, url, live_state, error))
+  }
+
+  /// Returns the active workspace of the [StorageUserService]. It's resolved once per operation, so
+  /// that the operation doesn't mix two workspaces when the user switches in the middle of it. It
+  /// fails when the storage was initialized for another workspace, rather than targeting a
+  /// workspace the storage isn't open for.
+  fn current_workspace_id(&self) -> FlowyResult<String> {
+    let current = self.user_service.workspace_id()?;
+    match self.opened_workspace_id.read().unwrap().as_ref() {
+      Some(opened) if *opened != current => Err(
+        StorageError::WorkspaceChanged {
+          opened: opened.clone(),
+          current,
+        }
+        .into(),
+      ),
+      _ => Ok(current),
+    }
+  }
+
+  async fn resume_upload_with_strategy(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    strategy: ResumeStrategy,
+  ) -> FlowyResult<()> {
+    let parent_dir = &canonical_parent_dir(parent_dir)?;
+    // Gathering the upload record and parts from the sqlite database. The connection is released
+    // before uploading, otherwise it would be held for the whole upload.
+    let upload_file = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_upload_file(&mut conn, workspace_id, parent_dir, file_id)?
+    };
+
+    if let Some(upload_file) = upload_file {
+      if strategy == ResumeStrategy::Restart && upload_file.is_finish {
+        info!(
+          "[File] upload already finished, skip restarting: {}",
+          file_id
+        );
+        return Ok(());
+      }
+      let active_upload = match self.register_active_upload(&upload_file) {
+        Some(active_upload) => active_upload,
+        None => {
+          info!(
+            "[File] {} is already uploading, skip resume upload",
+            upload_file.file_id
+          );
+          return Ok(());
+        },
+      };
+      let upload_file = match strategy {
+        ResumeStrategy::Continue => upload_file,
+        ResumeStrategy::Restart => self.discard_uploaded_parts(upload_file).await?,
+      };
+      self
+        .upload_log
+        .record(
+          &upload_file.workspace_id,
+          &upload_file.parent_dir,
+          &upload_file.file_id,
+          UploadLogEvent::Start,
+        )
+        .await;
+      let result = resume_upload(
+        &self.config,
+        &self.cloud_service,
+        &self.user_service,
+        &self.temp_storage,
+        upload_file.clone(),
+        self.global_notifier.clone(),
+        &active_upload.cancel_token,
+        &self.bandwidth,
+        &self.part_timings,
+        &self.upload_log,
+      )
+      .await;
+      self.record_upload_result(&upload_file, &result).await;
+      result?;
+    } else {
+      error!("[File] resume upload failed: record not found");
+    }
+    Ok(())
+  }
+
+  /// Discards the uploaded parts of the upload, so that it starts over with a new server-side
+  /// upload. The server-side upload is aborted, otherwise its parts would be orphaned.
+  async fn discard_uploaded_parts(
+    &self,
+    mut upload_file: UploadFileTable,
+  ) -> FlowyResult<UploadFileTable> {
+    if upload_file.upload_id.is_empty() {
+      return Ok(upload_file);
+    }
+    info!(
+      "[File] restart upload {} from scratch, discard upload: {}",
+      upload_file.file_id, upload_file.upload_id
+    );
+    abort_server_upload(&self.cloud_service, &upload_file).await;
+    let conn = acquire_sqlite_connection(&self.user_service).await?;
+    delete_all_upload_parts(conn, &upload_file.upload_id)?;
+    let conn = acquire_sqlite_connection(&self.user_service).await?;
+    update_upload_file_upload_id(
+      conn,
+      &upload_file.workspace_id,
+      &upload_file.parent_dir,
+      &upload_file.file_id,
+      "",
+    )?;
+    upload_file.upload_id = String::new();
+    Ok(upload_file)
+  }
+
+  /// Records the failure of the upload, or clears its previous failure once it succeeded, and logs
+  /// the result to the upload log. A cancelled upload isn't a failure.
+  async fn record_upload_result(&self, record: &UploadFileTable, result: &FlowyResult<()>) {
+    let event = match result {
+      Ok(_) => UploadLogEvent::Complete,
+      Err(err) if err.code == ErrorCode::UploadCancelled => return,
+      Err(err) => UploadLogEvent::Fail(err.to_string()),
+    };
+    match result {
+      Ok(_) => self.failure_notifier.succeeded(),
+      Err(err) => self.failure_notifier.failed(&record.workspace_id, &err.msg),
+    }
+    self
+      .upload_log
+      .record(
+        &record.workspace_id,
+        &record.parent_dir,
+        &record.file_id,
+        event,
+      )
+      .await;
+    let mut conn = match acquire_sqlite_connection(&self.user_service).await {
+      Ok(conn) => conn,
+      Err(err) => {
+        warn!("[File] record upload result failed: {}", err);
+        return;
+      },
+    };
+    let result = match result {
+      Ok(_) => delete_upload_failure(
+        &mut conn,
+        &record.workspace_id,
+        &record.parent_dir,
+        &record.file_id,
+      ),
+      Err(err) if err.code == ErrorCode::UploadCancelled => return,
+      Err(err) => upsert_upload_failure(
+        &mut conn,
+        &record.workspace_id,
+        &record.parent_dir,
+        &record.file_id,
+        &err.to_string(),
+        unix_timestamp(self.config.clock.system_now()),
+      ),
+    };
+    if let Err(err) = result {
+      warn!("[File] record upload result failed: {}", err);
+    }
+  }
+
+  async fn purge_failed_uploads(&self) -> FlowyResult<usize> {
+    let retention = match self.config.failed_upload_retention {
+      Some(retention) => retention,
+      None => return Ok(0),
+    };
+    let failed_before = self
+      .config
+      .clock
+      .system_now()
+      .checked_sub(retention)
+      .unwrap_or(UNIX_EPOCH);
+    let failures = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_expired_upload_failures(&mut conn, unix_timestamp(failed_before))?
+    };
+
+    let mut purged = 0;
+    for failure in failures {
+      // The upload is being retried, its result tells whether it still fails.
+      if self.active_uploads.contains_key(&upload_key(
+        &failure.workspace_id,
+        &failure.parent_dir,
+        &failure.file_id,
+      )) {
+        continue;
+      }
+      info!(
+        "[File] purge failed upload: {}/{}/{}, error: {}",
+        failure.workspace_id, failure.parent_dir, failure.file_id, failure.error
+      );
+      // Removes the queued tasks, the record, its parts and temp file, and the failure.
+      self
+        .cancel_upload(&failure.workspace_id, &failure.parent_dir, &failure.file_id)
+        .await?;
+      purged += 1;
+    }
+    Ok(purged)
+  }
+
+  async fn validate_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_path: &Path,
+  ) -> FlowyResult<UploadValidation> {
+    let rejected = |reason: StorageError| UploadValidation {
+      file_id: None,
+      url: None,
+      outcome: UploadOutcome::Rejected(reason),
+    };
+    if workspace_id.is_empty() {
+      return Ok(rejected(StorageError::EmptyWorkspaceId));
+    }
+    let parent_dir = &match canonical_parent_dir(parent_dir) {
+      Ok(parent_dir) => parent_dir,
+      Err(err) => return Ok(rejected(err)),
+    };
+    if file_path.as_os_str().is_empty() {
+      return Ok(rejected(StorageError::EmptyFilePath));
+    }
+    if self.is_exceed_storage_limit.load(Ordering::Relaxed) {
+      return Ok(rejected(StorageError::OverQuota));
+    }
+
+    let file_id = match self.upload_file_id(file_path).await {
+      Ok(file_id) => file_id,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+        return Ok(rejected(StorageError::FileMissing(
+          file_path.display().to_string(),
+        )));
+      },
+      Err(err) => return Err(err.into()),
+    };
+    let record = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_upload_file(&mut conn, workspace_id, parent_dir, &file_id)?
+    };
+    let link_source = match &record {
+      None => {
+        self
+          .linkable_upload(workspace_id, parent_dir, &file_id)
+          .await?
+      },
+      Some(_) => None,
+    };
+    // A link gets the url of the object it shares.
+    let object_parent_dir = match (&record, &link_source) {
+      (Some(record), _) => record.object_parent_dir(),
+      (None, Some(source)) => source.parent_dir.as_str(),
+      (None, None) => parent_dir.as_str(),
+    };
+    let url = self
+      .cloud_service
+      .get_object_url_v1(workspace_id, object_parent_dir, &file_id)
+      .await?;
+    let outcome = match &record {
+      None if link_source.is_some() => UploadOutcome::WouldLink,
+      None => UploadOutcome::WouldUpload,
+      Some(record) if !record.is_finish => UploadOutcome::InProgress,
+      Some(_) => {
+        // Unlike [Self::select_completed_upload], a record whose object is gone is kept.
+        let object_exists =
+          if self.config.verify_completed_upload && self.cloud_service.capabilities().head_object {
+            self
+              .cloud_service
+              .object_exists(workspace_id, object_parent_dir, &file_id)
+              .await
+              .unwrap_or(true)
+          } else {
+            true
+          };
+        if object_exists {
+          UploadOutcome::AlreadyUploaded
+        } else {
+          UploadOutcome::WouldUpload
+        }
+      },
+    };
+    let outcome = if outcome == UploadOutcome::WouldUpload {
+      let capabilities = self.cloud_service.capabilities();
+      let file_size = tokio::fs::metadata(file_path).await?.len() as usize;
+      let rejection = match self
+        .pending_uploads_error(workspace_id, parent_dir, &file_id)
+        .await?
+      {
+        Some(err) => Some(err),
+        None => {
+          self
+            .quota_error(workspace_id, parent_dir, file_size as u64)
+            .await?
+        },
+      };
+      match rejection {
+        Some(err) => UploadOutcome::Rejected(err),
+        None => match fit_max_parts(
+          &self.config,
+          effective_chunk_size(&self.config, capabilities.min_part_size),
+          file_size,
+          capabilities.max_parts,
+        ) {
+          Ok(_) => UploadOutcome::WouldUpload,
+          Err(err) => UploadOutcome::Rejected(err),
+        },
+      }
+    } else {
+      outcome
+    };
+    Ok(UploadValidation {
+      file_id: Some(file_id),
+      url: Some(url),
+      outcome,
+    })
+  }
+
+  /// Returns [StorageError::TooManyPendingUploads] when creating the upload would exceed
+  /// [StorageManagerConfig::max_pending_uploads]. An upload of the file that is already pending
+  /// doesn't add a pending upload, so it's accepted.
+  async fn pending_uploads_error(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<Option<StorageError>> {
+    let max_pending = match self.config.max_pending_uploads {
+      Some(max_pending) => max_pending,
+      None => return Ok(None),
+    };
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    let pending = count_unfinished_upload_files(&mut conn)?;
+    if pending < max_pending {
+      return Ok(None);
+    }
+    let is_pending = select_upload_file(&mut conn, workspace_id, parent_dir, file_id)?
+      .is_some_and(|record| !record.is_finish);
+    if is_pending {
+      return Ok(None);
+    }
+    Ok(Some(StorageError::TooManyPendingUploads {
+      pending,
+      max_pending,
+    }))
+  }
+
+  /// Computes the id of the file to upload with [StorageManagerConfig::file_id_strategy]. The
+  /// content is hashed whatever the strategy when the backend is content addressed.
+  async fn upload_file_id(&self, file_path: &Path) -> std::io::Result<String> {
+    let strategy = if self.cloud_service.capabilities().content_addressed {
+      FileIdStrategy::ContentHash
+    } else {
+      self.config.file_id_strategy
+    };
+    file_id_with_strategy(file_path, strategy).await
+  }
+
+  /// Returns [StorageError::OverQuota] with the [StorageLimitExceededPB] payload telling how much
+  /// space the upload of the file needs.
+  async fn storage_limit_error(&self, file_path: &Path) -> FlowyError {
+    let (used_bytes, total_bytes) = self.storage_usage.read().unwrap().unwrap_or_default();
+    let required_bytes = tokio::fs::metadata(file_path)
+      .await
+      .map(|metadata| metadata.len())
+      .unwrap_or_default();
+    FlowyError::from(StorageError::OverQuota).with_payload(StorageLimitExceededPB {
+      used_bytes: used_bytes as i64,
+      total_bytes: total_bytes as i64,
+      required_bytes: required_bytes as i64,
+    })
+  }
+
+  /// Returns [StorageError::QuotaExceeded] when the upload of the file exceeds a limit of
+  /// [StorageManagerConfig::upload_quota].
+  async fn quota_error(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_size: u64,
+  ) -> FlowyResult<Option<StorageError>> {
+    let upload_quota = match &self.config.upload_quota {
+      Some(upload_quota) => upload_quota,
+      None => return Ok(None),
+    };
+    let exceeded = upload_quota
+      .check(workspace_id, parent_dir, file_size)
+      .await?;
+    Ok(exceeded.map(StorageError::QuotaExceeded))
+  }
+
+  /// Returns the storage class to record for an upload. A class the backend doesn't list is
+  /// rejected, and any class is dropped when the backend doesn't support choosing one.
+  fn supported_storage_class(&self, storage_class: Option<&str>) -> Result<String, StorageError> {
+    let storage_class = match storage_class {
+      None | Some("") => return Ok(String::new()),
+      Some(storage_class) => storage_class,
+    };
+    let supported = self.cloud_service.capabilities().storage_classes;
+    if supported.is_empty() {
+      warn!(
+        "[File] the backend doesn't support storage classes, ignore: {}",
+        storage_class
+      );
+      return Ok(String::new());
+    }
+    if !supported.iter().any(|supported| supported == storage_class) {
+      return Err(StorageError::UnsupportedStorageClass(
+        storage_class.to_string(),
+      ));
+    }
+    Ok(storage_class.to_string())
+  }
+
+  /// Creates the upload of the file with the options, see [CreateUploadOptions]. The storage class
+  /// is checked against the classes the backend supports, see [Self::supported_storage_class].
+  async fn create_upload_with_options(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_path: &Path,
+    upload_immediately: bool,
+    options: CreateUploadOptions,
+  ) -> Result<(CreatedUpload, Option<FileProgressReceiver>), FlowyError> {
+    let CreateUploadOptions {
+      content_type,
+      metadata,
+      storage_class,
+      overwrite,
+      cancel_token,
+      ..
+    } = options;
+    if workspace_id.is_empty() {
+      return Err(StorageError::EmptyWorkspaceId.into());
+    }
+
+    // The dir keys the record and the url, its equivalent forms must map to the same upload.
+    let parent_dir = canonical_parent_dir(parent_dir)?;
+
+    if file_path.as_os_str().is_empty() {
+      return Err(StorageError::EmptyFilePath.into());
+    }
+    validate_metadata(&metadata)?;
+
+    let workspace_id = workspace_id.to_string();
+
+    let is_exceed_limit = self.is_exceed_storage_limit.load(Ordering::Relaxed);
+    if is_exceed_limit {
+      let err = self.storage_limit_error(file_path).await;
+      make_notification(StorageNotification::FileStorageLimitExceeded)
+        .payload(err.clone())
+        .send();
+
+      return Err(err);
+    }
+    let storage_class = self.supported_storage_class(storage_class.as_deref())?;
+
+    // Hashing a large file takes a while, stop it when the user cancels.
+    let file_id = tokio::select! {
+      biased;
+      _ = cancel_token.cancelled() => return Err(StorageError::Cancelled.into()),
+      file_id = self.upload_file_id(file_path) => file_id?,
+    };
+    // Skip the upload if the same file was already uploaded to the same place.
+    if let Some(record) = self
+      .select_completed_upload(&workspace_id, &parent_dir, &file_id)
+      .await?
+    {
+      info!("[File] file already uploaded, skip creating new upload task");
+      let url = self
+        .cloud_service
+        .get_object_url_v1(
+          &record.workspace_id,
+          record.object_parent_dir(),
+          &record.file_id,
+        )
+        .await?;
+      notify_duplicate_upload(&url, &file_id, DuplicateUploadStatePB::Completed);
+      let receiver = finished_receiver(&file_id);
+      return Ok((CreatedUpload { url, file_id }, Some(receiver)));
+    }
+    // Link to the same content uploaded under another parent dir instead of uploading it again.
+    if let Some(source) = self
+      .linkable_upload(&workspace_id, &parent_dir, &file_id)
+      .await?
+    {
+      let url = self.link_upload(&source, &parent_dir, &metadata).await?;
+      let receiver = finished_receiver(&file_id);
+      return Ok((CreatedUpload { url, file_id }, Some(receiver)));
+    }
+    if let Some(err) = self
+      .pending_uploads_error(&workspace_id, &parent_dir, &file_id)
+      .await?
+    {
+      return Err(err.into());
+    }
+
+    // The parts must meet the minimum part size and the maximum number of parts of the backend,
+    // otherwise completing the upload fails. It's checked before copying the file.
+    let capabilities = self.cloud_service.capabilities();
+    let file_size = tokio::fs::metadata(file_path).await?.len() as usize;
+    let chunk_size = fit_max_parts(
+      &self.config,
+      effective_chunk_size(&self.config, capabilities.min_part_size),
+      file_size,
+      capabilities.max_parts,
+    )?;
+    if let Some(err) = self
+      .quota_error(&workspace_id, &parent_dir, file_size as u64)
+      .await?
+    {
+      info!("[File] upload rejected: {}", err);
+      return Err(err.into());
+    }
+    // An upload that must not replace the object is rejected right away when the object exists.
+    // The backends with a precondition check it again when the upload is created on the server.
+    if !overwrite {
+      if capabilities.head_object {
+        if self
+          .cloud_service
+          .object_exists(&workspace_id, &parent_dir, &file_id)
+          .await?
+        {
+          return Err(StorageError::AlreadyExists(file_id).into());
+        }
+      } else if !capabilities.conditional_create {
+        return Err(
+          FlowyError::not_support()
+            .with_context("the backend can't tell whether the object already exists"),
+        );
+      }
+    }
 
     let local_file_path = self
       .temp_storage
-      .create_temp_file_from_existing(Path::new(&file_path))
+      .create_temp_file_from_existing(file_path, &cancel_token)
       .await
       .map_err(|err| {
+        if cancel_token.is_cancelled() {
+          info!("[File] create upload cancelled: {}", file_path.display());
+          return FlowyError::from(StorageError::Cancelled);
+        }
         error!("[File] create temp file failed: {}", err);
-        FlowyError::internal()
-          .with_context(format!("create temp file for upload file failed: {}", err))
+        StorageError::TempFileCreation(err.to_string()).into()
       })?;
+    // The copy might have finished right before the cancellation.
+    if cancel_token.is_cancelled() {
+      let _ = self.temp_storage.delete_temp_file(&local_file_path).await;
+      return Err(StorageError::Cancelled.into());
+    }
+
+    // 1. create a file record and chunk the file.
+    let mut record = create_upload_record(
+      &self.config,
+      workspace_id,
+      parent_dir,
+      local_file_path.clone(),
+      file_id,
+      content_type.as_deref(),
+      chunk_size,
+    )
+    .await?;
+    record.metadata = metadata_to_record(&metadata)?;
+    record.storage_class = storage_class;
+    record.overwrite = overwrite;
+    record.source_file_path = file_path.to_string_lossy().into_owned();
+    // 2. save the record to sqlite
+    let url = self
+      .cloud_service
+      .get_object_url_v1(&record.workspace_id, &record.parent_dir, &record.file_id)
+      .await?;
+    let file_id = record.file_id.clone();
+    let conn = acquire_sqlite_connection(&self.user_service).await?;
+    match insert_upload_file(conn, &record) {
+      Ok(seq) => {
+        record.seq = seq;
+        // Register the notifier before queueing the task, otherwise a fast upload could finish
+        // before anyone listens to it. Subscribers that arrived before the upload was created
+        // share the same notifier.
+        let receiver = {
+          let mut notifier = self
+            .progress_notifiers
+            .entry(upload_key(
+              &record.workspace_id,
+              &record.parent_dir,
+              &file_id,
+            ))
+            .or_insert_with(|| ProgressNotifier::new(file_id.to_string()));
+          let receiver = notifier.subscribe();
+          // The upload waits in the queue until the uploader picks it.
+          notifier.notify(FileUploadState::Queued).await;
+          receiver
+        };
+        self.notify_queued_floor(&record).await;
+
+        // 3. generate url for given file
+        if upload_immediately {
+          self
+            .task_queue
+            .queue_task(UploadTask::ImmediateTask {
+              local_file_path,
+              record,
+              retries_left: self.config.immediate_task_retries,
+            })
+            .await;
+        } else {
+          self
+            .task_queue
+            .queue_task(UploadTask::Task {
+              local_file_path,
+              record,
+              retries_left: self.config.task_retries,
+            })
+            .await;
+        }
+
+        Ok::<_, FlowyError>((CreatedUpload { url, file_id }, Some(receiver)))
+      },
+      Err(err) => {
+        if matches!(err.code, ErrorCode::DuplicateSqliteRecord) {
+          info!("[File] upload record already exists, skip creating new upload task");
+          let receiver = self
+            .existing_upload_receiver(&record.workspace_id, &record.parent_dir, &file_id)
+            .await?;
+          let state = if self
+            .is_upload_completed(&record.workspace_id, &record.parent_dir, &file_id)
+            .await?
+          {
+            DuplicateUploadStatePB::Completed
+          } else {
+            DuplicateUploadStatePB::InProgress
+          };
+          notify_duplicate_upload(&url, &file_id, state);
+          Ok::<_, FlowyError>((CreatedUpload { url, file_id }, receiver))
+        } else {
+          Err(err)
+        }
+      },
+    }
+  }
+
+  async fn retry_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    immediate: bool,
+  ) -> FlowyResult<()> {
+    let parent_dir = &canonical_parent_dir(parent_dir)?;
+    let record = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_upload_file(&mut conn, workspace_id, parent_dir, file_id)?
+    }
+    .ok_or_else(|| FlowyError::from(StorageError::RecordNotFound(file_id.to_string())))?;
+    if record.is_finish {
+      info!("[File] upload already finished, skip retrying: {}", file_id);
+      return Ok(());
+    }
+
+    // The running upload reports its own result, queueing it again would upload it twice.
+    if self
+      .active_uploads
+      .contains_key(&upload_key(workspace_id, parent_dir, file_id))
+    {
+      info!("[File] upload is running, skip retrying: {}", file_id);
+      return Ok(());
+    }
+
+    info!(
+      "[File] retry upload: {}/{}/{}, immediate: {}",
+      workspace_id, parent_dir, file_id, immediate
+    );
+    self.notify_queued_floor(&record).await;
+    let task = if immediate {
+      UploadTask::ImmediateTask {
+        local_file_path: record.local_file_path.clone(),
+        record,
+        retries_left: self.config.immediate_task_retries,
+      }
+    } else {
+      UploadTask::BackgroundTask {
+        workspace_id: record.workspace_id,
+        file_id: record.file_id,
+        parent_dir: record.parent_dir,
+        seq: record.seq,
+        retries_left: self.config.background_task_retries,
+      }
+    };
+    self.task_queue.replace_task(task).await;
+    Ok(())
+  }
+
+  async fn cancel_workspace_uploads(&self, workspace_id: &str) -> FlowyResult<CancelledUploads> {
+    info!("[File] cancel workspace uploads: {}", workspace_id);
+    let prefix = upload_key_prefix(workspace_id);
+    let mut active = 0;
+    for cancel_token in self.active_uploads.iter() {
+      if cancel_token.key().starts_with(&prefix) && !cancel_token.value().is_cancelled() {
+        cancel_token.value().cancel();
+        active += 1;
+      }
+    }
+    let queued = self.task_queue.remove_workspace_tasks(workspace_id).await;
+
+    let records = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_workspace_upload_files(&mut conn, workspace_id, false)?
+    };
+    for record in &records {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      delete_upload_failure(&mut conn, workspace_id, &record.parent_dir, &record.file_id)?;
+      delete_upload_file_by_file_id(conn, workspace_id, &record.parent_dir, &record.file_id)?;
+      abort_server_upload(&self.cloud_service, record).await;
+      if let Err(err) = self
+        .temp_storage
+        .delete_temp_file(&record.local_file_path)
+        .await
+      {
+        trace!("[File] delete temp file failed: {}", err);
+      }
+      self.bandwidth.remove_file_limit(&record.file_id);
+    }
+    let keys = self
+      .progress_notifiers
+      .iter()
+      .filter(|notifier| notifier.key().starts_with(&prefix))
+      .map(|notifier| notifier.key().clone())
+      .collect::<Vec<_>>();
+    for key in keys {
+      self.release_progress_notifier(&key).await;
+    }
+
+    Ok(CancelledUploads {
+      active,
+      queued,
+      records: records.len(),
+    })
+  }
+
+  /// Releases the notifier of a cancelled upload. A notifier that still has subscribers is kept and
+  /// reset to [FileUploadState::NotStarted], so the subscribers keep receiving the progress when the
+  /// file is uploaded again. Otherwise, the notifier is removed.
+  async fn release_progress_notifier(&self, key: &str) {
+    if let Some(mut notifier) = self.progress_notifiers.get_mut(key) {
+      if notifier.subscriber_count() > 0 {
+        notifier.notify(FileUploadState::NotStarted).await;
+      }
+    }
+    // Checked again under the lock of the map, a concurrent subscribe either keeps the notifier
+    // or creates a new one after the removal.
+    self
+      .progress_notifiers
+      .remove_if(key, |_, notifier| notifier.subscriber_count() == 0);
+  }
+
+  /// Marks the upload of the record as running, so that it can be cancelled by
+  /// [Self::cancel_upload]. Returns `None` if it's already running, e.g. when the user retries
+  /// while the uploader is uploading the file, so that the two don't race on the parts. The upload
+  /// stays marked until the returned [ActiveUpload] is dropped, which happens whether the upload
+  /// completes, fails or is cancelled.
+  fn register_active_upload(&self, record: &UploadFileTable) -> Option<ActiveUpload> {
+    let key = upload_key(&record.workspace_id, &record.parent_dir, &record.file_id);
+    let cancel_token = match self.active_uploads.entry(key.clone()) {
+      Entry::Occupied(_) => return None,
+      Entry::Vacant(entry) => entry.insert(CancellationToken::new()).clone(),
+    };
+    self.task_queue.notify_changed();
+    Some(ActiveUpload {
+      key,
+      cancel_token,
+      active_uploads: self.active_uploads.clone(),
+      task_queue: self.task_queue.clone(),
+    })
+  }
+
+  /// Returns the completed upload of the same file under another parent dir of the workspace, to
+  /// link the upload of the file to the parent dir to, see
+  /// [StorageManagerConfig::link_duplicate_uploads]. `None` when the file has a record under the
+  /// parent dir already.
+  async fn linkable_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<Option<UploadFileTable>> {
+    if !self.config.link_duplicate_uploads {
+      return Ok(None);
+    }
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    if select_upload_file(&mut conn, workspace_id, parent_dir, file_id)?.is_some() {
+      return Ok(None);
+    }
+    select_linkable_upload_file(&mut conn, workspace_id, file_id)
+  }
+
+  /// Records the upload of the file to the parent dir as a link to the object of the source
+  /// upload, no byte is uploaded. Returns the url of the shared object.
+  async fn link_upload(
+    &self,
+    source: &UploadFileTable,
+    parent_dir: &str,
+    metadata: &HashMap<String, String>,
+  ) -> FlowyResult<String> {
+    // The link shares the upload id of the source, so that dropping the upload of the object drops
+    // its links as well.
+    let link = UploadFileTable {
+      parent_dir: parent_dir.to_string(),
+      linked_parent_dir: source.parent_dir.clone(),
+      // The source owns the local copy of the file.
+      local_file_path: String::new(),
+      source_file_path: String::new(),
+      metadata: metadata_to_record(metadata)?,
+      created_at: unix_timestamp(self.config.clock.system_now()),
+      ..source.clone()
+    };
+    let conn = acquire_sqlite_connection(&self.user_service).await?;
+    insert_upload_file(conn, &link)?;
+    info!(
+      "[File] link upload {}/{} to the object under {}",
+      parent_dir, source.file_id, source.parent_dir
+    );
+    self
+      .cloud_service
+      .get_object_url_v1(&source.workspace_id, &source.parent_dir, &source.file_id)
+      .await
+  }
+
+  /// Returns the completed upload record of the file, if any.
+  ///
+  /// When [StorageManagerConfig::verify_completed_upload] is enabled, the record is only returned
+  /// if its object still exists on the server. A record whose object was deleted out-of-band is
+  /// removed, so that the file can be uploaded again.
+  async fn select_completed_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<Option<UploadFileTable>> {
+    let record = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_upload_file(&mut conn, workspace_id, parent_dir, file_id)?
+    };
+    let record = match record {
+      Some(record) if record.is_finish => record,
+      _ => return Ok(None),
+    };
+
+    if self.config.verify_completed_upload && self.cloud_service.capabilities().head_object {
+      match self
+        .cloud_service
+        .object_exists(workspace_id, record.object_parent_dir(), file_id)
+        .await
+      {
+        Ok(true) => {},
+        Ok(false) => {
+          info!(
+            "[File] object of completed upload not found on server: {}",
+            file_id
+          );
+          let conn = acquire_sqlite_connection(&self.user_service).await?;
+          delete_upload_file(conn, &record.upload_id)?;
+          return Ok(None);
+        },
+        Err(err) => {
+          // Trust the local record when the existence of the object can't be verified.
+          warn!("[File] check object existence failed: {}", err);
+        },
+      }
+    }
+    Ok(Some(record))
+  }
+
+  /// Sends the progress floor of an upload about to be queued, see [floor_progress], so that the
+  /// bar moves while the upload waits in the queue. It's sent before the task is queued, otherwise
+  /// a worker could report a higher progress first.
+  async fn notify_queued_floor(&self, record: &UploadFileTable) {
+    if self.config.progress_floor <= 0.0 {
+      return;
+    }
+    let uploaded_parts = if record.upload_id.is_empty() {
+      0
+    } else {
+      match acquire_sqlite_connection(&self.user_service).await {
+        Ok(mut conn) => select_upload_parts(&mut conn, &record.upload_id)
+          .map(|parts| parts.len())
+          .unwrap_or_default(),
+        Err(err) => {
+          error!("[File] select upload parts failed: {}", err);
+          return;
+        },
+      }
+    };
+    if let Some(progress_value) = floor_progress(
+      &self.config,
+      uploaded_parts as u64,
+      record.num_chunk as usize,
+    ) {
+      if let Err(err) = send_floor_progress(
+        &self.cloud_service,
+        &self.global_notifier,
+        record,
+        progress_value,
+      )
+      .await
+      {
+        error!("[File] send progress floor failed: {}", err);
+      }
+    }
+  }
+
+  /// Returns a receiver for an upload that already exists. An in-progress upload shares its live
+  /// notifier, and a completed upload gets a receiver that only yields the finished state. Returns
+  /// None when neither a notifier nor a record exists for the file.
+  async fn existing_upload_receiver(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<Option<FileProgressReceiver>> {
+    let key = upload_key(workspace_id, parent_dir, file_id);
+    if let Some(notifier) = self.progress_notifiers.get(&key) {
+      // The notifier is kept after the upload finishes, a new subscriber would miss the finish
+      // event.
+      if matches!(
+        notifier.current_value,
+        Some(FileUploadState::Finished { .. })
+      ) {
+        return Ok(Some(finished_receiver(file_id)));
+      }
+      return Ok(Some(notifier.subscribe()));
+    }
+
+    let record = {
+      let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+      select_upload_file(&mut conn, workspace_id, parent_dir, file_id)?
+    };
+    match record {
+      Some(record) if record.is_finish => Ok(Some(finished_receiver(file_id))),
+      // The record is pending without a notifier, e.g. it was restored from sqlite after a restart.
+      // The notifier receives the progress once the uploader picks the record.
+      Some(_) => {
+        let receiver = self
+          .progress_notifiers
+          .entry(key)
+          .or_insert_with(|| ProgressNotifier::new(file_id.to_string()))
+          .subscribe();
+        Ok(Some(receiver))
+      },
+      None => Ok(None),
+    }
+  }
+
+  async fn is_upload_completed(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<bool> {
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    Ok(is_upload_completed(&mut conn, workspace_id, parent_dir, file_id).unwrap_or(false))
+  }
+}
+
+struct ActiveUpload {
+  key: String,
+  cancel_token: CancellationToken,
+  active_uploads: Arc<DashMap<String, CancellationToken>>,
+  task_queue: Arc<UploadTaskQueue>,
+}
+
+impl Drop for ActiveUpload {
+  fn drop(&mut self) {
+    self.active_uploads.remove(&self.key);
+    self.task_queue.notify_changed();
+  }
+}
+
+async fn queue_status(
+  uploader: &FileUploader,
+  active_uploads: &DashMap<String, CancellationToken>,
+) -> QueueStatus {
+  let (queued, _) = uploader.queued_tasks().await;
+  // The uploads started outside of the uploader, e.g. a resume, are only in the active uploads.
+  let running = uploader.running_uploads().max(active_uploads.len());
+  QueueStatus { queued, running }
+}
+
+/// Returns the size of the parts of a new upload. The configured chunk size is bounded by
+/// [StorageManagerConfig::max_part_size], then raised to the minimum part size of the backend.
+fn effective_chunk_size(config: &StorageManagerConfig, min_part_size: usize) -> usize {
+  let chunk_size = match config.max_part_size {
+    Some(max_part_size) => config.chunk_size.min(max_part_size),
+    None => config.chunk_size,
+  };
+  chunk_size.max(min_part_size)
+}
+
+/// Raises the chunk size so that the file fits in the maximum number of parts of the backend. A
+/// raised chunk size above [StorageManagerConfig::max_part_size] is rejected instead.
+fn fit_max_parts(
+  config: &StorageManagerConfig,
+  chunk_size: usize,
+  file_size: usize,
+  max_parts: Option<usize>,
+) -> Result<usize, StorageError> {
+  let max_parts = match max_parts {
+    Some(max_parts) if max_parts > 0 => max_parts,
+    _ => return Ok(chunk_size),
+  };
+  let parts = file_size.div_ceil(chunk_size);
+  if parts <= max_parts {
+    return Ok(chunk_size);
+  }
+  let raised_chunk_size = file_size.div_ceil(max_parts);
+  if config
+    .max_part_size
+    .is_some_and(|max_part_size| raised_chunk_size > max_part_size)
+  {
+    return Err(StorageError::TooManyParts { parts, max_parts });
+  }
+  info!(
+    "[File] raise chunk size from {} to {} to fit {} parts",
+    chunk_size, raised_chunk_size, max_parts
+  );
+  Ok(raised_chunk_size)
+}
+
+/// Parses the workspace id, parent dir and file id of an object url. The v2 format is tried first,
+/// then the v1 format used by the older documents.
+pub(crate) async fn parse_object_url(
+  cloud_service: &Arc<dyn StorageCloudService>,
+  url: &str,
+) -> Option<(String, String, String)> {
+  match cloud_service.parse_object_url_v2(url).await {
+    Some(value) => Some(value),
+    None => cloud_service.parse_object_url_v1(url).await,
+  }
+}
+
+/// The seconds since the epoch, the unit of the timestamps stored in sqlite.
+pub(crate) fn unix_timestamp(time: SystemTime) -> i64 {
+  time
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs() as i64
+}
+
+/// The parent dir of a lookup that can't fail, see [canonical_parent_dir]. An invalid dir is kept
+/// as is, no upload is keyed by it.
+fn lookup_parent_dir(parent_dir: &str) -> String {
+  canonical_parent_dir(parent_dir).unwrap_or_else(|_| parent_dir.to_string())
+}
+
+fn upload_key(workspace_id: &str, parent_dir: &str, file_id: &str) -> String {
+  format!("{}/{}/{}", workspace_id, parent_dir, file_id)
+}
+
+fn upload_file_key(upload_file: &UploadFileTable) -> String {
+  upload_key(
+    &upload_file.workspace_id,
+    &upload_file.parent_dir,
+    &upload_file.file_id,
+  )
+}
 
-    // 1. create a file record and chunk the file
-    let record = create_upload_record(workspace_id, parent_dir, local_file_path.clone()).await?;
-    // 2. save the record to sqlite
-    let conn = self
-      .user_service
-      .sqlite_connection(self.user_service.user_id()?)?;
-    let url = self
-      .cloud_service
-      .get_object_url_v1(&record.workspace_id, &record.parent_dir, &record.file_id)
-      .await?;
-    let file_id = record.file_id.clone();
-    match insert_upload_file(conn, &record) {
-      Ok(_) => {
-        // 3. generate url for given file
-        if upload_immediately {
-          self
-            .task_queue
-            .queue_task(UploadTask::ImmediateTask {
-              local_file_path,
-              record,
-              retry_count: 3,
-            })
-            .await;
-        } else {
-          self
-            .task_queue
-            .queue_task(UploadTask::Task {
-              local_file_path,
-              record,
-              retry_count: 0,
-            })
-            .await;
-        }
+/// The prefix of the [upload_key]s of the workspace.
+fn upload_key_prefix(workspace_id: &str) -> String {
+  format!("{}/", workspace_id)
+}
 
-        let notifier = ProgressNotifier::new(file_id.to_string());
-        let receiver = notifier.subscribe();
-        self
-          .progress_notifiers
-          .insert(file_id.to_string(), notifier);
-        Ok::<_, FlowyError>((CreatedUpload { url, file_id }, Some(receiver)))
-      },
-      Err(err) => {
-        if matches!(err.code, ErrorCode::DuplicateSqliteRecord) {
-          info!("[File] upload record already exists, skip creating new upload task");
-          Ok::<_, FlowyError>((CreatedUpload { url, file_id }, None))
-        } else {
-          Err(err)
-        }
-      },
+/// The state of the stream returned by [StorageManager::uploads_stream].
+struct UploadsStreamState {
+  receiver: broadcast::Receiver<FileProgress>,
+  /// The workspace of the uploads, set once their initial snapshots are listed.
+  workspace_id: Option<String>,
+  /// The last snapshot of each upload, keyed by [upload_key].
+  snapshots: HashMap<String, UploadSnapshot>,
+  /// The snapshots waiting to be emitted.
+  pending: VecDeque<UploadSnapshot>,
+}
+
+/// Waits for the next progress of an upload. `None` once the progress stream is closed.
+async fn next_upload_progress(
+  receiver: &mut broadcast::Receiver<FileProgress>,
+) -> Option<FileProgress> {
+  loop {
+    match receiver.recv().await {
+      Ok(progress) if progress.direction == TransferDirection::Upload => return Some(progress),
+      Ok(_) | Err(RecvError::Lagged(_)) => {},
+      Err(RecvError::Closed) => return None,
     }
   }
+}
 
-  async fn start_upload(&self, record: &BoxAny) -> Result<(), FlowyError> {
-    let file_record = record.downcast_ref::<UploadFileTable>().ok_or_else(|| {
-      FlowyError::internal().with_context("failed to downcast record to UploadFileTable")
-    })?;
+/// The outcome of a reconciliation pass, see [StorageManager::reconcile_uploads].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReconcileSummary {
+  /// The records checked against the server.
+  pub checked: usize,
+  pub marked_finished: usize,
+  pub requeued: usize,
+  pub dropped: usize,
+}
 
-    start_upload(
-      &self.cloud_service,
-      &self.user_service,
-      &self.temp_storage,
-      file_record,
-      self.global_notifier.clone(),
-    )
-    .await?;
+impl From<ReconcileSummary> for ReconcileSummaryPB {
+  fn from(summary: ReconcileSummary) -> Self {
+    Self {
+      checked: summary.checked as i64,
+      marked_finished: summary.marked_finished as i64,
+      requeued: summary.requeued as i64,
+      dropped: summary.dropped as i64,
+    }
+  }
+}
 
-    Ok(())
+/// The outcome of [StorageManager::verify_storage].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StorageReport {
+  /// The upload records checked.
+  pub checked_records: usize,
+  /// The finished uploads whose object couldn't be checked against the server.
+  pub unchecked_objects: usize,
+  pub issues: Vec<StorageIssue>,
+}
+
+impl StorageReport {
+  pub fn is_healthy(&self) -> bool {
+    self.issues.is_empty()
   }
+}
 
-  async fn resume_upload(
-    &self,
-    workspace_id: &str,
-    parent_dir: &str,
-    file_id: &str,
-  ) -> Result<(), FlowyError> {
-    // Gathering the upload record and parts from the sqlite database.
-    let mut conn = self
-      .user_service
-      .sqlite_connection(self.user_service.user_id()?)?;
+/// An inconsistency found by [StorageManager::verify_storage].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageIssue {
+  /// The upload is finished but its object doesn't exist on the server.
+  MissingObject {
+    workspace_id: String,
+    parent_dir: String,
+    file_id: String,
+  },
+  /// The object of the upload exists on the server but the upload isn't marked as finished.
+  UploadedButUnfinished {
+    workspace_id: String,
+    parent_dir: String,
+    file_id: String,
+  },
+  /// The upload is unfinished but the file to upload doesn't exist anymore.
+  MissingLocalFile {
+    workspace_id: String,
+    parent_dir: String,
+    file_id: String,
+    local_file_path: String,
+  },
+  /// Uploaded parts whose upload has no record.
+  OrphanedParts { upload_id: String },
+  /// A temp file no upload record refers to, or the temp file of a completed upload that couldn't
+  /// be deleted.
+  OrphanedTempFile { path: String },
+}
 
-    if let Some(upload_file) = select_upload_file(&mut conn, workspace_id, parent_dir, file_id)? {
-      resume_upload(
-        &self.cloud_service,
-        &self.user_service,
-        &self.temp_storage,
-        upload_file,
-        self.global_notifier.clone(),
-      )
-      .await?;
-    } else {
-      error!("[File] resume upload failed: record not found");
+/// The fixes applied by [StorageManager::repair_storage].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RepairSummary {
+  /// The finished uploads whose missing object is uploaded again.
+  pub requeued: usize,
+  pub marked_finished: usize,
+  /// The records removed, either finished without object and local file, or unfinished without
+  /// local file.
+  pub dropped: usize,
+  /// The uploads whose orphaned parts were deleted.
+  pub deleted_parts: usize,
+  pub deleted_temp_files: usize,
+}
+
+/// The number of uploads cancelled by [StorageManager::cancel_workspace_uploads].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CancelledUploads {
+  /// The uploads that were running.
+  pub active: usize,
+  /// The tasks that were waiting in the queue.
+  pub queued: usize,
+  /// The unfinished upload records that were removed.
+  pub records: usize,
+}
+
+/// How [StorageManager::resume_upload_with_strategy] resumes an unfinished upload.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResumeStrategy {
+  /// Continues from the uploaded parts, only the missing parts are uploaded.
+  #[default]
+  Continue,
+  /// Discards the uploaded parts and aborts the server-side upload, the whole file is uploaded
+  /// again. It recovers an upload whose parts are stale.
+  Restart,
+}
+
+/// The options of an upload created by [StorageManager::create_upload_with]. The default options
+/// create the same upload as [StorageService::create_upload].
+pub struct CreateUploadOptions {
+  /// The content type of the file, detected from the file when `None`. A content type that isn't a
+  /// valid media type is replaced by [StorageManagerConfig::fallback_content_type].
+  content_type: Option<String>,
+  /// The metadata stored along with the object, e.g. the original file name or a checksum. The keys
+  /// and values must be valid http header names and values. The metadata is kept in the upload
+  /// record, and sent to the server when it supports
+  /// [flowy_storage_pub::cloud::StorageCapabilities::object_metadata]. It's ignored when the file
+  /// is already uploaded.
+  metadata: HashMap<String, String>,
+  /// The storage class of the object, e.g. a cheaper tier for archived attachments. The class must
+  /// be one of [flowy_storage_pub::cloud::StorageCapabilities::storage_classes], it's ignored when
+  /// the backend doesn't support choosing one, or when the file is already uploaded.
+  storage_class: Option<String>,
+  /// When false, the upload fails with [StorageError::AlreadyExists] instead of replacing the
+  /// object if it already exists, e.g. to protect shared content. The object is checked when the
+  /// upload is created, and again on the server when the backend supports
+  /// [flowy_storage_pub::cloud::StorageCapabilities::conditional_create]. A conflict found on the
+  /// server drops the upload. Fails with [ErrorCode::NotSupportYet] when the backend can't check
+  /// the object.
+  overwrite: bool,
+  /// Stops creating the upload, e.g. while hashing or copying a large file.
+  cancel_token: CancellationToken,
+  /// Receives the progress of the upload instead of a receiver, for the in-process callers. The
+  /// callback runs on a task of its own, a slow callback only misses the intermediate progress, it
+  /// never holds the upload. It's called until the upload finished, the last progress being 1.0.
+  on_progress: Option<Box<dyn Fn(FileProgress) + Send + 'static>>,
+}
+
+impl Default for CreateUploadOptions {
+  fn default() -> Self {
+    Self {
+      content_type: None,
+      metadata: HashMap::new(),
+      storage_class: None,
+      overwrite: true,
+      cancel_token: CancellationToken::new(),
+      on_progress: None,
     }
-    Ok(())
   }
+}
 
-  async fn subscribe_file_progress(
-    &self,
-    parent_idr: &str,
-    file_id: &str,
-  ) -> Result<Option<FileProgressReceiver>, FlowyError> {
-    trace!("[File]: subscribe file progress: {}", file_id);
+impl CreateUploadOptions {
+  pub fn content_type(mut self, content_type: &str) -> Self {
+    self.content_type = Some(content_type.to_string());
+    self
+  }
 
-    let is_completed = {
-      let mut conn = self
-        .user_service
-        .sqlite_connection(self.user_service.user_id()?)?;
-      let workspace_id = self.user_service.workspace_id()?;
-      is_upload_completed(&mut conn, &workspace_id, parent_idr, file_id).unwrap_or(false)
-    };
+  pub fn metadata(mut self, metadata: HashMap<String, String>) -> Self {
+    self.metadata = metadata;
+    self
+  }
 
-    if is_completed {
-      return Ok(None);
-    }
+  pub fn storage_class(mut self, storage_class: &str) -> Self {
+    self.storage_class = Some(storage_class.to_string());
+    self
+  }
 
-    let notifier = self
-      .progress_notifiers
-      .entry(file_id.to_string())
-      .or_insert_with(|| ProgressNotifier::new(file_id.to_string()));
-    Ok(Some(notifier.subscribe()))
+  pub fn overwrite(mut self, overwrite: bool) -> Self {
+    self.overwrite = overwrite;
+    self
+  }
+
+  pub fn cancel_token(mut self, cancel_token: CancellationToken) -> Self {
+    self.cancel_token = cancel_token;
+    self
+  }
+
+  pub fn on_progress<F>(mut self, on_progress: F) -> Self
+  where
+    F: Fn(FileProgress) + Send + 'static,
+  {
+    self.on_progress = Some(Box::new(on_progress));
+    self
   }
 }
 
+/// What [StorageManager::validate_upload] found creating the upload of a file would do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadOutcome {
+  /// A new upload would be created.
+  WouldUpload,
+  /// The file is already uploaded to the same place, no upload would be created.
+  AlreadyUploaded,
+  /// The file is already uploaded under another parent dir of the workspace, the upload would link
+  /// to it, see [StorageManagerConfig::link_duplicate_uploads].
+  WouldLink,
+  /// The file is being uploaded to the same place, the progress of that upload would be returned.
+  InProgress,
+  /// The upload would be rejected for the reason.
+  Rejected(StorageError),
+}
+
+/// The uploads of the queue, see [StorageManager::subscribe_queue_depth].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStatus {
+  /// The uploads waiting in the queue, the paused ones included.
+  pub queued: usize,
+  pub running: usize,
+}
+
+/// How [StorageManager::wait_until_idle] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleOutcome {
+  /// No upload is queued or running.
+  Idle,
+  /// Nothing is running, the queued uploads all wait for the reasons to be lifted.
+  Paused {
+    queued: usize,
+    reasons: PauseReasons,
+  },
+  /// The uploads didn't settle before the timeout.
+  TimedOut { queued: usize, running: usize },
+}
+
+/// The result of [StorageManager::validate_upload].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadValidation {
+  /// The id of the file, `None` when the upload is rejected before reading the file.
+  pub file_id: Option<String>,
+  /// The url the file would be uploaded to, `None` when the file id is unknown.
+  pub url: Option<String>,
+  pub outcome: UploadOutcome,
+}
+
 async fn create_upload_record(
+  config: &StorageManagerConfig,
   workspace_id: String,
   parent_dir: String,
   local_file_path: String,
+  file_id: String,
+  content_type: Option<&str>,
+  chunk_size: usize,
 ) -> FlowyResult<UploadFileTable> {
   let file_path = Path::new(&local_file_path);
-  let file = tokio::fs::File::open(&file_path).await?;
+  let mut file = tokio::fs::File::open(&file_path).await?;
   let metadata = file.metadata().await?;
   let file_size = metadata.len() as usize;
 
   // Calculate the total number of chunks
-  let num_chunk = calculate_offsets(file_size, MIN_CHUNK_SIZE).len();
-  let content_type = mime_guess::from_path(&file_path)
-    .first_or_octet_stream()
-    .to_string();
-  let file_id = FileId::from_path(&file_path.to_path_buf()).await?;
+  let num_chunk = calculate_offsets(file_size, chunk_size).len();
+  let content_type = match content_type {
+    Some(content_type) => content_type.to_string(),
+    None => match mime_guess::from_path(file_path).first() {
+      Some(content_type) => content_type,
+      None => {
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        (&mut file)
+          .take(SNIFF_LEN as u64)
+          .read_to_end(&mut head)
+          .await?;
+        sniff_mime(&head).unwrap_or_else(|| config.fallback_content_type.clone())
+      },
+    }
+    .to_string(),
+  };
+  // The server rejects a malformed content type, so the upload would only fail later.
+  let content_type = if is_valid_content_type(&content_type) {
+    content_type
+  } else {
+    warn!(
+      "[File] invalid content type: {} of {}, use {}",
+      content_type, local_file_path, config.fallback_content_type
+    );
+    config.fallback_content_type.to_string()
+  };
   let record = UploadFileTable {
     workspace_id,
     file_id,
@@ -486,57 +3689,76 @@ async fn create_upload_record(
     parent_dir,
     local_file_path,
     content_type,
-    chunk_size: MIN_CHUNK_SIZE as i32,
+    chunk_size: chunk_size as i32,
     num_chunk: num_chunk as i32,
-    created_at: timestamp(),
+    created_at: unix_timestamp(config.clock.system_now()),
     is_finish: false,
+    metadata: String::new(),
+    storage_class: String::new(),
+    // Assigned when the record is inserted.
+    seq: 0,
+    overwrite: true,
+    linked_parent_dir: String::new(),
+    source_file_path: String::new(),
   };
   Ok(record)
 }
 
+#[allow(clippy::too_many_arguments)]
 #[instrument(level = "debug", skip_all, err)]
 async fn start_upload(
+  config: &StorageManagerConfig,
   cloud_service: &Arc<dyn StorageCloudService>,
   user_service: &Arc<dyn StorageUserService>,
   temp_storage: &Arc<FileTempStorage>,
   upload_file: &UploadFileTable,
   global_notifier: GlobalNotifier,
+  cancel_token: &CancellationToken,
+  bandwidth: &UploadBandwidth,
+  part_timings: &PartTimings,
+  upload_log: &UploadLog,
 ) -> FlowyResult<()> {
+  let started_at = config.clock.now();
   // 4. gather existing completed parts
-  let mut conn = user_service.sqlite_connection(user_service.user_id()?)?;
-  let mut completed_parts = select_upload_parts(&mut conn, &upload_file.upload_id)
-    .unwrap_or_default()
-    .into_iter()
-    .map(|part| CompletedPartRequest {
-      e_tag: part.e_tag,
-      part_number: part.part_num,
-    })
-    .collect::<Vec<_>>();
+  let mut completed_parts = {
+    let mut conn = acquire_sqlite_connection(user_service).await?;
+    select_upload_parts(&mut conn, &upload_file.upload_id)
+      .unwrap_or_default()
+      .into_iter()
+      .map(|part| CompletedPartRequest {
+        e_tag: part.e_tag,
+        part_number: part.part_num,
+      })
+      .collect::<Vec<_>>()
+  };
   let upload_offset = completed_parts.len() as u64;
 
   let file_path = Path::new(&upload_file.local_file_path);
-  if !file_path.exists() {
+  if !file_path.exists() && !restore_temp_file(temp_storage, upload_file, cancel_token).await {
     error!("[File] file not found: {}", upload_file.local_file_path);
-    if let Ok(uid) = user_service.user_id() {
-      if let Ok(conn) = user_service.sqlite_connection(uid) {
-        delete_upload_file(conn, &upload_file.upload_id)?;
-      }
-    }
+    delete_upload_record(user_service, upload_file).await;
+    return Err(StorageError::FileMissing(upload_file.local_file_path.clone()).into());
   }
 
-  let mut chunked_bytes =
-    ChunkedBytes::from_file(&upload_file.local_file_path, MIN_CHUNK_SIZE).await?;
+  // Always chunk the file with the chunk size the record was created with. The parts uploaded
+  // before resuming were cut with that size, regardless of the current configuration.
+  let chunk_size = upload_file.chunk_size as usize;
+  let mut chunked_bytes = ChunkedBytes::from_file(&upload_file.local_file_path, chunk_size).await?;
   let total_parts = chunked_bytes.total_chunks();
-  if let Err(err) = chunked_bytes.set_offset(upload_offset).await {
+  let total_bytes = chunked_bytes.file_size();
+  if let Err(err) = chunked_bytes
+    .set_offset(upload_offset * chunk_size as u64)
+    .await
+  {
     error!(
       "[File] set offset failed: {} for file: {}",
       err, upload_file.local_file_path
     );
-    if let Ok(uid) = user_service.user_id() {
-      if let Ok(conn) = user_service.sqlite_connection(uid) {
-        delete_upload_file(conn, &upload_file.upload_id)?;
-      }
-    }
+    // The file is shorter than the uploaded parts, it was truncated since they were uploaded.
+    return Err(
+      abort_upload_with_missing_file(cloud_service, user_service, upload_file, &global_notifier)
+        .await,
+    );
   }
 
   info!(
@@ -545,43 +3767,98 @@ async fn start_upload(
   );
 
   let mut upload_file = upload_file.clone();
-  // 1. create upload
-  trace!(
-    "[File] create upload for workspace: {}, parent_dir: {}, file_id: {}",
-    upload_file.workspace_id,
-    upload_file.parent_dir,
-    upload_file.file_id
-  );
+  // 1. create upload. A resumed upload keeps its upload_id, so that the parts uploaded before
+  // belong to the same upload.
+  if upload_file.upload_id.is_empty() {
+    trace!(
+      "[File] create upload for workspace: {}, parent_dir: {}, file_id: {}",
+      upload_file.workspace_id,
+      upload_file.parent_dir,
+      upload_file.file_id
+    );
+
+    let metadata = metadata_from_record(&upload_file.metadata)?;
+    if !metadata.is_empty() && !cloud_service.capabilities().object_metadata {
+      warn!(
+        "[File] the backend doesn't store metadata, upload {} without it",
+        upload_file.file_id
+      );
+    }
+    let capabilities = cloud_service.capabilities();
+    let create_upload_resp_result = if !upload_file.overwrite && capabilities.conditional_create {
+      cloud_service
+        .create_upload_if_not_exists(
+          &upload_file.workspace_id,
+          &upload_file.parent_dir,
+          &upload_file.file_id,
+          &upload_file.content_type,
+          &metadata,
+          &upload_file.storage_class,
+        )
+        .await
+    } else if !upload_file.overwrite
+      && cloud_service
+        .object_exists(
+          &upload_file.workspace_id,
+          &upload_file.parent_dir,
+          &upload_file.file_id,
+        )
+        .await?
+    {
+      Err(StorageError::AlreadyExists(upload_file.file_id.clone()).into())
+    } else if !upload_file.storage_class.is_empty() {
+      cloud_service
+        .create_upload_with_storage_class(
+          &upload_file.workspace_id,
+          &upload_file.parent_dir,
+          &upload_file.file_id,
+          &upload_file.content_type,
+          &metadata,
+          &upload_file.storage_class,
+        )
+        .await
+    } else if metadata.is_empty() {
+      cloud_service
+        .create_upload(
+          &upload_file.workspace_id,
+          &upload_file.parent_dir,
+          &upload_file.file_id,
+          &upload_file.content_type,
+        )
+        .await
+    } else {
+      cloud_service
+        .create_upload_with_metadata(
+          &upload_file.workspace_id,
+          &upload_file.parent_dir,
+          &upload_file.file_id,
+          &upload_file.content_type,
+          &metadata,
+        )
+        .await
+    };
+    if let Err(err) = create_upload_resp_result.as_ref() {
+      handle_upload_error(cloud_service, user_service, &err, &upload_file).await;
+    }
+    let create_upload_resp = create_upload_resp_result?;
 
-  let create_upload_resp_result = cloud_service
-    .create_upload(
+    // 2. update upload_id
+    let conn = acquire_sqlite_connection(user_service).await?;
+    update_upload_file_upload_id(
+      conn,
       &upload_file.workspace_id,
       &upload_file.parent_dir,
       &upload_file.file_id,
-      &upload_file.content_type,
-    )
-    .await;
-  if let Err(err) = create_upload_resp_result.as_ref() {
-    handle_upload_error(user_service, &err, &upload_file.upload_id);
-  }
-  let create_upload_resp = create_upload_resp_result?;
-
-  // 2. update upload_id
-  let conn = user_service.sqlite_connection(user_service.user_id()?)?;
-  update_upload_file_upload_id(
-    conn,
-    &upload_file.workspace_id,
-    &upload_file.parent_dir,
-    &upload_file.file_id,
-    &create_upload_resp.upload_id,
-  )?;
+      &create_upload_resp.upload_id,
+    )?;
 
-  trace!(
-    "[File] {} update upload_id: {}",
-    upload_file.file_id,
-    create_upload_resp.upload_id
-  );
-  upload_file.upload_id = create_upload_resp.upload_id;
+    trace!(
+      "[File] {} update upload_id: {}",
+      upload_file.file_id,
+      create_upload_resp.upload_id
+    );
+    upload_file.upload_id = create_upload_resp.upload_id;
+  }
 
   // 3. start uploading parts
   info!(
@@ -592,9 +3869,50 @@ async fn start_upload(
   );
 
   let mut part_number = upload_offset + 1;
-  while let Some(chunk_result) = chunked_bytes.next_chunk().await {
+  let file_limiter = bandwidth.file_limiter(&upload_file.file_id);
+  // The finished and failed states are sent without throttling.
+  let mut progress_throttle = ProgressThrottle::new(
+    config.progress_min_interval,
+    config.progress_min_delta,
+    config.clock.clone(),
+  );
+  // The floor is emitted again right away, before the first part, for the subscribers that
+  // arrived after the upload was queued.
+  if let Some(progress_value) = floor_progress(config, upload_offset, total_parts) {
+    if progress_throttle.should_emit(progress_value) {
+      send_floor_progress(cloud_service, &global_notifier, upload_file, progress_value).await?;
+    }
+  }
+  let mut chunk_reader = ChunkReader::new(
+    chunked_bytes,
+    config.prefetch_depth,
+    bandwidth.in_flight_budget(),
+  );
+  while let Some(chunk_result) = chunk_reader.next_chunk().await {
+    if cancel_token.is_cancelled() {
+      info!("[File] {} upload cancelled", upload_file.file_id);
+      return Err(StorageError::Cancelled.into());
+    }
+
     match chunk_result {
       Ok(chunk_bytes) => {
+        // The open file can still be read after it was removed, check that it still exists
+        // before uploading the part.
+        if tokio::fs::metadata(&upload_file.local_file_path)
+          .await
+          .is_err()
+        {
+          return Err(
+            abort_upload_with_missing_file(
+              cloud_service,
+              user_service,
+              &upload_file,
+              &global_notifier,
+            )
+            .await,
+          );
+        }
+
         info!(
           "[File] {} uploading {}th part, size:{}KB",
           upload_file.file_id,
@@ -609,36 +3927,96 @@ async fn start_upload(
             &upload_file.file_id,
           )
           .await?;
-        // start uploading parts
-        match upload_part(
-          cloud_service,
-          user_service,
-          &upload_file.workspace_id,
-          &upload_file.parent_dir,
-          &upload_file.upload_id,
-          &upload_file.file_id,
-          part_number as i32,
-          chunk_bytes.to_vec(),
-        )
-        .await
-        {
+        // start uploading parts. A transient failure retries the part alone, up to
+        // [StorageManagerConfig::part_max_attempts] attempts, before failing the upload.
+        let part_size = chunk_bytes.len();
+        let mut attempt = 1;
+        let upload_part_result = loop {
+          bandwidth.acquire(&file_limiter, part_size as u64).await;
+          let started_at = config.clock.system_now();
+          let result = upload_part(
+            cloud_service,
+            user_service,
+            &upload_file.workspace_id,
+            &upload_file.parent_dir,
+            &upload_file.upload_id,
+            &upload_file.file_id,
+            part_number as i32,
+            chunk_bytes.to_vec(),
+            cancel_token,
+          )
+          .await;
+          part_timings.record(
+            &upload_file.file_id,
+            PartTiming {
+              part_number: part_number as i32,
+              size: part_size,
+              started_at,
+              finished_at: config.clock.system_now(),
+              succeeded: result.is_ok(),
+            },
+          );
+          match result {
+            Err(err) if err.should_retry_upload() && attempt < config.part_max_attempts => {
+              warn!(
+                "[File] {} part {} failed: {}, retry: {}",
+                upload_file.file_id, part_number, err, attempt
+              );
+              upload_log
+                .record(
+                  &upload_file.workspace_id,
+                  &upload_file.parent_dir,
+                  &upload_file.file_id,
+                  UploadLogEvent::PartRetry(part_number as i32),
+                )
+                .await;
+              let delay = retry_backoff(config.part_retry_delay, attempt);
+              tokio::select! {
+                _ = cancel_token.cancelled() => {
+                  break Err(FlowyError::from(StorageError::Cancelled));
+                },
+                _ = config.clock.sleep(delay) => {},
+              }
+              attempt += 1;
+            },
+            result => break result,
+          }
+        };
+        match upload_part_result {
           Ok(resp) => {
             trace!(
               "[File] {} part {} uploaded",
               upload_file.file_id,
               part_number
             );
-            let mut progress_value = (part_number as f64 / total_parts as f64).clamp(0.0, 1.0);
+            bandwidth.record_sent(part_size as u64);
+            upload_log
+              .record(
+                &upload_file.workspace_id,
+                &upload_file.parent_dir,
+                &upload_file.file_id,
+                UploadLogEvent::Part(resp.part_num),
+              )
+              .await;
+            let mut progress_value = (part_number as f64 / total_parts as f64)
+              .clamp(0.0, 1.0)
+              .max(config.progress_floor);
             // The 0.1 is reserved for the complete_upload progress
             if progress_value >= 0.9 {
               progress_value = 0.9;
             }
-            let progress =
-              FileProgress::new_progress(file_url, upload_file.file_id.clone(), progress_value);
-            trace!("[File] upload progress: {}", progress);
+            if progress_throttle.should_emit(progress_value) {
+              let progress =
+                FileProgress::new_progress(file_url, upload_file.file_id.clone(), progress_value)
+                  .with_workspace_id(upload_file.workspace_id.clone());
+              trace!("[File] upload progress: {}", progress);
 
-            if let Err(err) = global_notifier.send(progress) {
-              error!("[File] send global notifier failed: {}", err);
+              if let Err(err) = global_notifier
+                .send_upload(&upload_file_key(&upload_file), progress)
+                .await
+              {
+                error!("[File] send global notifier failed: {}", err);
+              }
             }
 
             // gather completed part
@@ -652,12 +4030,14 @@ async fn start_upload(
               "[File] {} failed to upload part: {}",
               upload_file.file_id, err
             );
-            handle_upload_error(user_service, &err, &upload_file.upload_id);
-            if let Err(err) = global_notifier.send(FileProgress::new_error(
-              file_url,
-              upload_file.file_id.clone(),
-              err.msg.clone(),
-            )) {
+            handle_upload_error(cloud_service, user_service, &err, &upload_file).await;
+            let progress =
+              FileProgress::new_error(file_url, upload_file.file_id.clone(), err.msg.clone())
+                .with_workspace_id(upload_file.workspace_id.clone());
+            if let Err(err) = global_notifier
+              .send_upload(&upload_file_key(&upload_file), progress)
+              .await
+            {
               error!("[File] send global notifier failed: {}", err);
             }
             return Err(err);
@@ -670,33 +4050,173 @@ async fn start_upload(
           "[File] {} failed to read chunk: {:?}",
           upload_file.file_id, e
         );
-        break;
+        return Err(
+          abort_upload_with_missing_file(
+            cloud_service,
+            user_service,
+            &upload_file,
+            &global_notifier,
+          )
+          .await,
+        );
       },
     }
   }
 
-  // mark it as completed
-  let complete_upload_result = complete_upload(
-    cloud_service,
-    user_service,
-    temp_storage,
-    &upload_file,
-    completed_parts,
-    &global_notifier,
-  )
-  .await;
-  if let Err(err) = complete_upload_result {
-    handle_upload_error(user_service, &err, &upload_file.upload_id);
-    return Err(err);
+  // The file was truncated while uploading, completing the upload would create a corrupted object.
+  if completed_parts.len() < total_parts {
+    error!(
+      "[File] {} only {} of {} parts uploaded",
+      upload_file.file_id,
+      completed_parts.len(),
+      total_parts
+    );
+    return Err(
+      abort_upload_with_missing_file(cloud_service, user_service, &upload_file, &global_notifier)
+        .await,
+    );
+  }
+
+  // mark it as completed
+  let complete_upload_result = complete_upload(
+    config,
+    cloud_service,
+    user_service,
+    temp_storage,
+    &upload_file,
+    completed_parts,
+    total_bytes,
+    started_at,
+    &global_notifier,
+  )
+  .await;
+  if let Err(err) = complete_upload_result {
+    handle_upload_error(cloud_service, user_service, &err, &upload_file).await;
+    return Err(err);
+  }
+
+  Ok(())
+}
+
+/// Returns the progress an upload starts at, see [StorageManagerConfig::progress_floor]. A
+/// resumed upload starts at the progress of its uploaded parts instead when above the floor, so
+/// that the bar doesn't go back. None when no floor is configured.
+fn floor_progress(
+  config: &StorageManagerConfig,
+  uploaded_parts: u64,
+  total_parts: usize,
+) -> Option<f64> {
+  if config.progress_floor <= 0.0 {
+    return None;
+  }
+  let progress_value =
+    (uploaded_parts as f64 / total_parts.max(1) as f64).max(config.progress_floor.min(0.9));
+  Some(progress_value)
+}
+
+async fn send_floor_progress(
+  cloud_service: &Arc<dyn StorageCloudService>,
+  global_notifier: &GlobalNotifier,
+  upload_file: &UploadFileTable,
+  progress_value: f64,
+) -> FlowyResult<()> {
+  let file_url = cloud_service
+    .get_object_url_v1(
+      &upload_file.workspace_id,
+      &upload_file.parent_dir,
+      &upload_file.file_id,
+    )
+    .await?;
+  let progress = FileProgress::new_progress(file_url, upload_file.file_id.clone(), progress_value)
+    .with_workspace_id(upload_file.workspace_id.clone());
+  if let Err(err) = global_notifier
+    .send_upload(&upload_file_key(upload_file), progress)
+    .await
+  {
+    error!("[File] send global notifier failed: {}", err);
+  }
+  Ok(())
+}
+
+/// Aborts the upload whose local file was removed or truncated while uploading. The upload record
+/// and its parts are deleted, and an error is sent to the progress subscribers.
+async fn abort_upload_with_missing_file(
+  cloud_service: &Arc<dyn StorageCloudService>,
+  user_service: &Arc<dyn StorageUserService>,
+  upload_file: &UploadFileTable,
+  global_notifier: &GlobalNotifier,
+) -> FlowyError {
+  let err = FlowyError::from(StorageError::FileMissing(
+    upload_file.local_file_path.clone(),
+  ));
+  error!("[File] {} abort upload: {}", upload_file.file_id, err);
+
+  match acquire_sqlite_connection(user_service).await {
+    Ok(conn) => {
+      if let Err(err) = delete_upload_file_by_file_id(
+        conn,
+        &upload_file.workspace_id,
+        &upload_file.parent_dir,
+        &upload_file.file_id,
+      ) {
+        error!("[File] delete upload file failed: {}", err);
+      }
+    },
+    Err(err) => error!("[File] delete upload file failed: {}", err),
+  }
+  abort_server_upload(cloud_service, upload_file).await;
+
+  if let Ok(file_url) = cloud_service
+    .get_object_url_v1(
+      &upload_file.workspace_id,
+      &upload_file.parent_dir,
+      &upload_file.file_id,
+    )
+    .await
+  {
+    let progress = FileProgress::new_error(file_url, upload_file.file_id.clone(), err.msg.clone())
+      .with_workspace_id(upload_file.workspace_id.clone());
+    if let Err(send_err) = global_notifier
+      .send_upload(&upload_file_key(upload_file), progress)
+      .await
+    {
+      error!("[File] send global notifier failed: {}", send_err);
+    }
+  }
+  err
+}
+
+/// Aborts the multipart upload of the record on the server, if one was created. Called whenever
+/// the uploaded parts are discarded, otherwise the abandoned upload keeps consuming the quota.
+async fn abort_server_upload(
+  cloud_service: &Arc<dyn StorageCloudService>,
+  upload_file: &UploadFileTable,
+) {
+  if upload_file.upload_id.is_empty() || !cloud_service.capabilities().abort_upload {
+    return;
   }
 
-  Ok(())
+  if let Err(err) = cloud_service
+    .abort_upload(
+      &upload_file.workspace_id,
+      &upload_file.parent_dir,
+      &upload_file.upload_id,
+      &upload_file.file_id,
+    )
+    .await
+  {
+    warn!(
+      "[File] abort upload {} failed: {}",
+      upload_file.upload_id, err
+    );
+  }
 }
 
-fn handle_upload_error(
+async fn handle_upload_error(
+  cloud_service: &Arc<dyn StorageCloudService>,
   user_service: &Arc<dyn StorageUserService>,
   err: &FlowyError,
-  upload_id: &str,
+  upload_file: &UploadFileTable,
 ) {
   if err.is_file_limit_exceeded() {
     make_notification(StorageNotification::FileStorageLimitExceeded)
@@ -704,29 +4224,40 @@ fn handle_upload_error(
       .send();
   }
 
+  if err.code == ErrorCode::ObjectAlreadyExists {
+    info!(
+      "[File] object already exists, drop upload:{}",
+      upload_file.file_id
+    );
+    delete_upload_record(user_service, upload_file).await;
+  }
+
   if err.is_single_file_limit_exceeded() {
-    info!("[File] file exceed limit:{}", upload_id);
-    if let Ok(user_id) = user_service.user_id() {
-      if let Ok(db_conn) = user_service.sqlite_connection(user_id) {
-        if let Err(err) = delete_upload_file(db_conn, upload_id) {
-          error!("[File] delete upload file:{} error:{}", upload_id, err);
-        }
-      }
-    }
+    info!("[File] file exceed limit:{}", upload_file.file_id);
+    // The upload_id is empty when creating the upload failed, so delete the record by its primary
+    // key.
+    delete_upload_record(user_service, upload_file).await;
 
+    abort_server_upload(cloud_service, upload_file).await;
     make_notification(StorageNotification::SingleFileLimitExceeded)
       .payload(err.clone())
       .send();
   }
 }
 
+#[allow(clippy::too_many_arguments)]
 #[instrument(level = "debug", skip_all, err)]
 async fn resume_upload(
+  config: &StorageManagerConfig,
   cloud_service: &Arc<dyn StorageCloudService>,
   user_service: &Arc<dyn StorageUserService>,
   temp_storage: &Arc<FileTempStorage>,
   upload_file: UploadFileTable,
   global_notifier: GlobalNotifier,
+  cancel_token: &CancellationToken,
+  bandwidth: &UploadBandwidth,
+  part_timings: &PartTimings,
+  upload_log: &UploadLog,
 ) -> FlowyResult<()> {
   trace!(
     "[File] resume upload for workspace: {}, parent_dir: {}, file_id: {}, local_file_path:{}",
@@ -737,11 +4268,16 @@ async fn resume_upload(
   );
 
   start_upload(
+    config,
     cloud_service,
     user_service,
     temp_storage,
     &upload_file,
     global_notifier,
+    cancel_token,
+    bandwidth,
+    part_timings,
+    upload_log,
   )
   .await?;
 
@@ -759,38 +4295,136 @@ async fn upload_part(
   file_id: &str,
   part_number: i32,
   body: Vec<u8>,
+  cancel_token: &CancellationToken,
 ) -> Result<UploadPartResponse, FlowyError> {
-  let resp = cloud_service
-    .upload_part(
-      workspace_id,
-      parent_dir,
-      upload_id,
-      file_id,
-      part_number,
-      body,
-    )
-    .await?;
+  // The part is always checksummed for the record, the backend only gets the checksum when it
+  // validates the parts with it.
+  let checksum_algorithm = cloud_service.capabilities().part_checksum;
+  let checksum = part_checksum(
+    checksum_algorithm.unwrap_or(ChecksumAlgorithm::Crc32),
+    &body,
+  );
+  let request = async {
+    match checksum_algorithm {
+      Some(_) => {
+        cloud_service
+          .upload_part_with_checksum(
+            workspace_id,
+            parent_dir,
+            upload_id,
+            file_id,
+            part_number,
+            body,
+            &checksum,
+          )
+          .await
+      },
+      None => {
+        cloud_service
+          .upload_part(
+            workspace_id,
+            parent_dir,
+            upload_id,
+            file_id,
+            part_number,
+            body,
+          )
+          .await
+      },
+    }
+  };
+  // Drop the in-flight request as soon as the upload is cancelled. The part is not recorded as
+  // uploaded in that case.
+  let resp = tokio::select! {
+    _ = cancel_token.cancelled() => return Err(FlowyError::from(StorageError::Cancelled)),
+    resp = request => resp?,
+  };
 
-  // save uploaded part to sqlite
-  let conn = user_service.sqlite_connection(user_service.user_id()?)?;
+  // Save the uploaded part to sqlite right away, the parts are never buffered in memory. After a
+  // crash, the resumed upload only sends again the parts that were in flight.
+  let conn = acquire_sqlite_connection(user_service).await?;
   insert_upload_part(
     conn,
     &UploadFilePartTable {
       upload_id: upload_id.to_string(),
       e_tag: resp.e_tag.clone(),
       part_num: resp.part_num,
+      checksum: checksum.to_string(),
     },
   )?;
 
   Ok(resp)
 }
 
+/// Compares the checksums of the sent parts with the checksums of the parts the server stored,
+/// see [StorageCloudService::part_checksums]. It's only done when the backend reports
+/// [flowy_storage_pub::cloud::StorageCapabilities::part_checksum], and skipped when the checksums
+/// can't be fetched. The parts sent without a checksum of the same algorithm aren't verified.
+async fn verify_part_checksums(
+  cloud_service: &Arc<dyn StorageCloudService>,
+  user_service: &Arc<dyn StorageUserService>,
+  upload_file: &UploadFileTable,
+) -> FlowyResult<()> {
+  let Some(algorithm) = cloud_service.capabilities().part_checksum else {
+    return Ok(());
+  };
+  let stored_checksums = match cloud_service
+    .part_checksums(
+      &upload_file.workspace_id,
+      &upload_file.parent_dir,
+      &upload_file.upload_id,
+      &upload_file.file_id,
+    )
+    .await
+  {
+    Ok(checksums) => checksums,
+    Err(err) => {
+      warn!(
+        "[File] skip verifying the part checksums of {}: {}",
+        upload_file.file_id, err
+      );
+      return Ok(());
+    },
+  };
+  let parts = {
+    let mut conn = acquire_sqlite_connection(user_service).await?;
+    select_upload_parts(&mut conn, &upload_file.upload_id)?
+  };
+  for part in parts {
+    let Ok(sent) = part.checksum.parse::<PartChecksum>() else {
+      continue;
+    };
+    if sent.algorithm != algorithm {
+      continue;
+    }
+    if let Some(stored) = stored_checksums.get(&part.part_num) {
+      if *stored != sent {
+        error!(
+          "[File] part {} of {} is corrupted, sent: {}, stored: {}",
+          part.part_num, upload_file.file_id, sent, stored
+        );
+        return Err(
+          StorageError::PartChecksumMismatch {
+            part_number: part.part_num,
+          }
+          .into(),
+        );
+      }
+    }
+  }
+  Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn complete_upload(
+  config: &StorageManagerConfig,
   cloud_service: &Arc<dyn StorageCloudService>,
   user_service: &Arc<dyn StorageUserService>,
   temp_storage: &Arc<FileTempStorage>,
   upload_file: &UploadFileTable,
   parts: Vec<CompletedPartRequest>,
+  total_bytes: u64,
+  started_at: Instant,
   global_notifier: &GlobalNotifier,
 ) -> Result<(), FlowyError> {
   let file_url = cloud_service
@@ -807,53 +4441,471 @@ async fn complete_upload(
     parts.len(),
     file_url
   );
-  match cloud_service
-    .complete_upload(
-      &upload_file.workspace_id,
-      &upload_file.parent_dir,
-      &upload_file.upload_id,
-      &upload_file.file_id,
-      parts,
-    )
-    .await
-  {
+  // The manifest is built before the parts are handed over to the cloud service. The temp file
+  // still exists at this point, its size is the size of the uploaded object.
+  let manifest = if config.upload_manifest {
+    match tokio::fs::metadata(&upload_file.local_file_path).await {
+      Ok(metadata) => Some(UploadManifest::new(
+        upload_file.workspace_id.clone(),
+        upload_file.parent_dir.clone(),
+        upload_file.file_id.clone(),
+        metadata.len(),
+        upload_file.chunk_size as u64,
+        &parts,
+        unix_timestamp(config.clock.system_now()),
+      )),
+      Err(err) => {
+        warn!(
+          "[File] skip the manifest of {}: {}",
+          upload_file.file_id, err
+        );
+        None
+      },
+    }
+  } else {
+    None
+  };
+  // A corrupted part fails like invalid parts, the upload restarts with new parts.
+  let result = match verify_part_checksums(cloud_service, user_service, upload_file).await {
+    Ok(_) => {
+      cloud_service
+        .complete_upload(
+          &upload_file.workspace_id,
+          &upload_file.parent_dir,
+          &upload_file.upload_id,
+          &upload_file.file_id,
+          parts,
+        )
+        .await
+    },
+    Err(err) => Err(err),
+  };
+  match result {
     Ok(_) => {
       info!("[File] completed upload file: {}", upload_file.file_id);
-      let progress = FileProgress::new_progress(file_url, upload_file.file_id.clone(), 1.0);
+      // Mark the record as completed before notifying, so that subscribers checking the record
+      // after receiving nothing won't miss the finish event.
+      let conn = acquire_sqlite_connection(user_service).await?;
+      update_upload_file_completed(conn, &upload_file.upload_id)?;
+      if let Some(manifest) = manifest {
+        save_upload_manifest(
+          cloud_service,
+          user_service,
+          &manifest,
+          config.upload_manifest_sidecar,
+        )
+        .await;
+      }
+
+      let progress = FileProgress::new_progress(file_url, upload_file.file_id.clone(), 1.0)
+        .with_workspace_id(upload_file.workspace_id.clone())
+        .with_summary(
+          total_bytes,
+          config.clock.now().saturating_duration_since(started_at),
+        );
       info!(
         "[File]: notify upload progress:{}, {}",
         upload_file.file_id, progress
       );
 
-      if let Err(err) = global_notifier.send(progress) {
+      // The temp file is handled before notifying, so that the subscribers find the local copy
+      // of the object once it's finished.
+      release_temp_file(config, user_service, temp_storage, upload_file).await;
+      if let Err(err) = global_notifier
+        .send_upload(&upload_file_key(upload_file), progress)
+        .await
+      {
         error!("[File] send global notifier failed: {}", err);
       }
-
-      let conn = user_service.sqlite_connection(user_service.user_id()?)?;
-      update_upload_file_completed(conn, &upload_file.upload_id)?;
-
-      if let Err(err) = temp_storage
-        .delete_temp_file(&upload_file.local_file_path)
+    },
+    Err(err) if !err.is_upload_parts_invalid() => {
+      // The parts are kept, the retry of the upload only completes it again.
+      warn!(
+        "[File] complete upload failed: {}, the parts are kept: {}",
+        upload_file.file_id, err
+      );
+      let progress =
+        FileProgress::new_error(file_url, upload_file.file_id.clone(), err.msg.clone())
+          .with_workspace_id(upload_file.workspace_id.clone())
+          .with_completion_pending();
+      if let Err(send_err) = global_notifier
+        .send_upload(&upload_file_key(upload_file), progress)
         .await
       {
-        trace!("[File] delete temp file failed: {}", err);
+        error!("[File] send global notifier failed: {}", send_err);
       }
+      return Err(err);
     },
     Err(err) => {
       error!("[File] complete upload failed: {}", err);
 
       let progress =
-        FileProgress::new_error(file_url, upload_file.file_id.clone(), err.msg.clone());
-      if let Err(send_err) = global_notifier.send(progress) {
+        FileProgress::new_error(file_url, upload_file.file_id.clone(), err.msg.clone())
+          .with_workspace_id(upload_file.workspace_id.clone());
+      if let Err(send_err) = global_notifier
+        .send_upload(&upload_file_key(upload_file), progress)
+        .await
+      {
         error!("[File] send global notifier failed: {}", send_err);
       }
 
-      let conn = user_service.sqlite_connection(user_service.user_id()?)?;
+      // The parts can't be reused, restart the upload from scratch with a new upload_id. The
+      // discarded upload is aborted.
+      let conn = acquire_sqlite_connection(user_service).await?;
       if let Err(err) = delete_all_upload_parts(conn, &upload_file.upload_id) {
         error!("[File] delete all upload parts failed: {}", err);
       }
+      let conn = acquire_sqlite_connection(user_service).await?;
+      update_upload_file_upload_id(
+        conn,
+        &upload_file.workspace_id,
+        &upload_file.parent_dir,
+        &upload_file.file_id,
+        "",
+      )?;
+      abort_server_upload(cloud_service, upload_file).await;
       return Err(err);
     },
   }
   Ok(())
 }
+
+/// Deletes or keeps the temp file of the completed upload, see
+/// [StorageManagerConfig::temp_file_policy]. The upload already succeeded, so failures are only
+/// logged, and the temp files that can't be deleted are left to the orphan sweep.
+async fn release_temp_file(
+  config: &StorageManagerConfig,
+  user_service: &Arc<dyn StorageUserService>,
+  temp_storage: &Arc<FileTempStorage>,
+  upload_file: &UploadFileTable,
+) {
+  match config.temp_file_policy {
+    TempFilePolicy::Delete => {
+      delete_completed_temp_file(config, temp_storage, &upload_file.local_file_path).await;
+    },
+    TempFilePolicy::KeepAsCache => {
+      trace!(
+        "[File] keep temp file as cache: {}",
+        upload_file.local_file_path
+      );
+    },
+    TempFilePolicy::MoveToCache => {
+      let cache_path = download_file_path(
+        config,
+        user_service,
+        &upload_file.workspace_id,
+        &upload_file.parent_dir,
+        &upload_file.file_id,
+      );
+      if let Err(err) = move_file(Path::new(&upload_file.local_file_path), &cache_path).await {
+        warn!(
+          "[File] move temp file {} to cache failed: {}",
+          upload_file.local_file_path, err
+        );
+        delete_completed_temp_file(config, temp_storage, &upload_file.local_file_path).await;
+        return;
+      }
+      let result = match acquire_sqlite_connection(user_service).await {
+        Ok(conn) => update_upload_file_local_path(
+          conn,
+          &upload_file.workspace_id,
+          &upload_file.parent_dir,
+          &upload_file.file_id,
+          &cache_path.to_string_lossy(),
+        ),
+        Err(err) => Err(err),
+      };
+      if let Err(err) = result {
+        warn!("[File] record cached file failed: {}", err);
+      }
+    },
+  }
+}
+
+/// Deletes the temp file of a completed upload. The failures are retried with a backoff, see
+/// [StorageManagerConfig::temp_delete_max_attempts]. A temp file that still can't be deleted is
+/// marked as undeleted, for [StorageManager::repair_storage] to delete it later.
+async fn delete_completed_temp_file(
+  config: &StorageManagerConfig,
+  temp_storage: &FileTempStorage,
+  local_file_path: &str,
+) {
+  let mut attempt = 1;
+  loop {
+    match temp_storage.delete_temp_file(local_file_path).await {
+      Ok(()) => return,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+      Err(err) if attempt < config.temp_delete_max_attempts => {
+        trace!(
+          "[File] delete temp file {} failed: {}, retry: {}",
+          local_file_path,
+          err,
+          attempt
+        );
+        config
+          .clock
+          .sleep(retry_backoff(config.temp_delete_retry_delay, attempt))
+          .await;
+        attempt += 1;
+      },
+      Err(err) => {
+        warn!(
+          "[File] delete temp file {} failed: {}, left to the orphan sweep",
+          local_file_path, err
+        );
+        temp_storage.mark_undeleted(PathBuf::from(local_file_path));
+        return;
+      },
+    }
+  }
+}
+
+/// Moves the file, copying it when it can't be renamed, e.g. across file systems.
+async fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+  if let Some(parent) = to.parent() {
+    tokio::fs::create_dir_all(parent).await?;
+  }
+  if tokio::fs::rename(from, to).await.is_ok() {
+    return Ok(());
+  }
+  tokio::fs::copy(from, to).await?;
+  tokio::fs::remove_file(from).await
+}
+
+/// Copies the file the upload was made from to its missing temp file, see
+/// [UploadFileTable::source_file_path]. The copy is only kept when the file still has the content
+/// the upload was created with. Returns true when the temp file was restored.
+async fn restore_temp_file(
+  temp_storage: &FileTempStorage,
+  upload_file: &UploadFileTable,
+  cancel_token: &CancellationToken,
+) -> bool {
+  let source_path = Path::new(&upload_file.source_file_path);
+  if upload_file.source_file_path.is_empty() || !source_path.exists() {
+    return false;
+  }
+  // The id of the file is derived either from its metadata or from its content.
+  let unchanged = match tokio::fs::metadata(source_path).await {
+    Ok(metadata) if file_id_from_metadata(source_path, &metadata) == upload_file.file_id => true,
+    Ok(_) => verify_file_id(source_path, &upload_file.file_id)
+      .await
+      .unwrap_or(false),
+    Err(_) => false,
+  };
+  if !unchanged {
+    warn!(
+      "[File] source file changed since the upload was created: {}",
+      upload_file.source_file_path
+    );
+    return false;
+  }
+  let temp_file_path = Path::new(&upload_file.local_file_path);
+  match temp_storage
+    .restore_temp_file(source_path, temp_file_path, cancel_token)
+    .await
+  {
+    Ok(_) => {
+      info!(
+        "[File] restored temp file {} from {}",
+        upload_file.local_file_path, upload_file.source_file_path
+      );
+      true
+    },
+    Err(err) => {
+      error!(
+        "[File] restore temp file {} failed: {}",
+        upload_file.local_file_path, err
+      );
+      false
+    },
+  }
+}
+
+/// Drops the record of the location the object of the url is deleted from, `parent_dir` or the
+/// parent dir of the url. The links share the url and the upload id of the object, see
+/// [StorageManagerConfig::link_duplicate_uploads]. Returns true when other records still reference
+/// the object, it must then be kept.
+async fn release_object_link(
+  cloud_service: &Arc<dyn StorageCloudService>,
+  user_service: &Arc<dyn StorageUserService>,
+  url: &str,
+  parent_dir: Option<&str>,
+) -> FlowyResult<bool> {
+  let Some((workspace_id, object_parent_dir, file_id)) = parse_object_url(cloud_service, url).await
+  else {
+    return Ok(false);
+  };
+  let parent_dir = parent_dir
+    .map(lookup_parent_dir)
+    .unwrap_or_else(|| object_parent_dir.clone());
+  let mut conn = acquire_sqlite_connection(user_service).await?;
+  let Some(record) = select_upload_file(&mut conn, &workspace_id, &parent_dir, &file_id)? else {
+    // The location isn't tracked, the object is kept while the upload that created it is.
+    return Ok(
+      select_upload_file(&mut conn, &workspace_id, &object_parent_dir, &file_id)?.is_some(),
+    );
+  };
+  let references = if record.upload_id.is_empty() {
+    1
+  } else {
+    count_upload_files_by_upload_id(&mut conn, &record.upload_id)?
+  };
+  delete_upload_file_by_file_id(conn, &workspace_id, &parent_dir, &file_id)?;
+  Ok(references > 1)
+}
+
+/// Returns the path of the object in the download cache, placed by
+/// [StorageManagerConfig::download_path_format].
+fn download_file_path(
+  config: &StorageManagerConfig,
+  user_service: &Arc<dyn StorageUserService>,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+) -> PathBuf {
+  let download_dir = PathBuf::from(format!(
+    "{}/downloads",
+    user_service.get_application_root_dir()
+  ));
+  download_dir.join(
+    config
+      .download_path_format
+      .relative_path(workspace_id, parent_dir, file_id),
+  )
+}
+
+/// Stores the manifest of a completed upload and, when `upload_sidecar` is true, uploads it next
+/// to the object. The upload already succeeded, so failures are only logged.
+async fn save_upload_manifest(
+  cloud_service: &Arc<dyn StorageCloudService>,
+  user_service: &Arc<dyn StorageUserService>,
+  manifest: &UploadManifest,
+  upload_sidecar: bool,
+) {
+  let json = match serde_json::to_string(manifest) {
+    Ok(json) => json,
+    Err(err) => {
+      error!("[File] serialize manifest failed: {}", err);
+      return;
+    },
+  };
+
+  let record = UploadFileManifestTable {
+    workspace_id: manifest.workspace_id.clone(),
+    parent_dir: manifest.parent_dir.clone(),
+    file_id: manifest.file_id.clone(),
+    manifest: json.clone(),
+    created_at: manifest.created_at,
+  };
+  match acquire_sqlite_connection(user_service).await {
+    Ok(conn) => {
+      if let Err(err) = upsert_upload_manifest(conn, &record) {
+        error!("[File] save manifest failed: {}", err);
+      }
+    },
+    Err(err) => error!("[File] save manifest failed: {}", err),
+  }
+
+  if upload_sidecar {
+    let sidecar_id = manifest_sidecar_id(&manifest.file_id);
+    let result = match cloud_service
+      .get_object_url_v1(&manifest.workspace_id, &manifest.parent_dir, &sidecar_id)
+      .await
+    {
+      Ok(url) => {
+        let value = ObjectValue {
+          raw: json.into(),
+          mime: mime_guess::mime::APPLICATION_JSON,
+        };
+        cloud_service.put_object(url, value).await
+      },
+      Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+      warn!(
+        "[File] upload manifest of {} failed: {}",
+        manifest.file_id, err
+      );
+    }
+  }
+}
+
+fn notify_storage_write_access(is_enabled: bool) {
+  make_notification(StorageNotification::StorageWriteAccessChanged)
+    .payload(StorageWriteAccessPB { is_enabled })
+    .send();
+}
+
+fn notify_duplicate_upload(url: &str, file_id: &str, state: DuplicateUploadStatePB) {
+  make_notification(StorageNotification::UploadSkippedAsDuplicate)
+    .payload(DuplicateUploadPB {
+      url: url.to_string(),
+      file_id: file_id.to_string(),
+      state,
+    })
+    .send();
+}
+
+/// Returns a receiver that yields the [FileUploadState::Finished] state of the file.
+fn finished_receiver(file_id: &str) -> FileProgressReceiver {
+  let (tx, rx) = broadcast::channel(1);
+  let _ = tx.send(FileUploadState::Finished {
+    file_id: file_id.to_string(),
+    total_bytes: None,
+    duration: None,
+  });
+  FileProgressReceiver {
+    rx,
+    file_id: file_id.to_string(),
+  }
+}
+
+/// Deletes the record of the upload by its primary key, along with its parts. The upload is dropped
+/// anyway, so a failure is only logged.
+async fn delete_upload_record(
+  user_service: &Arc<dyn StorageUserService>,
+  upload_file: &UploadFileTable,
+) {
+  let result = async {
+    let conn = acquire_sqlite_connection(user_service).await?;
+    delete_upload_file_by_file_id(
+      conn,
+      &upload_file.workspace_id,
+      &upload_file.parent_dir,
+      &upload_file.file_id,
+    )
+  };
+  if let Err(err) = result.await {
+    error!(
+      "[File] delete upload file:{} error:{}",
+      upload_file.file_id, err
+    );
+  }
+}
+
+/// Acquires a sqlite connection for the current user.
+///
+/// Under heavy concurrency the connection pool can be exhausted for a short time. Instead of
+/// failing the caller on the first attempt, the acquisition is retried with an exponential backoff.
+pub(crate) async fn acquire_sqlite_connection(
+  user_service: &Arc<dyn StorageUserService>,
+) -> FlowyResult<DBConnection> {
+  let uid = user_service.user_id()?;
+  let mut attempt = 0;
+  loop {
+    match user_service.sqlite_connection(uid) {
+      Ok(conn) => return Ok(conn),
+      Err(err) if attempt < SQLITE_CONNECTION_MAX_RETRIES => {
+        attempt += 1;
+        warn!(
+          "[File] acquire sqlite connection failed: {}, retry: {}",
+          err, attempt
+        );
+        // The pool frees up in real time, so this backoff doesn't go through the clock of the
+        // config.
+        tokio::time::sleep(Duration::from_millis(100 * 2u64.pow(attempt))).await;
+      },
+      Err(err) => return Err(err),
+    }
+  }
+}
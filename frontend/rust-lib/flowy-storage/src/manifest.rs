@@ -0,0 +1,78 @@
+use flowy_storage_pub::storage::CompletedPartRequest;
+use serde::{Deserialize, Serialize};
+
+/// The version of the [UploadManifest] format.
+pub const UPLOAD_MANIFEST_VERSION: u32 = 1;
+
+/// [UploadManifest] records what was sent for a completed multipart upload. It's used to verify
+/// later that the object on the server matches the local file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadManifest {
+  pub version: u32,
+  pub workspace_id: String,
+  pub parent_dir: String,
+  /// The content hash of the whole file.
+  pub file_id: String,
+  pub total_size: u64,
+  pub chunk_size: u64,
+  pub parts: Vec<ManifestPart>,
+  pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestPart {
+  pub part_number: i32,
+  pub size: u64,
+  pub e_tag: String,
+}
+
+impl UploadManifest {
+  /// Builds the manifest from the completed parts. Every part except the last one is
+  /// `chunk_size` bytes, the last one holds the remainder of the file.
+  pub fn new(
+    workspace_id: String,
+    parent_dir: String,
+    file_id: String,
+    total_size: u64,
+    chunk_size: u64,
+    completed_parts: &[CompletedPartRequest],
+    created_at: i64,
+  ) -> Self {
+    let mut parts = completed_parts
+      .iter()
+      .map(|part| ManifestPart {
+        part_number: part.part_number,
+        size: 0,
+        e_tag: part.e_tag.clone(),
+      })
+      .collect::<Vec<_>>();
+    parts.sort_by_key(|part| part.part_number);
+
+    let mut remaining = total_size;
+    for part in parts.iter_mut() {
+      part.size = remaining.min(chunk_size);
+      remaining -= part.size;
+    }
+
+    Self {
+      version: UPLOAD_MANIFEST_VERSION,
+      workspace_id,
+      parent_dir,
+      file_id,
+      total_size,
+      chunk_size,
+      parts,
+      created_at,
+    }
+  }
+
+  /// The total size of the parts, which equals `total_size` when every part was uploaded.
+  pub fn parts_size(&self) -> u64 {
+    self.parts.iter().map(|part| part.size).sum()
+  }
+}
+
+/// The id of the sidecar object holding the manifest of the file.
+pub fn manifest_sidecar_id(file_id: &str) -> String {
+  format!("{}.manifest.json", file_id)
+}
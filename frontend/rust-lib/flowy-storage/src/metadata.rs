@@ -0,0 +1,53 @@
+use flowy_error::{FlowyError, FlowyResult};
+use std::collections::HashMap;
+
+/// The maximum size of the metadata of an object, the sum of the lengths of its keys and values.
+/// The storage backends send the metadata as http headers, which they limit to a few kilobytes.
+pub const MAX_METADATA_SIZE: usize = 2 * 1024;
+
+/// Checks that the metadata can be sent as http headers: the keys are non-empty tokens as defined
+/// by RFC 9110, the values are visible ascii characters or spaces, and the whole metadata fits in
+/// [MAX_METADATA_SIZE].
+pub fn validate_metadata(metadata: &HashMap<String, String>) -> FlowyResult<()> {
+  let mut size = 0;
+  for (key, value) in metadata {
+    if key.is_empty() || !key.chars().all(is_token_char) {
+      return Err(
+        FlowyError::invalid_data().with_context(format!("invalid metadata key: {}", key)),
+      );
+    }
+    if !value.chars().all(|c| c == ' ' || c.is_ascii_graphic()) {
+      return Err(
+        FlowyError::invalid_data().with_context(format!("invalid metadata value of: {}", key)),
+      );
+    }
+    size += key.len() + value.len();
+  }
+  if size > MAX_METADATA_SIZE {
+    return Err(FlowyError::invalid_data().with_context(format!(
+      "metadata of {} bytes exceeds {} bytes",
+      size, MAX_METADATA_SIZE
+    )));
+  }
+  Ok(())
+}
+
+fn is_token_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+/// Serializes the metadata for the upload record, an empty metadata is stored as an empty string.
+pub fn metadata_to_record(metadata: &HashMap<String, String>) -> FlowyResult<String> {
+  if metadata.is_empty() {
+    return Ok(String::new());
+  }
+  Ok(serde_json::to_string(metadata)?)
+}
+
+/// Deserializes the metadata of the upload record, see [metadata_to_record].
+pub fn metadata_from_record(metadata: &str) -> FlowyResult<HashMap<String, String>> {
+  if metadata.is_empty() {
+    return Ok(HashMap::new());
+  }
+  Ok(serde_json::from_str(metadata)?)
+}
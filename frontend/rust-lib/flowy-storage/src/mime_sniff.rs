@@ -0,0 +1,68 @@
+use mime_guess::mime::Mime;
+
+/// The number of leading bytes of a file that [sniff_mime] looks at.
+pub const SNIFF_LEN: usize = 512;
+
+/// The signatures of the common file types, as (offset, magic bytes, mime).
+const SIGNATURES: &[(usize, &[u8], &str)] = &[
+  (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+  (0, b"\xff\xd8\xff", "image/jpeg"),
+  (0, b"GIF87a", "image/gif"),
+  (0, b"GIF89a", "image/gif"),
+  (0, b"BM", "image/bmp"),
+  (0, b"%PDF-", "application/pdf"),
+  (0, b"PK\x03\x04", "application/zip"),
+  (0, b"\x1f\x8b", "application/gzip"),
+  (0, b"ID3", "audio/mpeg"),
+  (0, b"OggS", "audio/ogg"),
+  (0, b"fLaC", "audio/flac"),
+  (4, b"ftyp", "video/mp4"),
+  (0, b"\x1a\x45\xdf\xa3", "video/webm"),
+];
+
+/// Guesses the mime of a file from its leading bytes. Returns `None` when the content matches no
+/// known signature.
+pub fn sniff_mime(bytes: &[u8]) -> Option<Mime> {
+  if let Some(mime) = sniff_riff(bytes) {
+    return Some(mime);
+  }
+  SIGNATURES
+    .iter()
+    .find(|(offset, magic, _)| bytes.get(*offset..*offset + magic.len()) == Some(*magic))
+    .and_then(|(_, _, mime)| mime.parse().ok())
+}
+
+/// Returns true if the content type is a valid media type: a type and a subtype that are
+/// restricted names as defined by RFC 6838, optionally followed by parameters.
+pub fn is_valid_content_type(content_type: &str) -> bool {
+  match content_type.parse::<Mime>() {
+    Ok(mime) => {
+      is_restricted_name(mime.type_().as_str())
+        && is_restricted_name(mime.subtype().as_str())
+        && mime
+          .suffix()
+          .map_or(true, |suffix| is_restricted_name(suffix.as_str()))
+    },
+    Err(_) => false,
+  }
+}
+
+fn is_restricted_name(name: &str) -> bool {
+  let mut chars = name.chars();
+  name.len() <= 127
+    && chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+    && chars.all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+}
+
+/// The RIFF containers carry their type after the size of the chunk.
+fn sniff_riff(bytes: &[u8]) -> Option<Mime> {
+  if !bytes.starts_with(b"RIFF") {
+    return None;
+  }
+  match bytes.get(8..12)? {
+    b"WEBP" => "image/webp".parse().ok(),
+    b"WAVE" => "audio/wav".parse().ok(),
+    b"AVI " => "video/x-msvideo".parse().ok(),
+    _ => None,
+  }
+}
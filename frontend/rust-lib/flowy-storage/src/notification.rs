@@ -1,14 +1,29 @@
+use crate::clock::Clock;
+use crate::entities::{UploadFailuresPB, UploadFailuresStatePB};
+use crate::spawner::Spawner;
 use flowy_derive::ProtoBuf_Enum;
 use flowy_notification::NotificationBuilder;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 const OBSERVABLE_SOURCE: &str = "storage";
 
 #[derive(ProtoBuf_Enum, Debug, Default)]
-pub(crate) enum StorageNotification {
+pub enum StorageNotification {
   #[default]
   FileStorageLimitExceeded = 0,
 
   SingleFileLimitExceeded = 1,
+
+  UploadsReconciled = 2,
+
+  StorageWriteAccessChanged = 3,
+
+  UploadPauseReasonsChanged = 4,
+
+  UploadSkippedAsDuplicate = 5,
+
+  UploadFailuresChanged = 6,
 }
 
 impl std::convert::From<StorageNotification> for i32 {
@@ -21,3 +36,112 @@ impl std::convert::From<StorageNotification> for i32 {
 pub(crate) fn make_notification(ty: StorageNotification) -> NotificationBuilder {
   NotificationBuilder::new("appflowy_file_storage_notification", ty, OBSERVABLE_SOURCE)
 }
+
+/// Sends the [StorageNotification::UploadFailuresChanged] notifications. When many uploads fail
+/// in quick succession, e.g. while the network is down, the first failure is notified right away
+/// and the ones that follow within the window are coalesced into a single notification. The
+/// first success after the failures notifies the recovery.
+#[derive(Clone)]
+pub(crate) struct UploadFailureNotifier {
+  window: Option<Duration>,
+  clock: Arc<dyn Clock>,
+  spawner: Arc<dyn Spawner>,
+  state: Arc<Mutex<UploadFailureState>>,
+}
+
+#[derive(Default)]
+struct UploadFailureState {
+  /// Whether the uploads are failing, until one of them succeeds.
+  failing: bool,
+  /// The failures not notified yet, sent when the window elapses.
+  pending: usize,
+  workspace_id: String,
+  last_error: String,
+  /// Whether a send of the pending failures is scheduled.
+  flush_scheduled: bool,
+}
+
+impl UploadFailureNotifier {
+  /// A `None` window notifies every failure.
+  pub(crate) fn new(
+    window: Option<Duration>,
+    clock: Arc<dyn Clock>,
+    spawner: Arc<dyn Spawner>,
+  ) -> Self {
+    Self {
+      window,
+      clock,
+      spawner,
+      state: Default::default(),
+    }
+  }
+
+  pub(crate) fn failed(&self, workspace_id: &str, error: &str) {
+    let Some(window) = self.window else {
+      send_failures(workspace_id, 1, error);
+      return;
+    };
+    let mut state = self.state.lock().unwrap();
+    state.workspace_id = workspace_id.to_string();
+    state.last_error = error.to_string();
+    if state.failing {
+      state.pending += 1;
+    } else {
+      state.failing = true;
+      send_failures(workspace_id, 1, error);
+    }
+    if !state.flush_scheduled {
+      state.flush_scheduled = true;
+      let sleep = self.clock.sleep(window);
+      let cloned_state = self.state.clone();
+      self.spawner.spawn(Box::pin(async move {
+        sleep.await;
+        let mut state = cloned_state.lock().unwrap();
+        state.flush_scheduled = false;
+        state.flush();
+      }));
+    }
+  }
+
+  pub(crate) fn succeeded(&self) {
+    let mut state = self.state.lock().unwrap();
+    if !state.failing {
+      return;
+    }
+    state.flush();
+    state.failing = false;
+    make_notification(StorageNotification::UploadFailuresChanged)
+      .payload(UploadFailuresPB {
+        workspace_id: state.workspace_id.clone(),
+        state: UploadFailuresStatePB::Recovered,
+        failed_count: 0,
+        message: String::new(),
+      })
+      .send();
+  }
+}
+
+impl UploadFailureState {
+  fn flush(&mut self) {
+    if self.pending > 0 {
+      send_failures(&self.workspace_id, self.pending, &self.last_error);
+      self.pending = 0;
+    }
+  }
+}
+
+fn send_failures(workspace_id: &str, failed_count: usize, error: &str) {
+  let message = if failed_count == 1 {
+    format!("1 upload failed: {}", error)
+  } else {
+    format!("{} uploads failed: {}", failed_count, error)
+  };
+  make_notification(StorageNotification::UploadFailuresChanged)
+    .payload(UploadFailuresPB {
+      workspace_id: workspace_id.to_string(),
+      state: UploadFailuresStatePB::Failed,
+      failed_count: failed_count as i64,
+      message,
+    })
+    .send();
+}
@@ -0,0 +1,28 @@
+use crate::error::StorageError;
+
+/// Returns the canonical form of the parent dir of an upload. The parent dir keys the records and
+/// the object urls, so the forms of the same dir must map to the same string: the backslashes are
+/// turned into slashes, and the empty and `.` segments, including the leading and trailing
+/// slashes, are dropped. `a/b`, `a//b/`, `/a/./b` and `a\b` are all `a/b`.
+///
+/// A dir made of no segment is [StorageError::EmptyParentDir]. A `..` segment or a control
+/// character is [StorageError::InvalidParentDir], they could address another dir.
+pub fn canonical_parent_dir(parent_dir: &str) -> Result<String, StorageError> {
+  if parent_dir.chars().any(char::is_control) {
+    return Err(StorageError::InvalidParentDir(
+      parent_dir.escape_debug().to_string(),
+    ));
+  }
+  let mut segments = vec![];
+  for segment in parent_dir.split(['/', '\\']) {
+    match segment {
+      "" | "." => {},
+      ".." => return Err(StorageError::InvalidParentDir(parent_dir.to_string())),
+      segment => segments.push(segment),
+    }
+  }
+  if segments.is_empty() {
+    return Err(StorageError::EmptyParentDir);
+  }
+  Ok(segments.join("/"))
+}
@@ -0,0 +1,93 @@
+use std::fmt::{Debug, Formatter};
+use std::ops::{BitOr, BitOrAssign};
+
+/// [PauseReasons] is the set of reasons that keep the uploads from making progress. An upload only
+/// proceeds when none of them applies.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PauseReasons(u8);
+
+impl PauseReasons {
+  pub const NONE: PauseReasons = PauseReasons(0);
+  /// The network is unreachable, see [crate::manager::StorageManager::update_network_reachable].
+  pub const NETWORK_UNREACHABLE: PauseReasons = PauseReasons(1);
+  /// The storage write access is disabled, either explicitly or because the storage quota is
+  /// exceeded.
+  pub const STORAGE_WRITE_DISABLED: PauseReasons = PauseReasons(1 << 1);
+  /// The uploads of the workspace are paused.
+  pub const WORKSPACE_PAUSED: PauseReasons = PauseReasons(1 << 2);
+  /// The upload of the file is paused.
+  pub const FILE_PAUSED: PauseReasons = PauseReasons(1 << 3);
+  /// The connection is metered, only the uploads started by the user proceed, see
+  /// [crate::manager::StorageManager::set_metered].
+  pub const METERED: PauseReasons = PauseReasons(1 << 4);
+
+  const ALL: [(PauseReasons, &'static str); 5] = [
+    (Self::NETWORK_UNREACHABLE, "NETWORK_UNREACHABLE"),
+    (Self::STORAGE_WRITE_DISABLED, "STORAGE_WRITE_DISABLED"),
+    (Self::WORKSPACE_PAUSED, "WORKSPACE_PAUSED"),
+    (Self::FILE_PAUSED, "FILE_PAUSED"),
+    (Self::METERED, "METERED"),
+  ];
+
+  pub fn bits(&self) -> u8 {
+    self.0
+  }
+
+  /// Builds the reasons from their bits, the unknown bits are dropped.
+  pub fn from_bits_truncate(bits: u8) -> Self {
+    let known = Self::ALL.iter().fold(0, |acc, (reason, _)| acc | reason.0);
+    Self(bits & known)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0 == 0
+  }
+
+  pub fn contains(&self, other: PauseReasons) -> bool {
+    self.0 & other.0 == other.0
+  }
+
+  pub fn insert(&mut self, other: PauseReasons) {
+    self.0 |= other.0;
+  }
+
+  pub fn remove(&mut self, other: PauseReasons) {
+    self.0 &= !other.0;
+  }
+
+  pub fn set(&mut self, other: PauseReasons, value: bool) {
+    if value {
+      self.insert(other);
+    } else {
+      self.remove(other);
+    }
+  }
+}
+
+impl BitOr for PauseReasons {
+  type Output = PauseReasons;
+
+  fn bitor(self, rhs: Self) -> Self::Output {
+    PauseReasons(self.0 | rhs.0)
+  }
+}
+
+impl BitOrAssign for PauseReasons {
+  fn bitor_assign(&mut self, rhs: Self) {
+    self.insert(rhs);
+  }
+}
+
+impl Debug for PauseReasons {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    if self.is_empty() {
+      return write!(f, "PauseReasons(NONE)");
+    }
+    let names = Self::ALL
+      .iter()
+      .filter(|(reason, _)| self.contains(*reason))
+      .map(|(_, name)| *name)
+      .collect::<Vec<_>>();
+    write!(f, "PauseReasons({})", names.join(" | "))
+  }
+}
@@ -0,0 +1,201 @@
+use crate::clock::Clock;
+use dashmap::DashMap;
+use flowy_storage_pub::storage::{FileProgress, FileUploadState, ProgressNotifier};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::SendError;
+
+/// [ProgressBroadcaster] broadcasts the [FileProgress] of the transfers, and keeps the most recent
+/// ones, so that a consumer attaching mid-session can replay the recent activity.
+pub(crate) struct ProgressBroadcaster {
+  tx: broadcast::Sender<FileProgress>,
+  history: Mutex<VecDeque<FileProgress>>,
+  history_size: usize,
+  /// The seq of the last broadcast progress, see [FileProgress::seq].
+  last_seq: AtomicU64,
+  /// The per-file notifiers driven by [Self::send_upload]. `None` when a relay forwards the
+  /// progress of the broadcast to the per-file notifiers instead.
+  file_notifiers: Option<Weak<DashMap<String, ProgressNotifier>>>,
+}
+
+impl ProgressBroadcaster {
+  pub(crate) fn new(capacity: usize, history_size: usize) -> Self {
+    let (tx, _) = broadcast::channel(capacity);
+    Self {
+      tx,
+      history: Mutex::new(VecDeque::with_capacity(history_size)),
+      history_size,
+      last_seq: AtomicU64::new(0),
+      file_notifiers: None,
+    }
+  }
+
+  /// Makes [Self::send_upload] notify the per-file notifiers directly.
+  pub(crate) fn with_file_notifiers(
+    mut self,
+    file_notifiers: Weak<DashMap<String, ProgressNotifier>>,
+  ) -> Self {
+    self.file_notifiers = Some(file_notifiers);
+    self
+  }
+
+  pub(crate) fn send(&self, progress: FileProgress) -> Result<usize, SendError<FileProgress>> {
+    self.send_with_seq(progress).1
+  }
+
+  /// Assigns the next seq to the progress and broadcasts it. Returns the assigned seq along with
+  /// the result of the broadcast.
+  fn send_with_seq(
+    &self,
+    mut progress: FileProgress,
+  ) -> (u64, Result<usize, SendError<FileProgress>>) {
+    let mut history = self.history.lock().unwrap();
+    // Assigned under the lock, so that the progress is broadcast in the order of the seqs.
+    let seq = self.last_seq.fetch_add(1, Ordering::Relaxed) + 1;
+    progress.seq = seq;
+    if self.history_size > 0 {
+      if history.len() == self.history_size {
+        history.pop_front();
+      }
+      history.push_back(progress.clone());
+    }
+    (seq, self.tx.send(progress))
+  }
+
+  /// Broadcasts the progress of an upload, and notifies the per-file notifier stored under `key`
+  /// when the notifiers are driven directly. The per-file notifier is notified after the
+  /// broadcast, and ignores a progress older than the last one it was notified, so both see the
+  /// progress of a file in the same order even when two progress are sent concurrently.
+  pub(crate) async fn send_upload(
+    &self,
+    key: &str,
+    progress: FileProgress,
+  ) -> Result<usize, SendError<FileProgress>> {
+    let state = upload_state(&progress);
+    let (seq, result) = self.send_with_seq(progress);
+    if let Some(notifiers) = self.file_notifiers.as_ref().and_then(Weak::upgrade) {
+      if let Some(mut notifier) = notifiers.get_mut(key) {
+        notifier.notify_in_order(seq, state).await;
+      }
+    }
+    result
+  }
+
+  pub(crate) fn subscribe(&self) -> broadcast::Receiver<FileProgress> {
+    self.tx.subscribe()
+  }
+
+  /// Returns the recent progress, oldest first, along with a receiver of the following progress.
+  ///
+  /// The snapshot and the subscription are taken under the same lock as [Self::send], so each
+  /// progress is either in the history or received by the receiver, never both or neither.
+  pub(crate) fn subscribe_with_history(
+    &self,
+  ) -> (Vec<FileProgress>, broadcast::Receiver<FileProgress>) {
+    let history = self.history.lock().unwrap();
+    (history.iter().cloned().collect(), self.tx.subscribe())
+  }
+}
+
+/// [ProgressThrottle] paces the progress updates of an upload. An update is emitted when
+/// `min_interval` elapsed since the last emitted one, or when the progress moved by at least
+/// `min_delta`. The first update is always emitted.
+pub(crate) struct ProgressThrottle {
+  min_interval: Duration,
+  min_delta: Option<f64>,
+  last_emitted: Option<(Instant, f64)>,
+  clock: Arc<dyn Clock>,
+}
+
+impl ProgressThrottle {
+  pub(crate) fn new(min_interval: Duration, min_delta: Option<f64>, clock: Arc<dyn Clock>) -> Self {
+    Self {
+      min_interval,
+      min_delta,
+      last_emitted: None,
+      clock,
+    }
+  }
+
+  /// Returns true if the progress should be emitted, in which case it's recorded as the last
+  /// emitted one.
+  pub(crate) fn should_emit(&mut self, progress: f64) -> bool {
+    let now = self.clock.now();
+    let should_emit = match self.last_emitted {
+      None => true,
+      Some((emitted_at, emitted_progress)) => {
+        now.duration_since(emitted_at) >= self.min_interval
+          || self
+            .min_delta
+            .is_some_and(|min_delta| progress - emitted_progress >= min_delta)
+      },
+    };
+    if should_emit {
+      self.last_emitted = Some((now, progress));
+    }
+    should_emit
+  }
+}
+
+/// The state of the per-file notifiers for the progress of an upload.
+pub(crate) fn upload_state(progress: &FileProgress) -> FileUploadState {
+  if let Some(reasons) = progress.paused_reasons {
+    FileUploadState::Paused { reasons }
+  } else if progress.completion_pending {
+    FileUploadState::CompletionPending {
+      error: progress.error.clone().unwrap_or_default(),
+    }
+  } else if progress.progress >= 1.0 {
+    FileUploadState::Finished {
+      file_id: progress.file_id.clone(),
+      total_bytes: progress.total_bytes,
+      duration: progress.duration_ms.map(Duration::from_millis),
+    }
+  } else {
+    FileUploadState::Uploading {
+      progress: progress.progress,
+    }
+  }
+}
+
+/// The progress of a state of the per-file notifiers, the inverse of [upload_state]. The states
+/// without a progress carry the last one, so that the progress never goes backward. `None` for
+/// the states before the upload started.
+pub(crate) fn state_progress(
+  file_url: &str,
+  file_id: &str,
+  state: FileUploadState,
+  last_progress: f64,
+) -> Option<FileProgress> {
+  let progress = match state {
+    FileUploadState::NotStarted | FileUploadState::Queued => return None,
+    FileUploadState::Uploading { progress } => {
+      FileProgress::new_progress(file_url.to_string(), file_id.to_string(), progress)
+    },
+    FileUploadState::Paused { reasons } => {
+      FileProgress::new_progress(file_url.to_string(), file_id.to_string(), last_progress)
+        .with_paused_reasons(reasons)
+    },
+    FileUploadState::CompletionPending { error } => {
+      let mut progress = FileProgress::new_error(file_url.to_string(), file_id.to_string(), error)
+        .with_completion_pending();
+      progress.progress = last_progress;
+      progress
+    },
+    FileUploadState::Finished {
+      total_bytes,
+      duration,
+      ..
+    } => {
+      let progress = FileProgress::new_progress(file_url.to_string(), file_id.to_string(), 1.0);
+      match (total_bytes, duration) {
+        (Some(total_bytes), Some(duration)) => progress.with_summary(total_bytes, duration),
+        _ => progress,
+      }
+    },
+  };
+  Some(progress)
+}
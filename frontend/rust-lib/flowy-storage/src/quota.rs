@@ -0,0 +1,156 @@
+use crate::parent_dir::canonical_parent_dir;
+use async_trait::async_trait;
+use flowy_error::FlowyResult;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+/// The scope of a quota limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotaScope {
+  Workspace(String),
+  /// The parent dir, along with its sub dirs, of a workspace.
+  ParentDir {
+    workspace_id: String,
+    parent_dir: String,
+  },
+}
+
+impl Display for QuotaScope {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      QuotaScope::Workspace(workspace_id) => write!(f, "workspace {}", workspace_id),
+      QuotaScope::ParentDir {
+        workspace_id,
+        parent_dir,
+      } => write!(f, "dir {} of workspace {}", parent_dir, workspace_id),
+    }
+  }
+}
+
+/// The limit an upload would exceed, see [UploadQuota::check].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaExceeded {
+  pub scope: QuotaScope,
+  /// The bytes stored in the scope before the upload.
+  pub used: u64,
+  /// The size of the file to upload.
+  pub requested: u64,
+  pub limit: u64,
+}
+
+impl Display for QuotaExceeded {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "the quota of the {} is exceeded: {} bytes used, {} requested, {} allowed",
+      self.scope, self.used, self.requested, self.limit
+    )
+  }
+}
+
+/// [UploadQuota] decides whether an upload can be created, see
+/// [crate::config::StorageManagerConfig::upload_quota].
+#[async_trait]
+pub trait UploadQuota: Debug + Send + Sync {
+  /// Returns the limit the upload of `file_size` bytes to the canonical `parent_dir` would exceed,
+  /// `None` when it's accepted.
+  async fn check(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_size: u64,
+  ) -> FlowyResult<Option<QuotaExceeded>>;
+}
+
+/// [StorageUsage] reports the bytes stored, supplied by the embedder, e.g. from the usage reported
+/// by the server.
+#[async_trait]
+pub trait StorageUsage: Debug + Send + Sync {
+  /// Returns the bytes stored in the workspace, or only in the canonical `parent_dir` and its sub
+  /// dirs when it's set.
+  async fn used_bytes(&self, workspace_id: &str, parent_dir: Option<&str>) -> FlowyResult<u64>;
+}
+
+/// Limits the bytes stored in each workspace and in some of their dirs, as measured by the
+/// [StorageUsage]. The limit of a dir applies to its sub dirs as well, the upload must fit in all
+/// the limits that apply to it.
+#[derive(Debug)]
+pub struct FolderQuota {
+  usage: Arc<dyn StorageUsage>,
+  workspace_limit: Option<u64>,
+  dir_limits: HashMap<String, u64>,
+}
+
+impl FolderQuota {
+  pub fn new(usage: Arc<dyn StorageUsage>) -> Self {
+    Self {
+      usage,
+      workspace_limit: None,
+      dir_limits: HashMap::new(),
+    }
+  }
+
+  pub fn workspace_limit(mut self, limit: u64) -> Self {
+    self.workspace_limit = Some(limit);
+    self
+  }
+
+  /// Limits the dir of every workspace. An invalid dir is ignored, no upload could target it.
+  pub fn dir_limit(mut self, parent_dir: &str, limit: u64) -> Self {
+    if let Ok(parent_dir) = canonical_parent_dir(parent_dir) {
+      self.dir_limits.insert(parent_dir, limit);
+    }
+    self
+  }
+
+  /// Returns the limited dirs the parent dir belongs to, the closest one first.
+  fn limited_dirs<'a>(&'a self, parent_dir: &'a str) -> impl Iterator<Item = (&'a str, u64)> {
+    let mut prefixes = parent_dir
+      .match_indices('/')
+      .map(|(index, _)| &parent_dir[..index])
+      .chain(std::iter::once(parent_dir))
+      .collect::<Vec<_>>();
+    prefixes.reverse();
+    prefixes
+      .into_iter()
+      .filter_map(|prefix| self.dir_limits.get(prefix).map(|limit| (prefix, *limit)))
+  }
+}
+
+#[async_trait]
+impl UploadQuota for FolderQuota {
+  async fn check(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_size: u64,
+  ) -> FlowyResult<Option<QuotaExceeded>> {
+    for (dir, limit) in self.limited_dirs(parent_dir) {
+      let used = self.usage.used_bytes(workspace_id, Some(dir)).await?;
+      if used.saturating_add(file_size) > limit {
+        return Ok(Some(QuotaExceeded {
+          scope: QuotaScope::ParentDir {
+            workspace_id: workspace_id.to_string(),
+            parent_dir: dir.to_string(),
+          },
+          used,
+          requested: file_size,
+          limit,
+        }));
+      }
+    }
+    if let Some(limit) = self.workspace_limit {
+      let used = self.usage.used_bytes(workspace_id, None).await?;
+      if used.saturating_add(file_size) > limit {
+        return Ok(Some(QuotaExceeded {
+          scope: QuotaScope::Workspace(workspace_id.to_string()),
+          used,
+          requested: file_size,
+          limit,
+        }));
+      }
+    }
+    Ok(None)
+  }
+}
@@ -0,0 +1,60 @@
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// [RangeCache] keeps the last ranges read by [crate::manager::StorageManager::read_range], so
+/// that a player seeking back and forth over the same spots doesn't fetch them again. The objects
+/// never change once uploaded, so a cached range stays valid until its object is deleted.
+#[derive(Debug)]
+pub struct RangeCache {
+  capacity: usize,
+  max_len: u64,
+  /// The cached ranges keyed by url, start and length, the most recently used last.
+  entries: Mutex<VecDeque<((String, u64, u64), Bytes)>>,
+}
+
+impl RangeCache {
+  /// Caches at most `capacity` ranges of at most `max_len` bytes each. A zero capacity disables
+  /// the cache.
+  pub fn new(capacity: usize, max_len: u64) -> Self {
+    Self {
+      capacity,
+      max_len,
+      entries: Default::default(),
+    }
+  }
+
+  pub fn get(&self, url: &str, start: u64, len: u64) -> Option<Bytes> {
+    let mut entries = self.entries.lock().unwrap();
+    let index = entries
+      .iter()
+      .position(|((cached_url, cached_start, cached_len), _)| {
+        cached_url == url && *cached_start == start && *cached_len == len
+      })?;
+    let entry = entries.remove(index)?;
+    let bytes = entry.1.clone();
+    entries.push_back(entry);
+    Some(bytes)
+  }
+
+  /// Caches the bytes of the range, unless the range is longer than the maximum length.
+  pub fn insert(&self, url: &str, start: u64, len: u64, bytes: Bytes) {
+    if self.capacity == 0 || len > self.max_len {
+      return;
+    }
+    let mut entries = self.entries.lock().unwrap();
+    if entries.len() >= self.capacity {
+      entries.pop_front();
+    }
+    entries.push_back(((url.to_string(), start, len), bytes));
+  }
+
+  /// Drops the cached ranges of the object.
+  pub fn remove_object(&self, url: &str) {
+    self
+      .entries
+      .lock()
+      .unwrap()
+      .retain(|((cached_url, _, _), _)| cached_url != url);
+  }
+}
@@ -0,0 +1,102 @@
+use crate::sqlite_sql::UploadFileTable;
+use flowy_storage_pub::storage::{FileProgress, FileUploadState};
+
+/// The state of an upload in an [UploadSnapshot].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadSnapshotState {
+  /// The upload waits to be uploaded.
+  Queued,
+  Uploading,
+  /// The upload waits until the reasons clear, see [FileUploadState::Paused].
+  Paused {
+    reasons: u8,
+  },
+  /// All the parts are uploaded, completing the upload is retried, see
+  /// [FileUploadState::CompletionPending].
+  CompletionPending,
+  /// The last attempt failed, see [UploadSnapshot::error].
+  Failed,
+  Finished,
+}
+
+/// An upload as listed by [crate::manager::StorageManager::uploads_stream]: its identity, its
+/// state and progress, its size and the error of its last attempt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadSnapshot {
+  pub workspace_id: String,
+  pub parent_dir: String,
+  pub file_id: String,
+  pub url: String,
+  pub state: UploadSnapshotState,
+  /// The uploaded fraction of the file, between 0 and 1.
+  pub progress: f64,
+  /// The size of the file in bytes. `None` when it's unknown, e.g. the local file is gone.
+  pub total_bytes: Option<u64>,
+  pub error: Option<String>,
+}
+
+impl UploadSnapshot {
+  /// The snapshot of the upload record. The live state of a running upload takes precedence over
+  /// the record, and the failure of the last attempt over the queued state.
+  pub(crate) fn from_record(
+    record: &UploadFileTable,
+    url: String,
+    live_state: Option<FileUploadState>,
+    error: Option<String>,
+  ) -> Self {
+    let total_bytes = std::fs::metadata(&record.local_file_path)
+      .ok()
+      .map(|metadata| metadata.len());
+    let (state, progress) = if record.is_finish {
+      (UploadSnapshotState::Finished, 1.0)
+    } else {
+      match live_state {
+        Some(FileUploadState::Uploading { progress }) => (UploadSnapshotState::Uploading, progress),
+        Some(FileUploadState::Paused { reasons }) => (UploadSnapshotState::Paused { reasons }, 0.0),
+        Some(FileUploadState::CompletionPending { .. }) => {
+          (UploadSnapshotState::CompletionPending, 1.0)
+        },
+        Some(FileUploadState::Finished { .. }) => (UploadSnapshotState::Finished, 1.0),
+        _ if error.is_some() => (UploadSnapshotState::Failed, 0.0),
+        _ => (UploadSnapshotState::Queued, 0.0),
+      }
+    };
+    Self {
+      workspace_id: record.workspace_id.clone(),
+      parent_dir: record.parent_dir.clone(),
+      file_id: record.file_id.clone(),
+      url,
+      state,
+      progress,
+      total_bytes,
+      error: if state == UploadSnapshotState::Failed {
+        error
+      } else {
+        None
+      },
+    }
+  }
+
+  /// Applies the progress of the upload to the snapshot.
+  pub(crate) fn apply(&mut self, progress: &FileProgress) {
+    self.error = None;
+    if let Some(error) = &progress.error {
+      self.state = if progress.completion_pending {
+        UploadSnapshotState::CompletionPending
+      } else {
+        UploadSnapshotState::Failed
+      };
+      self.error = Some(error.clone());
+    } else if let Some(reasons) = progress.paused_reasons {
+      self.state = UploadSnapshotState::Paused { reasons };
+    } else if progress.total_bytes.is_some() {
+      // Only the final progress of a completed upload carries its size.
+      self.state = UploadSnapshotState::Finished;
+      self.progress = 1.0;
+      self.total_bytes = progress.total_bytes;
+    } else {
+      self.state = UploadSnapshotState::Uploading;
+      self.progress = progress.progress;
+    }
+  }
+}
@@ -0,0 +1,20 @@
+use futures_util::future::BoxFuture;
+use std::fmt::Debug;
+
+/// [Spawner] runs the background tasks of the storage manager: the uploader and its uploads, the
+/// downloads, the reconciliation and the progress relay. The default [TokioSpawner] spawns them
+/// on the current tokio runtime, an embedder running another executor provides its own.
+pub trait Spawner: Debug + Send + Sync {
+  /// Runs the task in the background. The task is detached, nothing waits for it.
+  fn spawn(&self, task: BoxFuture<'static, ()>);
+}
+
+/// Spawns the tasks on the current tokio runtime.
+#[derive(Debug, Default)]
+pub struct TokioSpawner;
+
+impl Spawner for TokioSpawner {
+  fn spawn(&self, task: BoxFuture<'static, ()>) {
+    tokio::spawn(task);
+  }
+}
@@ -1,11 +1,15 @@
 use flowy_error::{FlowyError, FlowyResult};
 use flowy_sqlite::result::DatabaseErrorKind;
 use flowy_sqlite::result::Error::DatabaseError;
-use flowy_sqlite::schema::{upload_file_part, upload_file_table};
+use flowy_sqlite::schema::{
+  download_file_table, upload_file_failure, upload_file_manifest, upload_file_part,
+  upload_file_table, upload_log,
+};
 use flowy_sqlite::{
   diesel, AsChangeset, BoolExpressionMethods, DBConnection, ExpressionMethods, Identifiable,
   Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl, SqliteConnection,
 };
+use std::path::Path;
 use tracing::warn;
 
 #[derive(Queryable, Insertable, AsChangeset, Identifiable, Debug, Clone)]
@@ -20,8 +24,49 @@ pub struct UploadFileTable {
   pub chunk_size: i32,
   pub num_chunk: i32,
   pub upload_id: String,
+  /// The wall-clock time the upload was created, in seconds since the epoch. It's only displayed,
+  /// the uploads are ordered by [Self::seq].
   pub created_at: i64,
   pub is_finish: bool,
+  /// The metadata stored along with the object, serialized as a json map. Empty when the upload
+  /// has no metadata.
+  pub metadata: String,
+  /// The storage class of the object, see
+  /// [flowy_storage_pub::cloud::StorageCapabilities::storage_classes]. Empty for the default class
+  /// of the backend.
+  pub storage_class: String,
+  /// The position of the upload in the creation order, assigned by [insert_upload_file]. Unlike
+  /// [Self::created_at], it never goes backward when the system clock does.
+  pub seq: i64,
+  /// Whether the upload replaces an existing object. When false, the upload is dropped with
+  /// [crate::error::StorageError::AlreadyExists] if the object exists.
+  pub overwrite: bool,
+  /// The parent dir of the upload holding the object when the record links to it instead of
+  /// uploading the same content again, see
+  /// [crate::config::StorageManagerConfig::link_duplicate_uploads]. Empty for an upload holding
+  /// its own object.
+  pub linked_parent_dir: String,
+  /// The file the temp copy at [Self::local_file_path] was made from. The copy is made again from
+  /// it when the temp file is gone by the time the upload resumes. Empty when unknown.
+  pub source_file_path: String,
+}
+
+impl UploadFileTable {
+  /// The parent dir the object of the upload is stored under.
+  pub fn object_parent_dir(&self) -> &str {
+    if self.linked_parent_dir.is_empty() {
+      &self.parent_dir
+    } else {
+      &self.linked_parent_dir
+    }
+  }
+
+  /// Whether the content of the upload is still on the disk, either as the temp copy or as the
+  /// file it was copied from.
+  pub fn has_local_file(&self) -> bool {
+    Path::new(&self.local_file_path).exists()
+      || (!self.source_file_path.is_empty() && Path::new(&self.source_file_path).exists())
+  }
 }
 
 #[derive(Queryable, Insertable, AsChangeset, Identifiable, Debug)]
@@ -31,6 +76,76 @@ pub struct UploadFilePartTable {
   pub upload_id: String,
   pub e_tag: String,
   pub part_num: i32,
+  /// The checksum of the sent part, see [flowy_storage_pub::cloud::PartChecksum]. Empty for the
+  /// parts uploaded before the checksums were recorded.
+  pub checksum: String,
+}
+
+#[derive(Queryable, Insertable, AsChangeset, Identifiable, Debug, Clone)]
+#[diesel(table_name = upload_file_manifest)]
+#[diesel(primary_key(workspace_id, parent_dir, file_id))]
+pub struct UploadFileManifestTable {
+  pub workspace_id: String,
+  pub parent_dir: String,
+  pub file_id: String,
+  /// The [crate::manifest::UploadManifest] serialized as json.
+  pub manifest: String,
+  pub created_at: i64,
+}
+
+/// The last failure of an unfinished upload, kept until the upload succeeds, is cancelled, or is
+/// purged after [crate::config::StorageManagerConfig::failed_upload_retention].
+#[derive(Queryable, Insertable, AsChangeset, Identifiable, Debug, Clone)]
+#[diesel(table_name = upload_file_failure)]
+#[diesel(primary_key(workspace_id, parent_dir, file_id))]
+pub struct UploadFileFailureTable {
+  pub workspace_id: String,
+  pub parent_dir: String,
+  pub file_id: String,
+  pub error: String,
+  /// The time of the last failure, in seconds since the epoch.
+  pub failed_at: i64,
+  /// A pinned failure is kept for the user to retry, it's never purged.
+  pub pinned: bool,
+}
+
+/// A queued or running download, kept so that the download resumes after a restart.
+#[derive(Queryable, Insertable, AsChangeset, Identifiable, Debug, Clone)]
+#[diesel(table_name = download_file_table)]
+#[diesel(primary_key(url))]
+pub struct DownloadFileTable {
+  pub url: String,
+  pub local_file_path: String,
+  /// The [crate::downloader::DownloadPriority] of the download.
+  pub priority: i32,
+  pub created_at: i64,
+}
+
+/// A lifecycle event of an upload, see [crate::upload_log::UploadLogEvent].
+#[derive(Queryable, Identifiable, Debug, Clone)]
+#[diesel(table_name = upload_log)]
+pub struct UploadLogTable {
+  pub id: i64,
+  pub workspace_id: String,
+  pub parent_dir: String,
+  pub file_id: String,
+  pub event: String,
+  /// The part number of a part event, or the error of a failure. Empty otherwise.
+  pub detail: String,
+  /// The time of the event, in milliseconds since the epoch.
+  pub created_at: i64,
+}
+
+/// An [UploadLogTable] row to insert, its id is assigned by the database.
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = upload_log)]
+pub struct NewUploadLog {
+  pub workspace_id: String,
+  pub parent_dir: String,
+  pub file_id: String,
+  pub event: String,
+  pub detail: String,
+  pub created_at: i64,
 }
 
 pub fn is_upload_file_exist(
@@ -51,21 +166,34 @@ pub fn is_upload_file_exist(
   Ok(result.is_some())
 }
 
+/// Inserts the upload file record and returns its [UploadFileTable::seq], the one after the seq of
+/// the last inserted record. The seq of the given record is ignored.
 pub fn insert_upload_file(
   mut conn: DBConnection,
   upload_file: &UploadFileTable,
-) -> FlowyResult<()> {
-  match diesel::insert_into(upload_file_table::table)
-    .values(upload_file)
-    .execute(&mut *conn)
-  {
-    Ok(_) => Ok(()),
-    Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => Err(FlowyError::new(
-      flowy_error::ErrorCode::DuplicateSqliteRecord,
-      "Upload file already exists",
-    )),
-    Err(e) => Err(e.into()),
-  }
+) -> FlowyResult<i64> {
+  conn.immediate_transaction(|conn| {
+    let seq = upload_file_table::dsl::upload_file_table
+      .select(diesel::dsl::max(upload_file_table::seq))
+      .first::<Option<i64>>(conn)?
+      .unwrap_or(0)
+      + 1;
+    let upload_file = UploadFileTable {
+      seq,
+      ..upload_file.clone()
+    };
+    match diesel::insert_into(upload_file_table::table)
+      .values(&upload_file)
+      .execute(conn)
+    {
+      Ok(_) => Ok(seq),
+      Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => Err(FlowyError::new(
+        flowy_error::ErrorCode::DuplicateSqliteRecord,
+        "Upload file already exists",
+      )),
+      Err(e) => Err(e.into()),
+    }
+  })
 }
 
 pub fn update_upload_file_upload_id(
@@ -97,6 +225,69 @@ pub fn update_upload_file_completed(mut conn: DBConnection, upload_id: &str) ->
   Ok(())
 }
 
+pub fn update_upload_file_completed_by_file_id(
+  mut conn: DBConnection,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+) -> FlowyResult<()> {
+  diesel::update(
+    upload_file_table::dsl::upload_file_table.filter(
+      upload_file_table::workspace_id
+        .eq(workspace_id)
+        .and(upload_file_table::parent_dir.eq(parent_dir))
+        .and(upload_file_table::file_id.eq(file_id)),
+    ),
+  )
+  .set(upload_file_table::is_finish.eq(true))
+  .execute(&mut *conn)?;
+  Ok(())
+}
+
+/// Marks the upload as unfinished, so that the file is uploaded again with a new server-side
+/// upload.
+pub fn update_upload_file_unfinished_by_file_id(
+  mut conn: DBConnection,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+) -> FlowyResult<()> {
+  diesel::update(
+    upload_file_table::dsl::upload_file_table.filter(
+      upload_file_table::workspace_id
+        .eq(workspace_id)
+        .and(upload_file_table::parent_dir.eq(parent_dir))
+        .and(upload_file_table::file_id.eq(file_id)),
+    ),
+  )
+  .set((
+    upload_file_table::is_finish.eq(false),
+    upload_file_table::upload_id.eq(""),
+  ))
+  .execute(&mut *conn)?;
+  Ok(())
+}
+
+pub fn update_upload_file_local_path(
+  mut conn: DBConnection,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+  local_file_path: &str,
+) -> FlowyResult<()> {
+  diesel::update(
+    upload_file_table::dsl::upload_file_table.filter(
+      upload_file_table::workspace_id
+        .eq(workspace_id)
+        .and(upload_file_table::parent_dir.eq(parent_dir))
+        .and(upload_file_table::file_id.eq(file_id)),
+    ),
+  )
+  .set(upload_file_table::local_file_path.eq(local_file_path))
+  .execute(&mut *conn)?;
+  Ok(())
+}
+
 pub fn is_upload_completed(
   conn: &mut SqliteConnection,
   workspace_id: &str,
@@ -137,6 +328,41 @@ pub fn delete_upload_file(mut conn: DBConnection, upload_id: &str) -> FlowyResul
   Ok(())
 }
 
+/// Deletes the upload file record identified by workspace, parent dir and file id, along with its
+/// uploaded parts. Unlike [delete_upload_file], it works for records that haven't got an upload id.
+pub fn delete_upload_file_by_file_id(
+  mut conn: DBConnection,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+) -> FlowyResult<()> {
+  conn.immediate_transaction(|conn| {
+    let upload_id = select_upload_file(conn, workspace_id, parent_dir, file_id)?
+      .map(|record| record.upload_id)
+      .unwrap_or_default();
+
+    diesel::delete(
+      upload_file_table::dsl::upload_file_table.filter(
+        upload_file_table::workspace_id
+          .eq(workspace_id)
+          .and(upload_file_table::parent_dir.eq(parent_dir))
+          .and(upload_file_table::file_id.eq(file_id)),
+      ),
+    )
+    .execute(&mut *conn)?;
+
+    if !upload_id.is_empty() {
+      diesel::delete(
+        upload_file_part::dsl::upload_file_part.filter(upload_file_part::upload_id.eq(&upload_id)),
+      )
+      .execute(&mut *conn)?;
+    }
+    Ok::<_, FlowyError>(())
+  })?;
+
+  Ok(())
+}
+
 pub fn delete_all_upload_parts(mut conn: DBConnection, upload_id: &str) -> FlowyResult<()> {
   diesel::delete(
     upload_file_part::dsl::upload_file_part.filter(upload_file_part::upload_id.eq(upload_id)),
@@ -177,6 +403,15 @@ pub fn select_upload_parts(
   Ok(results)
 }
 
+/// Selects the distinct upload ids of the uploaded parts.
+pub fn select_upload_part_upload_ids(conn: &mut SqliteConnection) -> FlowyResult<Vec<String>> {
+  let results = upload_file_part::dsl::upload_file_part
+    .select(upload_file_part::upload_id)
+    .distinct()
+    .load::<String>(conn)?;
+  Ok(results)
+}
+
 pub fn batch_select_upload_file(
   mut conn: DBConnection,
   limit: i32,
@@ -184,7 +419,7 @@ pub fn batch_select_upload_file(
 ) -> FlowyResult<Vec<UploadFileTable>> {
   let results = upload_file_table::dsl::upload_file_table
     .filter(upload_file_table::is_finish.eq(is_finish))
-    .order(upload_file_table::created_at.desc())
+    .order(upload_file_table::seq.desc())
     .limit(limit.into())
     .load::<UploadFileTable>(&mut *conn)?;
 
@@ -208,3 +443,292 @@ pub fn select_upload_file(
     .optional()?;
   Ok(result)
 }
+
+/// Selects a completed upload of the file holding its own object, under any parent dir of the
+/// workspace. The oldest one is returned.
+pub fn select_linkable_upload_file(
+  conn: &mut SqliteConnection,
+  workspace_id: &str,
+  file_id: &str,
+) -> FlowyResult<Option<UploadFileTable>> {
+  let result = upload_file_table::dsl::upload_file_table
+    .filter(
+      upload_file_table::workspace_id
+        .eq(workspace_id)
+        .and(upload_file_table::file_id.eq(file_id))
+        .and(upload_file_table::is_finish.eq(true))
+        .and(upload_file_table::linked_parent_dir.eq("")),
+    )
+    .order(upload_file_table::seq.asc())
+    .first::<UploadFileTable>(conn)
+    .optional()?;
+  Ok(result)
+}
+
+/// Counts the upload records sharing the upload id, i.e. the upload and its links, see
+/// [UploadFileTable::linked_parent_dir].
+pub fn count_upload_files_by_upload_id(
+  conn: &mut SqliteConnection,
+  upload_id: &str,
+) -> FlowyResult<usize> {
+  let count = upload_file_table::dsl::upload_file_table
+    .filter(upload_file_table::upload_id.eq(upload_id))
+    .count()
+    .get_result::<i64>(conn)?;
+  Ok(count as usize)
+}
+
+/// Counts the upload records not completed yet, across all the workspaces.
+pub fn count_unfinished_upload_files(conn: &mut SqliteConnection) -> FlowyResult<usize> {
+  let count = upload_file_table::dsl::upload_file_table
+    .filter(upload_file_table::is_finish.eq(false))
+    .count()
+    .get_result::<i64>(conn)?;
+  Ok(count as usize)
+}
+
+pub fn select_workspace_upload_files(
+  conn: &mut SqliteConnection,
+  workspace_id: &str,
+  is_finish: bool,
+) -> FlowyResult<Vec<UploadFileTable>> {
+  let results = upload_file_table::dsl::upload_file_table
+    .filter(
+      upload_file_table::workspace_id
+        .eq(workspace_id)
+        .and(upload_file_table::is_finish.eq(is_finish)),
+    )
+    .load::<UploadFileTable>(conn)?;
+  Ok(results)
+}
+
+/// Selects a page of the upload records, in the order they were created.
+pub fn select_upload_files(
+  conn: &mut SqliteConnection,
+  offset: i64,
+  limit: i64,
+) -> FlowyResult<Vec<UploadFileTable>> {
+  let results = upload_file_table::dsl::upload_file_table
+    .order(upload_file_table::seq.asc())
+    .offset(offset)
+    .limit(limit)
+    .load::<UploadFileTable>(conn)?;
+  Ok(results)
+}
+
+/// Inserts the manifest of the upload, replacing the manifest of a previous upload of the file.
+pub fn upsert_upload_manifest(
+  mut conn: DBConnection,
+  manifest: &UploadFileManifestTable,
+) -> FlowyResult<()> {
+  diesel::replace_into(upload_file_manifest::table)
+    .values(manifest)
+    .execute(&mut *conn)?;
+  Ok(())
+}
+
+pub fn select_upload_manifest(
+  conn: &mut SqliteConnection,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+) -> FlowyResult<Option<UploadFileManifestTable>> {
+  let result = upload_file_manifest::dsl::upload_file_manifest
+    .filter(
+      upload_file_manifest::workspace_id
+        .eq(workspace_id)
+        .and(upload_file_manifest::parent_dir.eq(parent_dir))
+        .and(upload_file_manifest::file_id.eq(file_id)),
+    )
+    .first::<UploadFileManifestTable>(conn)
+    .optional()?;
+  Ok(result)
+}
+
+/// Records the failure of the upload, replacing the error and time of its previous failure. Whether
+/// the failure is pinned is kept.
+pub fn upsert_upload_failure(
+  conn: &mut SqliteConnection,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+  error: &str,
+  failed_at: i64,
+) -> FlowyResult<()> {
+  diesel::insert_into(upload_file_failure::table)
+    .values(UploadFileFailureTable {
+      workspace_id: workspace_id.to_string(),
+      parent_dir: parent_dir.to_string(),
+      file_id: file_id.to_string(),
+      error: error.to_string(),
+      failed_at,
+      pinned: false,
+    })
+    .on_conflict((
+      upload_file_failure::workspace_id,
+      upload_file_failure::parent_dir,
+      upload_file_failure::file_id,
+    ))
+    .do_update()
+    .set((
+      upload_file_failure::error.eq(error),
+      upload_file_failure::failed_at.eq(failed_at),
+    ))
+    .execute(conn)?;
+  Ok(())
+}
+
+pub fn delete_upload_failure(
+  conn: &mut SqliteConnection,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+) -> FlowyResult<()> {
+  diesel::delete(
+    upload_file_failure::dsl::upload_file_failure.filter(
+      upload_file_failure::workspace_id
+        .eq(workspace_id)
+        .and(upload_file_failure::parent_dir.eq(parent_dir))
+        .and(upload_file_failure::file_id.eq(file_id)),
+    ),
+  )
+  .execute(conn)?;
+  Ok(())
+}
+
+/// Selects the failed uploads of the workspace, the most recent failure first.
+pub fn select_upload_failures(
+  conn: &mut SqliteConnection,
+  workspace_id: &str,
+) -> FlowyResult<Vec<UploadFileFailureTable>> {
+  let results = upload_file_failure::dsl::upload_file_failure
+    .filter(upload_file_failure::workspace_id.eq(workspace_id))
+    .order(upload_file_failure::failed_at.desc())
+    .load::<UploadFileFailureTable>(conn)?;
+  Ok(results)
+}
+
+/// Selects the failures that aren't pinned and happened before `failed_before`, in seconds since
+/// the epoch.
+pub fn select_expired_upload_failures(
+  conn: &mut SqliteConnection,
+  failed_before: i64,
+) -> FlowyResult<Vec<UploadFileFailureTable>> {
+  let results = upload_file_failure::dsl::upload_file_failure
+    .filter(
+      upload_file_failure::failed_at
+        .lt(failed_before)
+        .and(upload_file_failure::pinned.eq(false)),
+    )
+    .load::<UploadFileFailureTable>(conn)?;
+  Ok(results)
+}
+
+/// Pins or unpins the failure of the upload. Returns false if the upload has no failure.
+pub fn update_upload_failure_pinned(
+  conn: &mut SqliteConnection,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+  pinned: bool,
+) -> FlowyResult<bool> {
+  let updated = diesel::update(
+    upload_file_failure::dsl::upload_file_failure.filter(
+      upload_file_failure::workspace_id
+        .eq(workspace_id)
+        .and(upload_file_failure::parent_dir.eq(parent_dir))
+        .and(upload_file_failure::file_id.eq(file_id)),
+    ),
+  )
+  .set(upload_file_failure::pinned.eq(pinned))
+  .execute(conn)?;
+  Ok(updated > 0)
+}
+
+/// Inserts the download record, replacing the record of a previous download of the url.
+pub fn upsert_download_file(
+  conn: &mut SqliteConnection,
+  download_file: &DownloadFileTable,
+) -> FlowyResult<()> {
+  diesel::replace_into(download_file_table::table)
+    .values(download_file)
+    .execute(conn)?;
+  Ok(())
+}
+
+pub fn delete_download_file(conn: &mut SqliteConnection, url: &str) -> FlowyResult<()> {
+  diesel::delete(
+    download_file_table::dsl::download_file_table.filter(download_file_table::url.eq(url)),
+  )
+  .execute(conn)?;
+  Ok(())
+}
+
+/// Selects the download records, ordered by their creation time.
+pub fn select_download_files(conn: &mut SqliteConnection) -> FlowyResult<Vec<DownloadFileTable>> {
+  let results = download_file_table::dsl::download_file_table
+    .order(download_file_table::created_at.asc())
+    .load::<DownloadFileTable>(conn)?;
+  Ok(results)
+}
+
+/// Inserts the rows of the upload log in a single transaction.
+pub fn insert_upload_logs(conn: &mut SqliteConnection, logs: &[NewUploadLog]) -> FlowyResult<()> {
+  conn.immediate_transaction(|conn| {
+    diesel::insert_into(upload_log::table)
+      .values(logs)
+      .execute(conn)?;
+    Ok::<_, FlowyError>(())
+  })
+}
+
+/// Deletes the rows of the upload log created before `created_before`, in milliseconds since the
+/// epoch, and the oldest rows beyond `max_rows`. Returns the number of deleted rows.
+pub fn prune_upload_logs(
+  conn: &mut SqliteConnection,
+  max_rows: usize,
+  created_before: Option<i64>,
+) -> FlowyResult<usize> {
+  let mut deleted = 0;
+  if let Some(created_before) = created_before {
+    deleted +=
+      diesel::delete(upload_log::dsl::upload_log.filter(upload_log::created_at.lt(created_before)))
+        .execute(conn)?;
+  }
+  // The ids only grow, so the rows to keep are the ones whose id is among the last `max_rows`.
+  let max_id = upload_log::dsl::upload_log
+    .select(diesel::dsl::max(upload_log::id))
+    .first::<Option<i64>>(conn)?;
+  if let Some(max_id) = max_id {
+    deleted += diesel::delete(
+      upload_log::dsl::upload_log.filter(upload_log::id.le(max_id - max_rows as i64)),
+    )
+    .execute(conn)?;
+  }
+  Ok(deleted)
+}
+
+/// Selects the upload log of the file, the oldest event first.
+pub fn select_upload_logs(
+  conn: &mut SqliteConnection,
+  file_id: &str,
+) -> FlowyResult<Vec<UploadLogTable>> {
+  let results = upload_log::dsl::upload_log
+    .filter(upload_log::file_id.eq(file_id))
+    .order(upload_log::id.asc())
+    .load::<UploadLogTable>(conn)?;
+  Ok(results)
+}
+
+/// Selects the last `limit` rows of the upload log of all the files, the oldest event first.
+pub fn select_upload_log_tail(
+  conn: &mut SqliteConnection,
+  limit: usize,
+) -> FlowyResult<Vec<UploadLogTable>> {
+  let mut results = upload_log::dsl::upload_log
+    .order(upload_log::id.desc())
+    .limit(limit as i64)
+    .load::<UploadLogTable>(conn)?;
+  results.reverse();
+  Ok(results)
+}
@@ -0,0 +1,156 @@
+use crate::config::StorageManagerConfig;
+use crate::manager::{acquire_sqlite_connection, StorageUserService};
+use crate::sqlite_sql::{
+  insert_upload_logs, prune_upload_logs, select_upload_log_tail, select_upload_logs, NewUploadLog,
+  UploadLogTable,
+};
+use flowy_error::FlowyResult;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// The number of buffered events that triggers a write of the upload log.
+const FLUSH_BATCH_SIZE: usize = 32;
+
+/// A lifecycle event of an upload, stored in [UploadLogTable::event] as [Self::name].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UploadLogEvent {
+  /// An attempt of the upload started.
+  Start,
+  /// The part with the given number was uploaded.
+  Part(i32),
+  /// The part with the given number failed and is sent again, within the same attempt.
+  PartRetry(i32),
+  Complete,
+  /// The attempt failed with the given error.
+  Fail(String),
+  /// The upload was queued again after a failure, with the given number of retries left.
+  Retry(u8),
+}
+
+impl UploadLogEvent {
+  pub fn name(&self) -> &'static str {
+    match self {
+      UploadLogEvent::Start => "start",
+      UploadLogEvent::Part(_) => "part",
+      UploadLogEvent::PartRetry(_) => "part_retry",
+      UploadLogEvent::Complete => "complete",
+      UploadLogEvent::Fail(_) => "fail",
+      UploadLogEvent::Retry(_) => "retry",
+    }
+  }
+
+  fn detail(&self) -> String {
+    match self {
+      UploadLogEvent::Start | UploadLogEvent::Complete => String::new(),
+      UploadLogEvent::Part(part_number) | UploadLogEvent::PartRetry(part_number) => {
+        part_number.to_string()
+      },
+      UploadLogEvent::Fail(error) => error.clone(),
+      UploadLogEvent::Retry(retries_left) => retries_left.to_string(),
+    }
+  }
+
+  /// Whether the event ends an attempt, which writes the buffered events right away.
+  fn is_terminal(&self) -> bool {
+    matches!(self, UploadLogEvent::Complete | UploadLogEvent::Fail(_))
+  }
+}
+
+/// [UploadLog] persists the lifecycle events of the uploads to sqlite, so that they survive a
+/// restart. The events are buffered and written in a single transaction, once
+/// [FLUSH_BATCH_SIZE] events are buffered or an attempt ends. Each write prunes the log down to
+/// [StorageManagerConfig::upload_log_max_rows] and [StorageManagerConfig::upload_log_max_age].
+pub struct UploadLog {
+  config: Arc<StorageManagerConfig>,
+  user_service: Arc<dyn StorageUserService>,
+  /// The events not written yet. The lock is held while writing, so that the batches are written
+  /// in the order of their events.
+  pending: Mutex<Vec<NewUploadLog>>,
+}
+
+impl UploadLog {
+  pub fn new(config: Arc<StorageManagerConfig>, user_service: Arc<dyn StorageUserService>) -> Self {
+    Self {
+      config,
+      user_service,
+      pending: Default::default(),
+    }
+  }
+
+  /// Records the event of the upload. A failure to write the log is only logged, it never fails
+  /// the upload.
+  pub async fn record(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    event: UploadLogEvent,
+  ) {
+    let mut pending = self.pending.lock().await;
+    pending.push(NewUploadLog {
+      workspace_id: workspace_id.to_string(),
+      parent_dir: parent_dir.to_string(),
+      file_id: file_id.to_string(),
+      event: event.name().to_string(),
+      detail: event.detail(),
+      created_at: unix_timestamp_millis(self.config.clock.system_now()),
+    });
+    if event.is_terminal() || pending.len() >= FLUSH_BATCH_SIZE {
+      if let Err(err) = self.write(&mut pending).await {
+        warn!("[File] write upload log failed: {}", err);
+      }
+    }
+  }
+
+  /// Returns the log of the file, the oldest event first.
+  pub async fn file_logs(&self, file_id: &str) -> FlowyResult<Vec<UploadLogTable>> {
+    self.flush().await?;
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    select_upload_logs(&mut conn, file_id)
+  }
+
+  /// Returns the last `limit` events of all the uploads, the oldest event first.
+  pub async fn tail(&self, limit: usize) -> FlowyResult<Vec<UploadLogTable>> {
+    self.flush().await?;
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    select_upload_log_tail(&mut conn, limit)
+  }
+
+  /// Writes the buffered events.
+  pub async fn flush(&self) -> FlowyResult<()> {
+    let mut pending = self.pending.lock().await;
+    self.write(&mut pending).await
+  }
+
+  async fn write(&self, pending: &mut Vec<NewUploadLog>) -> FlowyResult<()> {
+    if pending.is_empty() {
+      return Ok(());
+    }
+    // The events are dropped when the write fails, rather than growing the buffer while the
+    // database is unavailable.
+    let logs = std::mem::take(pending);
+    let mut conn = acquire_sqlite_connection(&self.user_service).await?;
+    insert_upload_logs(&mut conn, &logs)?;
+    let created_before = self.config.upload_log_max_age.map(|max_age| {
+      unix_timestamp_millis(
+        self
+          .config
+          .clock
+          .system_now()
+          .checked_sub(max_age)
+          .unwrap_or(UNIX_EPOCH),
+      )
+    });
+    prune_upload_logs(&mut conn, self.config.upload_log_max_rows, created_before)?;
+    Ok(())
+  }
+}
+
+fn unix_timestamp_millis(time: SystemTime) -> i64 {
+  time
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_millis() as i64
+}
@@ -1,14 +1,22 @@
+use crate::clock::Clock;
+use crate::diagnostics::UploadDetail;
+use crate::entities::UploadPauseReasonsPB;
+use crate::notification::{make_notification, StorageNotification};
+use crate::pause::PauseReasons;
+use crate::spawner::Spawner;
 use crate::sqlite_sql::UploadFileTable;
+use crate::upload_log::{UploadLog, UploadLogEvent};
 use crate::uploader::UploadTask::BackgroundTask;
+use dashmap::{DashMap, DashSet};
 use flowy_storage_pub::storage::StorageService;
 use lib_infra::box_any::BoxAny;
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::fmt::Display;
-use std::sync::atomic::{AtomicBool, AtomicU8};
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Weak};
 use std::time::Duration;
-use tokio::sync::{watch, RwLock};
+use tokio::sync::{watch, RwLock, Semaphore};
 use tracing::{error, info, instrument, trace, warn};
 
 #[derive(Clone)]
@@ -21,29 +29,173 @@ pub enum Signal {
 pub struct UploadTaskQueue {
   tasks: RwLock<BinaryHeap<UploadTask>>,
   notifier: watch::Sender<Signal>,
+  /// The queue and upload times of the files, keyed by workspace id, parent dir and file id.
+  details: DashMap<(String, String, String), UploadDetail>,
+  clock: Arc<dyn Clock>,
+  /// Sent whenever the queued or running uploads change, see [Self::subscribe_changes].
+  changes: watch::Sender<()>,
 }
 
 impl UploadTaskQueue {
-  pub fn new(notifier: watch::Sender<Signal>) -> Self {
+  pub fn new(notifier: watch::Sender<Signal>, clock: Arc<dyn Clock>) -> Self {
     Self {
       tasks: Default::default(),
       notifier,
+      details: Default::default(),
+      clock,
+      changes: watch::Sender::new(()),
     }
   }
   pub async fn queue_task(&self, task: UploadTask) {
     trace!("[File] Queued task: {}", task);
+    self.record_queued(&task);
     self.tasks.write().await.push(task);
     let _ = self.notifier.send_replace(Signal::Proceed);
+    self.notify_changed();
+  }
+
+  /// Returns a receiver that's marked changed whenever a task is queued, removed, started or done,
+  /// and whenever an upload starts or ends outside of the uploader.
+  pub fn subscribe_changes(&self) -> watch::Receiver<()> {
+    self.changes.subscribe()
+  }
+
+  pub fn notify_changed(&self) {
+    self.changes.send_replace(());
+  }
+
+  /// Queues the task in place of the queued tasks of the same file, so the file is only uploaded
+  /// once.
+  pub async fn replace_task(&self, task: UploadTask) {
+    trace!("[File] Replaced task: {}", task);
+    self.record_queued(&task);
+    {
+      let mut tasks = self.tasks.write().await;
+      let (workspace_id, parent_dir, file_id) = task.file();
+      tasks.retain(|queued| !queued.is_task_of(workspace_id, parent_dir, file_id));
+      tasks.push(task);
+    }
+    let _ = self.notifier.send_replace(Signal::Proceed);
+    self.notify_changed();
+  }
+
+  /// Removes the queued tasks of the given file and returns the number of removed tasks.
+  pub async fn remove_tasks(&self, workspace_id: &str, parent_dir: &str, file_id: &str) -> usize {
+    let removed = {
+      let mut tasks = self.tasks.write().await;
+      let len = tasks.len();
+      tasks.retain(|task| !task.is_task_of(workspace_id, parent_dir, file_id));
+      len - tasks.len()
+    };
+    if removed > 0 {
+      self.notify_changed();
+    }
+    removed
+  }
+
+  pub async fn contains_task(&self, workspace_id: &str, parent_dir: &str, file_id: &str) -> bool {
+    self
+      .tasks
+      .read()
+      .await
+      .iter()
+      .any(|task| task.is_task_of(workspace_id, parent_dir, file_id))
+  }
+
+  /// Removes the queued tasks of the given workspace and returns the number of removed tasks.
+  pub async fn remove_workspace_tasks(&self, workspace_id: &str) -> usize {
+    let removed = {
+      let mut tasks = self.tasks.write().await;
+      let len = tasks.len();
+      tasks.retain(|task| task.workspace_id() != workspace_id);
+      len - tasks.len()
+    };
+    if removed > 0 {
+      self.notify_changed();
+    }
+    removed
+  }
+
+  pub fn upload_detail(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> Option<UploadDetail> {
+    self
+      .details
+      .get(&(
+        workspace_id.to_string(),
+        parent_dir.to_string(),
+        file_id.to_string(),
+      ))
+      .map(|detail| detail.clone())
+  }
+
+  /// Records the time the file was queued. A file that's already waiting keeps its first queue
+  /// time, while a file queued again after starting, e.g. uploaded again, starts a new detail.
+  fn record_queued(&self, task: &UploadTask) {
+    let now = self.clock.system_now();
+    self
+      .details
+      .entry(task_key(task))
+      .and_modify(|detail| {
+        if detail.started_at.is_some() {
+          *detail = UploadDetail::new(now);
+        }
+      })
+      .or_insert_with(|| UploadDetail::new(now));
+  }
+
+  fn record_started(&self, task: &UploadTask) {
+    let now = self.clock.system_now();
+    let mut detail = self
+      .details
+      .entry(task_key(task))
+      .or_insert_with(|| UploadDetail::new(now));
+    detail.started_at = Some(now);
+    detail.finished_at = None;
+  }
+
+  fn record_finished(&self, key: &(String, String, String)) {
+    if let Some(mut detail) = self.details.get_mut(key) {
+      detail.finished_at = Some(self.clock.system_now());
+      info!(
+        "[File] upload {} waited {:?} in the queue, uploaded in {:?}",
+        key.2,
+        detail.queue_wait().unwrap_or_default(),
+        detail.upload_duration().unwrap_or_default()
+      );
+    }
   }
 }
 
+fn task_key(task: &UploadTask) -> (String, String, String) {
+  let (workspace_id, parent_dir, file_id) = task.file();
+  (
+    workspace_id.to_string(),
+    parent_dir.to_string(),
+    file_id.to_string(),
+  )
+}
+
 pub struct FileUploader {
   storage_service: Arc<dyn StorageService>,
   queue: Arc<UploadTaskQueue>,
-  max_uploads: u8,
-  current_uploads: AtomicU8,
-  pause_sync: AtomicBool,
+  max_uploads: usize,
+  /// Each running upload holds a permit, which caps the number of concurrent uploads to
+  /// `max_uploads`.
+  upload_permits: Arc<Semaphore>,
+  /// The reasons that pause all the uploads. The uploader only proceeds when it's empty.
+  pause_reasons: watch::Sender<PauseReasons>,
+  /// The workspaces whose uploads are paused.
+  paused_workspaces: DashSet<String>,
+  /// The files whose uploads are paused, keyed by workspace id, parent dir and file id.
+  paused_files: DashSet<(String, String, String)>,
+  /// Whether the connection is metered, which pauses all the tasks but the immediate ones.
+  metered: AtomicBool,
   has_exceeded_limit: Arc<AtomicBool>,
+  upload_log: Arc<UploadLog>,
 }
 
 impl Drop for FileUploader {
@@ -57,110 +209,247 @@ impl FileUploader {
     storage_service: Arc<dyn StorageService>,
     queue: Arc<UploadTaskQueue>,
     is_exceed_limit: Arc<AtomicBool>,
+    max_uploads: usize,
+    upload_log: Arc<UploadLog>,
   ) -> Self {
+    let mut pause_reasons = PauseReasons::NONE;
+    pause_reasons.set(
+      PauseReasons::STORAGE_WRITE_DISABLED,
+      is_exceed_limit.load(std::sync::atomic::Ordering::SeqCst),
+    );
     Self {
       storage_service,
       queue,
-      max_uploads: 3,
-      current_uploads: Default::default(),
-      pause_sync: Default::default(),
+      max_uploads,
+      upload_permits: Arc::new(Semaphore::new(max_uploads)),
+      pause_reasons: watch::Sender::new(pause_reasons),
+      paused_workspaces: Default::default(),
+      paused_files: Default::default(),
+      metered: AtomicBool::new(false),
       has_exceeded_limit: is_exceed_limit,
+      upload_log,
     }
   }
 
   pub async fn queue_tasks(&self, tasks: Vec<UploadTask>) {
-    let mut queue_lock = self.queue.tasks.write().await;
-    for task in tasks {
-      queue_lock.push(task);
+    {
+      let mut queue_lock = self.queue.tasks.write().await;
+      for task in tasks {
+        self.queue.record_queued(&task);
+        queue_lock.push(task);
+      }
     }
     let _ = self.queue.notifier.send(Signal::Proceed);
+    self.queue.notify_changed();
   }
 
-  pub fn pause(&self) {
-    self
-      .pause_sync
-      .store(true, std::sync::atomic::Ordering::SeqCst);
+  /// Returns the reasons that pause all the uploads. The workspace and file pauses are not
+  /// included, see [FileUploader::pause_reasons_of].
+  pub fn effective_pause_reasons(&self) -> PauseReasons {
+    *self.pause_reasons.borrow()
   }
 
-  pub fn disable_storage_write(&self) {
-    self
+  /// Returns the reasons that pause the upload of the given file.
+  pub fn pause_reasons_of(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> PauseReasons {
+    let mut reasons = self.effective_pause_reasons();
+    if self.is_workspace_paused(workspace_id) {
+      reasons.insert(PauseReasons::WORKSPACE_PAUSED);
+    }
+    let file_key = (
+      workspace_id.to_string(),
+      parent_dir.to_string(),
+      file_id.to_string(),
+    );
+    if self.paused_files.contains(&file_key) {
+      reasons.insert(PauseReasons::FILE_PAUSED);
+    }
+    reasons
+  }
+
+  /// Returns the reasons that pause the task: the reasons of its file, and
+  /// [PauseReasons::METERED] for the tasks that aren't immediate on a metered connection.
+  fn pause_reasons_of_task(&self, task: &UploadTask) -> PauseReasons {
+    let (workspace_id, parent_dir, file_id) = task.file();
+    let mut reasons = self.pause_reasons_of(workspace_id, parent_dir, file_id);
+    if !matches!(task, UploadTask::ImmediateTask { .. }) && self.is_metered() {
+      reasons.insert(PauseReasons::METERED);
+    }
+    reasons
+  }
+
+  /// Returns the number of the queued tasks, and the reasons that pause them when none of them can
+  /// run. The reasons are empty when the queue is empty or one of its tasks can run.
+  pub async fn queued_tasks(&self) -> (usize, PauseReasons) {
+    let tasks = self.queue.tasks.read().await;
+    let mut reasons = PauseReasons::NONE;
+    for task in tasks.iter() {
+      let task_reasons = self.pause_reasons_of_task(task);
+      if task_reasons.is_empty() {
+        return (tasks.len(), PauseReasons::NONE);
+      }
+      reasons.insert(task_reasons);
+    }
+    (tasks.len(), reasons)
+  }
+
+  /// Returns the number of the uploads the uploader is running.
+  pub fn running_uploads(&self) -> usize {
+    self.max_uploads - self.upload_permits.available_permits()
+  }
+
+  pub fn is_metered(&self) -> bool {
+    self.metered.load(std::sync::atomic::Ordering::SeqCst)
+  }
+
+  /// Sets whether the connection is metered. Returns true if it changed.
+  pub fn set_metered(&self, metered: bool) -> bool {
+    let changed = self
+      .metered
+      .swap(metered, std::sync::atomic::Ordering::SeqCst)
+      != metered;
+    if changed && !metered {
+      self.resume();
+    }
+    changed
+  }
+
+  pub fn set_network_reachable(&self, reachable: bool) {
+    self.set_pause_reason(PauseReasons::NETWORK_UNREACHABLE, !reachable);
+  }
+
+  /// Disables the storage write access. Returns true if the access was enabled before.
+  pub fn disable_storage_write(&self) -> bool {
+    let was_exceeded = self
       .has_exceeded_limit
-      .store(true, std::sync::atomic::Ordering::SeqCst);
-    self.pause();
+      .swap(true, std::sync::atomic::Ordering::SeqCst);
+    self.set_pause_reason(PauseReasons::STORAGE_WRITE_DISABLED, true);
+    !was_exceeded
   }
 
-  pub fn enable_storage_write(&self) {
-    self
+  /// Enables the storage write access. Returns true if the access was disabled before.
+  pub fn enable_storage_write(&self) -> bool {
+    let was_exceeded = self
       .has_exceeded_limit
-      .store(false, std::sync::atomic::Ordering::SeqCst);
-    self.resume();
+      .swap(false, std::sync::atomic::Ordering::SeqCst);
+    // The uploader stays paused while any other reason applies, e.g. the network is unreachable.
+    self.set_pause_reason(PauseReasons::STORAGE_WRITE_DISABLED, false);
+    was_exceeded
   }
 
-  pub fn resume(&self) {
-    self
-      .pause_sync
-      .store(false, std::sync::atomic::Ordering::SeqCst);
-    let _ = self.queue.notifier.send(Signal::ProceedAfterSecs(3));
+  pub fn is_storage_write_enabled(&self) -> bool {
+    !self
+      .has_exceeded_limit
+      .load(std::sync::atomic::Ordering::SeqCst)
   }
 
-  #[instrument(name = "[File]: process next", level = "debug", skip(self))]
-  pub async fn process_next(&self) -> Option<()> {
-    // Do not proceed if the uploader is paused.
-    if self.pause_sync.load(std::sync::atomic::Ordering::Relaxed) {
-      info!("[File] Uploader is paused");
-      return None;
+  pub fn is_workspace_paused(&self, workspace_id: &str) -> bool {
+    self.paused_workspaces.contains(workspace_id)
+  }
+
+  /// Pauses the uploads of the workspace. Returns true if they were not paused before.
+  pub fn pause_workspace(&self, workspace_id: &str) -> bool {
+    self.paused_workspaces.insert(workspace_id.to_string())
+  }
+
+  /// Resumes the uploads of the workspace. Returns true if they were paused before.
+  pub fn resume_workspace(&self, workspace_id: &str) -> bool {
+    let resumed = self.paused_workspaces.remove(workspace_id).is_some();
+    if resumed {
+      self.resume();
     }
+    resumed
+  }
 
-    let current_uploads = self
-      .current_uploads
-      .load(std::sync::atomic::Ordering::SeqCst);
-    if current_uploads > 0 {
-      trace!("[File] current upload tasks: {}", current_uploads)
+  /// Pauses the upload of the file. Returns true if it was not paused before.
+  pub fn pause_file(&self, workspace_id: &str, parent_dir: &str, file_id: &str) -> bool {
+    self.paused_files.insert((
+      workspace_id.to_string(),
+      parent_dir.to_string(),
+      file_id.to_string(),
+    ))
+  }
+
+  /// Resumes the upload of the file. Returns true if it was paused before.
+  pub fn resume_file(&self, workspace_id: &str, parent_dir: &str, file_id: &str) -> bool {
+    let file_key = (
+      workspace_id.to_string(),
+      parent_dir.to_string(),
+      file_id.to_string(),
+    );
+    let resumed = self.paused_files.remove(&file_key).is_some();
+    if resumed {
+      self.resume();
     }
+    resumed
+  }
 
-    if self
-      .current_uploads
-      .load(std::sync::atomic::Ordering::SeqCst)
-      >= self.max_uploads
-    {
-      // If the current uploads count is greater than or equal to the max uploads, do not proceed.
-      let _ = self.queue.notifier.send(Signal::ProceedAfterSecs(10));
-      trace!("[File] max uploads reached, process_next after 10 seconds");
-      return None;
+  /// Adds or removes the reason from the global pause reasons. The change is notified, and the
+  /// uploader resumes once no reason applies.
+  fn set_pause_reason(&self, reason: PauseReasons, paused: bool) {
+    let changed = self.pause_reasons.send_if_modified(|reasons| {
+      let old_reasons = *reasons;
+      reasons.set(reason, paused);
+      *reasons != old_reasons
+    });
+
+    if changed {
+      let reasons = self.effective_pause_reasons();
+      info!("[File] Uploader pause reasons changed: {:?}", reasons);
+      notify_pause_reasons(reasons);
+      self.resume();
     }
+  }
 
-    if self
-      .has_exceeded_limit
-      .load(std::sync::atomic::Ordering::SeqCst)
-    {
-      // If the storage limitation is enabled, do not proceed.
-      error!("[File] storage limit exceeded, uploader is disabled");
-      return None;
+  fn resume(&self) {
+    if self.effective_pause_reasons().is_empty() {
+      let _ = self.queue.notifier.send(Signal::ProceedAfterSecs(3));
     }
+  }
 
-    let task = self.queue.tasks.write().await.pop()?;
-    if task.retry_count() > 5 {
-      // If the task has been retried more than 5 times, we should not retry it anymore.
-      let _ = self.queue.notifier.send(Signal::ProceedAfterSecs(2));
-      warn!("[File] Task has been retried more than 5 times: {}", task);
+  #[instrument(name = "[File]: process next", level = "debug", skip(self))]
+  pub async fn process_next(&self) -> Option<()> {
+    // Do not proceed if the uploader is paused.
+    let pause_reasons = self.effective_pause_reasons();
+    if !pause_reasons.is_empty() {
+      info!("[File] Uploader is paused: {:?}", pause_reasons);
       return None;
     }
 
-    // increment the current uploads count
-    self
-      .current_uploads
-      .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    let current_uploads = self.max_uploads - self.upload_permits.available_permits();
+    if current_uploads > 0 {
+      trace!("[File] current upload tasks: {}", current_uploads)
+    }
+
+    // The permit is acquired before taking the task, so concurrent calls can't exceed the max
+    // uploads. The tasks beyond the limit stay in the queue.
+    let permit = match self.upload_permits.clone().try_acquire_owned() {
+      Ok(permit) => permit,
+      Err(_) => {
+        let _ = self.queue.notifier.send(Signal::ProceedAfterSecs(10));
+        trace!("[File] max uploads reached, process_next after 10 seconds");
+        return None;
+      },
+    };
 
+    let task = self.pop_unpaused_task().await?;
+    let key = task_key(&task);
+    self.queue.record_started(&task);
+    self.queue.notify_changed();
     match task {
       UploadTask::ImmediateTask {
         local_file_path,
         record,
-        mut retry_count,
+        retries_left,
       }
       | UploadTask::Task {
         local_file_path,
         record,
-        mut retry_count,
+        retries_left,
       } => {
         let record = BoxAny::new(record);
         if let Err(err) = self.storage_service.start_upload(&record).await {
@@ -169,26 +458,48 @@ impl FileUploader {
           }
 
           if err.should_retry_upload() {
-            info!(
-              "[File] Failed to upload file: {}, retry_count:{}",
-              err, retry_count
-            );
             let record = record.unbox_or_error().unwrap();
-            retry_count += 1;
-            self.queue.tasks.write().await.push(UploadTask::Task {
-              local_file_path,
-              record,
-              retry_count,
-            });
+            match retries_left.checked_sub(1) {
+              Some(retries_left) => {
+                info!(
+                  "[File] Failed to upload file: {}, retries left: {}",
+                  err, retries_left
+                );
+                self
+                  .upload_log
+                  .record(
+                    &record.workspace_id,
+                    &record.parent_dir,
+                    &record.file_id,
+                    UploadLogEvent::Retry(retries_left),
+                  )
+                  .await;
+                // Queued like any other task, so that it's counted, tracked and picked up.
+                self
+                  .queue
+                  .queue_task(UploadTask::Task {
+                    local_file_path,
+                    record,
+                    retries_left,
+                  })
+                  .await;
+              },
+              None => warn!(
+                "[File] give up uploading file: {}, no retry left: {}",
+                record.file_id, err
+              ),
+            }
           }
+        } else {
+          self.queue.record_finished(&key);
         }
       },
       UploadTask::BackgroundTask {
         workspace_id,
         parent_dir,
         file_id,
-        created_at,
-        mut retry_count,
+        seq,
+        retries_left,
       } => {
         if let Err(err) = self
           .storage_service
@@ -201,26 +512,48 @@ impl FileUploader {
           }
 
           if err.should_retry_upload() {
-            info!(
-              "[File] failed to resume upload file: {}, retry_count:{}",
-              err, retry_count
-            );
-            retry_count += 1;
-            self.queue.tasks.write().await.push(BackgroundTask {
-              workspace_id,
-              parent_dir,
-              file_id,
-              created_at,
-              retry_count,
-            });
+            match retries_left.checked_sub(1) {
+              Some(retries_left) => {
+                info!(
+                  "[File] failed to resume upload file: {}, retries left: {}",
+                  err, retries_left
+                );
+                self
+                  .upload_log
+                  .record(
+                    &workspace_id,
+                    &parent_dir,
+                    &file_id,
+                    UploadLogEvent::Retry(retries_left),
+                  )
+                  .await;
+                self
+                  .queue
+                  .queue_task(BackgroundTask {
+                    workspace_id,
+                    parent_dir,
+                    file_id,
+                    seq,
+                    retries_left,
+                  })
+                  .await;
+              },
+              None => warn!(
+                "[File] give up resuming upload file: {}, no retry left: {}",
+                file_id, err
+              ),
+            }
           }
+        } else {
+          self.queue.record_finished(&key);
         }
       },
     }
 
-    self
-      .current_uploads
-      .fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+    // The permit is released first, so that the done task isn't counted as running anymore.
+    drop(permit);
+    self.queue.notify_changed();
+
     trace!("[File] process_next after 2 seconds");
     self
       .queue
@@ -230,10 +563,43 @@ impl FileUploader {
   }
 }
 
+impl FileUploader {
+  /// Pops the next task whose file and workspace are not paused, skipping the tasks paused by a
+  /// metered connection. The paused tasks stay in the queue, they are picked up once resumed.
+  async fn pop_unpaused_task(&self) -> Option<UploadTask> {
+    let mut tasks = self.queue.tasks.write().await;
+    let mut paused_tasks = vec![];
+    let mut next_task = None;
+    while let Some(task) = tasks.pop() {
+      if self.pause_reasons_of_task(&task).is_empty() {
+        next_task = Some(task);
+        break;
+      }
+      trace!("[File] skip paused task: {}", task);
+      paused_tasks.push(task);
+    }
+    tasks.extend(paused_tasks);
+    next_task
+  }
+}
+
+fn notify_pause_reasons(reasons: PauseReasons) {
+  make_notification(StorageNotification::UploadPauseReasonsChanged)
+    .payload(UploadPauseReasonsPB {
+      reasons: reasons.bits() as i32,
+    })
+    .send();
+}
+
 pub struct FileUploaderRunner;
 
 impl FileUploaderRunner {
-  pub async fn run(weak_uploader: Weak<FileUploader>, mut notifier: watch::Receiver<Signal>) {
+  /// Processes the queue each time the notifier signals, the uploads running on the spawner.
+  pub async fn run(
+    weak_uploader: Weak<FileUploader>,
+    mut notifier: watch::Receiver<Signal>,
+    spawner: Arc<dyn Spawner>,
+  ) {
     loop {
       // stops the runner if the notifier was closed.
       if notifier.changed().await.is_err() {
@@ -253,18 +619,19 @@ impl FileUploaderRunner {
             break;
           },
           Signal::Proceed => {
-            tokio::spawn(async move {
+            spawner.spawn(Box::pin(async move {
               uploader.process_next().await;
-            });
+            }));
           },
           Signal::ProceedAfterSecs(secs) => {
-            tokio::time::sleep(Duration::from_secs(secs)).await;
-            tokio::spawn(async move {
+            uploader.queue.clock.sleep(Duration::from_secs(secs)).await;
+            spawner.spawn(Box::pin(async move {
               uploader.process_next().await;
-            });
+            }));
           },
         }
       } else {
+        // The uploader is owned by the storage manager, so this only happens on teardown.
         info!("[File]:Uploader runner stopped, uploader dropped");
         break;
       }
@@ -272,32 +639,81 @@ impl FileUploaderRunner {
   }
 }
 
+/// A queued upload. `retries_left` is the number of times the task is still queued again after a
+/// retryable failure: each failure requeues it with one retry less, and a failure with no retry
+/// left drops it. It starts at the retries configured for the kind of the task, see
+/// [crate::config::StorageManagerConfig::task_retries].
 pub enum UploadTask {
   ImmediateTask {
     local_file_path: String,
     record: UploadFileTable,
-    retry_count: u8,
+    retries_left: u8,
   },
   Task {
     local_file_path: String,
     record: UploadFileTable,
-    retry_count: u8,
+    retries_left: u8,
   },
   BackgroundTask {
     workspace_id: String,
     file_id: String,
     parent_dir: String,
-    created_at: i64,
-    retry_count: u8,
+    /// The [crate::sqlite_sql::UploadFileTable::seq] of the upload.
+    seq: i64,
+    retries_left: u8,
   },
 }
 
 impl UploadTask {
-  pub fn retry_count(&self) -> u8 {
+  pub fn retries_left(&self) -> u8 {
     match self {
-      UploadTask::ImmediateTask { retry_count, .. } => *retry_count,
-      UploadTask::Task { retry_count, .. } => *retry_count,
-      UploadTask::BackgroundTask { retry_count, .. } => *retry_count,
+      UploadTask::ImmediateTask { retries_left, .. } => *retries_left,
+      UploadTask::Task { retries_left, .. } => *retries_left,
+      UploadTask::BackgroundTask { retries_left, .. } => *retries_left,
+    }
+  }
+
+  pub fn workspace_id(&self) -> &str {
+    match self {
+      UploadTask::ImmediateTask { record, .. } | UploadTask::Task { record, .. } => {
+        &record.workspace_id
+      },
+      UploadTask::BackgroundTask { workspace_id, .. } => workspace_id,
+    }
+  }
+
+  /// Returns the workspace id, parent dir and file id of the task.
+  pub fn file(&self) -> (&str, &str, &str) {
+    match self {
+      UploadTask::ImmediateTask { record, .. } | UploadTask::Task { record, .. } => {
+        (&record.workspace_id, &record.parent_dir, &record.file_id)
+      },
+      UploadTask::BackgroundTask {
+        workspace_id,
+        parent_dir,
+        file_id,
+        ..
+      } => (workspace_id, parent_dir, file_id),
+    }
+  }
+
+  pub fn is_task_of(&self, workspace_id: &str, parent_dir: &str, file_id: &str) -> bool {
+    match self {
+      UploadTask::ImmediateTask { record, .. } | UploadTask::Task { record, .. } => {
+        record.workspace_id == workspace_id
+          && record.parent_dir == parent_dir
+          && record.file_id == file_id
+      },
+      UploadTask::BackgroundTask {
+        workspace_id: task_workspace_id,
+        parent_dir: task_parent_dir,
+        file_id: task_file_id,
+        ..
+      } => {
+        task_workspace_id == workspace_id
+          && task_parent_dir == parent_dir
+          && task_file_id == file_id
+      },
     }
   }
 }
@@ -350,23 +766,16 @@ impl Ord for UploadTask {
   fn cmp(&self, other: &Self) -> Ordering {
     match (self, other) {
       (Self::ImmediateTask { record: lhs, .. }, Self::ImmediateTask { record: rhs, .. }) => {
-        lhs.created_at.cmp(&rhs.created_at)
+        lhs.seq.cmp(&rhs.seq)
       },
       (_, Self::ImmediateTask { .. }) => Ordering::Less,
       (Self::ImmediateTask { .. }, _) => Ordering::Greater,
-      (Self::Task { record: lhs, .. }, Self::Task { record: rhs, .. }) => {
-        lhs.created_at.cmp(&rhs.created_at)
-      },
+      (Self::Task { record: lhs, .. }, Self::Task { record: rhs, .. }) => lhs.seq.cmp(&rhs.seq),
       (_, Self::Task { .. }) => Ordering::Less,
       (Self::Task { .. }, _) => Ordering::Greater,
-      (
-        Self::BackgroundTask {
-          created_at: lhs, ..
-        },
-        Self::BackgroundTask {
-          created_at: rhs, ..
-        },
-      ) => lhs.cmp(rhs),
+      (Self::BackgroundTask { seq: lhs, .. }, Self::BackgroundTask { seq: rhs, .. }) => {
+        lhs.cmp(rhs)
+      },
     }
   }
 }
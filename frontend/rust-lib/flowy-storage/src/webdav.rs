@@ -0,0 +1,447 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use flowy_error::{FlowyError, FlowyResult};
+use flowy_storage_pub::cloud::{
+  ObjectIdentity, ObjectRange, ObjectValue, StorageCapabilities, StorageCloudService,
+};
+use flowy_storage_pub::storage::{CompletedPartRequest, CreateUploadResponse, UploadPartResponse};
+use percent_encoding::percent_decode_str;
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, ETAG, RANGE};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use std::ops::Range;
+use tokio::sync::mpsc;
+use tracing::warn;
+use url::Url;
+
+/// The collection of each workspace holding the parts of the unfinished uploads.
+const UPLOADS_COLLECTION: &str = ".uploads";
+
+/// A [StorageCloudService] storing the objects on a WebDAV server, for the self-hosters.
+///
+/// The object of a file is stored at `{base_url}/{workspace_id}/{parent_dir}/{file_id}`, the
+/// collections are created on demand. WebDAV has no multipart upload, so it's emulated: each part
+/// is stored as a temp file in `{base_url}/{workspace_id}/.uploads/{upload_id}`, and completing the
+/// upload moves the single part to the object, or streams the parts in order into the object with a
+/// single PUT. The upload collection is deleted once the upload is completed or aborted.
+pub struct WebDavCloudService {
+  client: Client,
+  base_url: Url,
+  credentials: Option<(String, String)>,
+}
+
+impl WebDavCloudService {
+  /// Returns an error when the base url can't hold paths, e.g. a `mailto:` url.
+  pub fn new(base_url: Url) -> FlowyResult<Self> {
+    if base_url.cannot_be_a_base() {
+      return Err(
+        FlowyError::invalid_data()
+          .with_context(format!("the webdav url can't hold paths: {}", base_url)),
+      );
+    }
+    Ok(Self {
+      client: Client::new(),
+      base_url,
+      credentials: None,
+    })
+  }
+
+  /// Authenticates the requests with the basic authentication.
+  pub fn with_credentials(mut self, username: &str, password: &str) -> Self {
+    self.credentials = Some((username.to_string(), password.to_string()));
+    self
+  }
+
+  pub fn with_client(mut self, client: Client) -> Self {
+    self.client = client;
+    self
+  }
+
+  /// Returns the url of the resource at the segments under the base url.
+  fn url<'a>(&self, segments: impl IntoIterator<Item = &'a str>) -> Url {
+    let mut url = self.base_url.clone();
+    url
+      .path_segments_mut()
+      .expect("the base url can hold paths")
+      .pop_if_empty()
+      .extend(segments);
+    url
+  }
+
+  fn object_url(&self, workspace_id: &str, parent_dir: &str, file_id: &str) -> Url {
+    self.url(
+      std::iter::once(workspace_id)
+        .chain(parent_dir.split('/'))
+        .chain(std::iter::once(file_id)),
+    )
+  }
+
+  fn upload_url(&self, workspace_id: &str, upload_id: &str) -> Url {
+    self.url([workspace_id, UPLOADS_COLLECTION, upload_id])
+  }
+
+  fn part_url(&self, workspace_id: &str, upload_id: &str, part_number: i32) -> Url {
+    let part_number = part_number.to_string();
+    self.url([
+      workspace_id,
+      UPLOADS_COLLECTION,
+      upload_id,
+      part_number.as_str(),
+    ])
+  }
+
+  /// Returns the decoded segments of the url under the base url, `None` when the url is not under
+  /// the base url.
+  fn relative_segments(&self, url: &Url) -> Option<Vec<String>> {
+    if url.origin() != self.base_url.origin() {
+      return None;
+    }
+    let base_path = self.base_url.path().trim_end_matches('/');
+    let path = url.path().strip_prefix(base_path)?.strip_prefix('/')?;
+    path
+      .split('/')
+      .map(|segment| {
+        percent_decode_str(segment)
+          .decode_utf8()
+          .ok()
+          .map(|segment| segment.into_owned())
+      })
+      .collect()
+  }
+
+  fn request(&self, method: Method, url: Url) -> RequestBuilder {
+    let builder = self.client.request(method, url);
+    match &self.credentials {
+      Some((username, password)) => builder.basic_auth(username, Some(password)),
+      None => builder,
+    }
+  }
+
+  async fn send(&self, builder: RequestBuilder) -> FlowyResult<Response> {
+    let resp = builder.send().await?;
+    check_status(resp)
+  }
+
+  /// Creates the collections holding the resource at the url, from the base url down. A
+  /// collection that already exists is fine.
+  async fn create_parent_collections(&self, url: &Url) -> FlowyResult<()> {
+    let segments = self.relative_segments(url).ok_or_else(|| {
+      FlowyError::invalid_data().with_context(format!("not a webdav url: {}", url))
+    })?;
+    for depth in 1..segments.len() {
+      let mut collection = self.url(segments[..depth].iter().map(String::as_str));
+      // The collections are addressed with a trailing slash.
+      collection.path_segments_mut().unwrap().push("");
+      let resp = self.request(mkcol(), collection).send().await?;
+      if resp.status() != StatusCode::METHOD_NOT_ALLOWED {
+        check_status(resp)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Puts the parts, in order, into the object with a single PUT. The parts are streamed, so they
+  /// are never held in memory at once.
+  async fn concat_parts(&self, part_urls: Vec<Url>, object_url: Url) -> FlowyResult<()> {
+    let (tx, mut rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(4);
+    let body =
+      reqwest::Body::wrap_stream(futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx)));
+    let put = self.send(self.request(Method::PUT, object_url).body(body));
+    let read_parts = async move {
+      let result = async {
+        for part_url in part_urls {
+          let mut resp = self.send(self.request(Method::GET, part_url)).await?;
+          while let Some(chunk) = resp.chunk().await? {
+            if tx.send(Ok(chunk)).await.is_err() {
+              // The PUT failed, its error is returned.
+              return Ok(());
+            }
+          }
+        }
+        Ok::<_, FlowyError>(())
+      }
+      .await;
+      if let Err(err) = &result {
+        // Fail the PUT, so that the object is not stored without the rest of the parts.
+        let _ = tx.send(Err(std::io::Error::other(err.to_string()))).await;
+      }
+      result
+    };
+    let (put, read_parts) = futures_util::future::join(put, read_parts).await;
+    read_parts?;
+    put?;
+    Ok(())
+  }
+}
+
+#[async_trait]
+impl StorageCloudService for WebDavCloudService {
+  async fn get_object_url(&self, object_id: ObjectIdentity) -> Result<String, FlowyError> {
+    let file_name = format!("{}.{}", object_id.file_id, object_id.ext);
+    Ok(
+      self
+        .url([object_id.workspace_id.as_str(), file_name.as_str()])
+        .to_string(),
+    )
+  }
+
+  async fn put_object(&self, url: String, object_value: ObjectValue) -> Result<(), FlowyError> {
+    let url = parse_url(&url)?;
+    self.create_parent_collections(&url).await?;
+    self
+      .send(
+        self
+          .request(Method::PUT, url)
+          .header(CONTENT_TYPE, object_value.mime.to_string())
+          .body(object_value.raw),
+      )
+      .await?;
+    Ok(())
+  }
+
+  async fn delete_object(&self, url: &str) -> Result<(), FlowyError> {
+    let resp = self.request(Method::DELETE, parse_url(url)?).send().await?;
+    // The object is already gone.
+    if resp.status() != StatusCode::NOT_FOUND {
+      check_status(resp)?;
+    }
+    Ok(())
+  }
+
+  async fn get_object(&self, url: String) -> Result<ObjectValue, FlowyError> {
+    let url = parse_url(&url)?;
+    let resp = self.send(self.request(Method::GET, url.clone())).await?;
+    let mime = resp
+      .headers()
+      .get(CONTENT_TYPE)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.parse().ok())
+      .unwrap_or_else(|| mime_guess::from_path(url.path()).first_or_octet_stream());
+    let raw = resp.bytes().await?;
+    Ok(ObjectValue { raw, mime })
+  }
+
+  async fn get_object_url_v1(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<String> {
+    Ok(
+      self
+        .object_url(workspace_id, parent_dir, file_id)
+        .to_string(),
+    )
+  }
+
+  async fn parse_object_url_v1(&self, url: &str) -> Option<(String, String, String)> {
+    let segments = self.relative_segments(&Url::parse(url).ok()?)?;
+    match segments.as_slice() {
+      [workspace_id, parent_dir @ .., file_id]
+        if !parent_dir.is_empty() && parent_dir[0] != UPLOADS_COLLECTION =>
+      {
+        Some((workspace_id.clone(), parent_dir.join("/"), file_id.clone()))
+      },
+      _ => None,
+    }
+  }
+
+  async fn object_exists(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<bool> {
+    let url = self.object_url(workspace_id, parent_dir, file_id);
+    let resp = self.request(Method::HEAD, url).send().await?;
+    if resp.status() == StatusCode::NOT_FOUND {
+      return Ok(false);
+    }
+    check_status(resp)?;
+    Ok(true)
+  }
+
+  fn capabilities(&self) -> StorageCapabilities {
+    StorageCapabilities {
+      range_downloads: true,
+      abort_upload: true,
+      head_object: true,
+      ..Default::default()
+    }
+  }
+
+  async fn get_object_range(&self, url: String, range: Range<u64>) -> FlowyResult<ObjectRange> {
+    if range.is_empty() {
+      return Err(FlowyError::invalid_data().with_context("empty range"));
+    }
+    let resp = self
+      .request(Method::GET, parse_url(&url)?)
+      .header(RANGE, format!("bytes={}-{}", range.start, range.end - 1))
+      .send()
+      .await?;
+    let total_size = resp
+      .headers()
+      .get(CONTENT_RANGE)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.rsplit('/').next())
+      .and_then(|total| total.parse::<u64>().ok());
+    match resp.status() {
+      StatusCode::RANGE_NOT_SATISFIABLE => Ok(ObjectRange {
+        raw: Bytes::new(),
+        total_size: total_size.unwrap_or(range.start),
+      }),
+      StatusCode::PARTIAL_CONTENT => {
+        let raw = resp.bytes().await?;
+        let total_size = total_size.unwrap_or(range.start + raw.len() as u64);
+        Ok(ObjectRange { raw, total_size })
+      },
+      _ => {
+        // The server ignored the range and returned the whole object.
+        let raw = check_status(resp)?.bytes().await?;
+        let total_size = raw.len() as u64;
+        let start = (range.start as usize).min(raw.len());
+        let end = (range.end as usize).min(raw.len());
+        Ok(ObjectRange {
+          raw: raw.slice(start..end),
+          total_size,
+        })
+      },
+    }
+  }
+
+  async fn abort_upload(
+    &self,
+    workspace_id: &str,
+    _parent_dir: &str,
+    upload_id: &str,
+    _file_id: &str,
+  ) -> FlowyResult<()> {
+    let url = self.upload_url(workspace_id, upload_id);
+    let resp = self.request(Method::DELETE, url).send().await?;
+    if resp.status() != StatusCode::NOT_FOUND {
+      check_status(resp)?;
+    }
+    Ok(())
+  }
+
+  async fn create_upload(
+    &self,
+    workspace_id: &str,
+    _parent_dir: &str,
+    file_id: &str,
+    _content_type: &str,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    let upload_id = uuid::Uuid::new_v4().to_string();
+    // The part url is under the upload collection, so all the collections up to it are created.
+    let part_url = self.part_url(workspace_id, &upload_id, 1);
+    self.create_parent_collections(&part_url).await?;
+    Ok(CreateUploadResponse {
+      file_id: file_id.to_string(),
+      upload_id,
+    })
+  }
+
+  async fn upload_part(
+    &self,
+    workspace_id: &str,
+    _parent_dir: &str,
+    upload_id: &str,
+    _file_id: &str,
+    part_number: i32,
+    body: Vec<u8>,
+  ) -> Result<UploadPartResponse, FlowyError> {
+    let url = self.part_url(workspace_id, upload_id, part_number);
+    let resp = self.send(self.request(Method::PUT, url).body(body)).await?;
+    let e_tag = resp
+      .headers()
+      .get(ETAG)
+      .and_then(|value| value.to_str().ok())
+      .unwrap_or_default()
+      .to_string();
+    Ok(UploadPartResponse {
+      e_tag,
+      part_num: part_number,
+    })
+  }
+
+  async fn complete_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    upload_id: &str,
+    file_id: &str,
+    mut parts: Vec<CompletedPartRequest>,
+  ) -> Result<(), FlowyError> {
+    let object_url = self.object_url(workspace_id, parent_dir, file_id);
+    self.create_parent_collections(&object_url).await?;
+    parts.sort_by_key(|part| part.part_number);
+    match parts.as_slice() {
+      [] => {
+        self.send(self.request(Method::PUT, object_url)).await?;
+      },
+      [part] => {
+        // A single part is already the object, the server moves it without copying the bytes.
+        let part_url = self.part_url(workspace_id, upload_id, part.part_number);
+        self
+          .send(
+            self
+              .request(move_method(), part_url)
+              .header("Destination", object_url.as_str())
+              .header("Overwrite", "T"),
+          )
+          .await?;
+      },
+      parts => {
+        let part_urls = parts
+          .iter()
+          .map(|part| self.part_url(workspace_id, upload_id, part.part_number))
+          .collect();
+        self.concat_parts(part_urls, object_url).await?;
+      },
+    }
+
+    // The object is complete, a leftover upload collection only takes space on the server.
+    if let Err(err) = self
+      .abort_upload(workspace_id, parent_dir, upload_id, file_id)
+      .await
+    {
+      warn!(
+        "[File] delete the webdav upload collection {} failed: {}",
+        upload_id, err
+      );
+    }
+    Ok(())
+  }
+}
+
+fn parse_url(url: &str) -> FlowyResult<Url> {
+  Url::parse(url)
+    .map_err(|err| FlowyError::invalid_data().with_context(format!("invalid url {}: {}", url, err)))
+}
+
+fn mkcol() -> Method {
+  Method::from_bytes(b"MKCOL").expect("MKCOL is a valid method")
+}
+
+fn move_method() -> Method {
+  Method::from_bytes(b"MOVE").expect("MOVE is a valid method")
+}
+
+/// Maps the failed responses to the errors the storage tells apart.
+fn check_status(resp: Response) -> FlowyResult<Response> {
+  let status = resp.status();
+  if status.is_success() {
+    return Ok(resp);
+  }
+  let err = match status {
+    StatusCode::NOT_FOUND => FlowyError::record_not_found(),
+    StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => FlowyError::unauthorized(),
+    StatusCode::INSUFFICIENT_STORAGE => FlowyError::file_storage_limit(),
+    status if status.is_server_error() => FlowyError::server_error(),
+    _ => FlowyError::http(),
+  };
+  Err(err.with_context(format!(
+    "webdav request to {} failed: {}",
+    resp.url(),
+    status
+  )))
+}
@@ -0,0 +1,70 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn abort_server_upload_on_cancel_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_secs(1)));
+  let workspace_id = test.workspace_id();
+  let parent_dir = "abort_cancel_test";
+  // Three parts
+  let file_path = create_temp_file(12 * 1024 * 1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+
+  // Cancel after the first part was uploaded.
+  tokio::time::sleep(Duration::from_millis(1500)).await;
+  test
+    .manager
+    .cancel_upload(&workspace_id, parent_dir, &created_upload.file_id)
+    .await
+    .unwrap();
+  assert_eq!(
+    test.cloud_service.abort_upload_count.load(Ordering::SeqCst),
+    1
+  );
+  assert!(test.cloud_service.parts.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn abort_server_upload_on_restart_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .invalid_parts_failures
+    .store(1, Ordering::SeqCst);
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  let (_, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "abort_restart_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+
+  // The parts of the first upload are rejected, so it's discarded and the file is uploaded again.
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert_eq!(
+    test.cloud_service.abort_upload_count.load(Ordering::SeqCst),
+    1
+  );
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    2
+  );
+}
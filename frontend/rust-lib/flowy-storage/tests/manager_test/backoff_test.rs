@@ -0,0 +1,25 @@
+use flowy_storage::backoff::{retry_backoff, MAX_RETRY_BACKOFF};
+use std::time::Duration;
+
+#[test]
+fn retry_backoff_doubles_until_the_cap_test() {
+  let delay = Duration::from_millis(500);
+  assert_eq!(retry_backoff(delay, 1), delay);
+  assert_eq!(retry_backoff(delay, 2), Duration::from_secs(1));
+  assert_eq!(retry_backoff(delay, 4), Duration::from_secs(4));
+  assert_eq!(retry_backoff(delay, 10), MAX_RETRY_BACKOFF);
+}
+
+#[test]
+fn retry_backoff_with_large_attempts_test() {
+  // The attempts come from the config, a large count must not overflow the backoff.
+  for attempt in [32, 33, 64, 1000, u32::MAX] {
+    assert_eq!(
+      retry_backoff(Duration::from_millis(500), attempt),
+      MAX_RETRY_BACKOFF
+    );
+  }
+  assert_eq!(retry_backoff(Duration::ZERO, u32::MAX), Duration::ZERO);
+  // A configured delay above the cap is kept.
+  assert_eq!(retry_backoff(Duration::MAX, u32::MAX), Duration::MAX);
+}
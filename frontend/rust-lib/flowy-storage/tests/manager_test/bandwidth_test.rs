@@ -0,0 +1,56 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use std::time::{Duration, Instant};
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn file_bandwidth_limit_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let limited_file = create_temp_file(20 * MB, "txt");
+  let unlimited_file = create_temp_file(20 * MB, "txt");
+
+  let start = Instant::now();
+  let (limited_upload, limited_rx) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "bandwidth_test",
+      limited_file.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  // 5MB per second, the size of a part.
+  test
+    .manager
+    .set_file_bandwidth_limit(&limited_upload.file_id, Some(5 * MB as u64));
+  let (_, unlimited_rx) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "bandwidth_test",
+      unlimited_file.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+
+  let limited = tokio::spawn(async move {
+    assert!(wait_for_finished(&mut limited_rx.unwrap(), Duration::from_secs(60)).await);
+    start.elapsed()
+  });
+  let unlimited = tokio::spawn(async move {
+    assert!(wait_for_finished(&mut unlimited_rx.unwrap(), Duration::from_secs(60)).await);
+    start.elapsed()
+  });
+  let limited_elapsed = limited.await.unwrap();
+  let unlimited_elapsed = unlimited.await.unwrap();
+
+  // The first part might be sent before the limit is set, the remaining three parts are paced at
+  // one part per second.
+  assert!(limited_elapsed >= Duration::from_millis(1900));
+  assert!(unlimited_elapsed < limited_elapsed);
+}
@@ -0,0 +1,123 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_error::ErrorCode;
+use flowy_storage::sqlite_sql::{select_upload_file, select_upload_files};
+use flowy_storage_pub::storage::CancellationToken;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn cancel_upload_during_slow_part_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_secs(10)));
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "cancel_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+
+  // Wait until the part request is in flight, then cancel the upload.
+  tokio::time::sleep(Duration::from_secs(1)).await;
+  let start = std::time::Instant::now();
+  test
+    .manager
+    .cancel_upload(&workspace_id, "cancel_test", &created_upload.file_id)
+    .await
+    .unwrap();
+  tokio::time::sleep(Duration::from_millis(500)).await;
+  assert!(start.elapsed() < Duration::from_secs(5));
+
+  // The in-flight request was dropped before it finished, and nothing was persisted.
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    0
+  );
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    0
+  );
+  let mut conn = test.db_connection();
+  let record = select_upload_file(
+    &mut conn,
+    &workspace_id,
+    "cancel_test",
+    &created_upload.file_id,
+  )
+  .unwrap();
+  assert!(record.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn cancel_create_upload_during_preparation_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  // Large enough that hashing and copying the file takes a while.
+  let file_path = create_temp_file(64 * 1024 * 1024, "txt");
+
+  let cancel_token = CancellationToken::new();
+  let storage_service = test.manager.storage_service();
+  let token = cancel_token.clone();
+  let handle = tokio::spawn(async move {
+    storage_service
+      .create_upload_with_cancel(
+        &workspace_id,
+        "cancel_create_test",
+        file_path.to_str().unwrap(),
+        true,
+        token,
+      )
+      .await
+  });
+  tokio::time::sleep(Duration::from_millis(5)).await;
+  cancel_token.cancel();
+
+  let err = handle.await.unwrap().err().unwrap();
+  assert_eq!(err.code, ErrorCode::UploadCancelled);
+
+  // Neither a partial temp file nor an upload record is left behind.
+  let temp_dir = test.manager.user_temp_dir();
+  assert_eq!(std::fs::read_dir(temp_dir).unwrap().count(), 0);
+  assert!(select_upload_files(&mut test.db_connection(), 0, 10)
+    .unwrap()
+    .is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn create_upload_with_cancelled_token_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+
+  let cancel_token = CancellationToken::new();
+  cancel_token.cancel();
+  let err = test
+    .manager
+    .storage_service()
+    .create_upload_with_cancel(
+      &workspace_id,
+      "cancel_create_test",
+      file_path.to_str().unwrap(),
+      true,
+      cancel_token,
+    )
+    .await
+    .err()
+    .unwrap();
+  assert_eq!(err.code, ErrorCode::UploadCancelled);
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    0
+  );
+}
@@ -0,0 +1,62 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::manager::CancelledUploads;
+use flowy_storage::sqlite_sql::select_workspace_upload_files;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn cancel_workspace_uploads_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_secs(10)));
+  let parent_dir = "cancel_workspace_test";
+
+  let workspace_a = test.workspace_id();
+  for _ in 0..2 {
+    let file_path = create_temp_file(1024, "txt");
+    test
+      .manager
+      .storage_service()
+      .create_upload(&workspace_a, parent_dir, file_path.to_str().unwrap(), true)
+      .await
+      .unwrap();
+  }
+  let workspace_b = uuid::Uuid::new_v4().to_string();
+  let file_path = create_temp_file(1024, "txt");
+  test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_b, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  tokio::time::sleep(Duration::from_secs(1)).await;
+
+  let cancelled = test
+    .manager
+    .cancel_workspace_uploads(&workspace_a)
+    .await
+    .unwrap();
+  assert_eq!(cancelled.records, 2);
+  assert_eq!(cancelled.active + cancelled.queued, 2);
+
+  let mut conn = test.db_connection();
+  assert!(
+    select_workspace_upload_files(&mut conn, &workspace_a, false)
+      .unwrap()
+      .is_empty()
+  );
+  assert_eq!(
+    select_workspace_upload_files(&mut conn, &workspace_b, false)
+      .unwrap()
+      .len(),
+    1
+  );
+
+  // Cancelling again has nothing left to cancel.
+  let cancelled = test
+    .manager
+    .cancel_workspace_uploads(&workspace_a)
+    .await
+    .unwrap();
+  assert_eq!(cancelled, CancelledUploads::default());
+}
@@ -0,0 +1,77 @@
+use crate::util::{generate_random_string, StorageTest};
+use bytes::Bytes;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage_pub::storage::DownloadState;
+use std::env::temp_dir;
+use std::sync::atomic::Ordering;
+
+const URL: &str = "https://mock.appflowy.io/api/file_storage/capabilities_test";
+const CONTENT: &[u8] = b"0123456789";
+
+/// Downloads the object with ranges of 4 bytes, when the backend supports them.
+async fn download_with_small_ranges(range_downloads: bool) -> StorageTest {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .download_range_size(4)
+      .download_range_concurrency(2),
+  )
+  .await;
+  test
+    .cloud_service
+    .range_downloads
+    .store(range_downloads, Ordering::SeqCst);
+  test
+    .cloud_service
+    .objects
+    .insert(URL.to_string(), Bytes::from_static(CONTENT));
+
+  let local_file_path = temp_dir()
+    .join(generate_random_string(8))
+    .to_str()
+    .unwrap()
+    .to_string();
+  let handle = test
+    .manager
+    .download_objects(vec![(URL.to_string(), local_file_path.clone())]);
+  let mut state = handle.receiver(URL, &local_file_path).unwrap();
+  let state = state
+    .wait_for(DownloadState::is_finished)
+    .await
+    .unwrap()
+    .clone();
+  assert_eq!(state, DownloadState::Downloaded);
+  assert_eq!(std::fs::read(&local_file_path).unwrap(), CONTENT);
+  test
+}
+
+#[tokio::test]
+async fn download_without_range_support_uses_single_request_test() {
+  let test = download_with_small_ranges(false).await;
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    1
+  );
+  assert_eq!(
+    test
+      .cloud_service
+      .get_object_range_count
+      .load(Ordering::SeqCst),
+    0
+  );
+}
+
+#[tokio::test]
+async fn download_with_range_support_fetches_ranges_test() {
+  let test = download_with_small_ranges(true).await;
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    0
+  );
+  assert_eq!(
+    test
+      .cloud_service
+      .get_object_range_count
+      .load(Ordering::SeqCst),
+    3
+  );
+}
@@ -0,0 +1,149 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::clock::Clock;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::sqlite_sql::{select_upload_file, select_upload_files, UploadFileTable};
+use futures_util::future::BoxFuture;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+const PARENT_DIR: &str = "clock_skew_test";
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// The real time, except the wall-clock time which can be moved backward.
+#[derive(Debug, Default)]
+struct SkewedClock {
+  rewind: Mutex<Duration>,
+}
+
+impl SkewedClock {
+  fn rewind(&self, duration: Duration) {
+    *self.rewind.lock().unwrap() += duration;
+  }
+}
+
+impl Clock for SkewedClock {
+  fn now(&self) -> Instant {
+    Instant::now()
+  }
+
+  fn system_now(&self) -> SystemTime {
+    SystemTime::now() - *self.rewind.lock().unwrap()
+  }
+
+  fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+    Box::pin(tokio::time::sleep(duration))
+  }
+}
+
+async fn create_upload(test: &StorageTest) -> String {
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &test.workspace_id(),
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  created_upload.file_id
+}
+
+fn select_record(test: &StorageTest, file_id: &str) -> UploadFileTable {
+  select_upload_file(
+    &mut test.db_connection(),
+    &test.workspace_id(),
+    PARENT_DIR,
+    file_id,
+  )
+  .unwrap()
+  .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn backward_clock_keeps_upload_order_test() {
+  let clock = Arc::new(SkewedClock::default());
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .max_concurrent_uploads(1)
+      .clock(clock.clone()),
+  )
+  .await;
+  test.manager.update_network_reachable(false);
+
+  let first = create_upload(&test).await;
+  clock.rewind(HOUR);
+  let second = create_upload(&test).await;
+
+  // The second upload looks older by its wall-clock time, but it's ordered after the first one.
+  let first_record = select_record(&test, &first);
+  let second_record = select_record(&test, &second);
+  assert!(second_record.created_at < first_record.created_at);
+  assert!(second_record.seq > first_record.seq);
+  let file_ids = select_upload_files(&mut test.db_connection(), 0, 10)
+    .unwrap()
+    .into_iter()
+    .map(|record| record.file_id)
+    .collect::<Vec<_>>();
+  assert_eq!(file_ids, vec![first.clone(), second.clone()]);
+
+  // Both uploads run, one at a time, the most recent one first like without the skew.
+  let (_, mut events) = test.manager.subscribe_events();
+  test.manager.update_network_reachable(true);
+  let mut finished = vec![];
+  while finished.len() < 2 {
+    let event = tokio::time::timeout(Duration::from_secs(30), events.recv())
+      .await
+      .unwrap()
+      .unwrap();
+    if event.progress >= 1.0 && event.error.is_none() && !finished.contains(&event.file_id) {
+      finished.push(event.file_id);
+    }
+  }
+  assert_eq!(finished, vec![second, first]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn backward_clock_does_not_purge_early_test() {
+  let clock = Arc::new(SkewedClock::default());
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .failed_upload_retention(Some(24 * HOUR))
+      .part_max_attempts(1)
+      .clock(clock.clone()),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  test.manager.update_network_reachable(false);
+
+  let file_id = create_upload(&test).await;
+  test
+    .cloud_service
+    .fail_part_number
+    .store(1, Ordering::SeqCst);
+  assert!(test
+    .manager
+    .storage_service()
+    .resume_upload(&workspace_id, PARENT_DIR, &file_id)
+    .await
+    .is_err());
+
+  // The failure looks like it happened in the future, it's kept until the retention elapsed
+  // from the time it was recorded.
+  clock.rewind(48 * HOUR);
+  assert_eq!(test.manager.purge_failed_uploads().await.unwrap(), 0);
+  assert_eq!(select_record(&test, &file_id).file_id, file_id);
+  assert_eq!(
+    test
+      .manager
+      .list_failed_uploads(&workspace_id)
+      .await
+      .unwrap()
+      .len(),
+    1
+  );
+}
@@ -0,0 +1,128 @@
+use crate::util::{create_temp_file, generate_random_string, wait_for_finished, StorageTest};
+use bytes::Bytes;
+use flowy_storage::clock::{Clock, MockClock};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage_pub::storage::DownloadState;
+use std::env::temp_dir;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+const URL: &str = "https://mock.appflowy.io/api/file_storage/clock_test";
+
+/// Waits until the clock received the sleep. Only the scheduling of the tasks takes real time.
+async fn wait_for_sleep(clock: &MockClock, duration: Duration, count: usize) {
+  tokio::time::timeout(Duration::from_secs(5), async {
+    while clock
+      .sleeps()
+      .iter()
+      .filter(|sleep| **sleep == duration)
+      .count()
+      < count
+    {
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+  })
+  .await
+  .unwrap();
+}
+
+#[tokio::test]
+async fn download_backoff_follows_mock_clock_test() {
+  let clock = Arc::new(MockClock::default());
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .download_retry_delay(Duration::from_secs(60))
+      .clock(clock.clone()),
+  )
+  .await;
+  test
+    .cloud_service
+    .objects
+    .insert(URL.to_string(), Bytes::from_static(b"data"));
+  test
+    .cloud_service
+    .get_object_failures
+    .store(2, Ordering::SeqCst);
+
+  let local_file_path = temp_dir()
+    .join(generate_random_string(8))
+    .to_str()
+    .unwrap()
+    .to_string();
+  let handle = test
+    .manager
+    .download_objects(vec![(URL.to_string(), local_file_path.clone())]);
+  let mut state = handle.receiver(URL, &local_file_path).unwrap();
+
+  // The first retry waits for the retry delay.
+  wait_for_sleep(&clock, Duration::from_secs(60), 1).await;
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    1
+  );
+  clock.advance(Duration::from_secs(59));
+  tokio::time::sleep(Duration::from_millis(50)).await;
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    1
+  );
+
+  // The second retry waits twice as long.
+  clock.advance(Duration::from_secs(1));
+  wait_for_sleep(&clock, Duration::from_secs(120), 1).await;
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    2
+  );
+  clock.advance(Duration::from_secs(120));
+
+  let state = tokio::time::timeout(
+    Duration::from_secs(5),
+    state.wait_for(DownloadState::is_finished),
+  )
+  .await
+  .unwrap()
+  .unwrap()
+  .clone();
+  assert_eq!(state, DownloadState::Downloaded);
+  assert_eq!(clock.elapsed(), Duration::from_secs(180));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn manifest_timestamp_follows_mock_clock_test() {
+  let clock = Arc::new(MockClock::default());
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .upload_manifest(true)
+      .clock(clock.clone()),
+  )
+  .await;
+  // A day later on the mock clock, the real time barely moved.
+  clock.advance(Duration::from_secs(24 * 60 * 60));
+  let workspace_id = test.workspace_id();
+  let parent_dir = "clock_test";
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  let manifest = test
+    .manager
+    .upload_manifest(parent_dir, &created_upload.file_id)
+    .await
+    .unwrap()
+    .unwrap();
+  let now = clock
+    .system_now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap()
+    .as_secs() as i64;
+  assert_eq!(manifest.created_at, now);
+}
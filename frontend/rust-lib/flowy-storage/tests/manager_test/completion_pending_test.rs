@@ -0,0 +1,75 @@
+use crate::util::{create_temp_file, MockStorageCloudService, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage_pub::storage::FileUploadState;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn parts_are_kept_across_transient_complete_failure_test() {
+  let test = StorageTest::new_with_config(StorageManagerConfig::default().chunk_size(MB)).await;
+  test
+    .cloud_service
+    .complete_failures
+    .store(1, Ordering::SeqCst);
+  let workspace_id = test.workspace_id();
+  let parent_dir = "completion_pending_test";
+  let file_path = create_temp_file(3 * MB, "txt");
+  let content = std::fs::read(&file_path).unwrap();
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+
+  let mut receiver = receiver.unwrap();
+  let states = tokio::time::timeout(Duration::from_secs(30), async {
+    let mut states = vec![];
+    while let Ok(state) = receiver.recv().await {
+      let finished = matches!(state, FileUploadState::Finished { .. });
+      states.push(state);
+      if finished {
+        break;
+      }
+    }
+    states
+  })
+  .await
+  .unwrap();
+  assert!(matches!(
+    states.last(),
+    Some(FileUploadState::Finished { .. })
+  ));
+  assert!(states.iter().any(|state| matches!(
+    state,
+    FileUploadState::CompletionPending { error } if error.contains("complete upload failed")
+  )));
+
+  // The retry only completed the upload again, no part was sent twice and nothing was aborted.
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    2
+  );
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    3
+  );
+  assert_eq!(
+    test.cloud_service.uploaded_bytes.load(Ordering::SeqCst),
+    3 * MB
+  );
+  assert_eq!(
+    test.cloud_service.abort_upload_count.load(Ordering::SeqCst),
+    0
+  );
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+  assert_eq!(
+    test.cloud_service.objects.get(&url).unwrap().to_vec(),
+    content
+  );
+}
@@ -0,0 +1,44 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn max_concurrent_uploads_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().max_concurrent_uploads(2)).await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(500)));
+  let workspace_id = test.workspace_id();
+
+  let mut receivers = vec![];
+  for _ in 0..5 {
+    let file_path = create_temp_file(1024, "txt");
+    let (_, receiver) = test
+      .manager
+      .storage_service()
+      .create_upload(
+        &workspace_id,
+        "concurrency_test",
+        file_path.to_str().unwrap(),
+        true,
+      )
+      .await
+      .unwrap();
+    receivers.push(receiver.unwrap());
+  }
+
+  for mut receiver in receivers {
+    assert!(wait_for_finished(&mut receiver, Duration::from_secs(120)).await);
+  }
+  let max_in_flight_parts = test
+    .cloud_service
+    .max_in_flight_parts
+    .load(Ordering::SeqCst);
+  assert!(
+    max_in_flight_parts <= 2,
+    "{} uploads ran at once",
+    max_in_flight_parts
+  );
+}
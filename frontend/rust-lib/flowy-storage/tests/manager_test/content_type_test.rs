@@ -0,0 +1,160 @@
+use crate::util::{
+  create_temp_file, generate_random_string, wait_for_finished, MockStorageCloudService, StorageTest,
+};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::CreateUploadOptions;
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::collections::HashMap;
+use std::env::temp_dir;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn create_file(file_name: &str, content: &[u8]) -> PathBuf {
+  let dir = temp_dir().join(format!("storage-file-{}", generate_random_string(8)));
+  std::fs::create_dir_all(&dir).unwrap();
+  let file_path = dir.join(file_name);
+  std::fs::write(&file_path, content).unwrap();
+  file_path
+}
+
+async fn upload_content_type(test: &StorageTest, file_path: PathBuf) -> String {
+  let workspace_id = test.workspace_id();
+  let parent_dir = "content_type_test";
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap()
+  .content_type
+}
+
+fn text_plain_fallback_test() -> impl std::future::Future<Output = StorageTest> {
+  StorageTest::new_with_config(
+    StorageManagerConfig::default().fallback_content_type(mime::TEXT_PLAIN),
+  )
+}
+
+#[tokio::test]
+async fn unsniffable_file_uses_fallback_content_type_test() {
+  let test = text_plain_fallback_test().await;
+  let file_path = create_file("notes", b"just some words");
+  assert_eq!(upload_content_type(&test, file_path).await, "text/plain");
+
+  // Without a configured fallback, the unknown files are plain bytes.
+  let test = StorageTest::new().await;
+  let file_path = create_file("notes", b"just some other words");
+  assert_eq!(
+    upload_content_type(&test, file_path).await,
+    "application/octet-stream"
+  );
+}
+
+#[tokio::test]
+async fn sniffed_content_type_goes_before_fallback_test() {
+  let test = text_plain_fallback_test().await;
+  let file_path = create_file("image", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+  assert_eq!(upload_content_type(&test, file_path).await, "image/png");
+}
+
+#[tokio::test]
+async fn extension_content_type_goes_before_sniffing_test() {
+  let test = text_plain_fallback_test().await;
+  // The extension wins even if the content looks like another type.
+  let file_path = create_file("data.json", b"%PDF-1.7");
+  assert_eq!(
+    upload_content_type(&test, file_path).await,
+    "application/json"
+  );
+}
+
+async fn upload_with_content_type(test: &StorageTest, content_type: &str) -> String {
+  let workspace_id = test.workspace_id();
+  let parent_dir = "content_type_test";
+  let file_path = create_temp_file(1024, "md");
+  let (created_upload, receiver) = test
+    .manager
+    .create_upload_with(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      true,
+      CreateUploadOptions::default().content_type(content_type),
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap()
+  .content_type
+}
+
+#[tokio::test]
+async fn invalid_content_type_uses_fallback_test() {
+  let test = text_plain_fallback_test().await;
+  assert_eq!(
+    upload_with_content_type(&test, "not a content type").await,
+    "text/plain"
+  );
+  assert_eq!(upload_with_content_type(&test, "*/*").await, "text/plain");
+  // A valid content type given by the caller wins over the detected one.
+  assert_eq!(
+    upload_with_content_type(&test, "application/vnd.custom+json").await,
+    "application/vnd.custom+json"
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn content_type_with_metadata_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "content_type_test";
+  let file_path = create_temp_file(1024, "md");
+  let metadata = HashMap::from([("app-version".to_string(), "0.7.6".to_string())]);
+  let (created_upload, receiver) = test
+    .manager
+    .create_upload_with(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      true,
+      CreateUploadOptions::default()
+        .content_type("application/vnd.custom+json")
+        .metadata(metadata.clone()),
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  // Both options apply to the same upload.
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert_eq!(record.content_type, "application/vnd.custom+json");
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+  assert_eq!(test.manager.object_metadata(&url).await.unwrap(), metadata);
+}
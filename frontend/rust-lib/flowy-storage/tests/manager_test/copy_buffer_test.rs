@@ -0,0 +1,60 @@
+use crate::util::{
+  create_temp_file, generate_random_string, wait_for_finished, MockStorageCloudService, StorageTest,
+};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::file_cache::{FileTempStorage, HashTempFileNaming, MIN_COPY_BUFFER_SIZE};
+use std::env::temp_dir;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn copy_buffer_size_test() {
+  let storage_dir = temp_dir().join(format!("copy-buffer-{}", generate_random_string(8)));
+  // The file size is neither a multiple of the buffer sizes nor smaller than all of them.
+  let file_path = create_temp_file(100_003, "bin");
+  let content = std::fs::read(&file_path).unwrap();
+
+  for buffer_size in [1, MIN_COPY_BUFFER_SIZE, 10_000, 1024 * 1024] {
+    let temp_storage = FileTempStorage::new(
+      storage_dir.clone(),
+      Arc::new(HashTempFileNaming::default()),
+      buffer_size,
+    );
+    assert_eq!(
+      temp_storage.copy_buffer_size(),
+      buffer_size.max(MIN_COPY_BUFFER_SIZE)
+    );
+    let temp_file_path = temp_storage
+      .create_temp_file_from_existing(&file_path, &CancellationToken::new())
+      .await
+      .unwrap();
+    assert_eq!(std::fs::read(&temp_file_path).unwrap(), content);
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_with_small_copy_buffer_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default().temp_copy_buffer_size(MIN_COPY_BUFFER_SIZE),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "copy_buffer_test";
+  let file_path = create_temp_file(3 * 1024 * 1024 + 7, "bin");
+  let content = std::fs::read(&file_path).unwrap();
+
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+  assert_eq!(
+    test.cloud_service.objects.get(&url).unwrap().to_vec(),
+    content
+  );
+}
@@ -0,0 +1,175 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::IdleOutcome;
+use flowy_storage_pub::storage::FileUploadState;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn create_upload_for_completed_file_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  let file_path = file_path.to_str().unwrap();
+
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, "completed_test", file_path, true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  // Creating the upload again returns the existing upload with a finished receiver.
+  let (recreated_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, "completed_test", file_path, true)
+    .await
+    .unwrap();
+  assert_eq!(recreated_upload.file_id, created_upload.file_id);
+  assert_eq!(recreated_upload.url, created_upload.url);
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(1)).await);
+
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Idle
+  );
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    1
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn create_upload_for_completed_file_deleted_on_server_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().verify_completed_upload(true))
+      .await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  let file_path = file_path.to_str().unwrap();
+
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, "deleted_test", file_path, true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  // Delete the object out-of-band, the file should be uploaded again.
+  let url =
+    MockStorageCloudService::object_url(&workspace_id, "deleted_test", &created_upload.file_id);
+  test.cloud_service.objects.remove(&url);
+
+  let (_, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, "deleted_test", file_path, true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    2
+  );
+  assert!(test.cloud_service.objects.contains_key(&url));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn create_duplicate_upload_in_progress_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_secs(2)));
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  let file_path = file_path.to_str().unwrap();
+
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, "duplicate_test", file_path, true)
+    .await
+    .unwrap();
+
+  // The upload is still running, the duplicate shares the progress of the running upload.
+  let (duplicate_upload, duplicate_receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, "duplicate_test", file_path, true)
+    .await
+    .unwrap();
+  assert_eq!(duplicate_upload.file_id, created_upload.file_id);
+  assert!(wait_for_finished(&mut duplicate_receiver.unwrap(), Duration::from_secs(30)).await);
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(1)).await);
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    1
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn create_duplicate_upload_after_finished_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  let file_path = file_path.to_str().unwrap();
+
+  let (_, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, "duplicate_test", file_path, true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  // Every duplicate gets a receiver that yields the finished state.
+  for _ in 0..3 {
+    let (_, receiver) = test
+      .manager
+      .storage_service()
+      .create_upload(&workspace_id, "duplicate_test", file_path, true)
+      .await
+      .unwrap();
+    assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(1)).await);
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn create_upload_with_exposed_storage_service_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "exposed_service_test";
+  let file_path = create_temp_file(1024, "txt");
+
+  let storage_service = test.manager.storage_service();
+  let (created_upload, receiver) = storage_service
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  // The manager observes the upload created through the service.
+  let state = test
+    .manager
+    .get_file_state(parent_dir, &created_upload.file_id)
+    .await;
+  assert!(matches!(state, Some(FileUploadState::Finished { .. })));
+  let file_state = test
+    .manager
+    .query_file_state(&created_upload.url)
+    .await
+    .unwrap();
+  assert!(file_state.is_finish);
+}
@@ -0,0 +1,81 @@
+use crate::util::{create_temp_file, StorageTest};
+use bytes::Bytes;
+use flowy_storage_pub::storage::{DeleteProgress, DeleteState, DeleteTarget};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const URL: &str = "https://mock.appflowy.io/api/file_storage/delete_test";
+
+async fn collect_delete_progress(
+  rx: &mut broadcast::Receiver<DeleteProgress>,
+) -> Vec<(DeleteTarget, DeleteState)> {
+  let mut events = vec![];
+  while events.len() < 4 {
+    let progress = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(progress.file_url, URL);
+    events.push((progress.target, progress.state));
+  }
+  events
+}
+
+#[tokio::test]
+async fn delete_object_progress_test() {
+  let test = StorageTest::new().await;
+  let mut rx = test.manager.subscribe_delete_progress();
+  let file_path = create_temp_file(1024, "txt");
+  test
+    .cloud_service
+    .objects
+    .insert(URL.to_string(), Bytes::from_static(b"data"));
+
+  test
+    .manager
+    .storage_service()
+    .delete_object(
+      URL.to_string(),
+      None,
+      file_path.to_str().unwrap().to_string(),
+    )
+    .unwrap();
+  let events = collect_delete_progress(&mut rx).await;
+  assert_eq!(
+    events,
+    vec![
+      (DeleteTarget::Local, DeleteState::Deleting),
+      (DeleteTarget::Local, DeleteState::Deleted),
+      (DeleteTarget::Cloud, DeleteState::Deleting),
+      (DeleteTarget::Cloud, DeleteState::Deleted),
+    ]
+  );
+  assert!(!file_path.exists());
+  assert!(!test.cloud_service.objects.contains_key(URL));
+}
+
+#[tokio::test]
+async fn delete_object_failed_progress_test() {
+  let test = StorageTest::new().await;
+  let mut rx = test.manager.subscribe_delete_progress();
+  test.cloud_service.fail_delete.store(true, Ordering::SeqCst);
+
+  // The local file doesn't exist and the cloud delete fails, each one reports its own failure.
+  test
+    .manager
+    .storage_service()
+    .delete_object(URL.to_string(), None, "not_exist_file.txt".to_string())
+    .unwrap();
+  let events = collect_delete_progress(&mut rx).await;
+  assert_eq!(events[0], (DeleteTarget::Local, DeleteState::Deleting));
+  assert!(matches!(
+    events[1],
+    (DeleteTarget::Local, DeleteState::DeleteFailed { .. })
+  ));
+  assert_eq!(events[2], (DeleteTarget::Cloud, DeleteState::Deleting));
+  assert!(matches!(
+    events[3],
+    (DeleteTarget::Cloud, DeleteState::DeleteFailed { .. })
+  ));
+}
@@ -0,0 +1,94 @@
+use crate::util::{generate_random_string, StorageTest};
+use bytes::Bytes;
+use flowy_storage::downloader::DownloadBatchProgress;
+use flowy_storage_pub::storage::DownloadState;
+use std::env::temp_dir;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+fn object_url(name: &str) -> String {
+  format!("https://mock.appflowy.io/api/file_storage/{}", name)
+}
+
+fn local_file_path() -> String {
+  temp_dir()
+    .join(generate_random_string(8))
+    .to_str()
+    .unwrap()
+    .to_string()
+}
+
+#[tokio::test]
+async fn download_batch_test() {
+  let test = StorageTest::new().await;
+  let mut requests = vec![];
+  for i in 0..4 {
+    let url = object_url(&format!("batch_{}", i));
+    test
+      .cloud_service
+      .objects
+      .insert(url.clone(), Bytes::from(format!("content {}", i)));
+    requests.push((url, local_file_path()));
+  }
+  let missing = (object_url("missing"), local_file_path());
+  requests.push(missing.clone());
+
+  let handle = test.manager.download_objects(requests.clone());
+  let progress = tokio::time::timeout(Duration::from_secs(10), handle.wait())
+    .await
+    .unwrap();
+  assert_eq!(
+    progress,
+    DownloadBatchProgress {
+      total: 5,
+      downloaded: 4,
+      failed: 1,
+      cancelled: 0,
+    }
+  );
+  assert_eq!(progress.progress(), 1.0);
+
+  for (i, (url, local_file_path)) in requests.iter().take(4).enumerate() {
+    let state = handle.receiver(url, local_file_path).unwrap();
+    assert_eq!(*state.borrow(), DownloadState::Downloaded);
+    assert_eq!(
+      std::fs::read_to_string(local_file_path).unwrap(),
+      format!("content {}", i)
+    );
+  }
+  let state = handle.receiver(&missing.0, &missing.1).unwrap();
+  assert!(matches!(
+    *state.borrow(),
+    DownloadState::DownloadFailed { .. }
+  ));
+}
+
+#[tokio::test]
+async fn download_batch_deduplicates_urls_test() {
+  let test = StorageTest::new().await;
+  let url = object_url("dedup");
+  test
+    .cloud_service
+    .objects
+    .insert(url.clone(), Bytes::from_static(b"shared content"));
+
+  let path = local_file_path();
+  let other_path = local_file_path();
+  let handle = test.manager.download_objects(vec![
+    (url.clone(), path.clone()),
+    (url.clone(), path.clone()),
+    (url.clone(), other_path.clone()),
+  ]);
+  let progress = tokio::time::timeout(Duration::from_secs(10), handle.wait())
+    .await
+    .unwrap();
+  assert_eq!(progress.downloaded, 3);
+
+  // The object is only fetched once, the other local file is a copy.
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    1
+  );
+  assert_eq!(std::fs::read(&path).unwrap(), b"shared content");
+  assert_eq!(std::fs::read(&other_path).unwrap(), b"shared content");
+}
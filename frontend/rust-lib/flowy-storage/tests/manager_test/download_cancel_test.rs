@@ -0,0 +1,108 @@
+use crate::util::{generate_random_string, StorageTest};
+use bytes::Bytes;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::sqlite_sql::select_download_files;
+use flowy_storage_pub::storage::DownloadState;
+use std::env::temp_dir;
+use std::path::Path;
+use std::time::Duration;
+
+fn object_url(name: &str) -> String {
+  format!("https://mock.appflowy.io/api/file_storage/{}", name)
+}
+
+fn local_file_path() -> String {
+  temp_dir()
+    .join(generate_random_string(8))
+    .to_str()
+    .unwrap()
+    .to_string()
+}
+
+#[tokio::test]
+async fn cancel_running_download_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_get_object_delay(Some(Duration::from_secs(1)));
+  let url = object_url("cancel_running");
+  test
+    .cloud_service
+    .objects
+    .insert(url.clone(), Bytes::from_static(b"data"));
+  let local_file_path = local_file_path();
+  let part_file_path = format!("{}.part", local_file_path);
+
+  let handle = test
+    .manager
+    .download_objects(vec![(url.clone(), local_file_path.clone())]);
+  let state = handle.receiver(&url, &local_file_path).unwrap();
+  // Wait for the object request, the partial file is created before it.
+  while test.cloud_service.fetched_urls.lock().unwrap().is_empty() {
+    tokio::time::sleep(Duration::from_millis(10)).await;
+  }
+  assert_eq!(*state.borrow(), DownloadState::Downloading);
+  assert!(Path::new(&part_file_path).exists());
+
+  test.manager.cancel_download(&url).await.unwrap();
+  let progress = tokio::time::timeout(Duration::from_secs(5), handle.wait())
+    .await
+    .unwrap();
+  assert_eq!(progress.cancelled, 1);
+  assert_eq!(*state.borrow(), DownloadState::Cancelled);
+  assert!(!Path::new(&part_file_path).exists());
+  assert!(select_download_files(&mut test.db_connection())
+    .unwrap()
+    .is_empty());
+
+  // The aborted request never completes the download.
+  tokio::time::sleep(Duration::from_millis(1500)).await;
+  assert_eq!(*state.borrow(), DownloadState::Cancelled);
+  assert!(!Path::new(&local_file_path).exists());
+}
+
+#[tokio::test]
+async fn cancel_queued_download_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().max_concurrent_downloads(1)).await;
+  test
+    .cloud_service
+    .set_get_object_delay(Some(Duration::from_millis(200)));
+  let running_url = object_url("running");
+  let queued_url = object_url("queued");
+  for url in [&running_url, &queued_url] {
+    test
+      .cloud_service
+      .objects
+      .insert(url.clone(), Bytes::from_static(b"data"));
+  }
+  let queued_file_path = local_file_path();
+  let handle = test.manager.download_objects(vec![
+    (running_url.clone(), local_file_path()),
+    (queued_url.clone(), queued_file_path.clone()),
+  ]);
+
+  test.manager.cancel_download(&queued_url).await.unwrap();
+  let progress = tokio::time::timeout(Duration::from_secs(5), handle.wait())
+    .await
+    .unwrap();
+  assert_eq!(progress.downloaded, 1);
+  assert_eq!(progress.cancelled, 1);
+  assert_eq!(
+    *test.cloud_service.fetched_urls.lock().unwrap(),
+    vec![running_url]
+  );
+  assert!(!Path::new(&queued_file_path).exists());
+}
+
+#[tokio::test]
+async fn cancel_unknown_download_test() {
+  let test = StorageTest::new().await;
+  let mut rx = test.manager.subscribe_download_progress();
+  test
+    .manager
+    .cancel_download(&object_url("unknown"))
+    .await
+    .unwrap();
+  assert!(rx.try_recv().is_err());
+}
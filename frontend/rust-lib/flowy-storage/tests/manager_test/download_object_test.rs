@@ -0,0 +1,155 @@
+use crate::util::{generate_random_string, StorageTest};
+use bytes::Bytes;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage_pub::storage::{DownloadProgress, DownloadState};
+use std::env::temp_dir;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const URL: &str = "https://mock.appflowy.io/api/file_storage/download_test";
+
+async fn collect_download_states(
+  rx: &mut broadcast::Receiver<DownloadProgress>,
+) -> Vec<DownloadState> {
+  let mut states = vec![];
+  loop {
+    let progress = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(progress.file_url, URL);
+    let is_done = matches!(
+      progress.state,
+      DownloadState::Downloaded | DownloadState::DownloadFailed { .. }
+    );
+    states.push(progress.state);
+    if is_done {
+      return states;
+    }
+  }
+}
+
+fn download_test() -> impl std::future::Future<Output = StorageTest> {
+  StorageTest::new_with_config(
+    StorageManagerConfig::default().download_retry_delay(Duration::from_millis(10)),
+  )
+}
+
+#[tokio::test]
+async fn retry_download_after_transient_failure_test() {
+  let test = download_test().await;
+  let mut rx = test.manager.subscribe_download_progress();
+  test
+    .cloud_service
+    .objects
+    .insert(URL.to_string(), Bytes::from_static(b"data"));
+  test
+    .cloud_service
+    .get_object_failures
+    .store(1, Ordering::SeqCst);
+
+  let local_file_path = temp_dir().join(generate_random_string(8));
+  test
+    .manager
+    .storage_service()
+    .download_object(
+      URL.to_string(),
+      local_file_path.to_str().unwrap().to_string(),
+    )
+    .unwrap();
+  let states = collect_download_states(&mut rx).await;
+  assert_eq!(states.len(), 3);
+  assert_eq!(states[0], DownloadState::Downloading);
+  assert!(matches!(
+    states[1],
+    DownloadState::Retrying { attempt: 1, .. }
+  ));
+  assert_eq!(states[2], DownloadState::Downloaded);
+  assert_eq!(std::fs::read(&local_file_path).unwrap(), b"data");
+}
+
+#[tokio::test]
+async fn download_missing_object_fails_fast_test() {
+  let test = download_test().await;
+  let mut rx = test.manager.subscribe_download_progress();
+
+  let local_file_path = temp_dir().join(generate_random_string(8));
+  test
+    .manager
+    .storage_service()
+    .download_object(
+      URL.to_string(),
+      local_file_path.to_str().unwrap().to_string(),
+    )
+    .unwrap();
+  let states = collect_download_states(&mut rx).await;
+  assert_eq!(states.len(), 2);
+  assert_eq!(states[0], DownloadState::Downloading);
+  assert!(matches!(states[1], DownloadState::DownloadFailed { .. }));
+  assert!(!local_file_path.exists());
+}
+
+#[tokio::test]
+async fn download_gives_up_after_max_attempts_test() {
+  let test = download_test().await;
+  let mut rx = test.manager.subscribe_download_progress();
+  test
+    .cloud_service
+    .objects
+    .insert(URL.to_string(), Bytes::from_static(b"data"));
+  test
+    .cloud_service
+    .get_object_failures
+    .store(5, Ordering::SeqCst);
+
+  let local_file_path = temp_dir().join(generate_random_string(8));
+  test
+    .manager
+    .storage_service()
+    .download_object(
+      URL.to_string(),
+      local_file_path.to_str().unwrap().to_string(),
+    )
+    .unwrap();
+  // The default config makes three attempts.
+  let states = collect_download_states(&mut rx).await;
+  assert_eq!(states.len(), 4);
+  assert!(matches!(states[3], DownloadState::DownloadFailed { .. }));
+  assert!(!local_file_path.exists());
+}
+
+#[tokio::test]
+async fn download_completion_carries_size_and_path_test() {
+  let test = download_test().await;
+  let mut rx = test.manager.subscribe_download_progress();
+  test
+    .cloud_service
+    .objects
+    .insert(URL.to_string(), Bytes::from(vec![7u8; 3000]));
+
+  let local_file_path = temp_dir().join(generate_random_string(8));
+  let local_file_path = local_file_path.to_str().unwrap().to_string();
+  test
+    .manager
+    .storage_service()
+    .download_object(URL.to_string(), local_file_path.clone())
+    .unwrap();
+  let completion = loop {
+    let progress = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+      .await
+      .unwrap()
+      .unwrap();
+    if progress.state.is_finished() {
+      break progress;
+    }
+    // Only the completion carries the summary.
+    assert_eq!(progress.total_bytes, None);
+  };
+  assert_eq!(completion.state, DownloadState::Downloaded);
+  assert_eq!(completion.local_file_path, local_file_path);
+  assert_eq!(completion.total_bytes, Some(3000));
+  // The fresh download isn't verified.
+  assert_eq!(completion.verified_file_id, None);
+  assert_eq!(std::fs::metadata(&local_file_path).unwrap().len(), 3000);
+}
@@ -0,0 +1,86 @@
+use crate::util::{MockStorageCloudService, StorageTest};
+use bytes::Bytes;
+use flowy_storage::manager::StorageUserService;
+use flowy_storage_pub::storage::DownloadState;
+use std::collections::HashSet;
+use std::path::Path;
+
+#[tokio::test]
+async fn default_download_path_is_stable_and_namespaced_test() {
+  let test = StorageTest::new().await;
+  let download_dir = Path::new(test.user_service.get_application_root_dir()).join("downloads");
+  let path = |url: String| {
+    let manager = test.manager.clone();
+    async move { manager.default_download_path(&url).await.unwrap() }
+  };
+
+  // The same object maps to the same path, whatever the format of its url.
+  let first = path(MockStorageCloudService::object_url("w1", "dir", "file.png")).await;
+  assert_eq!(
+    first,
+    path(MockStorageCloudService::object_url("w1", "dir", "file.png")).await
+  );
+  assert_eq!(
+    first,
+    path(MockStorageCloudService::object_url_v2(
+      "w1", "dir", "file.png"
+    ))
+    .await
+  );
+
+  // The same file id in another workspace or parent dir lands elsewhere.
+  let paths = [
+    first.clone(),
+    path(MockStorageCloudService::object_url("w2", "dir", "file.png")).await,
+    path(MockStorageCloudService::object_url(
+      "w1",
+      "other_dir",
+      "file.png",
+    ))
+    .await,
+    path(MockStorageCloudService::object_url(
+      "w1",
+      "dir",
+      "other.png",
+    ))
+    .await,
+  ];
+  assert_eq!(paths.iter().collect::<HashSet<_>>().len(), paths.len());
+  for path in &paths {
+    assert!(path.starts_with(&download_dir));
+  }
+
+  // A component can't escape the download cache.
+  let escaped = path(MockStorageCloudService::object_url("w1", "..", "file.png")).await;
+  assert!(escaped.starts_with(&download_dir));
+  assert!(!escaped.components().any(|c| c.as_os_str() == ".."));
+
+  assert!(test
+    .manager
+    .default_download_path("https://mock.appflowy.io/api/file_storage/v3/unknown")
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn download_to_default_path_test() {
+  let test = StorageTest::new().await;
+  let url = MockStorageCloudService::object_url("w1", "dir", "file.txt");
+  test
+    .cloud_service
+    .objects
+    .insert(url.clone(), Bytes::from_static(b"content"));
+
+  let (local_file_path, mut state) = test.manager.download(&url).await.unwrap();
+  let state = state
+    .wait_for(DownloadState::is_finished)
+    .await
+    .unwrap()
+    .clone();
+  assert_eq!(state, DownloadState::Downloaded);
+  assert_eq!(
+    local_file_path,
+    test.manager.default_download_path(&url).await.unwrap()
+  );
+  assert_eq!(std::fs::read(&local_file_path).unwrap(), b"content");
+}
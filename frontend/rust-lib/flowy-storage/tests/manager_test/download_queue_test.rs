@@ -0,0 +1,127 @@
+use crate::util::{generate_random_string, StorageTest};
+use bytes::Bytes;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::sqlite_sql::{select_download_files, upsert_download_file, DownloadFileTable};
+use flowy_storage_pub::storage::DownloadState;
+use std::env::temp_dir;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+fn object_url(name: &str) -> String {
+  format!("https://mock.appflowy.io/api/file_storage/{}", name)
+}
+
+fn local_file_path() -> String {
+  temp_dir()
+    .join(generate_random_string(8))
+    .to_str()
+    .unwrap()
+    .to_string()
+}
+
+fn insert_objects(test: &StorageTest, names: &[&str]) -> Vec<(String, String)> {
+  names
+    .iter()
+    .map(|name| {
+      let url = object_url(name);
+      test
+        .cloud_service
+        .objects
+        .insert(url.clone(), Bytes::from(name.to_string()));
+      (url, local_file_path())
+    })
+    .collect()
+}
+
+#[tokio::test]
+async fn download_queue_concurrency_limit_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().max_concurrent_downloads(2)).await;
+  test
+    .cloud_service
+    .set_get_object_delay(Some(Duration::from_millis(100)));
+  let requests = insert_objects(&test, &["a", "b", "c", "d", "e", "f"]);
+
+  let handle = test.manager.download_objects(requests);
+  let progress = tokio::time::timeout(Duration::from_secs(10), handle.wait())
+    .await
+    .unwrap();
+  assert_eq!(progress.downloaded, 6);
+  assert_eq!(
+    test
+      .cloud_service
+      .max_in_flight_downloads
+      .load(Ordering::SeqCst),
+    2
+  );
+}
+
+#[tokio::test]
+async fn user_initiated_download_goes_before_prefetch_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().max_concurrent_downloads(1)).await;
+  test
+    .cloud_service
+    .set_get_object_delay(Some(Duration::from_millis(50)));
+  let prefetch_requests = insert_objects(&test, &["prefetch_1", "prefetch_2", "prefetch_3"]);
+  let user_requests = insert_objects(&test, &["user"]);
+
+  // The first prefetch download takes the only slot, the other downloads wait in the queue.
+  let prefetch_handle = test.manager.prefetch_objects(prefetch_requests);
+  let user_handle = test.manager.download_objects(user_requests);
+  tokio::time::timeout(Duration::from_secs(10), async {
+    user_handle.wait().await;
+    prefetch_handle.wait().await;
+  })
+  .await
+  .unwrap();
+
+  let fetched_urls = test.cloud_service.fetched_urls.lock().unwrap().clone();
+  assert_eq!(
+    fetched_urls,
+    vec![
+      object_url("prefetch_1"),
+      object_url("user"),
+      object_url("prefetch_2"),
+      object_url("prefetch_3"),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn resume_recorded_download_test() {
+  let test = StorageTest::new().await;
+  let (url, local_file_path) = insert_objects(&test, &["recorded"]).remove(0);
+  // A download recorded by a previous session, before it could finish.
+  upsert_download_file(
+    &mut test.db_connection(),
+    &DownloadFileTable {
+      url: url.clone(),
+      local_file_path: local_file_path.clone(),
+      priority: 0,
+      created_at: 0,
+    },
+  )
+  .unwrap();
+
+  let mut rx = test.manager.subscribe_download_progress();
+  test.manager.initialize(&test.workspace_id()).await.unwrap();
+  loop {
+    let progress = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+      .await
+      .unwrap()
+      .unwrap();
+    if progress.state.is_finished() {
+      assert_eq!(progress.file_url, url);
+      assert_eq!(progress.state, DownloadState::Downloaded);
+      break;
+    }
+  }
+  assert_eq!(std::fs::read(&local_file_path).unwrap(), b"recorded");
+
+  // The record is removed once the download finished.
+  tokio::time::sleep(Duration::from_millis(200)).await;
+  assert!(select_download_files(&mut test.db_connection())
+    .unwrap()
+    .is_empty());
+}
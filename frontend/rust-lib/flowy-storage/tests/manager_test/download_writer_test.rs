@@ -0,0 +1,137 @@
+use crate::util::StorageTest;
+use bytes::Bytes;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage_pub::storage::DownloadState;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::AsyncWrite;
+
+const URL: &str = "https://mock.appflowy.io/api/file_storage/download_writer_test";
+
+/// A writer whose writes always fail.
+struct BrokenWriter;
+
+impl AsyncWrite for BrokenWriter {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+    _buf: &[u8],
+  ) -> Poll<std::io::Result<usize>> {
+    Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()))
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
+
+#[tokio::test]
+async fn download_to_writer_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .download_range_size(1000)
+      .download_range_concurrency(3),
+  )
+  .await;
+  let content = (0..10_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+  test
+    .cloud_service
+    .objects
+    .insert(URL.to_string(), Bytes::from(content.clone()));
+
+  for range_downloads in [false, true] {
+    test
+      .cloud_service
+      .range_downloads
+      .store(range_downloads, Ordering::SeqCst);
+    let mut progress = test.manager.subscribe_download_progress();
+    let mut buffer = Vec::new();
+    let written = test
+      .manager
+      .storage_service()
+      .download_to_writer(URL, &mut buffer)
+      .await
+      .unwrap();
+    assert_eq!(written, content.len() as u64);
+    assert_eq!(buffer, content);
+
+    let mut states = vec![];
+    while let Ok(progress) = progress.try_recv() {
+      assert_eq!(progress.file_url, URL);
+      assert!(progress.local_file_path.is_empty());
+      states.push(progress.state);
+    }
+    assert_eq!(
+      states,
+      vec![DownloadState::Downloading, DownloadState::Downloaded]
+    );
+  }
+}
+
+#[tokio::test]
+async fn download_to_writer_retries_transient_failures_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default().download_retry_delay(Duration::from_millis(10)),
+  )
+  .await;
+  test
+    .cloud_service
+    .objects
+    .insert(URL.to_string(), Bytes::from_static(b"data"));
+  test
+    .cloud_service
+    .get_object_failures
+    .store(1, Ordering::SeqCst);
+
+  let mut buffer = Vec::new();
+  let written = test
+    .manager
+    .storage_service()
+    .download_to_writer(URL, &mut buffer)
+    .await
+    .unwrap();
+  assert_eq!(written, 4);
+  assert_eq!(buffer, b"data");
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    2
+  );
+}
+
+#[tokio::test]
+async fn download_to_writer_propagates_errors_test() {
+  let test = StorageTest::new().await;
+
+  // A missing object fails right away.
+  let mut buffer = Vec::new();
+  let err = test
+    .manager
+    .storage_service()
+    .download_to_writer(URL, &mut buffer)
+    .await
+    .unwrap_err();
+  assert!(err.is_record_not_found());
+  assert!(buffer.is_empty());
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    1
+  );
+
+  // A failing writer fails the download.
+  test
+    .cloud_service
+    .objects
+    .insert(URL.to_string(), Bytes::from_static(b"data"));
+  let result = test
+    .manager
+    .storage_service()
+    .download_to_writer(URL, &mut BrokenWriter)
+    .await;
+  assert!(result.is_err());
+}
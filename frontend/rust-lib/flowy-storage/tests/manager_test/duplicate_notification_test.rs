@@ -0,0 +1,90 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use bytes::Bytes;
+use flowy_notification::entities::SubscribeObject;
+use flowy_notification::{register_notification_sender, NotificationSender};
+use flowy_storage::entities::{DuplicateUploadPB, DuplicateUploadStatePB};
+use flowy_storage::notification::StorageNotification;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Clone, Default)]
+struct DuplicateNotificationCollector {
+  values: Arc<Mutex<Vec<(String, DuplicateUploadStatePB)>>>,
+}
+
+impl DuplicateNotificationCollector {
+  /// The notifications are global, other tests may create duplicates of their own files.
+  fn states_of(&self, file_id: &str) -> Vec<DuplicateUploadStatePB> {
+    self
+      .values
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|(value_file_id, _)| value_file_id == file_id)
+      .map(|(_, state)| *state)
+      .collect()
+  }
+}
+
+impl NotificationSender for DuplicateNotificationCollector {
+  fn send_subject(&self, subject: SubscribeObject) -> Result<(), String> {
+    if subject.ty == StorageNotification::UploadSkippedAsDuplicate as i32 {
+      let payload = DuplicateUploadPB::try_from(Bytes::from(subject.payload.unwrap()))
+        .map_err(|err| err.to_string())?;
+      self
+        .values
+        .lock()
+        .unwrap()
+        .push((payload.file_id, payload.state));
+    }
+    Ok(())
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn notify_duplicate_upload_test() {
+  let collector = DuplicateNotificationCollector::default();
+  register_notification_sender(collector.clone());
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "duplicate_notification_test";
+  let file_path = create_temp_file(1024, "txt");
+  let file_path = file_path.to_str().unwrap();
+
+  // Keep the first upload in progress while creating the duplicate.
+  test.manager.update_network_reachable(false);
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path, true)
+    .await
+    .unwrap();
+  assert!(collector.states_of(&created_upload.file_id).is_empty());
+
+  test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path, true)
+    .await
+    .unwrap();
+  assert_eq!(
+    collector.states_of(&created_upload.file_id),
+    vec![DuplicateUploadStatePB::InProgress]
+  );
+
+  test.manager.update_network_reachable(true);
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path, true)
+    .await
+    .unwrap();
+  assert_eq!(
+    collector.states_of(&created_upload.file_id),
+    vec![
+      DuplicateUploadStatePB::InProgress,
+      DuplicateUploadStatePB::Completed
+    ]
+  );
+}
@@ -0,0 +1,132 @@
+use crate::util::{generate_random_string, MockStorageCloudService, StorageTest};
+use bytes::Bytes;
+use flowy_storage::downloader::ExistingFilePolicy;
+use flowy_storage::file_id::file_id_from_bytes;
+use flowy_storage_pub::storage::DownloadState;
+use std::env::temp_dir;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const CONTENT: &[u8] = b"the content of the object";
+
+/// Downloads the object to a local file that already holds the existing content. Returns the
+/// content of the local file afterwards, and whether the object was fetched.
+async fn download_over_existing_file(
+  policy: ExistingFilePolicy,
+  existing: &[u8],
+) -> (Vec<u8>, bool) {
+  let test = StorageTest::new().await;
+  let file_id = file_id_from_bytes(CONTENT, Some("txt")).await;
+  let url =
+    MockStorageCloudService::object_url(&test.workspace_id(), "existing_download_test", &file_id);
+  test
+    .cloud_service
+    .objects
+    .insert(url.clone(), Bytes::from_static(CONTENT));
+  let local_file_path = temp_dir().join(generate_random_string(8));
+  std::fs::write(&local_file_path, existing).unwrap();
+
+  let mut state = test.manager.download_object_with_policy(
+    url,
+    local_file_path.to_str().unwrap().to_string(),
+    policy,
+  );
+  let state = state
+    .wait_for(DownloadState::is_finished)
+    .await
+    .unwrap()
+    .clone();
+  assert_eq!(state, DownloadState::Downloaded);
+  let fetched = test.cloud_service.get_object_count.load(Ordering::SeqCst) > 0;
+  (std::fs::read(&local_file_path).unwrap(), fetched)
+}
+
+#[tokio::test]
+async fn skip_keeps_existing_file_test() {
+  assert_eq!(
+    download_over_existing_file(ExistingFilePolicy::Skip, CONTENT).await,
+    (CONTENT.to_vec(), false)
+  );
+  assert_eq!(
+    download_over_existing_file(ExistingFilePolicy::Skip, b"stale").await,
+    (b"stale".to_vec(), false)
+  );
+}
+
+#[tokio::test]
+async fn overwrite_replaces_existing_file_test() {
+  assert_eq!(
+    download_over_existing_file(ExistingFilePolicy::Overwrite, CONTENT).await,
+    (CONTENT.to_vec(), true)
+  );
+  assert_eq!(
+    download_over_existing_file(ExistingFilePolicy::Overwrite, b"stale").await,
+    (CONTENT.to_vec(), true)
+  );
+}
+
+#[tokio::test]
+async fn verify_then_skip_downloads_mismatching_file_test() {
+  assert_eq!(
+    download_over_existing_file(ExistingFilePolicy::VerifyThenSkip, CONTENT).await,
+    (CONTENT.to_vec(), false)
+  );
+  assert_eq!(
+    download_over_existing_file(ExistingFilePolicy::VerifyThenSkip, b"stale").await,
+    (CONTENT.to_vec(), true)
+  );
+}
+
+#[tokio::test]
+async fn verify_then_skip_keeps_file_without_file_id_test() {
+  let test = StorageTest::new().await;
+  // The url doesn't carry a file id, the existing file can't be verified.
+  let url = "https://mock.appflowy.io/api/file_storage/no_file_id".to_string();
+  test
+    .cloud_service
+    .objects
+    .insert(url.clone(), Bytes::from_static(CONTENT));
+  let local_file_path = temp_dir().join(generate_random_string(8));
+  std::fs::write(&local_file_path, b"stale").unwrap();
+
+  let mut state = test.manager.download_object_with_policy(
+    url,
+    local_file_path.to_str().unwrap().to_string(),
+    ExistingFilePolicy::default(),
+  );
+  state.wait_for(DownloadState::is_finished).await.unwrap();
+  assert_eq!(std::fs::read(&local_file_path).unwrap(), b"stale");
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    0
+  );
+}
+
+#[tokio::test]
+async fn verify_then_skip_reports_verified_file_id_test() {
+  let test = StorageTest::new().await;
+  let file_id = file_id_from_bytes(CONTENT, Some("txt")).await;
+  let url =
+    MockStorageCloudService::object_url(&test.workspace_id(), "existing_download_test", &file_id);
+  let local_file_path = temp_dir().join(generate_random_string(8));
+  std::fs::write(&local_file_path, CONTENT).unwrap();
+  let mut rx = test.manager.subscribe_download_progress();
+
+  let _state = test.manager.download_object_with_policy(
+    url.clone(),
+    local_file_path.to_str().unwrap().to_string(),
+    ExistingFilePolicy::VerifyThenSkip,
+  );
+  let completion = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(completion.file_url, url);
+  assert_eq!(completion.state, DownloadState::Downloaded);
+  assert_eq!(
+    completion.local_file_path,
+    local_file_path.to_str().unwrap()
+  );
+  assert_eq!(completion.total_bytes, Some(CONTENT.len() as u64));
+  assert_eq!(completion.verified_file_id, Some(file_id));
+}
@@ -0,0 +1,160 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::clock::MockClock;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+const RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Creates an upload whose first attempt fails, and returns its file id. The parts must not be
+/// retried, see [StorageManagerConfig::part_max_attempts].
+async fn create_failed_upload(test: &StorageTest, parent_dir: &str) -> String {
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  test
+    .cloud_service
+    .fail_part_number
+    .store(1, Ordering::SeqCst);
+  assert!(test
+    .manager
+    .storage_service()
+    .resume_upload(&workspace_id, parent_dir, &created_upload.file_id)
+    .await
+    .is_err());
+  created_upload.file_id
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn purge_failed_uploads_after_retention_test() {
+  let clock = Arc::new(MockClock::default());
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .failed_upload_retention(Some(RETENTION))
+      .part_max_attempts(1)
+      .clock(clock.clone()),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "failed_upload_test";
+  test.manager.update_network_reachable(false);
+
+  let expired_file_id = create_failed_upload(&test, parent_dir).await;
+  let pinned_file_id = create_failed_upload(&test, parent_dir).await;
+  test
+    .manager
+    .pin_failed_upload(&workspace_id, parent_dir, &pinned_file_id, true)
+    .await
+    .unwrap();
+  let expired_record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &expired_file_id,
+  )
+  .unwrap()
+  .unwrap();
+
+  clock.advance(RETENTION + Duration::from_secs(60));
+  let recent_file_id = create_failed_upload(&test, parent_dir).await;
+  assert_eq!(
+    test
+      .manager
+      .list_failed_uploads(&workspace_id)
+      .await
+      .unwrap()
+      .len(),
+    3
+  );
+
+  assert_eq!(test.manager.purge_failed_uploads().await.unwrap(), 1);
+
+  // The expired upload is removed along with its temp file.
+  assert!(select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &expired_file_id
+  )
+  .unwrap()
+  .is_none());
+  assert!(!std::path::Path::new(&expired_record.local_file_path).exists());
+
+  // The recent and the pinned uploads remain for the user to retry.
+  let failures = test
+    .manager
+    .list_failed_uploads(&workspace_id)
+    .await
+    .unwrap();
+  let file_ids = failures
+    .iter()
+    .map(|failure| failure.file_id.as_str())
+    .collect::<Vec<_>>();
+  assert_eq!(
+    file_ids,
+    vec![recent_file_id.as_str(), pinned_file_id.as_str()]
+  );
+  for file_id in [&recent_file_id, &pinned_file_id] {
+    assert!(select_upload_file(
+      &mut test.db_connection(),
+      &workspace_id,
+      parent_dir,
+      file_id
+    )
+    .unwrap()
+    .is_some());
+  }
+
+  // Purging again finds nothing to purge.
+  assert_eq!(test.manager.purge_failed_uploads().await.unwrap(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn successful_retry_clears_failure_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .part_max_attempts(1),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "failed_upload_test";
+  test.manager.update_network_reachable(false);
+
+  let file_id = create_failed_upload(&test, parent_dir).await;
+  assert_eq!(
+    test
+      .manager
+      .list_failed_uploads(&workspace_id)
+      .await
+      .unwrap()
+      .len(),
+    1
+  );
+
+  test
+    .manager
+    .storage_service()
+    .resume_upload(&workspace_id, parent_dir, &file_id)
+    .await
+    .unwrap();
+  assert!(test
+    .manager
+    .list_failed_uploads(&workspace_id)
+    .await
+    .unwrap()
+    .is_empty());
+}
@@ -0,0 +1,122 @@
+use crate::util::{create_temp_file, StorageTest};
+use bytes::Bytes;
+use flowy_notification::entities::SubscribeObject;
+use flowy_notification::{register_notification_sender, NotificationSender};
+use flowy_storage::clock::MockClock;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::entities::{UploadFailuresPB, UploadFailuresStatePB};
+use flowy_storage::notification::StorageNotification;
+use futures_util::future::join_all;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const WINDOW: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Default)]
+struct FailureNotificationCollector {
+  values: Arc<Mutex<Vec<UploadFailuresPB>>>,
+}
+
+impl FailureNotificationCollector {
+  /// The notifications are global, other tests may fail uploads of their own workspace.
+  fn values_of(&self, workspace_id: &str) -> Vec<(UploadFailuresStatePB, i64, String)> {
+    self
+      .values
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|value| value.workspace_id == workspace_id)
+      .map(|value| (value.state, value.failed_count, value.message.clone()))
+      .collect()
+  }
+}
+
+impl NotificationSender for FailureNotificationCollector {
+  fn send_subject(&self, subject: SubscribeObject) -> Result<(), String> {
+    if subject.ty == StorageNotification::UploadFailuresChanged as i32 {
+      let payload = UploadFailuresPB::try_from(Bytes::from(subject.payload.unwrap()))
+        .map_err(|err| err.to_string())?;
+      self.values.lock().unwrap().push(payload);
+    }
+    Ok(())
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn coalesce_failure_notifications_test() {
+  let collector = FailureNotificationCollector::default();
+  register_notification_sender(collector.clone());
+  let clock = Arc::new(MockClock::default());
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .part_max_attempts(1)
+      .failure_notification_window(Some(WINDOW))
+      .clock(clock.clone()),
+  )
+  .await;
+  test.manager.update_network_reachable(false);
+  let workspace_id = test.workspace_id();
+  let parent_dir = "failure_notification_test";
+  let mut file_ids = vec![];
+  for _ in 0..5 {
+    let file_path = create_temp_file(1024, "txt");
+    let (created_upload, _) = test
+      .manager
+      .storage_service()
+      .create_upload(
+        &workspace_id,
+        parent_dir,
+        file_path.to_str().unwrap(),
+        false,
+      )
+      .await
+      .unwrap();
+    file_ids.push(created_upload.file_id);
+  }
+
+  // All the uploads fail at once, only the first failure is notified right away.
+  test
+    .cloud_service
+    .complete_failures
+    .store(file_ids.len(), Ordering::SeqCst);
+  let results = join_all(file_ids.iter().map(|file_id| {
+    test
+      .manager
+      .storage_service()
+      .resume_upload(&workspace_id, parent_dir, file_id)
+  }))
+  .await;
+  assert!(results.iter().all(|result| result.is_err()));
+  let values = collector.values_of(&workspace_id);
+  assert_eq!(values.len(), 1);
+  assert_eq!(values[0].0, UploadFailuresStatePB::Failed);
+  assert_eq!(values[0].1, 1);
+
+  // The rest are coalesced into a single notification once the window elapsed.
+  clock.advance(WINDOW);
+  tokio::time::timeout(Duration::from_secs(5), async {
+    while collector.values_of(&workspace_id).len() < 2 {
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+  })
+  .await
+  .unwrap();
+  let values = collector.values_of(&workspace_id);
+  assert_eq!(values.len(), 2);
+  assert_eq!(values[1].0, UploadFailuresStatePB::Failed);
+  assert_eq!(values[1].1, 4);
+  assert!(values[1].2.starts_with("4 uploads failed: "));
+
+  // The first success notifies the recovery.
+  test
+    .manager
+    .storage_service()
+    .resume_upload(&workspace_id, parent_dir, &file_ids[0])
+    .await
+    .unwrap();
+  let values = collector.values_of(&workspace_id);
+  assert_eq!(values.len(), 3);
+  assert_eq!(values[2].0, UploadFailuresStatePB::Recovered);
+}
@@ -0,0 +1,100 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::config::{ProgressFanOut, StorageManagerConfig};
+use flowy_storage_pub::storage::FileUploadState;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+const FILE_COUNT: usize = 6;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn direct_fan_out_matches_global_stream_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default().progress_fan_out(ProgressFanOut::Direct),
+  )
+  .await;
+  let (global_progress, file_progress) = upload_files(&test).await;
+
+  // Every progress of the global stream reached the per-file notifier, in the same order.
+  assert_eq!(file_progress.len(), FILE_COUNT);
+  for (file_id, progress) in &file_progress {
+    assert_eq!(progress, &global_progress[file_id], "{}", file_id);
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn relay_fan_out_matches_global_stream_test() {
+  let test = StorageTest::new().await;
+  let (global_progress, file_progress) = upload_files(&test).await;
+
+  // The relay may skip progress when it lags behind, but both streams finish every file.
+  assert_eq!(file_progress.len(), FILE_COUNT);
+  for (file_id, progress) in &file_progress {
+    assert_eq!(progress.last(), Some(&1.0), "{}", file_id);
+    assert_eq!(global_progress[file_id].last(), Some(&1.0), "{}", file_id);
+  }
+}
+
+/// Uploads [FILE_COUNT] files at the same time, and returns the progress of each file received
+/// from the global stream and from the per-file notifiers. A finished upload is reported as 1.0.
+async fn upload_files(
+  test: &StorageTest,
+) -> (HashMap<String, Vec<f64>>, HashMap<String, Vec<f64>>) {
+  let workspace_id = test.workspace_id();
+  let parent_dir = "fan_out_test";
+  let (_, mut global_rx) = test.manager.subscribe_events();
+
+  let mut receivers = vec![];
+  for _ in 0..FILE_COUNT {
+    let file_path = create_temp_file(11 * MB, "txt");
+    let (_, receiver) = test
+      .manager
+      .storage_service()
+      .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+      .await
+      .unwrap();
+    receivers.push(receiver.unwrap());
+  }
+
+  let mut file_progress = HashMap::new();
+  for mut receiver in receivers {
+    let mut progress = vec![];
+    tokio::time::timeout(Duration::from_secs(60), async {
+      while let Ok(state) = receiver.recv().await {
+        match state {
+          FileUploadState::Uploading { progress: value } => progress.push(value),
+          FileUploadState::Finished { .. } => {
+            progress.push(1.0);
+            break;
+          },
+          _ => {},
+        }
+      }
+    })
+    .await
+    .unwrap();
+    file_progress.insert(receiver.file_id.clone(), progress);
+  }
+
+  let mut global_progress: HashMap<String, Vec<f64>> = HashMap::new();
+  let mut finished = 0;
+  while finished < FILE_COUNT {
+    let progress = tokio::time::timeout(Duration::from_secs(5), global_rx.recv())
+      .await
+      .unwrap()
+      .unwrap();
+    if progress.progress >= 1.0 {
+      finished += 1;
+    }
+    global_progress
+      .entry(progress.file_id)
+      .or_default()
+      .push(progress.progress);
+  }
+
+  for file_id in file_progress.keys() {
+    let state = test.manager.get_file_state(parent_dir, file_id).await;
+    assert!(matches!(state, Some(FileUploadState::Finished { .. })));
+  }
+  (global_progress, file_progress)
+}
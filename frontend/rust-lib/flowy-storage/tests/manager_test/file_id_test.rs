@@ -0,0 +1,124 @@
+use crate::util::{create_temp_file, StorageTest};
+use collab_importer::util::FileId;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::file_id::{
+  compute_file_id, file_id_from_bytes, file_id_from_metadata, file_id_from_path, FileIdStrategy,
+};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Counts the bytes read from the inner reader.
+struct CountingReader<R> {
+  inner: R,
+  read: Arc<AtomicUsize>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CountingReader<R> {
+  fn poll_read(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let filled = buf.filled().len();
+    let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+    let read = buf.filled().len() - filled;
+    self.read.fetch_add(read, Ordering::SeqCst);
+    poll
+  }
+}
+
+#[tokio::test]
+async fn file_id_from_bytes_matches_file_id_from_path_test() {
+  // Larger than the read buffer, so the content is hashed in several reads.
+  let file_path = create_temp_file(200 * 1024, "txt");
+  let content = std::fs::read(&file_path).unwrap();
+
+  let path_file_id = file_id_from_path(&file_path).await.unwrap();
+  let bytes_file_id = file_id_from_bytes(&content, Some("txt")).await;
+  assert_eq!(path_file_id, bytes_file_id);
+  // The ids stay the same as the ones of the existing upload records.
+  assert_eq!(path_file_id, FileId::from_path(&file_path).await.unwrap());
+}
+
+#[tokio::test]
+async fn file_id_of_file_without_extension_test() {
+  let file_path = create_temp_file(1024, "txt").with_extension("");
+  std::fs::write(&file_path, b"no extension").unwrap();
+
+  let path_file_id = file_id_from_path(&file_path).await.unwrap();
+  assert_eq!(
+    path_file_id,
+    file_id_from_bytes(b"no extension", None).await
+  );
+  assert_eq!(path_file_id, FileId::from_path(&file_path).await.unwrap());
+}
+
+#[tokio::test]
+async fn metadata_file_id_skips_content_test() {
+  let file_path = create_temp_file(200 * 1024, "txt");
+  let metadata = std::fs::metadata(&file_path).unwrap();
+
+  let mut file_ids = vec![];
+  for (strategy, expected_read) in [
+    (FileIdStrategy::ContentHash, 200 * 1024),
+    (FileIdStrategy::Metadata, 0),
+  ] {
+    let read = Arc::new(AtomicUsize::new(0));
+    let reader = CountingReader {
+      inner: tokio::fs::File::open(&file_path).await.unwrap(),
+      read: read.clone(),
+    };
+    let file_id = compute_file_id(reader, &file_path, &metadata, strategy)
+      .await
+      .unwrap();
+    assert_eq!(read.load(Ordering::SeqCst), expected_read, "{:?}", strategy);
+    assert!(file_id.ends_with(".txt"));
+    file_ids.push(file_id);
+  }
+  assert_eq!(file_ids[0], file_id_from_path(&file_path).await.unwrap());
+  assert_eq!(file_ids[1], file_id_from_metadata(&file_path, &metadata));
+  assert_ne!(file_ids[0], file_ids[1]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_with_metadata_file_id_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default().file_id_strategy(FileIdStrategy::Metadata),
+  )
+  .await;
+  test.manager.update_network_reachable(false);
+  let file_path = create_temp_file(1024, "txt");
+  let metadata = std::fs::metadata(&file_path).unwrap();
+  let create_upload = || async {
+    test
+      .manager
+      .storage_service()
+      .create_upload(
+        &test.workspace_id(),
+        "file_id_test",
+        file_path.to_str().unwrap(),
+        false,
+      )
+      .await
+      .unwrap()
+      .0
+      .file_id
+  };
+
+  assert_eq!(
+    create_upload().await,
+    file_id_from_metadata(&file_path, &metadata)
+  );
+  // A content addressed backend always gets the hash of the content.
+  test
+    .cloud_service
+    .content_addressed
+    .store(true, Ordering::SeqCst);
+  assert_eq!(
+    create_upload().await,
+    file_id_from_path(&file_path).await.unwrap()
+  );
+}
@@ -0,0 +1,83 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use collab_importer::util::FileId;
+use flowy_storage_pub::storage::FileUploadState;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn finished_state_carries_upload_summary_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(200)));
+  let workspace_id = test.workspace_id();
+  let parent_dir = "finished_state_test";
+  let file_path = create_temp_file(3000, "txt");
+  let file_id = FileId::from_path(&file_path).await.unwrap();
+  let mut receiver = test
+    .manager
+    .subscribe_file_state(parent_dir, &file_id)
+    .await
+    .unwrap()
+    .unwrap();
+
+  test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+
+  let finished = tokio::time::timeout(Duration::from_secs(30), async {
+    loop {
+      if let FileUploadState::Finished {
+        total_bytes,
+        duration,
+        ..
+      } = receiver.recv().await.unwrap()
+      {
+        return (total_bytes, duration);
+      }
+    }
+  })
+  .await
+  .unwrap();
+  assert_eq!(finished.0, Some(3000));
+  assert!(finished.1.unwrap() >= Duration::from_millis(200));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn finished_state_of_completed_upload_has_no_summary_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "finished_state_test";
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  let mut receiver = receiver.unwrap();
+  assert!(wait_for_finished(&mut receiver, Duration::from_secs(30)).await);
+
+  // Uploading the same file again returns the terminal state of the completed upload.
+  let (_, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  let state = receiver.unwrap().recv().await.unwrap();
+  match state {
+    FileUploadState::Finished {
+      file_id,
+      total_bytes,
+      duration,
+    } => {
+      assert_eq!(file_id, created_upload.file_id);
+      assert_eq!(total_bytes, None);
+      assert_eq!(duration, None);
+    },
+    state => panic!("unexpected state: {:?}", state),
+  }
+}
@@ -0,0 +1,29 @@
+use crate::util::{MockStorageCloudService, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+
+#[tokio::test]
+async fn replay_progress_history_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().progress_history_size(3)).await;
+  let workspace_id = test.workspace_id();
+  let url =
+    |file_id: &str| MockStorageCloudService::object_url(&workspace_id, "history_test", file_id);
+
+  // Each query emits a synthetic progress event.
+  for file_id in ["1", "2", "3", "4"] {
+    test.manager.query_file_state(&url(file_id)).await.unwrap();
+  }
+
+  // Only the last three events are kept, oldest first.
+  let (history, mut rx) = test.manager.subscribe_events();
+  let file_ids = history
+    .iter()
+    .map(|progress| progress.file_id.as_str())
+    .collect::<Vec<_>>();
+  assert_eq!(file_ids, vec!["2", "3", "4"]);
+
+  // The live events follow the history without duplicating it.
+  test.manager.query_file_state(&url("5")).await.unwrap();
+  assert_eq!(rx.recv().await.unwrap().file_id, "5");
+  assert!(rx.try_recv().is_err());
+}
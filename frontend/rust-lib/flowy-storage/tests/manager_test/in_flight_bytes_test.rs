@@ -0,0 +1,58 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn max_in_flight_bytes_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .prefetch_depth(2)
+      .max_concurrent_uploads(4)
+      .max_in_flight_bytes(Some(2 * MB)),
+  )
+  .await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(200)));
+  let workspace_id = test.workspace_id();
+
+  let mut receivers = vec![];
+  for _ in 0..4 {
+    let file_path = create_temp_file(3 * MB, "txt");
+    let (_, receiver) = test
+      .manager
+      .storage_service()
+      .create_upload(
+        &workspace_id,
+        "in_flight_bytes_test",
+        file_path.to_str().unwrap(),
+        true,
+      )
+      .await
+      .unwrap();
+    receivers.push(receiver.unwrap());
+  }
+
+  for mut receiver in receivers {
+    assert!(wait_for_finished(&mut receiver, Duration::from_secs(120)).await);
+  }
+  // The four uploads run at once, but only two parts fit in the budget, including the parts read
+  // ahead.
+  let max_in_flight_part_bytes = test
+    .cloud_service
+    .max_in_flight_part_bytes
+    .load(Ordering::SeqCst);
+  assert!(
+    max_in_flight_part_bytes <= 2 * MB,
+    "{} bytes were in flight at once",
+    max_in_flight_part_bytes
+  );
+  assert_eq!(
+    test.cloud_service.uploaded_bytes.load(Ordering::SeqCst),
+    12 * MB
+  );
+}
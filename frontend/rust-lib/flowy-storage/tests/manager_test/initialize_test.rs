@@ -0,0 +1,33 @@
+use crate::util::{MockStorageCloudService, MockStorageUserService, StorageTest};
+use flowy_error::ErrorCode;
+use flowy_sqlite::PoolConfig;
+use flowy_storage::manager::{StorageManager, StorageUserService};
+use std::path::Path;
+use std::sync::Arc;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn initialize_storage_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  test.manager.initialize(&workspace_id).await.unwrap();
+  // Initializing again, e.g. when reopening the workspace, succeeds as well.
+  test.manager.initialize(&workspace_id).await.unwrap();
+  assert!(test.manager.is_storage_write_enabled());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn initialize_with_unwritable_temp_dir_test() {
+  let user_service = Arc::new(MockStorageUserService::new(PoolConfig::default()));
+  // A file takes the place of the temp dir, so the temp files can't be created.
+  let root_dir = Path::new(user_service.get_application_root_dir());
+  std::fs::create_dir_all(root_dir).unwrap();
+  std::fs::write(root_dir.join("cache_files"), b"").unwrap();
+  let manager = StorageManager::new(
+    Arc::new(MockStorageCloudService::default()),
+    user_service.clone(),
+  );
+
+  let workspace_id = user_service.workspace_id().unwrap();
+  let err = manager.initialize(&workspace_id).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::UploadTempFileError);
+}
@@ -0,0 +1,42 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::file_id::file_id_from_path;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn is_uploading_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(500)));
+  let workspace_id = test.workspace_id();
+  let parent_dir = "is_uploading_test";
+  let file_path = create_temp_file(1024, "txt");
+  let file_id = file_id_from_path(&file_path).await.unwrap();
+  assert!(!test.manager.is_uploading(parent_dir, &file_id));
+
+  // Queued while the network is unreachable.
+  test.manager.update_network_reachable(false);
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  assert_eq!(created_upload.file_id, file_id);
+  assert!(test.manager.is_uploading(parent_dir, &file_id));
+  // Another parent dir is a different upload.
+  assert!(!test.manager.is_uploading("other_dir", &file_id));
+
+  // Running, the part takes a while to upload.
+  test.manager.update_network_reachable(true);
+  tokio::time::sleep(Duration::from_millis(200)).await;
+  assert!(test.manager.is_uploading(parent_dir, &file_id));
+
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert!(!test.manager.is_uploading(parent_dir, &file_id));
+}
@@ -0,0 +1,164 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::UploadOutcome;
+use flowy_storage::sqlite_sql::select_upload_file;
+use flowy_storage_pub::storage::{CreatedUpload, DeleteState, DeleteTarget, FileProgressReceiver};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+async fn create_upload(
+  test: &StorageTest,
+  file_path: &Path,
+  parent_dir: &str,
+) -> (CreatedUpload, Option<FileProgressReceiver>) {
+  test
+    .manager
+    .storage_service()
+    .create_upload(
+      &test.workspace_id(),
+      parent_dir,
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap()
+}
+
+async fn delete_object(test: &StorageTest, url: &str, parent_dir: &str) {
+  let mut rx = test.manager.subscribe_delete_progress();
+  test
+    .manager
+    .storage_service()
+    .delete_object(
+      url.to_string(),
+      Some(parent_dir.to_string()),
+      "not_exist_file.txt".to_string(),
+    )
+    .unwrap();
+  tokio::time::timeout(Duration::from_secs(5), async {
+    loop {
+      let progress = rx.recv().await.unwrap();
+      if progress.target == DeleteTarget::Cloud && progress.state == DeleteState::Deleted {
+        break;
+      }
+    }
+  })
+  .await
+  .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn link_duplicate_upload_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().link_duplicate_uploads(true))
+      .await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "png");
+  let (created_upload, receiver) = create_upload(&test, &file_path, "page_a").await;
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  let validation = test
+    .manager
+    .validate_upload(&workspace_id, "page_b", file_path.to_str().unwrap())
+    .await
+    .unwrap();
+  assert_eq!(validation.outcome, UploadOutcome::WouldLink);
+  assert_eq!(validation.url.as_ref(), Some(&created_upload.url));
+
+  // The same content attached to another page links to the uploaded object.
+  let (linked_upload, receiver) = create_upload(&test, &file_path, "page_b").await;
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert_eq!(linked_upload.url, created_upload.url);
+  assert_eq!(linked_upload.file_id, created_upload.file_id);
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    1
+  );
+  let link = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    "page_b",
+    &linked_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert!(link.is_finish);
+  assert_eq!(link.linked_parent_dir, "page_a");
+  // Attaching it again to the same page returns the link.
+  let (again, _) = create_upload(&test, &file_path, "page_b").await;
+  assert_eq!(again.url, created_upload.url);
+
+  // Removing one of the locations keeps the shared object, removing the last one deletes it.
+  delete_object(&test, &created_upload.url, "page_b").await;
+  assert!(test.cloud_service.objects.contains_key(&created_upload.url));
+  delete_object(&test, &created_upload.url, "page_a").await;
+  assert!(!test.cloud_service.objects.contains_key(&created_upload.url));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn delete_linked_source_upload_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().link_duplicate_uploads(true))
+      .await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "png");
+  let (created_upload, receiver) = create_upload(&test, &file_path, "page_a").await;
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  let (linked_upload, receiver) = create_upload(&test, &file_path, "page_b").await;
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  // Removing the source location only drops its own record, the link keeps the object.
+  delete_object(&test, &created_upload.url, "page_a").await;
+  assert!(test.cloud_service.objects.contains_key(&created_upload.url));
+  {
+    let mut conn = test.db_connection();
+    assert!(
+      select_upload_file(&mut conn, &workspace_id, "page_a", &created_upload.file_id)
+        .unwrap()
+        .is_none()
+    );
+    let link = select_upload_file(&mut conn, &workspace_id, "page_b", &linked_upload.file_id)
+      .unwrap()
+      .unwrap();
+    assert!(link.is_finish);
+  }
+  // The link location still resolves to the object without uploading it again.
+  let (again, _) = create_upload(&test, &file_path, "page_b").await;
+  assert_eq!(again.url, created_upload.url);
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    1
+  );
+
+  delete_object(&test, &created_upload.url, "page_b").await;
+  assert!(!test.cloud_service.objects.contains_key(&created_upload.url));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn duplicate_upload_not_linked_by_default_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "png");
+  for parent_dir in ["page_a", "page_b"] {
+    let (_, receiver) = test
+      .manager
+      .storage_service()
+      .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+      .await
+      .unwrap();
+    assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  }
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    2
+  );
+}
@@ -0,0 +1,80 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_error::ErrorCode;
+use flowy_storage_pub::cloud::ObjectInfo;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+async fn upload_file(test: &StorageTest, parent_dir: &str, size: usize, ext: &str) -> String {
+  let file_path = create_temp_file(size, ext);
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &test.workspace_id(),
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  created_upload.file_id
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn list_objects_pages_through_parent_dir_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .list_objects_support
+    .store(true, Ordering::SeqCst);
+  test.cloud_service.list_page_size.store(2, Ordering::SeqCst);
+  let workspace_id = test.workspace_id();
+  let parent_dir = "list_objects_test";
+
+  let mut expected = vec![];
+  for (size, ext, content_type) in [
+    (100, "txt", "text/plain"),
+    (200, "png", "image/png"),
+    (300, "json", "application/json"),
+  ] {
+    let file_id = upload_file(&test, parent_dir, size, ext).await;
+    expected.push(ObjectInfo {
+      file_id,
+      size: Some(size as u64),
+      content_type: Some(content_type.to_string()),
+    });
+  }
+  expected.sort_by(|a, b| a.file_id.cmp(&b.file_id));
+  // The objects of another parent dir aren't listed.
+  upload_file(&test, "other_dir", 100, "txt").await;
+
+  let first_page = test
+    .manager
+    .list_objects(&workspace_id, parent_dir, None)
+    .await
+    .unwrap();
+  assert_eq!(first_page.objects, expected[..2]);
+  let cursor = first_page.next_cursor.unwrap();
+
+  let last_page = test
+    .manager
+    .list_objects(&workspace_id, parent_dir, Some(&cursor))
+    .await
+    .unwrap();
+  assert_eq!(last_page.objects, expected[2..]);
+  assert!(last_page.next_cursor.is_none());
+}
+
+#[tokio::test]
+async fn list_objects_unsupported_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+
+  let err = test
+    .manager
+    .list_objects(&workspace_id, "list_objects_test", None)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotSupportYet);
+}
@@ -0,0 +1,88 @@
+mod abort_upload_test;
+mod backoff_test;
+mod bandwidth_test;
+mod cancel_upload_test;
+mod cancel_workspace_test;
+mod capabilities_test;
+mod clock_skew_test;
+mod clock_test;
+mod completion_pending_test;
+mod concurrency_test;
+mod content_type_test;
+mod copy_buffer_test;
+mod create_upload_test;
+mod delete_object_test;
+mod download_batch_test;
+mod download_cancel_test;
+mod download_object_test;
+mod download_path_test;
+mod download_queue_test;
+mod download_writer_test;
+mod duplicate_notification_test;
+mod existing_download_test;
+mod failed_upload_test;
+mod failure_notification_test;
+mod fan_out_test;
+mod file_id_test;
+mod finished_state_test;
+mod history_test;
+mod in_flight_bytes_test;
+mod initialize_test;
+mod is_uploading_test;
+mod link_upload_test;
+mod list_objects_test;
+mod manifest_test;
+mod manual_tasks_test;
+mod metadata_test;
+mod metered_test;
+mod missing_file_test;
+#[cfg(target_os = "linux")]
+mod non_utf8_path_test;
+mod object_url_test;
+mod overwrite_test;
+mod parent_dir_test;
+mod part_checksum_test;
+mod part_retry_test;
+mod part_size_test;
+#[cfg(feature = "diagnostics")]
+mod part_timing_test;
+mod pause_reasons_test;
+mod pending_uploads_test;
+mod prefetch_test;
+mod progress_callback_test;
+mod progress_floor_test;
+mod progress_interval_test;
+mod progress_order_test;
+mod query_state_throttle_test;
+mod queue_depth_test;
+mod quota_test;
+mod read_range_test;
+mod reconcile_test;
+mod relay_test;
+mod repair_storage_test;
+mod resume_strategy_test;
+mod resume_upload_test;
+mod retry_budget_test;
+mod retry_upload_test;
+mod sqlite_pool_test;
+mod spawner_test;
+mod storage_class_test;
+mod storage_error_test;
+mod subscribe_test;
+mod temp_delete_retry_test;
+mod temp_file_naming_test;
+mod temp_file_policy_test;
+mod throughput_test;
+mod upload_detail_test;
+mod upload_guard_test;
+mod upload_log_test;
+mod uploads_stream_test;
+mod user_temp_test;
+mod util;
+mod validate_upload_test;
+mod verify_storage_test;
+mod wait_idle_test;
+#[cfg(feature = "webdav")]
+mod webdav_test;
+mod workspace_scope_test;
+mod write_access_test;
@@ -0,0 +1,83 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manifest::{manifest_sidecar_id, UploadManifest};
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_manifest_matches_upload_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .upload_manifest(true)
+      .upload_manifest_sidecar(true),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "manifest_test";
+  let file_path = create_temp_file(12 * MB, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  let manifest = test
+    .manager
+    .upload_manifest(parent_dir, &created_upload.file_id)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(manifest.file_id, created_upload.file_id);
+  assert_eq!(manifest.total_size, 12 * MB as u64);
+  assert_eq!(manifest.parts_size(), manifest.total_size);
+  // 5MB, 5MB and the last part of 2MB.
+  let parts = manifest
+    .parts
+    .iter()
+    .map(|part| (part.part_number, part.size as usize))
+    .collect::<Vec<_>>();
+  assert_eq!(parts, vec![(1, 5 * MB), (2, 5 * MB), (3, 2 * MB)]);
+  assert!(manifest.parts.iter().all(|part| !part.e_tag.is_empty()));
+
+  // The object on the server has the size recorded in the manifest.
+  let object_url =
+    MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+  let object_len = test.cloud_service.objects.get(&object_url).unwrap().len();
+  assert_eq!(object_len as u64, manifest.total_size);
+
+  // The sidecar holds the same manifest.
+  let sidecar_url = MockStorageCloudService::object_url(
+    &workspace_id,
+    parent_dir,
+    &manifest_sidecar_id(&created_upload.file_id),
+  );
+  let sidecar = test.cloud_service.objects.get(&sidecar_url).unwrap();
+  let sidecar_manifest: UploadManifest = serde_json::from_slice(&sidecar).unwrap();
+  assert_eq!(sidecar_manifest, manifest);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_manifest_disabled_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "manifest_test";
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  let manifest = test
+    .manager
+    .upload_manifest(parent_dir, &created_upload.file_id)
+    .await
+    .unwrap();
+  assert!(manifest.is_none());
+  assert_eq!(test.cloud_service.objects.len(), 1);
+}
@@ -0,0 +1,90 @@
+use crate::util::{create_temp_file, MockStorageCloudService, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::{IdleOutcome, QueueStatus, StorageManager};
+use flowy_storage_pub::storage::FileUploadState;
+use std::sync::Arc;
+use std::time::Duration;
+
+const PARENT_DIR: &str = "manual_tasks_test";
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn manual_progress_relay_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .manual_background_tasks(true)
+      .progress_min_interval(Duration::ZERO),
+  )
+  .await;
+  let file_path = create_temp_file(1024, "txt");
+  let (_, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &test.workspace_id(),
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  let mut receiver = receiver.unwrap();
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Idle
+  );
+
+  // Nothing reaches the per-file notifier until the relay is ticked.
+  assert!(receiver.try_recv().is_err());
+  assert!(test.manager.tick_relay().await > 0);
+  let mut finished = false;
+  while let Ok(state) = receiver.try_recv() {
+    finished = matches!(state, FileUploadState::Finished { .. });
+  }
+  assert!(finished);
+  assert_eq!(test.manager.tick_relay().await, 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn manual_startup_resume_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  // The upload is held while the network is unreachable, so that its record stays unfinished.
+  test.manager.update_network_reachable(false);
+  test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+
+  let manager = StorageManager::new_with_config(
+    Arc::new(MockStorageCloudService::default()),
+    test.user_service.clone(),
+    StorageManagerConfig::default().manual_background_tasks(true),
+  );
+  manager.update_network_reachable(false);
+  manager.initialize(&workspace_id).await.unwrap();
+  assert_eq!(
+    manager.queue_status().await,
+    QueueStatus {
+      queued: 0,
+      running: 0
+    }
+  );
+
+  // The unfinished upload is only queued once the startup resume runs.
+  manager.run_startup_resume().await.unwrap();
+  assert_eq!(
+    manager.queue_status().await,
+    QueueStatus {
+      queued: 1,
+      running: 0
+    }
+  );
+}
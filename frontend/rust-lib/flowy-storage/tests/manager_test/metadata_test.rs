@@ -0,0 +1,82 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use flowy_storage::manager::CreateUploadOptions;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+fn metadata() -> HashMap<String, String> {
+  HashMap::from([
+    (
+      "original-filename".to_string(),
+      "report 2024.pdf".to_string(),
+    ),
+    ("app-version".to_string(), "0.7.6".to_string()),
+  ])
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn metadata_round_trips_through_upload_test() {
+  for object_metadata_support in [true, false] {
+    let test = StorageTest::new().await;
+    test
+      .cloud_service
+      .object_metadata_support
+      .store(object_metadata_support, Ordering::SeqCst);
+    let workspace_id = test.workspace_id();
+    let parent_dir = "metadata_test";
+    let file_path = create_temp_file(1024, "pdf");
+
+    let (created_upload, receiver) = test
+      .manager
+      .create_upload_with(
+        &workspace_id,
+        parent_dir,
+        file_path.to_str().unwrap(),
+        true,
+        CreateUploadOptions::default().metadata(metadata()),
+      )
+      .await
+      .unwrap();
+    assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+    // The backend stores the metadata when it supports it, otherwise it's read from the record.
+    let url =
+      MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+    assert_eq!(
+      test.cloud_service.metadata.get(&url).map(|m| m.clone()),
+      object_metadata_support.then(metadata)
+    );
+    assert_eq!(
+      test.manager.object_metadata(&url).await.unwrap(),
+      metadata()
+    );
+  }
+}
+
+#[tokio::test]
+async fn invalid_metadata_is_rejected_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+
+  let invalid = [
+    HashMap::from([("".to_string(), "value".to_string())]),
+    HashMap::from([("file name".to_string(), "value".to_string())]),
+    HashMap::from([("name".to_string(), "line\r\nInjected: header".to_string())]),
+    HashMap::from([("name".to_string(), "é".to_string())]),
+    HashMap::from([("name".to_string(), "a".repeat(4096))]),
+  ];
+  for metadata in invalid {
+    let result = test
+      .manager
+      .create_upload_with(
+        &workspace_id,
+        "metadata_test",
+        file_path.to_str().unwrap(),
+        false,
+        CreateUploadOptions::default().metadata(metadata),
+      )
+      .await;
+    assert!(result.is_err());
+  }
+}
@@ -0,0 +1,50 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::time::Duration;
+
+const PARENT_DIR: &str = "metered_test";
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn metered_connection_only_runs_immediate_uploads_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  test.manager.set_metered(true);
+  assert!(test.manager.is_metered());
+
+  let mut uploads = vec![];
+  for upload_immediately in [false, true] {
+    let file_path = create_temp_file(1024, "txt");
+    let (created_upload, receiver) = test
+      .manager
+      .storage_service()
+      .create_upload(
+        &workspace_id,
+        PARENT_DIR,
+        file_path.to_str().unwrap(),
+        upload_immediately,
+      )
+      .await
+      .unwrap();
+    uploads.push((created_upload.file_id, receiver.unwrap()));
+  }
+  let (background_file_id, mut background_rx) = uploads.remove(0);
+  let (_, mut immediate_rx) = uploads.remove(0);
+
+  // The immediate upload runs, while the other one waits in the queue.
+  assert!(wait_for_finished(&mut immediate_rx, Duration::from_secs(30)).await);
+  assert!(!wait_for_finished(&mut background_rx, Duration::from_secs(2)).await);
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    PARENT_DIR,
+    &background_file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert!(!record.is_finish);
+  assert!(record.upload_id.is_empty());
+
+  // It runs once the connection is no longer metered.
+  test.manager.set_metered(false);
+  assert!(wait_for_finished(&mut background_rx, Duration::from_secs(30)).await);
+}
@@ -0,0 +1,146 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_error::ErrorCode;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::{IdleOutcome, ResumeStrategy};
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn temp_file_removed_between_parts_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_secs(1)));
+  let workspace_id = test.workspace_id();
+  let parent_dir = "missing_file_test";
+  // Three parts
+  let file_path = create_temp_file(12 * 1024 * 1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+
+  // Remove the temp file while the first part is in flight.
+  tokio::time::sleep(Duration::from_millis(500)).await;
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  std::fs::remove_file(&record.local_file_path).unwrap();
+
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Idle
+  );
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    1
+  );
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    0
+  );
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &created_upload.file_id,
+  )
+  .unwrap();
+  assert!(record.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn temp_file_truncated_before_resume_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .part_max_attempts(1),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "missing_file_test";
+  let file_path = create_temp_file(4 * MB, "txt");
+  // Keep the uploader from picking the upload, it's driven by the resume calls below.
+  test.manager.update_network_reachable(false);
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let file_id = created_upload.file_id;
+
+  // The first attempt uploads two parts before it's interrupted.
+  test
+    .cloud_service
+    .fail_part_number
+    .store(3, Ordering::SeqCst);
+  assert!(test
+    .manager
+    .resume_upload_with_strategy(
+      &workspace_id,
+      parent_dir,
+      &file_id,
+      ResumeStrategy::Continue
+    )
+    .await
+    .is_err());
+
+  // The temp file is now shorter than the uploaded parts.
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &file_id,
+  )
+  .unwrap()
+  .unwrap();
+  let file = std::fs::OpenOptions::new()
+    .write(true)
+    .open(&record.local_file_path)
+    .unwrap();
+  file.set_len(MB as u64).unwrap();
+
+  let err = test
+    .manager
+    .resume_upload_with_strategy(
+      &workspace_id,
+      parent_dir,
+      &file_id,
+      ResumeStrategy::Continue,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::UploadFileMissing);
+  // Nothing was uploaded from the start under the same record.
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    2
+  );
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &file_id,
+  )
+  .unwrap();
+  assert!(record.is_none());
+}
@@ -0,0 +1,83 @@
+use crate::util::{generate_random_string, MockStorageCloudService, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::ResumeStrategy;
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::env::temp_dir;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::sync::atomic::Ordering;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_and_resume_non_utf8_path_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .part_max_attempts(1),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "non_utf8_path_test";
+
+  // "café.txt" in Latin-1, which isn't valid UTF-8.
+  let dir = temp_dir().join(format!("storage-file-{}", generate_random_string(8)));
+  std::fs::create_dir_all(&dir).unwrap();
+  let file_path = dir.join(OsStr::from_bytes(b"caf\xe9.txt"));
+  assert!(file_path.to_str().is_none());
+  let content = (0..3 * MB).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+  std::fs::write(&file_path, &content).unwrap();
+
+  // Keep the uploader from picking the upload, it's driven by the resume calls below.
+  test.manager.update_network_reachable(false);
+  let (created_upload, _) = test
+    .manager
+    .create_upload_from_path(&workspace_id, parent_dir, &file_path, false)
+    .await
+    .unwrap();
+  assert!(created_upload.file_id.ends_with(".txt"));
+
+  // The first attempt is interrupted while sending the 2nd part.
+  test
+    .cloud_service
+    .fail_part_number
+    .store(2, Ordering::SeqCst);
+  assert!(test
+    .manager
+    .resume_upload_with_strategy(
+      &workspace_id,
+      parent_dir,
+      &created_upload.file_id,
+      ResumeStrategy::Continue,
+    )
+    .await
+    .is_err());
+
+  test
+    .manager
+    .resume_upload_with_strategy(
+      &workspace_id,
+      parent_dir,
+      &created_upload.file_id,
+      ResumeStrategy::Continue,
+    )
+    .await
+    .unwrap();
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert!(record.is_finish);
+  assert_eq!(record.content_type, "text/plain");
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+  assert_eq!(
+    test.cloud_service.objects.get(&url).unwrap().to_vec(),
+    content
+  );
+  // The original file is left untouched.
+  assert_eq!(std::fs::read(&file_path).unwrap(), content);
+}
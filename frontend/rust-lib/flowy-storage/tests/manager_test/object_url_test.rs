@@ -0,0 +1,37 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn query_file_state_with_v1_and_v2_urls_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "object_url_test";
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  // The urls of the older documents use the v1 format, both resolve to the same file.
+  for url in [
+    MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id),
+    MockStorageCloudService::object_url_v2(&workspace_id, parent_dir, &created_upload.file_id),
+  ] {
+    let state = test.manager.query_file_state(&url).await.unwrap();
+    assert_eq!(state.file_id, created_upload.file_id);
+    assert!(state.is_finish);
+  }
+
+  let url = MockStorageCloudService::object_url_v2(&workspace_id, parent_dir, "unknown_file");
+  let state = test.manager.query_file_state(&url).await.unwrap();
+  assert!(!state.is_finish);
+
+  assert!(test
+    .manager
+    .query_file_state("https://mock.appflowy.io/api/file_storage/v3/unknown")
+    .await
+    .is_none());
+}
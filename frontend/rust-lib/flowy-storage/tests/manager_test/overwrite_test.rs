@@ -0,0 +1,132 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use bytes::Bytes;
+use flowy_error::ErrorCode;
+use flowy_storage::file_id::file_id_from_path;
+use flowy_storage::manager::{CreateUploadOptions, IdleOutcome};
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const PARENT_DIR: &str = "overwrite_test";
+
+/// Stores an object with other content at the url the file would be uploaded to.
+async fn store_existing_object(test: &StorageTest, file_path: &Path) -> (String, String) {
+  let file_id = file_id_from_path(file_path).await.unwrap();
+  let url = MockStorageCloudService::object_url(&test.workspace_id(), PARENT_DIR, &file_id);
+  test
+    .cloud_service
+    .objects
+    .insert(url.clone(), Bytes::from_static(b"existing"));
+  (file_id, url)
+}
+
+fn has_record(test: &StorageTest, file_id: &str) -> bool {
+  select_upload_file(
+    &mut test.db_connection(),
+    &test.workspace_id(),
+    PARENT_DIR,
+    file_id,
+  )
+  .unwrap()
+  .is_some()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn overwrite_replaces_existing_object_test() {
+  let test = StorageTest::new().await;
+  let file_path = create_temp_file(1024, "txt");
+  let (_, url) = store_existing_object(&test, &file_path).await;
+
+  let (_, receiver) = test
+    .manager
+    .create_upload_with(
+      &test.workspace_id(),
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      true,
+      CreateUploadOptions::default().overwrite(true),
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert_eq!(
+    test.cloud_service.objects.get(&url).unwrap().to_vec(),
+    std::fs::read(&file_path).unwrap()
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn no_overwrite_rejects_existing_object_test() {
+  let test = StorageTest::new().await;
+  let file_path = create_temp_file(1024, "txt");
+  let (file_id, url) = store_existing_object(&test, &file_path).await;
+
+  let err = test
+    .manager
+    .create_upload_with(
+      &test.workspace_id(),
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      true,
+      CreateUploadOptions::default().overwrite(false),
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::ObjectAlreadyExists);
+  assert!(!has_record(&test, &file_id));
+  assert_eq!(
+    test.cloud_service.objects.get(&url).unwrap().as_ref(),
+    b"existing"
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn no_overwrite_conflict_on_server_drops_upload_test() {
+  for conditional_create in [true, false] {
+    let test = StorageTest::new().await;
+    test
+      .cloud_service
+      .conditional_create
+      .store(conditional_create, Ordering::SeqCst);
+    let file_path = create_temp_file(1024, "txt");
+
+    // The object is stored by someone else while the upload waits in the queue.
+    test.manager.update_network_reachable(false);
+    let (created_upload, _) = test
+      .manager
+      .create_upload_with(
+        &test.workspace_id(),
+        PARENT_DIR,
+        file_path.to_str().unwrap(),
+        false,
+        CreateUploadOptions::default().overwrite(false),
+      )
+      .await
+      .unwrap();
+    let (_, url) = store_existing_object(&test, &file_path).await;
+    test.manager.update_network_reachable(true);
+    assert_eq!(
+      test.manager.wait_until_idle(Duration::from_secs(30)).await,
+      IdleOutcome::Idle
+    );
+
+    // The upload is dropped without sending a part, the object is kept.
+    assert!(!has_record(&test, &created_upload.file_id));
+    assert_eq!(
+      test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+      0
+    );
+    assert_eq!(
+      test.cloud_service.objects.get(&url).unwrap().as_ref(),
+      b"existing"
+    );
+    assert_eq!(
+      test
+        .cloud_service
+        .create_if_not_exists_count
+        .load(Ordering::SeqCst),
+      usize::from(conditional_create)
+    );
+  }
+}
@@ -0,0 +1,97 @@
+use crate::util::{create_temp_file, MockStorageCloudService, StorageTest};
+use flowy_error::ErrorCode;
+use flowy_storage::error::StorageError;
+use flowy_storage::manager::IdleOutcome;
+use flowy_storage::parent_dir::canonical_parent_dir;
+use std::time::Duration;
+
+#[test]
+fn canonical_parent_dir_test() {
+  for parent_dir in [
+    "dir/sub",
+    "dir/sub/",
+    "/dir//sub",
+    "./dir/./sub",
+    "dir\\sub",
+  ] {
+    assert_eq!(canonical_parent_dir(parent_dir).unwrap(), "dir/sub");
+  }
+  for parent_dir in ["", "/", "./", "\\"] {
+    assert_eq!(
+      canonical_parent_dir(parent_dir).unwrap_err(),
+      StorageError::EmptyParentDir
+    );
+  }
+  for parent_dir in ["..", "dir/../sub", "dir\\..", "dir\nsub"] {
+    assert!(matches!(
+      canonical_parent_dir(parent_dir).unwrap_err(),
+      StorageError::InvalidParentDir(_)
+    ));
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn equivalent_parent_dirs_share_upload_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+
+  let mut created_uploads = vec![];
+  for parent_dir in ["dir/sub", "/dir//sub/", "dir\\sub"] {
+    let (created_upload, _) = test
+      .manager
+      .storage_service()
+      .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+      .await
+      .unwrap();
+    created_uploads.push(created_upload);
+  }
+  let url =
+    MockStorageCloudService::object_url(&workspace_id, "dir/sub", &created_uploads[0].file_id);
+  assert!(created_uploads
+    .iter()
+    .all(|created_upload| created_upload.url == url));
+
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Idle
+  );
+  assert_eq!(test.cloud_service.objects.len(), 1);
+  assert!(test.cloud_service.objects.contains_key(&url));
+  // The lookups find the upload by any of the forms.
+  assert!(test
+    .manager
+    .storage_service()
+    .subscribe_file_progress("dir/sub/", &created_uploads[0].file_id)
+    .await
+    .unwrap()
+    .is_none());
+}
+
+#[tokio::test]
+async fn invalid_parent_dir_is_rejected_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+
+  let err = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "dir/../other",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidParentDir);
+
+  let err = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, "//", file_path.to_str().unwrap(), true)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::UploadParentDirIsEmpty);
+}
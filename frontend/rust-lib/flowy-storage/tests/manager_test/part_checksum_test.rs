@@ -0,0 +1,95 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use flowy_storage::checksum::part_checksum;
+use flowy_storage_pub::chunked_byte::MIN_CHUNK_SIZE;
+use flowy_storage_pub::cloud::ChecksumAlgorithm;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_corrupted_part_test() {
+  let test = StorageTest::new().await;
+  *test.cloud_service.part_checksum.write().unwrap() = Some(ChecksumAlgorithm::Sha256);
+  test
+    .cloud_service
+    .corrupt_part_number
+    .store(1, Ordering::SeqCst);
+  let workspace_id = test.workspace_id();
+  let parent_dir = "part_checksum_test";
+  // Three parts of the default chunk size
+  let file_path = create_temp_file(12 * 1024 * 1024, "txt");
+  let content = std::fs::read(&file_path).unwrap();
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+
+  // The server stored a corrupted first part, so the upload is discarded before completing it and
+  // the file is uploaded again.
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert_eq!(
+    test.cloud_service.abort_upload_count.load(Ordering::SeqCst),
+    1
+  );
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    1
+  );
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+  assert_eq!(
+    test.cloud_service.objects.get(&url).unwrap().to_vec(),
+    content
+  );
+
+  // Each part was sent along with the checksum of its content.
+  let received_checksums = test
+    .cloud_service
+    .received_checksums
+    .lock()
+    .unwrap()
+    .clone();
+  assert_eq!(received_checksums.len(), 6);
+  for (part_number, checksum) in received_checksums {
+    let start = (part_number as usize - 1) * MIN_CHUNK_SIZE;
+    let end = (start + MIN_CHUNK_SIZE).min(content.len());
+    assert_eq!(
+      checksum,
+      part_checksum(ChecksumAlgorithm::Sha256, &content[start..end])
+    );
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_without_part_checksum_capability_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(12 * 1024 * 1024, "txt");
+  let (_, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "part_checksum_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+
+  // The parts are uploaded without checksums when the server doesn't validate them.
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert!(test
+    .cloud_service
+    .received_checksums
+    .lock()
+    .unwrap()
+    .is_empty());
+  assert_eq!(
+    test.cloud_service.abort_upload_count.load(Ordering::SeqCst),
+    0
+  );
+}
@@ -0,0 +1,83 @@
+use crate::util::{create_temp_file, MockStorageCloudService, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::ResumeStrategy;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn flaky_part_retries_alone_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .part_retry_delay(Duration::from_millis(10)),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "part_retry_test";
+  let file_path = create_temp_file(4 * MB, "txt");
+  let content = std::fs::read(&file_path).unwrap();
+  test.manager.update_network_reachable(false);
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let file_id = created_upload.file_id;
+
+  // The 2nd part fails once, a single attempt of the upload still finishes it.
+  test
+    .cloud_service
+    .fail_part_number
+    .store(2, Ordering::SeqCst);
+  test
+    .manager
+    .resume_upload_with_strategy(
+      &workspace_id,
+      parent_dir,
+      &file_id,
+      ResumeStrategy::Continue,
+    )
+    .await
+    .unwrap();
+
+  let logs = test.manager.upload_log(&file_id).await.unwrap();
+  let events = logs
+    .iter()
+    .map(|log| (log.event.as_str(), log.detail.as_str()))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    events,
+    vec![
+      ("start", ""),
+      ("part", "1"),
+      ("part_retry", "2"),
+      ("part", "2"),
+      ("part", "3"),
+      ("part", "4"),
+      ("complete", ""),
+    ]
+  );
+
+  // Only the flaky part was sent twice.
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    4
+  );
+  assert_eq!(
+    test.cloud_service.uploaded_bytes.load(Ordering::SeqCst),
+    5 * MB
+  );
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &file_id);
+  assert_eq!(
+    test.cloud_service.objects.get(&url).unwrap().to_vec(),
+    content
+  );
+}
@@ -0,0 +1,201 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use flowy_error::ErrorCode;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::error::StorageError;
+use flowy_storage::manager::UploadOutcome;
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn chunk_size_raised_to_backend_min_part_size_test() {
+  let test = StorageTest::new_with_config(StorageManagerConfig::default().chunk_size(MB)).await;
+  test
+    .cloud_service
+    .min_part_size
+    .store(5 * MB, Ordering::SeqCst);
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(12 * MB, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "part_size_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    "part_size_test",
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert_eq!(record.chunk_size as usize, 5 * MB);
+  assert_eq!(record.num_chunk, 3);
+  // 5MB, 5MB and the last part of 2MB.
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    3
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn interrupted_upload_resends_at_most_one_bounded_part_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(8 * MB)
+      .max_part_size(Some(MB))
+      .part_max_attempts(1),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "part_size_test";
+  let file_size = 6 * MB + 1024;
+  let file_path = create_temp_file(file_size, "txt");
+  let content = std::fs::read(&file_path).unwrap();
+
+  // Keep the uploader from picking the upload, it's driven by the resume calls below.
+  test.manager.update_network_reachable(false);
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert_eq!(record.chunk_size as usize, MB);
+  assert_eq!(record.num_chunk, 7);
+
+  // The upload is interrupted while sending the 4th part.
+  test
+    .cloud_service
+    .fail_part_number
+    .store(4, Ordering::SeqCst);
+  assert!(test
+    .manager
+    .storage_service()
+    .resume_upload(&workspace_id, parent_dir, &created_upload.file_id)
+    .await
+    .is_err());
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    3
+  );
+
+  test
+    .manager
+    .storage_service()
+    .resume_upload(&workspace_id, parent_dir, &created_upload.file_id)
+    .await
+    .unwrap();
+
+  // Only the interrupted part was sent again.
+  let uploaded_bytes = test.cloud_service.uploaded_bytes.load(Ordering::SeqCst);
+  assert!(uploaded_bytes <= file_size + MB);
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+  let object = test.cloud_service.objects.get(&url).unwrap().clone();
+  assert_eq!(object.to_vec(), content);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn chunk_size_raised_to_fit_backend_max_parts_test() {
+  let test = StorageTest::new_with_config(StorageManagerConfig::default().chunk_size(MB)).await;
+  test.cloud_service.max_parts.store(4, Ordering::SeqCst);
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(10 * MB, "txt");
+  let content = std::fs::read(&file_path).unwrap();
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "part_size_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    "part_size_test",
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert_eq!(record.chunk_size as usize, 10 * MB / 4);
+  assert_eq!(record.num_chunk, 4);
+  let url =
+    MockStorageCloudService::object_url(&workspace_id, "part_size_test", &created_upload.file_id);
+  let object = test.cloud_service.objects.get(&url).unwrap().clone();
+  assert_eq!(object.to_vec(), content);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn too_many_parts_rejected_up_front_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .max_part_size(Some(2 * MB)),
+  )
+  .await;
+  test.cloud_service.max_parts.store(4, Ordering::SeqCst);
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(10 * MB, "txt");
+
+  // The validation reports the rejection too.
+  let validation = test
+    .manager
+    .validate_upload(&workspace_id, "part_size_test", file_path.to_str().unwrap())
+    .await
+    .unwrap();
+  let expected = StorageError::TooManyParts {
+    parts: 5,
+    max_parts: 4,
+  };
+  assert_eq!(validation.outcome, UploadOutcome::Rejected(expected));
+
+  let err = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "part_size_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::UploadTooManyParts);
+
+  // Nothing was created for the rejected upload.
+  let cache_dir = test.manager.user_temp_dir();
+  assert_eq!(
+    std::fs::read_dir(cache_dir)
+      .map(|entries| entries.count())
+      .unwrap_or(0),
+    0
+  );
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    0
+  );
+}
@@ -0,0 +1,39 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn part_timings_are_recorded_test() {
+  let test = StorageTest::new_with_config(StorageManagerConfig::default().chunk_size(5 * MB)).await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(50)));
+  let file_path = create_temp_file(12 * MB, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &test.workspace_id(),
+      "part_timing_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  let timings = test.manager.upload_timing(&created_upload.file_id);
+  assert_eq!(
+    timings
+      .iter()
+      .map(|timing| (timing.part_number, timing.size))
+      .collect::<Vec<_>>(),
+    vec![(1, 5 * MB), (2, 5 * MB), (3, 2 * MB)]
+  );
+  for timing in timings {
+    assert!(timing.succeeded);
+    assert!(timing.duration() >= Duration::from_millis(50));
+  }
+}
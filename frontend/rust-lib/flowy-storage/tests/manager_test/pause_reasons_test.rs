@@ -0,0 +1,223 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::IdleOutcome;
+use flowy_storage::pause::PauseReasons;
+use flowy_storage_pub::storage::{FileProgress, FileUploadState};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::timeout;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_resumes_only_when_all_pause_reasons_are_cleared_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  assert_eq!(test.manager.effective_pause_reasons(), PauseReasons::NONE);
+
+  test.manager.update_network_reachable(false);
+  test.manager.pause_workspace_uploads(&workspace_id);
+  assert_eq!(
+    test.manager.effective_pause_reasons(),
+    PauseReasons::NETWORK_UNREACHABLE | PauseReasons::WORKSPACE_PAUSED
+  );
+
+  let file_path = create_temp_file(1024, "txt");
+  let (_, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "pause_reasons_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  // The storage write access is disabled after creating the upload, new uploads are rejected
+  // while it's disabled.
+  test.manager.disable_storage_write_access();
+  assert_eq!(
+    test.manager.effective_pause_reasons(),
+    PauseReasons::NETWORK_UNREACHABLE
+      | PauseReasons::STORAGE_WRITE_DISABLED
+      | PauseReasons::WORKSPACE_PAUSED
+  );
+
+  // Clearing the reasons one by one, the upload waits until the last one is cleared.
+  test.manager.update_network_reachable(true);
+  assert_eq!(
+    test.manager.effective_pause_reasons(),
+    PauseReasons::STORAGE_WRITE_DISABLED | PauseReasons::WORKSPACE_PAUSED
+  );
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Paused {
+      queued: 1,
+      reasons: PauseReasons::STORAGE_WRITE_DISABLED | PauseReasons::WORKSPACE_PAUSED,
+    }
+  );
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    0
+  );
+
+  test.manager.enable_storage_write_access();
+  assert_eq!(
+    test.manager.effective_pause_reasons(),
+    PauseReasons::WORKSPACE_PAUSED
+  );
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Paused {
+      queued: 1,
+      reasons: PauseReasons::WORKSPACE_PAUSED,
+    }
+  );
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    0
+  );
+
+  test.manager.resume_workspace_uploads(&workspace_id);
+  assert_eq!(test.manager.effective_pause_reasons(), PauseReasons::NONE);
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn paused_file_does_not_block_other_uploads_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "pause_reasons_test";
+  test.manager.update_network_reachable(false);
+
+  let paused_file_path = create_temp_file(1024, "txt");
+  let (paused_upload, paused_receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      paused_file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  test
+    .manager
+    .pause_file_upload(&workspace_id, parent_dir, &paused_upload.file_id);
+
+  let file_path = create_temp_file(1024, "txt");
+  let (_, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+
+  test.manager.update_network_reachable(true);
+  // The file pause only applies to the paused file.
+  assert_eq!(test.manager.effective_pause_reasons(), PauseReasons::NONE);
+  assert_eq!(
+    test
+      .manager
+      .file_pause_reasons(&workspace_id, parent_dir, &paused_upload.file_id),
+    PauseReasons::FILE_PAUSED
+  );
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  let part_count = test.cloud_service.upload_part_count.load(Ordering::SeqCst);
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Paused {
+      queued: 1,
+      reasons: PauseReasons::FILE_PAUSED,
+    }
+  );
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    part_count
+  );
+
+  test
+    .manager
+    .resume_file_upload(&workspace_id, parent_dir, &paused_upload.file_id);
+  assert!(wait_for_finished(&mut paused_receiver.unwrap(), Duration::from_secs(30)).await);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn storage_write_pause_reaches_subscribers_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().max_concurrent_uploads(1)).await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_secs(3)));
+  let workspace_id = test.workspace_id();
+  let parent_dir = "pause_reasons_test";
+
+  // The first upload takes the only upload slot, the second one waits in the queue.
+  let running_file_path = create_temp_file(1024, "txt");
+  test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      running_file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  tokio::time::sleep(Duration::from_millis(500)).await;
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  let mut receiver = receiver.unwrap();
+  assert!(matches!(
+    receiver.recv().await.unwrap(),
+    FileUploadState::Queued
+  ));
+  let (_, mut events) = test.manager.subscribe_events();
+
+  // The waiting upload tells why it's paused.
+  test.manager.disable_storage_write_access();
+  let state = timeout(Duration::from_secs(1), receiver.recv())
+    .await
+    .unwrap()
+    .unwrap();
+  let reasons = PauseReasons::STORAGE_WRITE_DISABLED.bits();
+  assert!(matches!(state, FileUploadState::Paused { reasons: r } if r == reasons));
+  let event = next_event_of(&mut events, &created_upload.file_id).await;
+  assert_eq!(event.paused_reasons, Some(reasons));
+
+  // And that it resumed once the access is enabled again.
+  test.manager.enable_storage_write_access();
+  let state = timeout(Duration::from_secs(1), receiver.recv())
+    .await
+    .unwrap()
+    .unwrap();
+  assert!(matches!(state, FileUploadState::Uploading { progress } if progress == 0.0));
+  let event = next_event_of(&mut events, &created_upload.file_id).await;
+  assert_eq!(event.paused_reasons, None);
+
+  assert!(wait_for_finished(&mut receiver, Duration::from_secs(30)).await);
+}
+
+/// Returns the next progress of the file, skipping the progress of the other files.
+async fn next_event_of(
+  events: &mut broadcast::Receiver<FileProgress>,
+  file_id: &str,
+) -> FileProgress {
+  timeout(Duration::from_secs(1), async {
+    loop {
+      let event = events.recv().await.unwrap();
+      if event.file_id == file_id {
+        return event;
+      }
+    }
+  })
+  .await
+  .unwrap()
+}
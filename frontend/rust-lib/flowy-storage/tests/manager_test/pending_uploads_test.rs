@@ -0,0 +1,79 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_error::ErrorCode;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::error::StorageError;
+use flowy_storage::manager::UploadOutcome;
+use std::path::Path;
+
+const PARENT_DIR: &str = "pending_uploads_test";
+
+async fn create_upload(test: &StorageTest, file_path: &Path) -> Result<String, ErrorCode> {
+  test
+    .manager
+    .storage_service()
+    .create_upload(
+      &test.workspace_id(),
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .map(|(created_upload, _)| created_upload.file_id)
+    .map_err(|err| err.code)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn create_upload_beyond_max_pending_uploads_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().max_pending_uploads(Some(2)))
+      .await;
+  // Keep the uploads pending.
+  test.manager.update_network_reachable(false);
+
+  let first_path = create_temp_file(1024, "txt");
+  let first_file_id = create_upload(&test, &first_path).await.unwrap();
+  create_upload(&test, &create_temp_file(1024, "txt"))
+    .await
+    .unwrap();
+  assert_eq!(test.manager.pending_upload_count().await.unwrap(), 2);
+
+  // The cap is reached, a new upload is rejected before anything is created.
+  let file_path = create_temp_file(1024, "txt");
+  assert_eq!(
+    create_upload(&test, &file_path).await.unwrap_err(),
+    ErrorCode::TooManyPendingUploads
+  );
+  let validation = test
+    .manager
+    .validate_upload(
+      &test.workspace_id(),
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(
+    validation.outcome,
+    UploadOutcome::Rejected(StorageError::TooManyPendingUploads {
+      pending: 2,
+      max_pending: 2,
+    })
+  );
+  assert_eq!(test.manager.pending_upload_count().await.unwrap(), 2);
+
+  // Creating an upload that is already pending adds nothing, so it's accepted.
+  assert_eq!(
+    create_upload(&test, &first_path).await.unwrap(),
+    first_file_id
+  );
+
+  // Once an upload is no longer pending, the caller can retry.
+  test
+    .manager
+    .cancel_upload(&test.workspace_id(), PARENT_DIR, &first_file_id)
+    .await
+    .unwrap();
+  assert_eq!(test.manager.pending_upload_count().await.unwrap(), 1);
+  create_upload(&test, &file_path).await.unwrap();
+  assert_eq!(test.manager.pending_upload_count().await.unwrap(), 2);
+}
@@ -0,0 +1,73 @@
+use crate::util::{MockStorageCloudService, StorageTest};
+use bytes::Bytes;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::downloader::{DownloadBatchProgress, DownloadPriority};
+use flowy_storage::sqlite_sql::select_download_files;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[tokio::test]
+async fn prefetch_into_download_cache_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().max_concurrent_downloads(1)).await;
+  test
+    .cloud_service
+    .set_get_object_delay(Some(Duration::from_millis(100)));
+  let workspace_id = test.workspace_id();
+  let urls = (0..3)
+    .map(|i| {
+      let url =
+        MockStorageCloudService::object_url(&workspace_id, "offline", &format!("{}.txt", i));
+      test
+        .cloud_service
+        .objects
+        .insert(url.clone(), Bytes::from(format!("content {}", i)));
+      url
+    })
+    .collect::<Vec<_>>();
+  // Already in the cache, it's not fetched again.
+  let cached_url = MockStorageCloudService::object_url(&workspace_id, "offline", "cached.txt");
+  let cached_path = test
+    .manager
+    .default_download_path(&cached_url)
+    .await
+    .unwrap();
+  std::fs::create_dir_all(cached_path.parent().unwrap()).unwrap();
+  std::fs::write(&cached_path, b"cached").unwrap();
+
+  let mut prefetch_urls = urls.clone();
+  prefetch_urls.push(cached_url);
+  prefetch_urls.push("https://mock.appflowy.io/api/file_storage/v3/unknown".to_string());
+  let handle = test.manager.prefetch(prefetch_urls).await;
+
+  // The downloads wait at the prefetch priority.
+  let records = select_download_files(&mut test.db_connection()).unwrap();
+  assert_eq!(records.len(), 3);
+  assert!(records
+    .iter()
+    .all(|record| record.priority == DownloadPriority::Prefetch as i32));
+
+  let progress = tokio::time::timeout(Duration::from_secs(10), handle.wait())
+    .await
+    .unwrap();
+  assert_eq!(
+    progress,
+    DownloadBatchProgress {
+      total: 5,
+      downloaded: 4,
+      failed: 1,
+      cancelled: 0,
+    }
+  );
+  for (i, url) in urls.iter().enumerate() {
+    let path = test.manager.default_download_path(url).await.unwrap();
+    assert_eq!(
+      std::fs::read_to_string(path).unwrap(),
+      format!("content {}", i)
+    );
+  }
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    3
+  );
+}
@@ -0,0 +1,58 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::CreateUploadOptions;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn progress_callback_ends_at_finished_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .progress_min_interval(Duration::ZERO),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(4 * MB, "txt");
+  let (tx, mut rx) = mpsc::unbounded_channel();
+  let (created_upload, receiver) = test
+    .manager
+    .create_upload_with(
+      &workspace_id,
+      "progress_callback_test",
+      file_path.to_str().unwrap(),
+      true,
+      CreateUploadOptions::default().on_progress(move |progress| {
+        let _ = tx.send(progress);
+      }),
+    )
+    .await
+    .unwrap();
+  // The progress goes to the callback rather than a receiver.
+  assert!(receiver.is_none());
+
+  // The callback is dropped after the final progress, which closes the channel.
+  let progress = tokio::time::timeout(Duration::from_secs(30), async {
+    let mut progress = vec![];
+    while let Some(value) = rx.recv().await {
+      progress.push(value);
+    }
+    progress
+  })
+  .await
+  .unwrap();
+  assert!(progress.len() > 1);
+  assert!(progress
+    .iter()
+    .all(|value| value.file_id == created_upload.file_id
+      && value.file_url == created_upload.url
+      && value.workspace_id.as_deref() == Some(workspace_id.as_str())));
+  assert!(progress
+    .windows(2)
+    .all(|pair| pair[0].progress <= pair[1].progress));
+  let last = progress.last().unwrap();
+  assert_eq!(last.progress, 1.0);
+  assert!(last.total_bytes.is_some());
+}
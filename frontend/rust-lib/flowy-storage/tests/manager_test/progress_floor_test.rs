@@ -0,0 +1,105 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::CreateUploadOptions;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn progress_starts_at_floor_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .progress_min_interval(Duration::ZERO)
+      .progress_floor(0.3),
+  )
+  .await;
+  let file_path = create_temp_file(4 * MB, "txt");
+  let (tx, mut rx) = mpsc::unbounded_channel();
+  test
+    .manager
+    .create_upload_with(
+      &test.workspace_id(),
+      "progress_floor_test",
+      file_path.to_str().unwrap(),
+      true,
+      CreateUploadOptions::default().on_progress(move |progress| {
+        let _ = tx.send(progress.progress);
+      }),
+    )
+    .await
+    .unwrap();
+
+  let progress = tokio::time::timeout(Duration::from_secs(30), async {
+    let mut progress = vec![];
+    while let Some(value) = rx.recv().await {
+      progress.push(value);
+    }
+    progress
+  })
+  .await
+  .unwrap();
+  // The first part, at 0.25, doesn't go below the floor.
+  assert_eq!(progress.first(), Some(&0.3));
+  assert!(progress.iter().all(|value| *value >= 0.3));
+  assert!(progress.windows(2).all(|pair| pair[0] <= pair[1]));
+  assert_eq!(progress.last(), Some(&1.0));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn queued_upload_starts_at_floor_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .progress_min_interval(Duration::ZERO)
+      .progress_floor(0.3),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "progress_floor_test";
+  let file_path = create_temp_file(4 * MB, "txt");
+  // The upload waits in the queue while the network is unreachable.
+  test.manager.update_network_reachable(false);
+  let (tx, mut rx) = mpsc::unbounded_channel();
+  let (created_upload, _) = test
+    .manager
+    .create_upload_with(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+      CreateUploadOptions::default().on_progress(move |progress| {
+        let _ = tx.send(progress.progress);
+      }),
+    )
+    .await
+    .unwrap();
+
+  let first = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+    .await
+    .unwrap();
+  assert_eq!(first, Some(0.3));
+  let detail = test
+    .manager
+    .upload_detail(&workspace_id, parent_dir, &created_upload.file_id)
+    .unwrap();
+  assert!(detail.started_at.is_none());
+  assert!(test
+    .manager
+    .is_uploading(parent_dir, &created_upload.file_id));
+
+  test.manager.update_network_reachable(true);
+  let progress = tokio::time::timeout(Duration::from_secs(30), async {
+    let mut progress = vec![];
+    while let Some(value) = rx.recv().await {
+      progress.push(value);
+    }
+    progress
+  })
+  .await
+  .unwrap();
+  assert!(progress.iter().all(|value| *value >= 0.3));
+  assert!(progress.windows(2).all(|pair| pair[0] <= pair[1]));
+  assert_eq!(progress.last(), Some(&1.0));
+}
@@ -0,0 +1,61 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use std::time::{Duration, Instant};
+
+const KB: usize = 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn fast_upload_progress_respects_interval_floor_test() {
+  let min_interval = Duration::from_millis(200);
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(KB)
+      .progress_min_interval(min_interval),
+  )
+  .await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(10)));
+  let workspace_id = test.workspace_id();
+  let (_, mut rx) = test.manager.subscribe_events();
+
+  // 100 parts, each of them would emit a progress without the interval floor.
+  let file_path = create_temp_file(100 * KB, "txt");
+  let start = Instant::now();
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "progress_interval_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+
+  let mut uploading_count = 0;
+  loop {
+    let progress = tokio::time::timeout(Duration::from_secs(30), rx.recv())
+      .await
+      .unwrap()
+      .unwrap();
+    if progress.file_id != created_upload.file_id {
+      continue;
+    }
+    // The terminal state is always emitted.
+    if progress.progress >= 1.0 {
+      break;
+    }
+    uploading_count += 1;
+  }
+
+  let max_count = (start.elapsed().as_millis() / min_interval.as_millis()) as usize + 1;
+  assert!(uploading_count > 0);
+  assert!(
+    uploading_count <= max_count,
+    "{} progress emitted, expected at most {}",
+    uploading_count,
+    max_count
+  );
+}
@@ -0,0 +1,98 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::config::{ProgressFanOut, StorageManagerConfig};
+use flowy_storage_pub::storage::{FileUploadState, ProgressNotifier};
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+
+fn finished() -> FileUploadState {
+  FileUploadState::Finished {
+    file_id: "file_id".to_string(),
+    total_bytes: None,
+    duration: None,
+  }
+}
+
+#[tokio::test]
+async fn notifier_ignores_out_of_order_progress_test() {
+  let mut notifier = ProgressNotifier::new("file_id".to_string());
+  let mut receiver = notifier.subscribe();
+
+  assert!(
+    notifier
+      .notify_in_order(3, FileUploadState::Uploading { progress: 0.5 })
+      .await
+  );
+  // The 0.9 progress arrives after the final one, and a progress older than the last one is
+  // ignored.
+  assert!(notifier.notify_in_order(5, finished()).await);
+  assert!(
+    !notifier
+      .notify_in_order(4, FileUploadState::Uploading { progress: 0.9 })
+      .await
+  );
+  assert!(
+    !notifier
+      .notify_in_order(2, FileUploadState::Uploading { progress: 0.1 })
+      .await
+  );
+  // Nothing moves a finished upload backward, even with a higher seq.
+  assert!(
+    !notifier
+      .notify_in_order(6, FileUploadState::Uploading { progress: 0.9 })
+      .await
+  );
+
+  assert!(matches!(
+    receiver.try_recv().unwrap(),
+    FileUploadState::Uploading { progress } if progress == 0.5
+  ));
+  assert!(matches!(
+    receiver.try_recv().unwrap(),
+    FileUploadState::Finished { .. }
+  ));
+  assert!(receiver.try_recv().is_err());
+  assert!(matches!(
+    notifier.current_value,
+    Some(FileUploadState::Finished { .. })
+  ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn progress_seq_increases_to_finished_test() {
+  for fan_out in [ProgressFanOut::Direct, ProgressFanOut::Relay] {
+    let test = StorageTest::new_with_config(
+      StorageManagerConfig::default()
+        .chunk_size(MB)
+        .progress_min_interval(Duration::ZERO)
+        .progress_fan_out(fan_out),
+    )
+    .await;
+    let (_, mut events) = test.manager.subscribe_events();
+    let file_path = create_temp_file(4 * MB, "txt");
+    let (created_upload, receiver) = test
+      .manager
+      .storage_service()
+      .create_upload(
+        &test.workspace_id(),
+        "progress_order_test",
+        file_path.to_str().unwrap(),
+        true,
+      )
+      .await
+      .unwrap();
+    assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+    let mut progress = vec![];
+    while let Ok(event) = events.try_recv() {
+      if event.file_id == created_upload.file_id {
+        progress.push(event);
+      }
+    }
+    assert!(progress.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+    assert!(progress
+      .windows(2)
+      .all(|pair| pair[0].progress <= pair[1].progress));
+    assert_eq!(progress.last().unwrap().progress, 1.0);
+  }
+}
@@ -0,0 +1,63 @@
+use crate::util::{create_temp_file, MockStorageCloudService, StorageTest};
+use flowy_storage::clock::MockClock;
+use flowy_storage::config::StorageManagerConfig;
+use std::sync::Arc;
+use std::time::Duration;
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn query_file_state_throttles_progress_test() {
+  let clock = Arc::new(MockClock::default());
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .file_state_emit_interval(INTERVAL)
+      .clock(clock.clone()),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "query_state_throttle_test";
+  test.manager.update_network_reachable(false);
+
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+
+  // Querying the file on each render only emits once per interval, the state is still returned.
+  let (_, mut rx) = test.manager.subscribe_events();
+  for _ in 0..100 {
+    let state = test.manager.query_file_state(&url).await.unwrap();
+    assert!(!state.is_finish);
+  }
+  assert_eq!(rx.try_recv().unwrap().progress, 0.0);
+  assert!(rx.try_recv().is_err());
+
+  clock.advance(INTERVAL);
+  test.manager.query_file_state(&url).await.unwrap();
+  test.manager.query_file_state(&url).await.unwrap();
+  assert_eq!(rx.try_recv().unwrap().progress, 0.0);
+  assert!(rx.try_recv().is_err());
+
+  // A finished upload is emitted right away.
+  test
+    .manager
+    .storage_service()
+    .resume_upload(&workspace_id, parent_dir, &created_upload.file_id)
+    .await
+    .unwrap();
+  let (_, mut rx) = test.manager.subscribe_events();
+  let state = test.manager.query_file_state(&url).await.unwrap();
+  assert!(state.is_finish);
+  assert_eq!(rx.try_recv().unwrap().progress, 1.0);
+  assert!(rx.try_recv().is_err());
+}
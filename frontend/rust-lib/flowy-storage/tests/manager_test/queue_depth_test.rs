@@ -0,0 +1,80 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::QueueStatus;
+use futures_util::{Stream, StreamExt};
+use std::time::Duration;
+
+/// Reads the statuses until one matches, and returns them all.
+async fn next_until(
+  stream: &mut (impl Stream<Item = QueueStatus> + Unpin),
+  until: impl Fn(&QueueStatus) -> bool,
+) -> Vec<QueueStatus> {
+  tokio::time::timeout(Duration::from_secs(30), async {
+    let mut statuses = vec![];
+    while let Some(status) = stream.next().await {
+      let done = until(&status);
+      statuses.push(status);
+      if done {
+        break;
+      }
+    }
+    statuses
+  })
+  .await
+  .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn queue_depth_stream_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .max_concurrent_uploads(1)
+      .queue_status_debounce(Duration::from_millis(20)),
+  )
+  .await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(300)));
+  let mut stream = Box::pin(test.manager.subscribe_queue_depth());
+
+  // The current status is emitted right away.
+  assert_eq!(
+    next_until(&mut stream, |_| true).await,
+    vec![QueueStatus::default()]
+  );
+
+  test.manager.update_network_reachable(false);
+  for _ in 0..3 {
+    let file_path = create_temp_file(1024, "txt");
+    test
+      .manager
+      .storage_service()
+      .create_upload(
+        &test.workspace_id(),
+        "queue_depth_test",
+        file_path.to_str().unwrap(),
+        false,
+      )
+      .await
+      .unwrap();
+  }
+  let statuses = next_until(&mut stream, |status| status.queued == 3).await;
+  assert!(statuses
+    .windows(2)
+    .all(|pair| pair[0].queued < pair[1].queued));
+  assert!(statuses.iter().all(|status| status.running == 0));
+
+  // The uploads run one at a time until the queue is drained.
+  test.manager.update_network_reachable(true);
+  let statuses = next_until(&mut stream, |status| *status == QueueStatus::default()).await;
+  assert!(statuses
+    .windows(2)
+    .all(|pair| pair[0] != pair[1] && pair[0].queued >= pair[1].queued));
+  assert!(statuses.iter().all(|status| status.running <= 1));
+  assert!(statuses
+    .iter()
+    .any(|status| status.queued == 2 && status.running == 1));
+  assert!(statuses
+    .iter()
+    .any(|status| status.queued == 0 && status.running == 1));
+}
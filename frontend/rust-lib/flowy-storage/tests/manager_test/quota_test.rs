@@ -0,0 +1,114 @@
+use crate::util::{create_temp_file, StorageTest};
+use async_trait::async_trait;
+use flowy_error::{ErrorCode, FlowyResult};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::error::StorageError;
+use flowy_storage::manager::UploadOutcome;
+use flowy_storage::quota::{FolderQuota, QuotaExceeded, QuotaScope, StorageUsage};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Reports a fixed usage for each dir, keyed by the dir, and for each workspace, keyed by "".
+#[derive(Debug, Default)]
+struct FixedUsage(HashMap<String, u64>);
+
+#[async_trait]
+impl StorageUsage for FixedUsage {
+  async fn used_bytes(&self, _workspace_id: &str, parent_dir: Option<&str>) -> FlowyResult<u64> {
+    Ok(
+      self
+        .0
+        .get(parent_dir.unwrap_or_default())
+        .copied()
+        .unwrap_or_default(),
+    )
+  }
+}
+
+async fn create_upload(
+  test: &StorageTest,
+  parent_dir: &str,
+  file_path: &Path,
+) -> Result<String, ErrorCode> {
+  test
+    .manager
+    .storage_service()
+    .create_upload(
+      &test.workspace_id(),
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .map(|(created_upload, _)| created_upload.file_id)
+    .map_err(|err| err.code)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn folder_quota_rejects_upload_test() {
+  let usage = FixedUsage(HashMap::from([
+    ("photos".to_string(), 3 * 1024),
+    ("".to_string(), 3 * 1024),
+  ]));
+  let quota = FolderQuota::new(Arc::new(usage))
+    .workspace_limit(16 * 1024)
+    .dir_limit("/photos/", 4 * 1024);
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default().upload_quota(Some(Arc::new(quota))),
+  )
+  .await;
+  test.manager.update_network_reachable(false);
+  let workspace_id = test.workspace_id();
+
+  // The limit of the dir applies to its sub dirs, the upload is rejected before anything is
+  // created.
+  let file_path = create_temp_file(2 * 1024, "txt");
+  assert_eq!(
+    create_upload(&test, "photos/2024", &file_path)
+      .await
+      .unwrap_err(),
+    ErrorCode::StorageQuotaExceeded
+  );
+  let validation = test
+    .manager
+    .validate_upload(&workspace_id, "photos/2024", file_path.to_str().unwrap())
+    .await
+    .unwrap();
+  assert_eq!(
+    validation.outcome,
+    UploadOutcome::Rejected(StorageError::QuotaExceeded(QuotaExceeded {
+      scope: QuotaScope::ParentDir {
+        workspace_id: workspace_id.clone(),
+        parent_dir: "photos".to_string(),
+      },
+      used: 3 * 1024,
+      requested: 2 * 1024,
+      limit: 4 * 1024,
+    }))
+  );
+  assert_eq!(test.manager.pending_upload_count().await.unwrap(), 0);
+
+  // A file that fits in the remaining quota of the dir, and any file of another dir, are accepted.
+  create_upload(&test, "photos/2024", &create_temp_file(1024, "txt"))
+    .await
+    .unwrap();
+  create_upload(&test, "docs", &file_path).await.unwrap();
+
+  // The limit of the workspace applies to all the dirs.
+  let file_path = create_temp_file(14 * 1024, "txt");
+  let validation = test
+    .manager
+    .validate_upload(&workspace_id, "docs", file_path.to_str().unwrap())
+    .await
+    .unwrap();
+  assert_eq!(
+    validation.outcome,
+    UploadOutcome::Rejected(StorageError::QuotaExceeded(QuotaExceeded {
+      scope: QuotaScope::Workspace(workspace_id),
+      used: 3 * 1024,
+      requested: 14 * 1024,
+      limit: 16 * 1024,
+    }))
+  );
+}
@@ -0,0 +1,99 @@
+use crate::util::StorageTest;
+use bytes::Bytes;
+use flowy_error::ErrorCode;
+use flowy_storage::config::StorageManagerConfig;
+use std::sync::atomic::Ordering;
+
+const URL: &str = "https://mock.appflowy.io/api/file_storage/read_range";
+
+async fn range_test(config: StorageManagerConfig) -> (StorageTest, Vec<u8>) {
+  let test = StorageTest::new_with_config(config).await;
+  test
+    .cloud_service
+    .range_downloads
+    .store(true, Ordering::SeqCst);
+  let content = (0..100u8).collect::<Vec<_>>();
+  test
+    .cloud_service
+    .objects
+    .insert(URL.to_string(), Bytes::from(content.clone()));
+  (test, content)
+}
+
+#[tokio::test]
+async fn read_range_test() {
+  let (test, content) = range_test(StorageManagerConfig::default()).await;
+
+  for (start, len, expected) in [
+    (0, 10, &content[..10]),
+    (42, 8, &content[42..50]),
+    // The range is cut at the end of the object, and empty past it.
+    (95, 10, &content[95..]),
+    (100, 10, &[][..]),
+    (150, 10, &[][..]),
+    (10, 0, &[][..]),
+  ] {
+    let bytes = test.manager.read_range(URL, start, len).await.unwrap();
+    assert_eq!(bytes.as_ref(), expected, "range {}+{}", start, len);
+  }
+
+  let err = test
+    .manager
+    .read_range("https://mock.appflowy.io/api/file_storage/missing", 0, 10)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}
+
+#[tokio::test]
+async fn read_range_unsupported_test() {
+  let (test, _) = range_test(StorageManagerConfig::default()).await;
+  test
+    .cloud_service
+    .range_downloads
+    .store(false, Ordering::SeqCst);
+
+  let err = test.manager.read_range(URL, 0, 10).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotSupportYet);
+  assert_eq!(
+    test
+      .cloud_service
+      .get_object_range_count
+      .load(Ordering::SeqCst),
+    0
+  );
+}
+
+#[tokio::test]
+async fn read_range_cache_test() {
+  let (test, content) = range_test(StorageManagerConfig::default().range_cache(2, 16)).await;
+  let range_count = || {
+    test
+      .cloud_service
+      .get_object_range_count
+      .load(Ordering::SeqCst)
+  };
+
+  // A small range is only fetched once.
+  for _ in 0..2 {
+    let bytes = test.manager.read_range(URL, 0, 16).await.unwrap();
+    assert_eq!(bytes.as_ref(), &content[..16]);
+  }
+  assert_eq!(range_count(), 1);
+
+  // A larger range is always fetched.
+  for _ in 0..2 {
+    let bytes = test.manager.read_range(URL, 0, 32).await.unwrap();
+    assert_eq!(bytes.as_ref(), &content[..32]);
+  }
+  assert_eq!(range_count(), 3);
+
+  // The least recently used range is evicted beyond the capacity.
+  test.manager.read_range(URL, 16, 16).await.unwrap();
+  test.manager.read_range(URL, 32, 16).await.unwrap();
+  assert_eq!(range_count(), 5);
+  test.manager.read_range(URL, 0, 16).await.unwrap();
+  assert_eq!(range_count(), 6);
+  test.manager.read_range(URL, 32, 16).await.unwrap();
+  assert_eq!(range_count(), 6);
+}
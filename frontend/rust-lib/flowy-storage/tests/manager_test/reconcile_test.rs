@@ -0,0 +1,101 @@
+use crate::util::{create_temp_file, MockStorageCloudService, StorageTest};
+use bytes::Bytes;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::ReconcileSummary;
+use flowy_storage::sqlite_sql::{insert_upload_file, select_upload_file, UploadFileTable};
+use flowy_storage_pub::chunked_byte::MIN_CHUNK_SIZE;
+use std::time::Duration;
+
+fn upload_record(
+  workspace_id: &str,
+  file_id: &str,
+  local_file_path: &str,
+  is_finish: bool,
+) -> UploadFileTable {
+  UploadFileTable {
+    workspace_id: workspace_id.to_string(),
+    file_id: file_id.to_string(),
+    parent_dir: "reconcile_test".to_string(),
+    local_file_path: local_file_path.to_string(),
+    content_type: "text/plain".to_string(),
+    chunk_size: MIN_CHUNK_SIZE as i32,
+    num_chunk: 1,
+    upload_id: "".to_string(),
+    created_at: 0,
+    is_finish,
+    metadata: "".to_string(),
+    storage_class: "".to_string(),
+    seq: 0,
+    overwrite: true,
+    linked_parent_dir: String::new(),
+    source_file_path: String::new(),
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn reconcile_mismatched_records_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .reconcile_batch_size(10)
+      .reconcile_request_interval(Duration::ZERO),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let local_file = create_temp_file(1024, "txt");
+  let local_file = local_file.to_str().unwrap();
+
+  // Finished locally, but the object was deleted on the server.
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(&workspace_id, "deleted_on_server", "", true),
+  )
+  .unwrap();
+  // Uploaded, but not marked as finished locally.
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(&workspace_id, "uploaded", "", false),
+  )
+  .unwrap();
+  test.cloud_service.objects.insert(
+    MockStorageCloudService::object_url(&workspace_id, "reconcile_test", "uploaded"),
+    Bytes::from_static(b"uploaded"),
+  );
+  // Not uploaded, the local file still exists.
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(&workspace_id, "not_uploaded", local_file, false),
+  )
+  .unwrap();
+  // Not uploaded, and the local file is gone.
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(&workspace_id, "file_missing", "not_exist_file.txt", false),
+  )
+  .unwrap();
+
+  let summary = test.manager.reconcile_uploads().await.unwrap();
+  assert_eq!(
+    summary,
+    ReconcileSummary {
+      checked: 4,
+      marked_finished: 1,
+      requeued: 1,
+      dropped: 2,
+    }
+  );
+
+  let select = |file_id: &str| {
+    select_upload_file(
+      &mut test.db_connection(),
+      &workspace_id,
+      "reconcile_test",
+      file_id,
+    )
+    .unwrap()
+  };
+  assert!(select("deleted_on_server").is_none());
+  assert!(select("uploaded").unwrap().is_finish);
+  assert!(select("not_uploaded").is_some());
+  assert!(select("file_missing").is_none());
+}
@@ -0,0 +1,30 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn progress_relay_keeps_running_across_many_events_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+
+  // Each query emits a synthetic progress event. Emit more events than the capacity of the
+  // global notifier so that the relay may lag behind.
+  for i in 0..3000 {
+    let url = MockStorageCloudService::object_url(&workspace_id, "relay_test", &i.to_string());
+    test.manager.query_file_state(&url).await.unwrap();
+  }
+
+  let file_path = create_temp_file(1024, "txt");
+  let (_, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "relay_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  let mut receiver = receiver.unwrap();
+  assert!(wait_for_finished(&mut receiver, Duration::from_secs(30)).await);
+}
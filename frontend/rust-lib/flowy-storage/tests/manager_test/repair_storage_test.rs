@@ -0,0 +1,143 @@
+use crate::util::{create_temp_file, MockStorageCloudService, StorageTest};
+use bytes::Bytes;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::RepairSummary;
+use flowy_storage::sqlite_sql::{
+  insert_upload_file, insert_upload_part, select_upload_file, UploadFilePartTable, UploadFileTable,
+};
+use flowy_storage_pub::chunked_byte::MIN_CHUNK_SIZE;
+use std::time::Duration;
+
+const PARENT_DIR: &str = "repair_storage_test";
+
+fn upload_record(
+  workspace_id: &str,
+  file_id: &str,
+  local_file_path: &str,
+  is_finish: bool,
+) -> UploadFileTable {
+  UploadFileTable {
+    workspace_id: workspace_id.to_string(),
+    file_id: file_id.to_string(),
+    parent_dir: PARENT_DIR.to_string(),
+    local_file_path: local_file_path.to_string(),
+    content_type: "text/plain".to_string(),
+    chunk_size: MIN_CHUNK_SIZE as i32,
+    num_chunk: 1,
+    upload_id: "".to_string(),
+    created_at: 0,
+    is_finish,
+    metadata: "".to_string(),
+    storage_class: "".to_string(),
+    seq: 0,
+    overwrite: true,
+    linked_parent_dir: String::new(),
+    source_file_path: String::new(),
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn repair_storage_fixes_reported_issues_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .reconcile_request_interval(Duration::ZERO),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  // Keep the uploader from picking the requeued upload.
+  test.manager.update_network_reachable(false);
+  let cache_dir = test.manager.user_temp_dir();
+  std::fs::create_dir_all(&cache_dir).unwrap();
+
+  // Finished uploads whose object was deleted on the server, with and without the local file.
+  let local_file = create_temp_file(1024, "txt");
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(
+      &workspace_id,
+      "deleted_with_file",
+      local_file.to_str().unwrap(),
+      true,
+    ),
+  )
+  .unwrap();
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(&workspace_id, "deleted_without_file", "", true),
+  )
+  .unwrap();
+  // Uploaded, but not marked as finished locally.
+  let uploaded_path = cache_dir.join("uploaded.txt");
+  std::fs::write(&uploaded_path, b"uploaded").unwrap();
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(
+      &workspace_id,
+      "uploaded",
+      uploaded_path.to_str().unwrap(),
+      false,
+    ),
+  )
+  .unwrap();
+  test.cloud_service.objects.insert(
+    MockStorageCloudService::object_url(&workspace_id, PARENT_DIR, "uploaded"),
+    Bytes::from_static(b"uploaded"),
+  );
+  // Not uploaded, and the local file is gone.
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(&workspace_id, "file_missing", "not_exist_file.txt", false),
+  )
+  .unwrap();
+  // Orphaned parts and temp file.
+  insert_upload_part(
+    test.db_connection(),
+    &UploadFilePartTable {
+      upload_id: "orphaned_upload".to_string(),
+      e_tag: "e_tag".to_string(),
+      part_num: 1,
+      checksum: String::new(),
+    },
+  )
+  .unwrap();
+  let orphaned_path = cache_dir.join("orphaned.txt");
+  std::fs::write(&orphaned_path, b"orphaned").unwrap();
+
+  let report = test.manager.verify_storage().await.unwrap();
+  assert_eq!(report.issues.len(), 6);
+  let summary = test.manager.repair_storage(report.clone()).await.unwrap();
+  assert_eq!(
+    summary,
+    RepairSummary {
+      requeued: 1,
+      marked_finished: 1,
+      dropped: 2,
+      deleted_parts: 1,
+      deleted_temp_files: 1,
+    }
+  );
+
+  let record = |file_id: &str| {
+    select_upload_file(
+      &mut test.db_connection(),
+      &workspace_id,
+      PARENT_DIR,
+      file_id,
+    )
+    .unwrap()
+  };
+  assert!(!record("deleted_with_file").unwrap().is_finish);
+  assert!(record("deleted_without_file").is_none());
+  assert!(record("uploaded").unwrap().is_finish);
+  assert!(!uploaded_path.exists());
+  assert!(record("file_missing").is_none());
+  assert!(!orphaned_path.exists());
+
+  // The storage is consistent afterwards, and repairing again does nothing.
+  assert!(test.manager.verify_storage().await.unwrap().is_healthy());
+  assert_eq!(
+    test.manager.repair_storage(report).await.unwrap(),
+    RepairSummary::default()
+  );
+}
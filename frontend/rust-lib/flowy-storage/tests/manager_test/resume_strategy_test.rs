@@ -0,0 +1,164 @@
+use crate::util::{create_temp_file, MockStorageCloudService, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::ResumeStrategy;
+use flowy_storage::sqlite_sql::{select_upload_file, select_upload_parts};
+use std::sync::atomic::Ordering;
+
+const MB: usize = 1024 * 1024;
+const FILE_SIZE: usize = 4 * MB;
+
+/// Creates an upload of 4 parts whose first attempt is interrupted while sending the 3rd part.
+/// Returns the file id and the content of the file.
+async fn create_interrupted_upload(test: &StorageTest, parent_dir: &str) -> (String, Vec<u8>) {
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(FILE_SIZE, "txt");
+  let content = std::fs::read(&file_path).unwrap();
+  // Keep the uploader from picking the upload, it's driven by the resume calls below.
+  test.manager.update_network_reachable(false);
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+
+  test
+    .cloud_service
+    .fail_part_number
+    .store(3, Ordering::SeqCst);
+  assert!(test
+    .manager
+    .resume_upload_with_strategy(
+      &workspace_id,
+      parent_dir,
+      &created_upload.file_id,
+      ResumeStrategy::Continue,
+    )
+    .await
+    .is_err());
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    2
+  );
+  (created_upload.file_id, content)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn continue_reuses_uploaded_parts_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .part_max_attempts(1),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "resume_strategy_test";
+  let (file_id, content) = create_interrupted_upload(&test, parent_dir).await;
+  let upload_id = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &file_id,
+  )
+  .unwrap()
+  .unwrap()
+  .upload_id;
+
+  test
+    .manager
+    .resume_upload_with_strategy(
+      &workspace_id,
+      parent_dir,
+      &file_id,
+      ResumeStrategy::Continue,
+    )
+    .await
+    .unwrap();
+
+  // Only the interrupted and the remaining parts were sent, to the same server-side upload.
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    4
+  );
+  assert_eq!(
+    test.cloud_service.abort_upload_count.load(Ordering::SeqCst),
+    0
+  );
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert_eq!(record.upload_id, upload_id);
+  assert!(record.is_finish);
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &file_id);
+  assert_eq!(
+    test.cloud_service.objects.get(&url).unwrap().to_vec(),
+    content
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn restart_discards_uploaded_parts_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .part_max_attempts(1),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "resume_strategy_test";
+  let (file_id, content) = create_interrupted_upload(&test, parent_dir).await;
+  let upload_id = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &file_id,
+  )
+  .unwrap()
+  .unwrap()
+  .upload_id;
+
+  test
+    .manager
+    .resume_upload_with_strategy(&workspace_id, parent_dir, &file_id, ResumeStrategy::Restart)
+    .await
+    .unwrap();
+
+  // The server-side upload was aborted, and all the parts were sent again to a new one.
+  assert_eq!(
+    test.cloud_service.abort_upload_count.load(Ordering::SeqCst),
+    1
+  );
+  assert!(!test.cloud_service.parts.contains_key(&upload_id));
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    2 + 4
+  );
+  assert!(select_upload_parts(&mut test.db_connection(), &upload_id)
+    .unwrap()
+    .is_empty());
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert_ne!(record.upload_id, upload_id);
+  assert!(record.is_finish);
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &file_id);
+  assert_eq!(
+    test.cloud_service.objects.get(&url).unwrap().to_vec(),
+    content
+  );
+}
@@ -0,0 +1,165 @@
+use crate::util::{create_temp_file, MockStorageCloudService, StorageTest};
+use flowy_error::ErrorCode;
+use flowy_storage::sqlite_sql::{
+  insert_upload_file, insert_upload_part, select_upload_file, UploadFilePartTable, UploadFileTable,
+};
+use flowy_storage_pub::chunked_byte::MIN_CHUNK_SIZE;
+use flowy_storage_pub::cloud::StorageCloudService;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn resume_upload_with_stored_chunk_size_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "resume_test";
+  let file_id = "resume_file";
+
+  // The record was created by a release using a bigger chunk size than the current one.
+  let chunk_size = MIN_CHUNK_SIZE + 1024 * 1024;
+  let file_path = create_temp_file(chunk_size * 2, "txt");
+  let content = std::fs::read(&file_path).unwrap();
+
+  // The first part was uploaded before the app quit.
+  let upload_id = test
+    .cloud_service
+    .create_upload(&workspace_id, parent_dir, file_id, "text/plain")
+    .await
+    .unwrap()
+    .upload_id;
+  let resp = test
+    .cloud_service
+    .upload_part(
+      &workspace_id,
+      parent_dir,
+      &upload_id,
+      file_id,
+      1,
+      content[..chunk_size].to_vec(),
+    )
+    .await
+    .unwrap();
+  insert_upload_file(
+    test.db_connection(),
+    &UploadFileTable {
+      workspace_id: workspace_id.clone(),
+      file_id: file_id.to_string(),
+      parent_dir: parent_dir.to_string(),
+      local_file_path: file_path.to_str().unwrap().to_string(),
+      content_type: "text/plain".to_string(),
+      chunk_size: chunk_size as i32,
+      num_chunk: 2,
+      upload_id: upload_id.clone(),
+      created_at: 0,
+      is_finish: false,
+      metadata: "".to_string(),
+      storage_class: "".to_string(),
+      seq: 0,
+      overwrite: true,
+      linked_parent_dir: String::new(),
+      source_file_path: String::new(),
+    },
+  )
+  .unwrap();
+  insert_upload_part(
+    test.db_connection(),
+    &UploadFilePartTable {
+      upload_id,
+      e_tag: resp.e_tag,
+      part_num: resp.part_num,
+      checksum: String::new(),
+    },
+  )
+  .unwrap();
+
+  test
+    .manager
+    .storage_service()
+    .resume_upload(&workspace_id, parent_dir, file_id)
+    .await
+    .unwrap();
+
+  // Only the second part was uploaded when resuming, and the object matches the file.
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    2
+  );
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, file_id);
+  let object = test.cloud_service.objects.get(&url).unwrap().clone();
+  assert_eq!(object.to_vec(), content);
+}
+
+/// Creates an upload that isn't started, and evicts its temp file.
+async fn create_evicted_upload(test: &StorageTest, parent_dir: &str) -> (PathBuf, String) {
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert_eq!(record.source_file_path, file_path.to_str().unwrap());
+  std::fs::remove_file(&record.local_file_path).unwrap();
+  (file_path, created_upload.file_id)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn resume_upload_restores_evicted_temp_file_test() {
+  let test = StorageTest::new().await;
+  test.manager.update_network_reachable(false);
+  let workspace_id = test.workspace_id();
+  let parent_dir = "resume_evicted_test";
+  let (file_path, file_id) = create_evicted_upload(&test, parent_dir).await;
+
+  // The temp file is copied again from the source, and the upload completes.
+  test
+    .manager
+    .storage_service()
+    .resume_upload(&workspace_id, parent_dir, &file_id)
+    .await
+    .unwrap();
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &file_id);
+  let object = test.cloud_service.objects.get(&url).unwrap().clone();
+  assert_eq!(object.to_vec(), std::fs::read(&file_path).unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn resume_upload_without_temp_and_source_file_test() {
+  let test = StorageTest::new().await;
+  test.manager.update_network_reachable(false);
+  let workspace_id = test.workspace_id();
+  let parent_dir = "resume_evicted_test";
+  let (file_path, file_id) = create_evicted_upload(&test, parent_dir).await;
+  std::fs::remove_file(&file_path).unwrap();
+
+  // Nothing is left to upload, the upload is dropped.
+  let err = test
+    .manager
+    .storage_service()
+    .resume_upload(&workspace_id, parent_dir, &file_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::UploadFileMissing);
+  assert!(select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &file_id
+  )
+  .unwrap()
+  .is_none());
+}
@@ -0,0 +1,98 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::IdleOutcome;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const PARENT_DIR: &str = "retry_budget_test";
+
+#[derive(Clone, Copy)]
+enum TaskKind {
+  Immediate,
+  Task,
+  Background,
+}
+
+/// Uploads a file as a task of the kind whose completion always fails, and returns the details of
+/// its retry events.
+async fn failing_upload(kind: TaskKind) -> (StorageTest, Vec<String>) {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .part_max_attempts(1)
+      .immediate_task_retries(1)
+      .task_retries(2)
+      .background_task_retries(3),
+  )
+  .await;
+  test
+    .cloud_service
+    .complete_failures
+    .store(usize::MAX, Ordering::SeqCst);
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+
+  // The upload is held while the network is unreachable, so that its task can be replaced by a
+  // task of the kind.
+  test.manager.update_network_reachable(false);
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let file_id = created_upload.file_id;
+  match kind {
+    TaskKind::Immediate => test
+      .manager
+      .retry_failed_upload(&workspace_id, PARENT_DIR, &file_id, true)
+      .await
+      .unwrap(),
+    TaskKind::Task => {},
+    TaskKind::Background => test
+      .manager
+      .retry_failed_upload(&workspace_id, PARENT_DIR, &file_id, false)
+      .await
+      .unwrap(),
+  }
+  test.manager.update_network_reachable(true);
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(60)).await,
+    IdleOutcome::Idle
+  );
+
+  let retries = test
+    .manager
+    .upload_log(&file_id)
+    .await
+    .unwrap()
+    .into_iter()
+    .filter(|log| log.event == "retry")
+    .map(|log| log.detail)
+    .collect();
+  (test, retries)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn retry_budget_per_task_kind_test() {
+  for (kind, expected) in [
+    (TaskKind::Immediate, vec!["0"]),
+    (TaskKind::Task, vec!["1", "0"]),
+    (TaskKind::Background, vec!["2", "1", "0"]),
+  ] {
+    let (test, retries) = failing_upload(kind).await;
+    // Each failure requeues the task with one retry less, the failure with none left drops it.
+    assert_eq!(retries, expected);
+    assert_eq!(
+      test
+        .cloud_service
+        .complete_upload_count
+        .load(Ordering::SeqCst),
+      expected.len() + 1
+    );
+  }
+}
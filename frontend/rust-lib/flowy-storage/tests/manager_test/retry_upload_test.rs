@@ -0,0 +1,87 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::IdleOutcome;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn immediate_retry_is_processed_before_pending_tasks_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().max_concurrent_uploads(1)).await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(500)));
+  let workspace_id = test.workspace_id();
+  let parent_dir = "retry_upload_test";
+  test.manager.update_network_reachable(false);
+
+  let mut pending_uploads = vec![];
+  for _ in 0..2 {
+    let file_path = create_temp_file(1024, "txt");
+    let (created_upload, receiver) = test
+      .manager
+      .storage_service()
+      .create_upload(
+        &workspace_id,
+        parent_dir,
+        file_path.to_str().unwrap(),
+        false,
+      )
+      .await
+      .unwrap();
+    pending_uploads.push((created_upload, receiver.unwrap()));
+  }
+  let file_path = create_temp_file(1024, "txt");
+  let (retried_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+
+  // Retrying twice still queues a single task for the file.
+  for _ in 0..2 {
+    test
+      .manager
+      .retry_failed_upload(&workspace_id, parent_dir, &retried_upload.file_id, true)
+      .await
+      .unwrap();
+  }
+
+  test.manager.update_network_reachable(true);
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  // The uploads run one at a time, the pending ones didn't finish yet.
+  for (created_upload, _) in &pending_uploads {
+    let url =
+      MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+    assert!(!test.cloud_service.objects.contains_key(&url));
+  }
+
+  for (_, mut receiver) in pending_uploads {
+    assert!(wait_for_finished(&mut receiver, Duration::from_secs(30)).await);
+  }
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Idle
+  );
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    3
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn retry_unknown_upload_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let result = test
+    .manager
+    .retry_failed_upload(&workspace_id, "retry_upload_test", "unknown_file", true)
+    .await;
+  assert!(result.is_err());
+}
@@ -0,0 +1,109 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::spawner::{Spawner, TokioSpawner};
+use futures_util::future::BoxFuture;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Records the spawned tasks. While held, the tasks are kept instead of run, until released.
+#[derive(Default)]
+struct RecordingSpawner {
+  spawned: AtomicUsize,
+  /// The held tasks, `None` once released.
+  held: Mutex<Option<Vec<BoxFuture<'static, ()>>>>,
+}
+
+impl RecordingSpawner {
+  fn held() -> Self {
+    Self {
+      held: Mutex::new(Some(vec![])),
+      ..Default::default()
+    }
+  }
+
+  fn release(&self) {
+    for task in self.held.lock().unwrap().take().unwrap_or_default() {
+      TokioSpawner.spawn(task);
+    }
+  }
+}
+
+impl std::fmt::Debug for RecordingSpawner {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("RecordingSpawner")
+      .field("spawned", &self.spawned)
+      .finish()
+  }
+}
+
+impl Spawner for RecordingSpawner {
+  fn spawn(&self, task: BoxFuture<'static, ()>) {
+    self.spawned.fetch_add(1, Ordering::SeqCst);
+    match self.held.lock().unwrap().as_mut() {
+      Some(held) => held.push(task),
+      None => TokioSpawner.spawn(task),
+    }
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn custom_spawner_runs_background_tasks_test() {
+  let spawner = Arc::new(RecordingSpawner::default());
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .spawner(spawner.clone()),
+  )
+  .await;
+  // The uploader runner and the progress relay.
+  assert_eq!(spawner.spawned.load(Ordering::SeqCst), 2);
+
+  let workspace_id = test.workspace_id();
+  let parent_dir = "spawner_test";
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  // The upload ran on the spawner too.
+  assert!(spawner.spawned.load(Ordering::SeqCst) > 2);
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+  assert!(test.cloud_service.objects.contains_key(&url));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn held_spawner_defers_uploads_test() {
+  let spawner = Arc::new(RecordingSpawner::held());
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .spawner(spawner.clone()),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "spawner_test";
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+
+  // Nothing runs the uploader, so the upload doesn't start.
+  tokio::time::sleep(Duration::from_millis(200)).await;
+  let url = MockStorageCloudService::object_url(&workspace_id, parent_dir, &created_upload.file_id);
+  assert!(!test.cloud_service.objects.contains_key(&url));
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    0
+  );
+
+  spawner.release();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert!(test.cloud_service.objects.contains_key(&url));
+}
@@ -0,0 +1,38 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_sqlite::PoolConfig;
+use futures_util::future::join_all;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_uploads_with_tiny_pool_test() {
+  let test = StorageTest::new_with_pool_config(PoolConfig::default().max_size(1)).await;
+  let workspace_id = test.workspace_id();
+
+  let futures = (0..6).map(|_| {
+    let manager = test.manager.clone();
+    let workspace_id = workspace_id.clone();
+    async move {
+      let file_path = create_temp_file(1024, "txt");
+      manager
+        .storage_service()
+        .create_upload(
+          &workspace_id,
+          "pool_test",
+          file_path.to_str().unwrap(),
+          true,
+        )
+        .await
+    }
+  });
+
+  let mut receivers = vec![];
+  for result in join_all(futures).await {
+    let (_, receiver) = result.expect("create upload should not fail due to pool contention");
+    receivers.push(receiver.unwrap());
+  }
+
+  for mut receiver in receivers {
+    let finished = wait_for_finished(&mut receiver, Duration::from_secs(60)).await;
+    assert!(finished, "upload {} did not finish", receiver.file_id);
+  }
+}
@@ -0,0 +1,84 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use flowy_error::ErrorCode;
+use flowy_storage::manager::CreateUploadOptions;
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::time::Duration;
+
+const PARENT_DIR: &str = "storage_class_test";
+
+/// Uploads a file in the storage class and returns its file id.
+async fn upload_with_storage_class(test: &StorageTest, storage_class: &str) -> String {
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .create_upload_with(
+      &test.workspace_id(),
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      false,
+      CreateUploadOptions::default().storage_class(storage_class),
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  created_upload.file_id
+}
+
+fn record_storage_class(test: &StorageTest, file_id: &str) -> String {
+  select_upload_file(
+    &mut test.db_connection(),
+    &test.workspace_id(),
+    PARENT_DIR,
+    file_id,
+  )
+  .unwrap()
+  .unwrap()
+  .storage_class
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn storage_class_is_forwarded_and_stored_test() {
+  let test = StorageTest::new().await;
+  *test
+    .cloud_service
+    .supported_storage_classes
+    .write()
+    .unwrap() = vec!["STANDARD".to_string(), "STANDARD_IA".to_string()];
+
+  let file_id = upload_with_storage_class(&test, "STANDARD_IA").await;
+  assert_eq!(record_storage_class(&test, &file_id), "STANDARD_IA");
+  let url = MockStorageCloudService::object_url(&test.workspace_id(), PARENT_DIR, &file_id);
+  assert_eq!(
+    test
+      .cloud_service
+      .storage_classes
+      .get(&url)
+      .unwrap()
+      .as_str(),
+    "STANDARD_IA"
+  );
+
+  // A class the backend doesn't list is rejected before anything is created.
+  let file_path = create_temp_file(1024, "txt");
+  let err = test
+    .manager
+    .create_upload_with(
+      &test.workspace_id(),
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      false,
+      CreateUploadOptions::default().storage_class("GLACIER"),
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::UnsupportedStorageClass);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn storage_class_is_ignored_without_support_test() {
+  let test = StorageTest::new().await;
+
+  let file_id = upload_with_storage_class(&test, "STANDARD_IA").await;
+  assert!(record_storage_class(&test, &file_id).is_empty());
+  assert!(test.cloud_service.storage_classes.is_empty());
+}
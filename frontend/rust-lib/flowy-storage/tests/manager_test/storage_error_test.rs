@@ -0,0 +1,103 @@
+use crate::util::{create_temp_file, StorageTest};
+use bytes::Bytes;
+use flowy_error::ErrorCode;
+use flowy_storage::entities::StorageLimitExceededPB;
+use flowy_storage::error::StorageError;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn create_upload_with_invalid_input_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  let file_path = file_path.to_str().unwrap();
+
+  let cases = [
+    ("", "error_test", file_path, StorageError::EmptyWorkspaceId),
+    (
+      workspace_id.as_str(),
+      "",
+      file_path,
+      StorageError::EmptyParentDir,
+    ),
+    (
+      workspace_id.as_str(),
+      "error_test",
+      "",
+      StorageError::EmptyFilePath,
+    ),
+  ];
+  for (workspace_id, parent_dir, file_path, expected) in cases {
+    let err = test
+      .manager
+      .storage_service()
+      .create_upload(workspace_id, parent_dir, file_path, true)
+      .await
+      .unwrap_err();
+    assert_eq!(err.code, expected.code());
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn create_upload_over_quota_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+
+  test.manager.disable_storage_write_access();
+  let err = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "error_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::FileStorageLimitExceeded);
+  assert!(err.is_file_limit_exceeded());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn storage_limit_error_payload_test() {
+  let test = StorageTest::new().await;
+  let file_path = create_temp_file(50 * 1024, "txt");
+  test.manager.update_storage_usage(900 * 1024, 920 * 1024);
+  test.manager.disable_storage_write_access();
+
+  let err = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &test.workspace_id(),
+      "error_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap_err();
+  // The generic error stays, the payload tells how much space the upload needs.
+  assert!(err.is_file_limit_exceeded());
+  assert_eq!(
+    StorageLimitExceededPB::try_from(Bytes::from(err.payload)).unwrap(),
+    StorageLimitExceededPB {
+      used_bytes: 900 * 1024,
+      total_bytes: 920 * 1024,
+      required_bytes: 50 * 1024,
+    }
+  );
+}
+
+#[test]
+fn storage_error_code_test() {
+  assert_eq!(StorageError::Cancelled.code(), ErrorCode::UploadCancelled);
+  assert_eq!(
+    StorageError::RecordNotFound("file".to_string()).code(),
+    ErrorCode::RecordNotFound
+  );
+  assert_eq!(
+    StorageError::FileMissing("file".to_string()).code(),
+    ErrorCode::UploadFileMissing
+  );
+}
@@ -0,0 +1,129 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use collab_importer::util::FileId;
+use flowy_storage_pub::storage::FileUploadState;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn subscribe_while_upload_completes_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "subscribe_test";
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+
+  // Keep subscribing while the upload is running, so that some of the subscriptions race with
+  // the completion of the upload.
+  let mut handles = vec![];
+  for _ in 0..50 {
+    let manager = test.manager.clone();
+    let file_id = created_upload.file_id.clone();
+    handles.push(tokio::spawn(async move {
+      match manager
+        .subscribe_file_state(parent_dir, &file_id)
+        .await
+        .unwrap()
+      {
+        Some(mut receiver) => wait_for_finished(&mut receiver, Duration::from_secs(30)).await,
+        // The upload was already completed when subscribing.
+        None => true,
+      }
+    }));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+  }
+
+  for handle in handles {
+    assert!(handle.await.unwrap(), "subscriber never received Finished");
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_subscribe_before_upload_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "subscribe_test";
+  let file_path = create_temp_file(1024, "txt");
+  let file_id = FileId::from_path(&file_path).await.unwrap();
+
+  // All the subscribers arrive before the upload exists and share one notifier.
+  let mut handles = vec![];
+  for _ in 0..50 {
+    let manager = test.manager.clone();
+    let file_id = file_id.clone();
+    handles.push(tokio::spawn(async move {
+      manager
+        .subscribe_file_state(parent_dir, &file_id)
+        .await
+        .unwrap()
+        .unwrap()
+    }));
+  }
+  let mut receivers = vec![];
+  for handle in handles {
+    receivers.push(handle.await.unwrap());
+  }
+
+  // Creating the upload keeps the notifier, so the early subscribers receive its progress.
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  assert_eq!(created_upload.file_id, file_id);
+  for receiver in receivers.iter_mut() {
+    assert!(wait_for_finished(receiver, Duration::from_secs(30)).await);
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn cancel_upload_keeps_subscribed_notifier_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_secs(10)));
+  let workspace_id = test.workspace_id();
+  let parent_dir = "subscribe_test";
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+  let other_receiver = test
+    .manager
+    .subscribe_file_state(parent_dir, &created_upload.file_id)
+    .await
+    .unwrap();
+
+  // The notifier is still subscribed, it's kept and reset after the cancellation.
+  test
+    .manager
+    .cancel_upload(&workspace_id, parent_dir, &created_upload.file_id)
+    .await
+    .unwrap();
+  let state = test
+    .manager
+    .get_file_state(parent_dir, &created_upload.file_id)
+    .await;
+  assert!(matches!(state, Some(FileUploadState::NotStarted)));
+
+  // Without subscribers, the notifier is removed.
+  drop(receiver);
+  drop(other_receiver);
+  test
+    .manager
+    .cancel_upload(&workspace_id, parent_dir, &created_upload.file_id)
+    .await
+    .unwrap();
+  let state = test
+    .manager
+    .get_file_state(parent_dir, &created_upload.file_id)
+    .await;
+  assert!(state.is_none());
+}
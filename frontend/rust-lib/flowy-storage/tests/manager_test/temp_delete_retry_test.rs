@@ -0,0 +1,114 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use async_trait::async_trait;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::file_cache::TempFileRemover;
+use flowy_storage::manager::{RepairSummary, StorageIssue};
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const PARENT_DIR: &str = "temp_delete_retry_test";
+
+/// Fails the next `failures` removals, as when the file is locked by another process.
+#[derive(Debug, Default)]
+struct FlakyRemover {
+  failures: AtomicUsize,
+  attempts: AtomicUsize,
+}
+
+#[async_trait]
+impl TempFileRemover for FlakyRemover {
+  async fn remove_temp_file(&self, path: &Path) -> io::Result<()> {
+    self.attempts.fetch_add(1, Ordering::SeqCst);
+    let failed = self
+      .failures
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+        failures.checked_sub(1)
+      })
+      .is_ok();
+    if failed {
+      return Err(io::Error::other("file is locked"));
+    }
+    tokio::fs::remove_file(path).await
+  }
+}
+
+/// Uploads a file whose temp file deletion fails `failures` times, and returns its temp file.
+async fn upload_file(failures: usize) -> (StorageTest, Arc<FlakyRemover>, PathBuf) {
+  let remover = Arc::new(FlakyRemover::default());
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .temp_file_remover(remover.clone())
+      .temp_delete_max_attempts(3)
+      .temp_delete_retry_delay(Duration::from_millis(10)),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  remover.failures.store(failures, Ordering::SeqCst);
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    PARENT_DIR,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  (test, remover, PathBuf::from(record.local_file_path))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn retry_transient_temp_file_delete_failure_test() {
+  let (test, remover, temp_file) = upload_file(2).await;
+
+  // The third attempt deletes the file, nothing is left to the orphan sweep.
+  assert!(!temp_file.exists());
+  assert_eq!(remover.attempts.load(Ordering::SeqCst), 3);
+  let report = test.manager.verify_storage().await.unwrap();
+  assert!(!report
+    .issues
+    .iter()
+    .any(|issue| matches!(issue, StorageIssue::OrphanedTempFile { .. })));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn sweep_undeleted_temp_file_test() {
+  let (test, _, temp_file) = upload_file(3).await;
+
+  // All the attempts failed, the temp file is reported as orphaned although its record refers to
+  // it.
+  assert!(temp_file.exists());
+  let report = test.manager.verify_storage().await.unwrap();
+  assert!(report.issues.contains(&StorageIssue::OrphanedTempFile {
+    path: temp_file.to_string_lossy().into_owned(),
+  }));
+
+  let summary = test.manager.repair_storage(report).await.unwrap();
+  assert_eq!(
+    summary,
+    RepairSummary {
+      deleted_temp_files: 1,
+      ..Default::default()
+    }
+  );
+  assert!(!temp_file.exists());
+
+  // The deleted file is forgotten.
+  let report = test.manager.verify_storage().await.unwrap();
+  assert!(report.issues.is_empty());
+}
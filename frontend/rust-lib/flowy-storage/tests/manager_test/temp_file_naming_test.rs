@@ -0,0 +1,80 @@
+use crate::util::{create_temp_file, generate_random_string, wait_for_finished, StorageTest};
+use flowy_storage::file_cache::{HashTempFileNaming, TempFileNaming};
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::env::temp_dir;
+use std::path::Path;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_file_with_long_and_odd_path_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_secs(1)));
+  let workspace_id = test.workspace_id();
+
+  // Each component is close to the file name limit, and the names contain characters that are
+  // illegal or awkward on some platforms.
+  let mut dir = temp_dir().join(format!("storage-file-{}", generate_random_string(8)));
+  for _ in 0..3 {
+    dir = dir.join(format!(
+      "{} #(copy) 测试 {}",
+      "a".repeat(180),
+      generate_random_string(8)
+    ));
+  }
+  std::fs::create_dir_all(&dir).unwrap();
+  let file_path = dir.join(format!("{} résumé [final]?.PNG", "b".repeat(180)));
+  std::fs::copy(create_temp_file(1024, "png"), &file_path).unwrap();
+
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "naming_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    "naming_test",
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  let temp_file_name = Path::new(&record.local_file_path)
+    .file_name()
+    .unwrap()
+    .to_str()
+    .unwrap()
+    .to_string();
+  assert_eq!(temp_file_name.len(), 20);
+  assert!(temp_file_name.ends_with(".png"));
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+}
+
+#[test]
+fn hash_temp_file_naming_test() {
+  let naming = HashTempFileNaming::default();
+  let path = Path::new("/tmp/dir/report.final.PDF");
+  let first = naming.temp_file_name(path);
+  let second = naming.temp_file_name(path);
+  assert_ne!(first, second);
+  assert!(first.ends_with(".pdf"));
+
+  // Missing or unusual extensions are dropped.
+  for path in [
+    "/tmp/dir/README",
+    "/tmp/dir/archive.t@r",
+    "/tmp/dir/.hidden",
+  ] {
+    let name = naming.temp_file_name(Path::new(path));
+    assert_eq!(name.len(), 16, "{}", path);
+    assert!(name.chars().all(|c| c.is_ascii_hexdigit()));
+  }
+}
@@ -0,0 +1,105 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::config::{StorageManagerConfig, TempFilePolicy};
+use flowy_storage::sqlite_sql::select_upload_file;
+use flowy_storage_pub::storage::DownloadState;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const PARENT_DIR: &str = "temp_file_policy_test";
+
+/// Uploads a file and returns the url of the object, its file id, the temp file of the upload and
+/// the content.
+async fn upload_file(test: &StorageTest) -> (String, String, PathBuf, Vec<u8>) {
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+  let content = std::fs::read(&file_path).unwrap();
+  // Hold the upload until its temp file is known.
+  test.manager.update_network_reachable(false);
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let temp_file = record_local_file_path(test, &created_upload.file_id);
+  test.manager.update_network_reachable(true);
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  (
+    created_upload.url,
+    created_upload.file_id,
+    temp_file,
+    content,
+  )
+}
+
+fn record_local_file_path(test: &StorageTest, file_id: &str) -> PathBuf {
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &test.workspace_id(),
+    PARENT_DIR,
+    file_id,
+  )
+  .unwrap()
+  .unwrap();
+  PathBuf::from(record.local_file_path)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn delete_temp_file_after_upload_test() {
+  let test = StorageTest::new().await;
+  let (url, _, temp_file, _) = upload_file(&test).await;
+
+  assert!(!temp_file.exists());
+  assert!(test.manager.cached_upload_path(&url).await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn keep_temp_file_as_cache_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default().temp_file_policy(TempFilePolicy::KeepAsCache),
+  )
+  .await;
+  let (url, _, temp_file, content) = upload_file(&test).await;
+
+  assert_eq!(std::fs::read(&temp_file).unwrap(), content);
+  assert_eq!(
+    test.manager.cached_upload_path(&url).await.unwrap(),
+    temp_file
+  );
+
+  // The download uses the kept file instead of fetching the object.
+  let (local_file_path, state) = test.manager.download(&url).await.unwrap();
+  assert_eq!(local_file_path, temp_file);
+  assert_eq!(*state.borrow(), DownloadState::Downloaded);
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    0
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn move_temp_file_to_cache_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default().temp_file_policy(TempFilePolicy::MoveToCache),
+  )
+  .await;
+  let (url, file_id, temp_file, content) = upload_file(&test).await;
+
+  let cache_path = test.manager.default_download_path(&url).await.unwrap();
+  assert!(!temp_file.exists());
+  assert_eq!(std::fs::read(&cache_path).unwrap(), content);
+  assert_eq!(record_local_file_path(&test, &file_id), cache_path);
+
+  let (local_file_path, _) = test.manager.download(&url).await.unwrap();
+  assert_eq!(local_file_path, cache_path);
+  assert_eq!(
+    test.cloud_service.get_object_count.load(Ordering::SeqCst),
+    0
+  );
+}
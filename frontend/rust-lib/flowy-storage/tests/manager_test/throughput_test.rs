@@ -0,0 +1,54 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn current_throughput_test() {
+  let window = Duration::from_secs(2);
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .throughput_window(window),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  assert_eq!(test.manager.current_throughput_bps(), 0);
+
+  // The uploads share a 2MB per second limit, so they take about 4.5 seconds together.
+  let limit = 2 * MB as u64;
+  test.manager.set_bandwidth_limit(Some(limit));
+  let mut receivers = vec![];
+  for _ in 0..3 {
+    let file_path = create_temp_file(3 * MB, "txt");
+    let (_, receiver) = test
+      .manager
+      .storage_service()
+      .create_upload(
+        &workspace_id,
+        "throughput_test",
+        file_path.to_str().unwrap(),
+        true,
+      )
+      .await
+      .unwrap();
+    receivers.push(receiver.unwrap());
+  }
+
+  // Once the window is full, the throughput is close to the limit.
+  tokio::time::sleep(Duration::from_millis(2500)).await;
+  let throughput = test.manager.current_throughput_bps();
+  assert!(
+    throughput >= limit / 2 && throughput <= limit * 3 / 2,
+    "throughput: {}",
+    throughput
+  );
+
+  for mut receiver in receivers {
+    assert!(wait_for_finished(&mut receiver, Duration::from_secs(30)).await);
+  }
+  // Nothing was uploaded for a whole window.
+  tokio::time::sleep(window).await;
+  assert_eq!(test.manager.current_throughput_bps(), 0);
+}
@@ -0,0 +1,98 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_detail_separates_queue_wait_and_upload_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(300)));
+  let workspace_id = test.workspace_id();
+  let parent_dir = "upload_detail_test";
+  // The upload waits in the queue while the network is unreachable.
+  test.manager.update_network_reachable(false);
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let detail = test
+    .manager
+    .upload_detail(&workspace_id, parent_dir, &created_upload.file_id)
+    .unwrap();
+  assert!(detail.queue_wait().is_none());
+
+  tokio::time::sleep(Duration::from_millis(500)).await;
+  test.manager.update_network_reachable(true);
+  let mut receiver = receiver.unwrap();
+  assert!(wait_for_finished(&mut receiver, Duration::from_secs(30)).await);
+
+  // The detail is updated once the upload task returns.
+  let detail = tokio::time::timeout(Duration::from_secs(5), async {
+    loop {
+      let detail = test
+        .manager
+        .upload_detail(&workspace_id, parent_dir, &created_upload.file_id)
+        .unwrap();
+      if detail.finished_at.is_some() {
+        return detail;
+      }
+      tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+  })
+  .await
+  .unwrap();
+  assert!(detail.queue_wait().unwrap() >= Duration::from_millis(500));
+  assert!(detail.upload_duration().unwrap() >= Duration::from_millis(300));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn retried_upload_waits_in_queue_again_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().part_max_attempts(1)).await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "upload_detail_test";
+  // The only part fails once, which fails the first attempt of the upload.
+  test
+    .cloud_service
+    .fail_part_number
+    .store(1, Ordering::SeqCst);
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+
+  // The retry is queued like a new task, so its detail waits in the queue again.
+  tokio::time::timeout(Duration::from_secs(5), async {
+    loop {
+      let detail = test
+        .manager
+        .upload_detail(&workspace_id, parent_dir, &created_upload.file_id)
+        .unwrap();
+      let logs = test
+        .manager
+        .upload_log(&created_upload.file_id)
+        .await
+        .unwrap();
+      if detail.started_at.is_none() && logs.iter().any(|log| log.event == "retry") {
+        break;
+      }
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+  })
+  .await
+  .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+}
@@ -0,0 +1,118 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::sqlite_sql::select_upload_file;
+use lib_infra::box_any::BoxAny;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn start_and_resume_upload_of_same_file_run_once_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "upload_guard_test";
+  // Keep the uploader from picking the queued upload, the test starts it by itself.
+  test.manager.update_network_reachable(false);
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(500)));
+
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+
+  let storage_service = test.manager.storage_service();
+  let record = BoxAny::new(record);
+  let (start_result, resume_result) = tokio::join!(
+    storage_service.start_upload(&record),
+    storage_service.resume_upload(&workspace_id, parent_dir, &created_upload.file_id),
+  );
+  assert!(start_result.is_ok());
+  assert!(resume_result.is_ok());
+
+  // Only one of them uploaded the single part and completed the upload.
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    1
+  );
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    1
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn failed_upload_releases_guard_test() {
+  let test =
+    StorageTest::new_with_config(StorageManagerConfig::default().part_max_attempts(1)).await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "upload_guard_test";
+  test.manager.update_network_reachable(false);
+
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+
+  test
+    .cloud_service
+    .fail_part_number
+    .store(1, Ordering::SeqCst);
+  let storage_service = test.manager.storage_service();
+  assert!(storage_service
+    .start_upload(&BoxAny::new(record))
+    .await
+    .is_err());
+
+  // The failed upload released the guard, the retry sends the part again.
+  storage_service
+    .resume_upload(&workspace_id, parent_dir, &created_upload.file_id)
+    .await
+    .unwrap();
+  assert_eq!(
+    test.cloud_service.uploaded_bytes.load(Ordering::SeqCst),
+    2 * 1024
+  );
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    1
+  );
+}
@@ -0,0 +1,120 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::ResumeStrategy;
+use std::sync::atomic::Ordering;
+
+const MB: usize = 1024 * 1024;
+
+/// Creates an upload of 3 parts, held in the queue while the network is unreachable.
+async fn create_held_upload(test: &StorageTest, parent_dir: &str) -> String {
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(3 * MB, "txt");
+  test.manager.update_network_reachable(false);
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  created_upload.file_id
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_log_records_lifecycle_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .part_max_attempts(1),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "upload_log_test";
+  let file_id = create_held_upload(&test, parent_dir).await;
+
+  // The first attempt fails on the 2nd part, the second one finishes the upload.
+  test
+    .cloud_service
+    .fail_part_number
+    .store(2, Ordering::SeqCst);
+  for _ in 0..2 {
+    let _ = test
+      .manager
+      .resume_upload_with_strategy(
+        &workspace_id,
+        parent_dir,
+        &file_id,
+        ResumeStrategy::Continue,
+      )
+      .await;
+  }
+
+  let logs = test.manager.upload_log(&file_id).await.unwrap();
+  let events = logs
+    .iter()
+    .map(|log| (log.event.as_str(), log.detail.as_str()))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    events,
+    vec![
+      ("start", ""),
+      ("part", "1"),
+      ("fail", events[2].1),
+      ("start", ""),
+      ("part", "2"),
+      ("part", "3"),
+      ("complete", ""),
+    ]
+  );
+  assert!(events[2].1.contains("upload part interrupted"));
+  assert!(logs.iter().all(|log| log.workspace_id == workspace_id
+    && log.parent_dir == parent_dir
+    && log.created_at > 0));
+  assert!(logs.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+  // The tail covers all the files, the last event last.
+  let tail = test.manager.upload_log_tail(2).await.unwrap();
+  assert_eq!(tail.len(), 2);
+  assert_eq!(tail[0].event, "part");
+  assert_eq!(tail[1].event, "complete");
+  assert!(test.manager.upload_log("unknown").await.unwrap().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn upload_log_is_bounded_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(MB)
+      .upload_log_max_rows(3),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "upload_log_test";
+  let file_id = create_held_upload(&test, parent_dir).await;
+
+  test
+    .manager
+    .resume_upload_with_strategy(
+      &workspace_id,
+      parent_dir,
+      &file_id,
+      ResumeStrategy::Continue,
+    )
+    .await
+    .unwrap();
+
+  // Only the last 3 of the 5 events are kept.
+  let events = test
+    .manager
+    .upload_log(&file_id)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|log| log.event)
+    .collect::<Vec<_>>();
+  assert_eq!(events, vec!["part", "part", "complete"]);
+}
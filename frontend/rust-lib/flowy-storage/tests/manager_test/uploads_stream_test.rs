@@ -0,0 +1,105 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::snapshot::{UploadSnapshot, UploadSnapshotState};
+use futures_util::{Stream, StreamExt};
+use std::time::Duration;
+
+const MB: usize = 1024 * 1024;
+
+/// Reads the snapshots until one matches, and returns them all.
+async fn next_until(
+  stream: &mut (impl Stream<Item = UploadSnapshot> + Unpin),
+  until: impl Fn(&UploadSnapshot) -> bool,
+) -> Vec<UploadSnapshot> {
+  tokio::time::timeout(Duration::from_secs(30), async {
+    let mut snapshots = vec![];
+    while let Some(snapshot) = stream.next().await {
+      let done = until(&snapshot);
+      snapshots.push(snapshot);
+      if done {
+        break;
+      }
+    }
+    snapshots
+  })
+  .await
+  .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn uploads_stream_lifecycle_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .chunk_size(5 * MB)
+      .uploads_stream_debounce(Duration::from_millis(20)),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "uploads_stream_test";
+
+  let finished_path = create_temp_file(1024, "txt");
+  let (finished_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      finished_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  test.manager.update_network_reachable(false);
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_millis(200)));
+  let file_path = create_temp_file(12 * MB, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_id, parent_dir, file_path.to_str().unwrap(), true)
+    .await
+    .unwrap();
+
+  // The snapshot of every upload comes first, in the creation order.
+  let mut stream = Box::pin(test.manager.uploads_stream());
+  let snapshots = next_until(&mut stream, |snapshot| {
+    snapshot.file_id == created_upload.file_id
+  })
+  .await;
+  assert_eq!(snapshots.len(), 2);
+  assert_eq!(snapshots[0].file_id, finished_upload.file_id);
+  assert_eq!(snapshots[0].url, finished_upload.url);
+  assert_eq!(snapshots[0].state, UploadSnapshotState::Finished);
+  assert_eq!(snapshots[1].parent_dir, parent_dir);
+  assert_eq!(snapshots[1].url, created_upload.url);
+  assert!(matches!(
+    snapshots[1].state,
+    UploadSnapshotState::Queued | UploadSnapshotState::Paused { .. }
+  ));
+  assert_eq!(snapshots[1].progress, 0.0);
+  assert_eq!(snapshots[1].total_bytes, Some(12 * MB as u64));
+
+  // Then the changes of the upload, until it finishes.
+  test.manager.update_network_reachable(true);
+  let snapshots = next_until(&mut stream, |snapshot| {
+    snapshot.state == UploadSnapshotState::Finished
+  })
+  .await;
+  assert!(snapshots
+    .iter()
+    .all(|snapshot| snapshot.file_id == created_upload.file_id && snapshot.error.is_none()));
+  assert!(snapshots
+    .iter()
+    .any(|snapshot| snapshot.state == UploadSnapshotState::Uploading));
+  let progress = snapshots
+    .iter()
+    .map(|snapshot| snapshot.progress)
+    .collect::<Vec<_>>();
+  assert!(progress.windows(2).all(|pair| pair[0] <= pair[1]));
+  let finished = snapshots.last().unwrap();
+  assert_eq!(finished.progress, 1.0);
+  assert_eq!(finished.total_bytes, Some(12 * MB as u64));
+}
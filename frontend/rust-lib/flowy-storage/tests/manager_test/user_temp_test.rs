@@ -0,0 +1,68 @@
+use crate::util::{create_temp_file, StorageTest};
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::path::Path;
+
+const PARENT_DIR: &str = "user_temp_test";
+
+/// Creates an upload held in the queue, and returns the path of its temp file.
+async fn create_held_upload(test: &StorageTest) -> String {
+  let workspace_id = test.workspace_id();
+  test.manager.initialize(&workspace_id).await.unwrap();
+  test.manager.update_network_reachable(false);
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let record = select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    PARENT_DIR,
+    &created_upload.file_id,
+  )
+  .unwrap()
+  .unwrap();
+  record.local_file_path
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn switch_user_isolates_temp_files_test() {
+  let test = StorageTest::new().await;
+  test.user_service.set_user_id(1);
+  let first_temp_file = create_held_upload(&test).await;
+  let first_temp_dir = test.manager.user_temp_dir();
+  assert!(Path::new(&first_temp_file).starts_with(&first_temp_dir));
+  assert!(Path::new(&first_temp_file).exists());
+
+  // Initializing the storage for another user removes the temp files of the previous one.
+  test.user_service.set_user_id(2);
+  let second_temp_file = create_held_upload(&test).await;
+  let second_temp_dir = test.manager.user_temp_dir();
+  assert_ne!(first_temp_dir, second_temp_dir);
+  assert!(Path::new(&second_temp_file).starts_with(&second_temp_dir));
+  assert!(!first_temp_dir.exists());
+  assert_eq!(std::fs::read_dir(&second_temp_dir).unwrap().count(), 1);
+
+  // Signing out clears the temp files of the user.
+  test.manager.clear_user_temp(2).await.unwrap();
+  assert!(!second_temp_dir.exists());
+  // Clearing a user without temp files is fine.
+  test.manager.clear_user_temp(3).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn initialize_same_user_keeps_temp_files_test() {
+  let test = StorageTest::new().await;
+  let temp_file = create_held_upload(&test).await;
+
+  let workspace_id = test.workspace_id();
+  test.manager.initialize(&workspace_id).await.unwrap();
+  assert!(Path::new(&temp_file).exists());
+}
@@ -0,0 +1,718 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use dashmap::DashMap;
+use flowy_error::{ErrorCode, FlowyError, FlowyResult};
+use flowy_sqlite::{DBConnection, Database, PoolConfig, DB_NAME};
+use flowy_storage::checksum::part_checksum;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::{StorageManager, StorageUserService};
+use flowy_storage_pub::cloud::{
+  ChecksumAlgorithm, ObjectIdentity, ObjectInfo, ObjectPage, ObjectRange, ObjectValue,
+  PartChecksum, StorageCapabilities, StorageCloudService,
+};
+use flowy_storage_pub::storage::{
+  CompletedPartRequest, CreateUploadResponse, FileProgressReceiver, FileUploadState,
+  UploadPartResponse,
+};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+use std::env::temp_dir;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicI64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+const MOCK_URL_PREFIX: &str = "https://mock.appflowy.io/api/file_storage";
+
+pub struct StorageTest {
+  pub manager: Arc<StorageManager>,
+  pub cloud_service: Arc<MockStorageCloudService>,
+  pub user_service: Arc<MockStorageUserService>,
+}
+
+impl StorageTest {
+  pub async fn new() -> Self {
+    Self::new_with(StorageManagerConfig::default(), PoolConfig::default()).await
+  }
+
+  pub async fn new_with_config(config: StorageManagerConfig) -> Self {
+    Self::new_with(config, PoolConfig::default()).await
+  }
+
+  pub async fn new_with_pool_config(pool_config: PoolConfig) -> Self {
+    Self::new_with(StorageManagerConfig::default(), pool_config).await
+  }
+
+  async fn new_with(config: StorageManagerConfig, pool_config: PoolConfig) -> Self {
+    let cloud_service = Arc::new(MockStorageCloudService::default());
+    let user_service = Arc::new(MockStorageUserService::new(pool_config));
+    let manager = Arc::new(StorageManager::new_with_config(
+      cloud_service.clone(),
+      user_service.clone(),
+      config,
+    ));
+    Self {
+      manager,
+      cloud_service,
+      user_service,
+    }
+  }
+
+  pub fn workspace_id(&self) -> String {
+    self.user_service.workspace_id().unwrap()
+  }
+
+  pub fn db_connection(&self) -> DBConnection {
+    self.user_service.sqlite_connection(0).unwrap()
+  }
+}
+
+pub struct MockStorageUserService {
+  db: Database,
+  root_dir: String,
+  workspace_id: RwLock<String>,
+  uid: AtomicI64,
+}
+
+impl MockStorageUserService {
+  pub fn new(pool_config: PoolConfig) -> Self {
+    let root_dir = temp_dir().join(format!("storage-test-{}", generate_random_string(8)));
+    let db_dir = root_dir.join("db");
+    // run the migrations with the default pool, then reopen the database with the given config.
+    flowy_sqlite::init(&db_dir).unwrap();
+    let db = Database::new(db_dir.to_str().unwrap(), DB_NAME, pool_config).unwrap();
+    Self {
+      db,
+      root_dir: root_dir.to_str().unwrap().to_string(),
+      workspace_id: RwLock::new(uuid::Uuid::new_v4().to_string()),
+      uid: AtomicI64::new(0),
+    }
+  }
+
+  pub fn set_workspace_id(&self, workspace_id: &str) {
+    *self.workspace_id.write().unwrap() = workspace_id.to_string();
+  }
+
+  /// Switches the signed-in user. All the users share the same database.
+  pub fn set_user_id(&self, uid: i64) {
+    self.uid.store(uid, Ordering::SeqCst);
+  }
+}
+
+impl StorageUserService for MockStorageUserService {
+  fn user_id(&self) -> Result<i64, FlowyError> {
+    Ok(self.uid.load(Ordering::SeqCst))
+  }
+
+  fn workspace_id(&self) -> Result<String, FlowyError> {
+    Ok(self.workspace_id.read().unwrap().clone())
+  }
+
+  fn sqlite_connection(&self, _uid: i64) -> Result<DBConnection, FlowyError> {
+    let conn = self
+      .db
+      .get_connection()
+      .map_err(|err| FlowyError::internal().with_context(err))?;
+    Ok(conn)
+  }
+
+  fn get_application_root_dir(&self) -> &str {
+    &self.root_dir
+  }
+}
+
+/// An in-memory [StorageCloudService]. Parts are kept per upload id and concatenated into an
+/// object when the upload is completed.
+#[derive(Default)]
+pub struct MockStorageCloudService {
+  pub objects: DashMap<String, Bytes>,
+  pub parts: DashMap<String, Vec<(i32, Vec<u8>)>>,
+  pub upload_part_count: AtomicUsize,
+  /// The bytes sent by the upload_part calls, including the failed ones.
+  pub uploaded_bytes: AtomicUsize,
+  /// The part number whose next upload fails, zero means none.
+  pub fail_part_number: AtomicI32,
+  pub complete_upload_count: AtomicUsize,
+  pub part_delay: RwLock<Option<Duration>>,
+  pub fail_delete: AtomicBool,
+  pub get_object_count: AtomicUsize,
+  /// The urls passed to get_object, in the order of the calls.
+  pub fetched_urls: Mutex<Vec<String>>,
+  pub get_object_delay: RwLock<Option<Duration>>,
+  pub in_flight_downloads: AtomicUsize,
+  pub max_in_flight_downloads: AtomicUsize,
+  /// The number of the next get_object calls that fail.
+  pub get_object_failures: AtomicUsize,
+  pub abort_upload_count: AtomicUsize,
+  /// The number of the next complete_upload calls that fail.
+  pub complete_failures: AtomicUsize,
+  /// The number of the next complete_upload calls that reject the uploaded parts.
+  pub invalid_parts_failures: AtomicUsize,
+  pub in_flight_parts: AtomicUsize,
+  pub max_in_flight_parts: AtomicUsize,
+  /// The bytes of the parts being uploaded.
+  pub in_flight_part_bytes: AtomicUsize,
+  pub max_in_flight_part_bytes: AtomicUsize,
+  pub min_part_size: AtomicUsize,
+  /// Whether the objects can be fetched by ranges.
+  pub range_downloads: AtomicBool,
+  pub get_object_range_count: AtomicUsize,
+  /// Whether metadata can be stored along with the objects.
+  pub object_metadata_support: AtomicBool,
+  /// The metadata of the uploads, keyed by the url of their object.
+  pub metadata: DashMap<String, HashMap<String, String>>,
+  /// The maximum number of parts of an upload, zero means unlimited.
+  pub max_parts: AtomicUsize,
+  /// Whether the objects under a parent dir can be listed.
+  pub list_objects_support: AtomicBool,
+  /// The number of objects of a listed page, zero means unlimited.
+  pub list_page_size: AtomicUsize,
+  /// The content types of the uploads, keyed by the url of their object.
+  pub content_types: DashMap<String, String>,
+  /// The storage classes the backend supports.
+  pub supported_storage_classes: RwLock<Vec<String>>,
+  /// The storage classes of the uploads, keyed by the url of their object.
+  pub storage_classes: DashMap<String, String>,
+  /// Whether an upload can be created on the condition that its object doesn't exist.
+  pub conditional_create: AtomicBool,
+  /// Whether the file ids must be the hashes of the content.
+  pub content_addressed: AtomicBool,
+  pub create_if_not_exists_count: AtomicUsize,
+  /// The checksum algorithm the parts are validated with, `None` when they aren't.
+  pub part_checksum: RwLock<Option<ChecksumAlgorithm>>,
+  /// The part number whose next upload is stored corrupted, zero means none.
+  pub corrupt_part_number: AtomicI32,
+  /// The checksums sent along with the parts, in the order of the calls.
+  pub received_checksums: Mutex<Vec<(i32, PartChecksum)>>,
+}
+
+impl MockStorageCloudService {
+  pub fn object_url(workspace_id: &str, parent_dir: &str, file_id: &str) -> String {
+    format!(
+      "{}/{}/v1/blob/{}/{}",
+      MOCK_URL_PREFIX, workspace_id, parent_dir, file_id
+    )
+  }
+
+  pub fn object_url_v2(workspace_id: &str, parent_dir: &str, file_id: &str) -> String {
+    format!(
+      "{}/v2/{}/{}/{}",
+      MOCK_URL_PREFIX, workspace_id, parent_dir, file_id
+    )
+  }
+
+  pub fn set_part_delay(&self, delay: Option<Duration>) {
+    *self.part_delay.write().unwrap() = delay;
+  }
+
+  pub fn set_get_object_delay(&self, delay: Option<Duration>) {
+    *self.get_object_delay.write().unwrap() = delay;
+  }
+}
+
+#[async_trait]
+impl StorageCloudService for MockStorageCloudService {
+  async fn get_object_url(&self, object_id: ObjectIdentity) -> Result<String, FlowyError> {
+    Ok(format!(
+      "{}/{}/{}.{}",
+      MOCK_URL_PREFIX, object_id.workspace_id, object_id.file_id, object_id.ext
+    ))
+  }
+
+  async fn put_object(&self, url: String, object_value: ObjectValue) -> Result<(), FlowyError> {
+    self.objects.insert(url, object_value.raw);
+    Ok(())
+  }
+
+  async fn delete_object(&self, url: &str) -> Result<(), FlowyError> {
+    if self.fail_delete.load(Ordering::SeqCst) {
+      return Err(FlowyError::internal().with_context("delete failed"));
+    }
+    self.objects.remove(url);
+    Ok(())
+  }
+
+  async fn get_object(&self, url: String) -> Result<ObjectValue, FlowyError> {
+    self.get_object_count.fetch_add(1, Ordering::SeqCst);
+    self.fetched_urls.lock().unwrap().push(url.clone());
+    let in_flight = self.in_flight_downloads.fetch_add(1, Ordering::SeqCst) + 1;
+    self
+      .max_in_flight_downloads
+      .fetch_max(in_flight, Ordering::SeqCst);
+    let delay = *self.get_object_delay.read().unwrap();
+    if let Some(delay) = delay {
+      tokio::time::sleep(delay).await;
+    }
+    self.in_flight_downloads.fetch_sub(1, Ordering::SeqCst);
+    if self
+      .get_object_failures
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+      .is_ok()
+    {
+      return Err(FlowyError::internal().with_context("get object failed"));
+    }
+    let raw = self
+      .objects
+      .get(&url)
+      .map(|value| value.clone())
+      .ok_or_else(FlowyError::record_not_found)?;
+    Ok(ObjectValue {
+      raw,
+      mime: mime::APPLICATION_OCTET_STREAM,
+    })
+  }
+
+  async fn get_object_url_v1(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<String> {
+    Ok(Self::object_url(workspace_id, parent_dir, file_id))
+  }
+
+  async fn parse_object_url_v1(&self, url: &str) -> Option<(String, String, String)> {
+    let path = url.strip_prefix(MOCK_URL_PREFIX)?;
+    let segments = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+    match segments.as_slice() {
+      [workspace_id, "v1", "blob", parent_dir, file_id] => Some((
+        workspace_id.to_string(),
+        parent_dir.to_string(),
+        file_id.to_string(),
+      )),
+      _ => None,
+    }
+  }
+
+  async fn get_object_url_v2(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<String> {
+    Ok(Self::object_url_v2(workspace_id, parent_dir, file_id))
+  }
+
+  async fn parse_object_url_v2(&self, url: &str) -> Option<(String, String, String)> {
+    let path = url.strip_prefix(MOCK_URL_PREFIX)?;
+    let segments = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+    match segments.as_slice() {
+      ["v2", workspace_id, parent_dir, file_id] => Some((
+        workspace_id.to_string(),
+        parent_dir.to_string(),
+        file_id.to_string(),
+      )),
+      _ => None,
+    }
+  }
+
+  async fn object_exists(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<bool> {
+    let url = Self::object_url(workspace_id, parent_dir, file_id);
+    Ok(self.objects.contains_key(&url))
+  }
+
+  fn min_part_size(&self) -> usize {
+    self.min_part_size.load(Ordering::SeqCst)
+  }
+
+  fn capabilities(&self) -> StorageCapabilities {
+    StorageCapabilities {
+      range_downloads: self.range_downloads.load(Ordering::SeqCst),
+      abort_upload: true,
+      head_object: true,
+      object_metadata: self.object_metadata_support.load(Ordering::SeqCst),
+      list_objects: self.list_objects_support.load(Ordering::SeqCst),
+      conditional_create: self.conditional_create.load(Ordering::SeqCst),
+      content_addressed: self.content_addressed.load(Ordering::SeqCst),
+      storage_classes: self.supported_storage_classes.read().unwrap().clone(),
+      min_part_size: self.min_part_size(),
+      max_parts: match self.max_parts.load(Ordering::SeqCst) {
+        0 => None,
+        max_parts => Some(max_parts),
+      },
+      part_checksum: *self.part_checksum.read().unwrap(),
+      ..Default::default()
+    }
+  }
+
+  async fn get_object_range(&self, url: String, range: Range<u64>) -> FlowyResult<ObjectRange> {
+    self.get_object_range_count.fetch_add(1, Ordering::SeqCst);
+    let object = self
+      .objects
+      .get(&url)
+      .map(|value| value.clone())
+      .ok_or_else(FlowyError::record_not_found)?;
+    let total_size = object.len() as u64;
+    let end = range.end.min(total_size);
+    Ok(ObjectRange {
+      raw: object.slice(range.start.min(end) as usize..end as usize),
+      total_size,
+    })
+  }
+
+  async fn abort_upload(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    upload_id: &str,
+    _file_id: &str,
+  ) -> FlowyResult<()> {
+    self.abort_upload_count.fetch_add(1, Ordering::SeqCst);
+    self.parts.remove(upload_id);
+    Ok(())
+  }
+
+  async fn create_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    content_type: &str,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    // Like the server, reject the malformed content types.
+    if content_type.parse::<mime::Mime>().is_err() {
+      return Err(FlowyError::invalid_data().with_context("invalid content type"));
+    }
+    self.content_types.insert(
+      Self::object_url(workspace_id, parent_dir, file_id),
+      content_type.to_string(),
+    );
+    let upload_id = uuid::Uuid::new_v4().to_string();
+    self.parts.insert(upload_id.clone(), vec![]);
+    Ok(CreateUploadResponse {
+      file_id: file_id.to_string(),
+      upload_id,
+    })
+  }
+
+  async fn create_upload_with_metadata(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    content_type: &str,
+    metadata: &HashMap<String, String>,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    let resp = self
+      .create_upload(workspace_id, parent_dir, file_id, content_type)
+      .await?;
+    self.metadata.insert(
+      Self::object_url(workspace_id, parent_dir, file_id),
+      metadata.clone(),
+    );
+    Ok(resp)
+  }
+
+  async fn create_upload_with_storage_class(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    content_type: &str,
+    metadata: &HashMap<String, String>,
+    storage_class: &str,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    let resp = self
+      .create_upload_with_metadata(workspace_id, parent_dir, file_id, content_type, metadata)
+      .await?;
+    self.storage_classes.insert(
+      Self::object_url(workspace_id, parent_dir, file_id),
+      storage_class.to_string(),
+    );
+    Ok(resp)
+  }
+
+  async fn create_upload_if_not_exists(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    content_type: &str,
+    metadata: &HashMap<String, String>,
+    storage_class: &str,
+  ) -> Result<CreateUploadResponse, FlowyError> {
+    self
+      .create_if_not_exists_count
+      .fetch_add(1, Ordering::SeqCst);
+    if self
+      .objects
+      .contains_key(&Self::object_url(workspace_id, parent_dir, file_id))
+    {
+      return Err(FlowyError::new(
+        ErrorCode::ObjectAlreadyExists,
+        "object already exists",
+      ));
+    }
+    if storage_class.is_empty() {
+      self
+        .create_upload_with_metadata(workspace_id, parent_dir, file_id, content_type, metadata)
+        .await
+    } else {
+      self
+        .create_upload_with_storage_class(
+          workspace_id,
+          parent_dir,
+          file_id,
+          content_type,
+          metadata,
+          storage_class,
+        )
+        .await
+    }
+  }
+
+  async fn object_metadata(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> FlowyResult<HashMap<String, String>> {
+    let url = Self::object_url(workspace_id, parent_dir, file_id);
+    if !self.objects.contains_key(&url) {
+      return Err(FlowyError::record_not_found());
+    }
+    Ok(
+      self
+        .metadata
+        .get(&url)
+        .map(|metadata| metadata.clone())
+        .unwrap_or_default(),
+    )
+  }
+
+  async fn list_objects(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    cursor: Option<&str>,
+  ) -> FlowyResult<ObjectPage> {
+    let prefix = Self::object_url(workspace_id, parent_dir, "");
+    let mut objects = self
+      .objects
+      .iter()
+      .filter_map(|entry| {
+        let file_id = entry.key().strip_prefix(&prefix)?;
+        Some(ObjectInfo {
+          file_id: file_id.to_string(),
+          size: Some(entry.value().len() as u64),
+          content_type: self
+            .content_types
+            .get(entry.key())
+            .map(|content_type| content_type.clone()),
+        })
+      })
+      // The file ids are never empty, so the first page starts after the empty string.
+      .filter(|object| object.file_id.as_str() > cursor.unwrap_or_default())
+      .collect::<Vec<_>>();
+    objects.sort_by(|a, b| a.file_id.cmp(&b.file_id));
+    let page_size = self.list_page_size.load(Ordering::SeqCst);
+    let next_cursor = if page_size > 0 && objects.len() > page_size {
+      objects.truncate(page_size);
+      objects.last().map(|object| object.file_id.clone())
+    } else {
+      None
+    };
+    Ok(ObjectPage {
+      objects,
+      next_cursor,
+    })
+  }
+
+  async fn upload_part(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    upload_id: &str,
+    _file_id: &str,
+    part_number: i32,
+    body: Vec<u8>,
+  ) -> Result<UploadPartResponse, FlowyError> {
+    let in_flight = self.in_flight_parts.fetch_add(1, Ordering::SeqCst) + 1;
+    self
+      .max_in_flight_parts
+      .fetch_max(in_flight, Ordering::SeqCst);
+    let in_flight_bytes = self
+      .in_flight_part_bytes
+      .fetch_add(body.len(), Ordering::SeqCst)
+      + body.len();
+    self
+      .max_in_flight_part_bytes
+      .fetch_max(in_flight_bytes, Ordering::SeqCst);
+    let delay = *self.part_delay.read().unwrap();
+    if let Some(delay) = delay {
+      tokio::time::sleep(delay).await;
+    }
+    self.in_flight_parts.fetch_sub(1, Ordering::SeqCst);
+    self
+      .in_flight_part_bytes
+      .fetch_sub(body.len(), Ordering::SeqCst);
+    self.uploaded_bytes.fetch_add(body.len(), Ordering::SeqCst);
+    if self
+      .fail_part_number
+      .compare_exchange(part_number, 0, Ordering::SeqCst, Ordering::SeqCst)
+      .is_ok()
+    {
+      return Err(FlowyError::internal().with_context("upload part interrupted"));
+    }
+    self.upload_part_count.fetch_add(1, Ordering::SeqCst);
+    let mut body = body;
+    if self
+      .corrupt_part_number
+      .compare_exchange(part_number, 0, Ordering::SeqCst, Ordering::SeqCst)
+      .is_ok()
+    {
+      body[0] ^= 0xff;
+    }
+    // Uploading the same part number again replaces the part.
+    let mut parts = self.parts.entry(upload_id.to_string()).or_default();
+    parts.retain(|(part_num, _)| *part_num != part_number);
+    parts.push((part_number, body));
+    Ok(UploadPartResponse {
+      e_tag: format!("{}-{}", upload_id, part_number),
+      part_num: part_number,
+    })
+  }
+
+  async fn upload_part_with_checksum(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    upload_id: &str,
+    file_id: &str,
+    part_number: i32,
+    body: Vec<u8>,
+    checksum: &PartChecksum,
+  ) -> Result<UploadPartResponse, FlowyError> {
+    self
+      .received_checksums
+      .lock()
+      .unwrap()
+      .push((part_number, checksum.clone()));
+    self
+      .upload_part(
+        workspace_id,
+        parent_dir,
+        upload_id,
+        file_id,
+        part_number,
+        body,
+      )
+      .await
+  }
+
+  async fn part_checksums(
+    &self,
+    _workspace_id: &str,
+    _parent_dir: &str,
+    upload_id: &str,
+    _file_id: &str,
+  ) -> FlowyResult<HashMap<i32, PartChecksum>> {
+    let algorithm = self
+      .part_checksum
+      .read()
+      .unwrap()
+      .ok_or_else(FlowyError::not_support)?;
+    Ok(
+      self
+        .parts
+        .get(upload_id)
+        .map(|parts| {
+          parts
+            .iter()
+            .map(|(part_num, body)| (*part_num, part_checksum(algorithm, body)))
+            .collect()
+        })
+        .unwrap_or_default(),
+    )
+  }
+
+  async fn complete_upload(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    upload_id: &str,
+    file_id: &str,
+    parts: Vec<CompletedPartRequest>,
+  ) -> Result<(), FlowyError> {
+    self.complete_upload_count.fetch_add(1, Ordering::SeqCst);
+    if self
+      .complete_failures
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+      .is_ok()
+    {
+      return Err(FlowyError::internal().with_context("complete upload failed"));
+    }
+    if self
+      .invalid_parts_failures
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+      .is_ok()
+    {
+      return Err(FlowyError::new(
+        ErrorCode::UploadPartsInvalid,
+        "the uploaded parts are invalid",
+      ));
+    }
+    let mut uploaded = self
+      .parts
+      .remove(upload_id)
+      .map(|(_, parts)| parts)
+      .unwrap_or_default();
+    uploaded.retain(|(part_num, _)| parts.iter().any(|p| p.part_number == *part_num));
+    uploaded.sort_by_key(|(part_num, _)| *part_num);
+    let raw = uploaded
+      .into_iter()
+      .flat_map(|(_, body)| body)
+      .collect::<Vec<_>>();
+    self.objects.insert(
+      Self::object_url(workspace_id, parent_dir, file_id),
+      Bytes::from(raw),
+    );
+    Ok(())
+  }
+}
+
+pub fn generate_random_string(len: usize) -> String {
+  let rng = thread_rng();
+  rng
+    .sample_iter(&Alphanumeric)
+    .take(len)
+    .map(char::from)
+    .collect()
+}
+
+/// Creates a file with random content under a unique directory, so that each call yields a
+/// distinct file id.
+pub fn create_temp_file(size_in_bytes: usize, ext: &str) -> PathBuf {
+  let content: String = thread_rng()
+    .sample_iter(&Alphanumeric)
+    .take(size_in_bytes)
+    .map(char::from)
+    .collect();
+  let dir = temp_dir().join(format!("storage-file-{}", generate_random_string(8)));
+  std::fs::create_dir_all(&dir).unwrap();
+  let file_path = dir.join(format!("{}.{}", generate_random_string(6), ext));
+  std::fs::write(&file_path, content).unwrap();
+  file_path
+}
+
+/// Waits until the receiver gets the [FileUploadState::Finished] state. Returns false if the
+/// state wasn't received within the timeout.
+pub async fn wait_for_finished(receiver: &mut FileProgressReceiver, timeout: Duration) -> bool {
+  tokio::time::timeout(timeout, async {
+    while let Ok(state) = receiver.recv().await {
+      if let FileUploadState::Finished { .. } = state {
+        return true;
+      }
+    }
+    false
+  })
+  .await
+  .unwrap_or(false)
+}
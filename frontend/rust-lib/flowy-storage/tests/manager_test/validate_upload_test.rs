@@ -0,0 +1,143 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use flowy_storage::error::StorageError;
+use flowy_storage::manager::UploadOutcome;
+use flowy_storage::sqlite_sql::select_upload_file;
+use std::time::Duration;
+
+fn temp_file_count(test: &StorageTest) -> usize {
+  let cache_dir = test.manager.user_temp_dir();
+  std::fs::read_dir(cache_dir)
+    .map(|entries| entries.count())
+    .unwrap_or(0)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn validate_upload_has_no_side_effects_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "validate_upload_test";
+  let file_path = create_temp_file(1024, "txt");
+
+  let validation = test
+    .manager
+    .validate_upload(&workspace_id, parent_dir, file_path.to_str().unwrap())
+    .await
+    .unwrap();
+  assert_eq!(validation.outcome, UploadOutcome::WouldUpload);
+  let file_id = validation.file_id.unwrap();
+  assert_eq!(
+    validation.url.unwrap(),
+    MockStorageCloudService::object_url(&workspace_id, parent_dir, &file_id)
+  );
+
+  // Neither a record, a temp file, nor a task was created.
+  assert!(select_upload_file(
+    &mut test.db_connection(),
+    &workspace_id,
+    parent_dir,
+    &file_id
+  )
+  .unwrap()
+  .is_none());
+  assert_eq!(temp_file_count(&test), 0);
+  assert!(test
+    .manager
+    .upload_detail(&workspace_id, parent_dir, &file_id)
+    .is_none());
+  assert!(test.cloud_service.objects.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn validate_upload_classifies_existing_uploads_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let parent_dir = "validate_upload_test";
+  test.manager.update_network_reachable(false);
+
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      parent_dir,
+      file_path.to_str().unwrap(),
+      false,
+    )
+    .await
+    .unwrap();
+  let validation = test
+    .manager
+    .validate_upload(&workspace_id, parent_dir, file_path.to_str().unwrap())
+    .await
+    .unwrap();
+  assert_eq!(validation.outcome, UploadOutcome::InProgress);
+  assert_eq!(validation.file_id.unwrap(), created_upload.file_id);
+
+  test.manager.update_network_reachable(true);
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  let validation = test
+    .manager
+    .validate_upload(&workspace_id, parent_dir, file_path.to_str().unwrap())
+    .await
+    .unwrap();
+  assert_eq!(validation.outcome, UploadOutcome::AlreadyUploaded);
+
+  // The same file in another parent dir would be uploaded again.
+  let validation = test
+    .manager
+    .validate_upload(&workspace_id, "other_dir", file_path.to_str().unwrap())
+    .await
+    .unwrap();
+  assert_eq!(validation.outcome, UploadOutcome::WouldUpload);
+}
+
+#[tokio::test]
+async fn validate_upload_rejects_invalid_upload_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  let file_path = create_temp_file(1024, "txt");
+
+  let validation = test
+    .manager
+    .validate_upload(&workspace_id, "", file_path.to_str().unwrap())
+    .await
+    .unwrap();
+  assert_eq!(
+    validation.outcome,
+    UploadOutcome::Rejected(StorageError::EmptyParentDir)
+  );
+  assert!(validation.file_id.is_none());
+
+  let missing_path = file_path.with_extension("missing");
+  let validation = test
+    .manager
+    .validate_upload(
+      &workspace_id,
+      "validate_upload_test",
+      missing_path.to_str().unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(
+    validation.outcome,
+    UploadOutcome::Rejected(StorageError::FileMissing(
+      missing_path.to_str().unwrap().to_string()
+    ))
+  );
+
+  test.manager.disable_storage_write_access();
+  let validation = test
+    .manager
+    .validate_upload(
+      &workspace_id,
+      "validate_upload_test",
+      file_path.to_str().unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(
+    validation.outcome,
+    UploadOutcome::Rejected(StorageError::OverQuota)
+  );
+}
@@ -0,0 +1,159 @@
+use crate::util::{MockStorageCloudService, StorageTest};
+use bytes::Bytes;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::StorageIssue;
+use flowy_storage::sqlite_sql::{
+  insert_upload_file, insert_upload_part, select_upload_file, select_upload_parts,
+  UploadFilePartTable, UploadFileTable,
+};
+use flowy_storage_pub::chunked_byte::MIN_CHUNK_SIZE;
+use std::time::Duration;
+
+const PARENT_DIR: &str = "verify_storage_test";
+
+fn upload_record(
+  workspace_id: &str,
+  file_id: &str,
+  local_file_path: &str,
+  is_finish: bool,
+) -> UploadFileTable {
+  UploadFileTable {
+    workspace_id: workspace_id.to_string(),
+    file_id: file_id.to_string(),
+    parent_dir: PARENT_DIR.to_string(),
+    local_file_path: local_file_path.to_string(),
+    content_type: "text/plain".to_string(),
+    chunk_size: MIN_CHUNK_SIZE as i32,
+    num_chunk: 1,
+    upload_id: "".to_string(),
+    created_at: 0,
+    is_finish,
+    metadata: "".to_string(),
+    storage_class: "".to_string(),
+    seq: 0,
+    overwrite: true,
+    linked_parent_dir: String::new(),
+    source_file_path: String::new(),
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn verify_storage_reports_inconsistencies_test() {
+  let test = StorageTest::new_with_config(
+    StorageManagerConfig::default()
+      .reconcile_interval(None)
+      .reconcile_batch_size(2)
+      .reconcile_request_interval(Duration::ZERO),
+  )
+  .await;
+  let workspace_id = test.workspace_id();
+  test.manager.update_network_reachable(false);
+  let cache_dir = test.manager.user_temp_dir();
+  std::fs::create_dir_all(&cache_dir).unwrap();
+
+  // Consistent records: a finished upload whose object exists, and an unfinished upload whose
+  // temp file exists.
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(&workspace_id, "uploaded", "", true),
+  )
+  .unwrap();
+  test.cloud_service.objects.insert(
+    MockStorageCloudService::object_url(&workspace_id, PARENT_DIR, "uploaded"),
+    Bytes::from_static(b"uploaded"),
+  );
+  let pending_path = cache_dir.join("pending.txt");
+  std::fs::write(&pending_path, b"pending").unwrap();
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(
+      &workspace_id,
+      "pending",
+      pending_path.to_str().unwrap(),
+      false,
+    ),
+  )
+  .unwrap();
+
+  // A finished upload whose object was deleted on the server.
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(&workspace_id, "deleted_on_server", "", true),
+  )
+  .unwrap();
+  // An unfinished upload whose file is gone.
+  insert_upload_file(
+    test.db_connection(),
+    &upload_record(&workspace_id, "file_missing", "not_exist_file.txt", false),
+  )
+  .unwrap();
+  // Parts of an upload without record.
+  insert_upload_part(
+    test.db_connection(),
+    &UploadFilePartTable {
+      upload_id: "orphaned_upload".to_string(),
+      e_tag: "e_tag".to_string(),
+      part_num: 1,
+      checksum: String::new(),
+    },
+  )
+  .unwrap();
+  // A temp file without record.
+  let orphaned_path = cache_dir.join("orphaned.txt");
+  std::fs::write(&orphaned_path, b"orphaned").unwrap();
+
+  let report = test.manager.verify_storage().await.unwrap();
+  assert_eq!(report.checked_records, 4);
+  assert_eq!(report.unchecked_objects, 0);
+  assert!(!report.is_healthy());
+  let expected = vec![
+    StorageIssue::MissingObject {
+      workspace_id: workspace_id.clone(),
+      parent_dir: PARENT_DIR.to_string(),
+      file_id: "deleted_on_server".to_string(),
+    },
+    StorageIssue::MissingLocalFile {
+      workspace_id: workspace_id.clone(),
+      parent_dir: PARENT_DIR.to_string(),
+      file_id: "file_missing".to_string(),
+      local_file_path: "not_exist_file.txt".to_string(),
+    },
+    StorageIssue::OrphanedParts {
+      upload_id: "orphaned_upload".to_string(),
+    },
+    StorageIssue::OrphanedTempFile {
+      path: orphaned_path.to_str().unwrap().to_string(),
+    },
+  ];
+  assert_eq!(report.issues.len(), expected.len());
+  for issue in expected {
+    assert!(report.issues.contains(&issue), "missing {:?}", issue);
+  }
+
+  // Nothing was fixed.
+  for file_id in ["deleted_on_server", "file_missing"] {
+    assert!(select_upload_file(
+      &mut test.db_connection(),
+      &workspace_id,
+      PARENT_DIR,
+      file_id
+    )
+    .unwrap()
+    .is_some());
+  }
+  assert_eq!(
+    select_upload_parts(&mut test.db_connection(), "orphaned_upload")
+      .unwrap()
+      .len(),
+    1
+  );
+  assert!(orphaned_path.exists());
+}
+
+#[tokio::test]
+async fn verify_empty_storage_test() {
+  let test = StorageTest::new().await;
+  let report = test.manager.verify_storage().await.unwrap();
+  assert!(report.is_healthy());
+  assert_eq!(report.checked_records, 0);
+}
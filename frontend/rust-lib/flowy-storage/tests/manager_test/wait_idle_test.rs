@@ -0,0 +1,110 @@
+use crate::util::{create_temp_file, MockStorageCloudService, StorageTest};
+use flowy_storage::manager::IdleOutcome;
+use flowy_storage::pause::PauseReasons;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+const PARENT_DIR: &str = "wait_idle_test";
+
+async fn create_upload(test: &StorageTest) -> String {
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, _) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &test.workspace_id(),
+      PARENT_DIR,
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  created_upload.file_id
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn wait_until_idle_after_uploads_test() {
+  let test = StorageTest::new().await;
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::ZERO).await,
+    IdleOutcome::Idle
+  );
+
+  let mut file_ids = vec![];
+  for _ in 0..3 {
+    file_ids.push(create_upload(&test).await);
+  }
+  let started_at = Instant::now();
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Idle
+  );
+  // It returns as soon as the uploads settled, not at the timeout.
+  assert!(started_at.elapsed() < Duration::from_secs(10));
+  for file_id in file_ids {
+    let url = MockStorageCloudService::object_url(&test.workspace_id(), PARENT_DIR, &file_id);
+    assert!(test.cloud_service.objects.contains_key(&url));
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn wait_until_idle_with_paused_uploads_test() {
+  let test = StorageTest::new().await;
+  test.manager.update_network_reachable(false);
+  create_upload(&test).await;
+  create_upload(&test).await;
+
+  // The paused uploads don't settle, it doesn't wait for the timeout.
+  let started_at = Instant::now();
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Paused {
+      queued: 2,
+      reasons: PauseReasons::NETWORK_UNREACHABLE,
+    }
+  );
+  assert!(started_at.elapsed() < Duration::from_secs(10));
+
+  test.manager.update_network_reachable(true);
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Idle
+  );
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    2
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn wait_until_idle_timeout_test() {
+  let test = StorageTest::new().await;
+  test
+    .cloud_service
+    .set_part_delay(Some(Duration::from_secs(2)));
+  create_upload(&test).await;
+
+  // Wait for the upload to start.
+  while test.manager.wait_until_idle(Duration::ZERO).await
+    != (IdleOutcome::TimedOut {
+      queued: 0,
+      running: 1,
+    })
+  {
+    tokio::time::sleep(Duration::from_millis(10)).await;
+  }
+  assert_eq!(
+    test
+      .manager
+      .wait_until_idle(Duration::from_millis(100))
+      .await,
+    IdleOutcome::TimedOut {
+      queued: 0,
+      running: 1,
+    }
+  );
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Idle
+  );
+}
@@ -0,0 +1,381 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageUserService};
+use bytes::Bytes;
+use flowy_sqlite::PoolConfig;
+use flowy_storage::config::StorageManagerConfig;
+use flowy_storage::manager::{StorageManager, StorageUserService};
+use flowy_storage::webdav::WebDavCloudService;
+use flowy_storage_pub::cloud::{ObjectValue, StorageCloudService};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+const MB: usize = 1024 * 1024;
+const ROOT: &str = "/dav";
+
+enum Resource {
+  Collection,
+  File {
+    content: Vec<u8>,
+    content_type: String,
+  },
+}
+
+struct Response {
+  status: u16,
+  headers: Vec<(&'static str, String)>,
+  body: Vec<u8>,
+}
+
+impl Response {
+  fn status(status: u16) -> Self {
+    Self {
+      status,
+      headers: vec![],
+      body: vec![],
+    }
+  }
+}
+
+/// A WebDAV server keeping the resources in memory, it implements the methods the
+/// [WebDavCloudService] sends.
+#[derive(Default)]
+struct LocalWebDavServer {
+  resources: Mutex<BTreeMap<String, Resource>>,
+  /// The method and the path of the handled requests.
+  requests: Mutex<Vec<(String, String)>>,
+}
+
+impl LocalWebDavServer {
+  /// Starts the server, returns it along with its base url.
+  async fn start() -> (Arc<Self>, Url) {
+    let server = Arc::new(Self::default());
+    server
+      .resources
+      .lock()
+      .unwrap()
+      .insert(ROOT.to_string(), Resource::Collection);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = Url::parse(&format!(
+      "http://{}{}/",
+      listener.local_addr().unwrap(),
+      ROOT
+    ))
+    .unwrap();
+    let cloned_server = server.clone();
+    tokio::spawn(async move {
+      while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(cloned_server.clone().serve(stream));
+      }
+    });
+    (server, base_url)
+  }
+
+  fn paths(&self) -> Vec<String> {
+    self.resources.lock().unwrap().keys().cloned().collect()
+  }
+
+  fn request_count(&self, method: &str) -> usize {
+    let requests = self.requests.lock().unwrap();
+    requests.iter().filter(|(m, _)| m == method).count()
+  }
+
+  async fn serve(self: Arc<Self>, stream: TcpStream) {
+    let mut reader = BufReader::new(stream);
+    loop {
+      let mut request_line = String::new();
+      if reader.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+        return;
+      }
+      let mut parts = request_line.split_whitespace();
+      let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return;
+      };
+      let (method, target) = (method.to_string(), target.to_string());
+
+      let mut headers = HashMap::new();
+      loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+          break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+          headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+      }
+
+      let mut body = vec![];
+      if headers.get("transfer-encoding").map(String::as_str) == Some("chunked") {
+        loop {
+          let mut size = String::new();
+          reader.read_line(&mut size).await.unwrap();
+          let size = usize::from_str_radix(size.trim(), 16).unwrap();
+          let mut chunk = vec![0; size + 2];
+          reader.read_exact(&mut chunk).await.unwrap();
+          if size == 0 {
+            break;
+          }
+          body.extend_from_slice(&chunk[..size]);
+        }
+      } else if let Some(len) = headers.get("content-length") {
+        body.resize(len.parse().unwrap(), 0);
+        reader.read_exact(&mut body).await.unwrap();
+      }
+
+      let response = self.handle(&method, &target, &headers, body);
+      let mut head = format!(
+        "HTTP/1.1 {} WebDAV\r\nContent-Length: {}\r\n",
+        response.status,
+        response.body.len()
+      );
+      for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+      }
+      head.push_str("\r\n");
+      let stream = reader.get_mut();
+      stream.write_all(head.as_bytes()).await.unwrap();
+      stream.write_all(&response.body).await.unwrap();
+    }
+  }
+
+  fn handle(
+    &self,
+    method: &str,
+    target: &str,
+    headers: &HashMap<String, String>,
+    body: Vec<u8>,
+  ) -> Response {
+    let path = target.trim_end_matches('/').to_string();
+    self
+      .requests
+      .lock()
+      .unwrap()
+      .push((method.to_string(), path.clone()));
+    let mut resources = self.resources.lock().unwrap();
+    let has_parent = |resources: &BTreeMap<String, Resource>, path: &str| {
+      let parent = path
+        .rsplit_once('/')
+        .map(|(parent, _)| parent)
+        .unwrap_or("");
+      matches!(resources.get(parent), Some(Resource::Collection))
+    };
+
+    match method {
+      "MKCOL" if resources.contains_key(&path) => Response::status(405),
+      "MKCOL" if !has_parent(&resources, &path) => Response::status(409),
+      "MKCOL" => {
+        resources.insert(path, Resource::Collection);
+        Response::status(201)
+      },
+      "PUT" if !has_parent(&resources, &path) => Response::status(409),
+      "PUT" => {
+        let e_tag = format!("\"{}\"", body.len());
+        let content_type = headers
+          .get("content-type")
+          .cloned()
+          .unwrap_or_else(|| "application/octet-stream".to_string());
+        resources.insert(
+          path,
+          Resource::File {
+            content: body,
+            content_type,
+          },
+        );
+        let mut response = Response::status(201);
+        response.headers.push(("ETag", e_tag));
+        response
+      },
+      "GET" | "HEAD" => match resources.get(&path) {
+        None => Response::status(404),
+        Some(_) if method == "HEAD" => Response::status(200),
+        Some(Resource::Collection) => Response::status(200),
+        Some(Resource::File {
+          content,
+          content_type,
+        }) => {
+          let total = content.len();
+          let range = headers
+            .get("range")
+            .and_then(|range| range.strip_prefix("bytes="))
+            .and_then(|range| range.split_once('-'))
+            .map(|(start, end)| {
+              (
+                start.parse::<usize>().unwrap(),
+                end.parse::<usize>().unwrap(),
+              )
+            });
+          let mut response = match range {
+            Some((start, _)) if start >= total => {
+              let mut response = Response::status(416);
+              response
+                .headers
+                .push(("Content-Range", format!("bytes */{}", total)));
+              return response;
+            },
+            Some((start, end)) => {
+              let end = end.min(total - 1);
+              let mut response = Response::status(206);
+              response.headers.push((
+                "Content-Range",
+                format!("bytes {}-{}/{}", start, end, total),
+              ));
+              response.body = content[start..=end].to_vec();
+              response
+            },
+            None => {
+              let mut response = Response::status(200);
+              response.body = content.clone();
+              response
+            },
+          };
+          response
+            .headers
+            .push(("Content-Type", content_type.clone()));
+          response
+        },
+      },
+      "DELETE" if !resources.contains_key(&path) => Response::status(404),
+      "DELETE" => {
+        let prefix = format!("{}/", path);
+        resources.retain(|key, _| key != &path && !key.starts_with(&prefix));
+        Response::status(204)
+      },
+      "MOVE" => {
+        let destination = Url::parse(&headers["destination"]).unwrap();
+        let destination = destination.path().trim_end_matches('/').to_string();
+        if !has_parent(&resources, &destination) {
+          return Response::status(409);
+        }
+        match resources.remove(&path) {
+          Some(resource) => {
+            resources.insert(destination, resource);
+            Response::status(201)
+          },
+          None => Response::status(404),
+        }
+      },
+      _ => Response::status(405),
+    }
+  }
+}
+
+#[tokio::test]
+async fn webdav_object_test() {
+  let (server, base_url) = LocalWebDavServer::start().await;
+  let service = WebDavCloudService::new(base_url).unwrap();
+  let url = service
+    .get_object_url_v1("workspace", "docs/my files", "file.txt")
+    .await
+    .unwrap();
+  assert_eq!(
+    service.parse_object_url_v1(&url).await,
+    Some((
+      "workspace".to_string(),
+      "docs/my files".to_string(),
+      "file.txt".to_string()
+    ))
+  );
+  assert!(!service
+    .object_exists("workspace", "docs/my files", "file.txt")
+    .await
+    .unwrap());
+
+  // The collections of the object are created on demand.
+  service
+    .put_object(
+      url.clone(),
+      ObjectValue {
+        raw: Bytes::from_static(b"hello webdav"),
+        mime: mime::TEXT_PLAIN,
+      },
+    )
+    .await
+    .unwrap();
+  assert!(service
+    .object_exists("workspace", "docs/my files", "file.txt")
+    .await
+    .unwrap());
+  let object = service.get_object(url.clone()).await.unwrap();
+  assert_eq!(object.raw.as_ref(), b"hello webdav");
+  assert_eq!(object.mime, mime::TEXT_PLAIN);
+
+  let range = service.get_object_range(url.clone(), 6..100).await.unwrap();
+  assert_eq!(range.raw.as_ref(), b"webdav");
+  assert_eq!(range.total_size, 12);
+
+  service.delete_object(&url).await.unwrap();
+  assert!(service.get_object(url.clone()).await.is_err());
+  // Deleting a missing object is fine.
+  service.delete_object(&url).await.unwrap();
+  assert_eq!(server.request_count("DELETE"), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn webdav_multipart_upload_test() {
+  let (server, base_url) = LocalWebDavServer::start().await;
+  let service = Arc::new(WebDavCloudService::new(base_url).unwrap());
+  let user_service = Arc::new(MockStorageUserService::new(PoolConfig::default()));
+  let workspace_id = user_service.workspace_id().unwrap();
+  let manager = StorageManager::new_with_config(
+    service.clone(),
+    user_service,
+    StorageManagerConfig::default().chunk_size(5 * MB),
+  );
+
+  // Three parts, concatenated into the object when the upload is completed.
+  let file_path = create_temp_file(12 * MB, "txt");
+  let (created_upload, receiver) = manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "webdav_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  let object = service.get_object(created_upload.url).await.unwrap();
+  assert_eq!(object.raw.as_ref(), std::fs::read(&file_path).unwrap());
+  // The parts are removed along with the upload collection.
+  let uploads = format!("{}/{}/.uploads/", ROOT, workspace_id);
+  assert!(!server.paths().iter().any(|path| path.starts_with(&uploads)));
+  assert_eq!(server.request_count("MOVE"), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn webdav_single_part_upload_test() {
+  let (server, base_url) = LocalWebDavServer::start().await;
+  let service = Arc::new(WebDavCloudService::new(base_url).unwrap());
+  let user_service = Arc::new(MockStorageUserService::new(PoolConfig::default()));
+  let workspace_id = user_service.workspace_id().unwrap();
+  let manager = StorageManager::new_with_config(
+    service.clone(),
+    user_service,
+    StorageManagerConfig::default(),
+  );
+
+  let file_path = create_temp_file(1024, "txt");
+  let (created_upload, receiver) = manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "webdav_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+
+  // The single part is moved to the object, its bytes are sent once.
+  let object = service.get_object(created_upload.url).await.unwrap();
+  assert_eq!(object.raw.as_ref(), std::fs::read(&file_path).unwrap());
+  assert_eq!(server.request_count("MOVE"), 1);
+  assert_eq!(server.request_count("PUT"), 1);
+}
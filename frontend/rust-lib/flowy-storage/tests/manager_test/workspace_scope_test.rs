@@ -0,0 +1,129 @@
+use crate::util::{create_temp_file, wait_for_finished, MockStorageCloudService, StorageTest};
+use flowy_error::ErrorCode;
+use flowy_storage_pub::storage::FileUploadState;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn same_file_in_two_workspaces_test() {
+  let test = StorageTest::new().await;
+  let parent_dir = "workspace_scope_test";
+  let file_path = create_temp_file(1024, "txt");
+  let file_path = file_path.to_str().unwrap();
+
+  let workspace_a = test.workspace_id();
+  let (upload_a, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_a, parent_dir, file_path, true)
+    .await
+    .unwrap();
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert!(matches!(
+    test
+      .manager
+      .get_file_state(parent_dir, &upload_a.file_id)
+      .await,
+    Some(FileUploadState::Finished { .. })
+  ));
+
+  // The same content in another workspace shares the file id, but the completion of the upload in
+  // the first workspace doesn't satisfy the second one.
+  let workspace_b = uuid::Uuid::new_v4().to_string();
+  test.user_service.set_workspace_id(&workspace_b);
+  assert!(test
+    .manager
+    .subscribe_file_state(parent_dir, &upload_a.file_id)
+    .await
+    .unwrap()
+    .is_some());
+  assert!(test
+    .manager
+    .get_file_state(parent_dir, &upload_a.file_id)
+    .await
+    .is_none());
+
+  let (upload_b, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_b, parent_dir, file_path, true)
+    .await
+    .unwrap();
+  assert_eq!(upload_a.file_id, upload_b.file_id);
+  assert_ne!(upload_a.url, upload_b.url);
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert_eq!(
+    test
+      .cloud_service
+      .complete_upload_count
+      .load(Ordering::SeqCst),
+    2
+  );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn switched_workspace_fails_until_initialized_test() {
+  let test = StorageTest::new().await;
+  let parent_dir = "workspace_scope_test";
+  let workspace_a = test.workspace_id();
+  test.manager.initialize(&workspace_a).await.unwrap();
+  test.manager.update_network_reachable(false);
+  let file_path = create_temp_file(1024, "txt");
+  let (upload_a, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(&workspace_a, parent_dir, file_path.to_str().unwrap(), false)
+    .await
+    .unwrap();
+
+  // The active workspace changes before the storage is initialized for it.
+  let workspace_b = uuid::Uuid::new_v4().to_string();
+  test.user_service.set_workspace_id(&workspace_b);
+  let err = test
+    .manager
+    .subscribe_file_state(parent_dir, &upload_a.file_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::StorageWorkspaceChanged);
+  let err = test
+    .manager
+    .upload_manifest(parent_dir, &upload_a.file_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::StorageWorkspaceChanged);
+  assert!(test
+    .manager
+    .get_file_state(parent_dir, &upload_a.file_id)
+    .await
+    .is_none());
+  assert!(test.manager.query_file_state(&upload_a.url).await.is_none());
+
+  // Once initialized, the operations target the new workspace.
+  test.manager.initialize(&workspace_b).await.unwrap();
+  assert!(test
+    .manager
+    .subscribe_file_state(parent_dir, &upload_a.file_id)
+    .await
+    .unwrap()
+    .is_some());
+
+  // The queued upload of the previous workspace still uploads to it.
+  test.manager.update_network_reachable(true);
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+  assert!(test
+    .cloud_service
+    .objects
+    .contains_key(&MockStorageCloudService::object_url(
+      &workspace_a,
+      parent_dir,
+      &upload_a.file_id
+    )));
+  assert!(!test
+    .cloud_service
+    .objects
+    .contains_key(&MockStorageCloudService::object_url(
+      &workspace_b,
+      parent_dir,
+      &upload_a.file_id
+    )));
+}
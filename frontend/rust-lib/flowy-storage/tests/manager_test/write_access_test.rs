@@ -0,0 +1,83 @@
+use crate::util::{create_temp_file, wait_for_finished, StorageTest};
+use bytes::Bytes;
+use flowy_notification::entities::SubscribeObject;
+use flowy_notification::{register_notification_sender, NotificationSender};
+use flowy_storage::entities::StorageWriteAccessPB;
+use flowy_storage::manager::IdleOutcome;
+use flowy_storage::notification::StorageNotification;
+use flowy_storage::pause::PauseReasons;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Clone, Default)]
+struct WriteAccessNotificationCollector {
+  values: Arc<Mutex<Vec<bool>>>,
+}
+
+impl NotificationSender for WriteAccessNotificationCollector {
+  fn send_subject(&self, subject: SubscribeObject) -> Result<(), String> {
+    if subject.ty == StorageNotification::StorageWriteAccessChanged as i32 {
+      let payload = StorageWriteAccessPB::try_from(Bytes::from(subject.payload.unwrap()))
+        .map_err(|err| err.to_string())?;
+      self.values.lock().unwrap().push(payload.is_enabled);
+    }
+    Ok(())
+  }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn toggle_storage_write_access_test() {
+  let collector = WriteAccessNotificationCollector::default();
+  register_notification_sender(collector.clone());
+  let test = StorageTest::new().await;
+  assert!(test.manager.is_storage_write_enabled());
+
+  test.manager.disable_storage_write_access();
+  assert!(!test.manager.is_storage_write_enabled());
+  test.manager.enable_storage_write_access();
+  assert!(test.manager.is_storage_write_enabled());
+
+  // The notifications are global, other tests may toggle the access of their own manager.
+  let values = collector.values.lock().unwrap().clone();
+  let disabled = values.iter().position(|value| !value).unwrap();
+  assert!(values[disabled..].contains(&true));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn enable_storage_write_access_while_offline_test() {
+  let test = StorageTest::new().await;
+  let workspace_id = test.workspace_id();
+  test.manager.update_network_reachable(false);
+  test.manager.disable_storage_write_access();
+  test.manager.enable_storage_write_access();
+  assert!(test.manager.is_storage_write_enabled());
+
+  // The network is still unreachable, the upload waits.
+  let file_path = create_temp_file(1024, "txt");
+  let (_, receiver) = test
+    .manager
+    .storage_service()
+    .create_upload(
+      &workspace_id,
+      "write_access_test",
+      file_path.to_str().unwrap(),
+      true,
+    )
+    .await
+    .unwrap();
+  assert_eq!(
+    test.manager.wait_until_idle(Duration::from_secs(30)).await,
+    IdleOutcome::Paused {
+      queued: 1,
+      reasons: PauseReasons::NETWORK_UNREACHABLE,
+    }
+  );
+  assert_eq!(
+    test.cloud_service.upload_part_count.load(Ordering::SeqCst),
+    0
+  );
+
+  test.manager.update_network_reachable(true);
+  assert!(wait_for_finished(&mut receiver.unwrap(), Duration::from_secs(30)).await);
+}
@@ -81,6 +81,7 @@ async fn test_upload_part_test() {
     upload_id: upload_id.clone(),
     e_tag: "1".to_string(),
     part_num: 1,
+    checksum: String::new(),
   };
   let conn = db.get_connection().unwrap();
   insert_upload_part(conn, &part).unwrap();
@@ -90,6 +91,7 @@ async fn test_upload_part_test() {
     upload_id: upload_id.clone(),
     e_tag: "2".to_string(),
     part_num: 2,
+    checksum: String::new(),
   };
   let conn = db.get_connection().unwrap();
   insert_upload_part(conn, &part).unwrap();
@@ -186,5 +188,11 @@ pub async fn create_upload_file_record(
     num_chunk: num_chunk as i32,
     created_at: chrono::Utc::now().timestamp(),
     is_finish: false,
+    metadata: "".to_string(),
+    storage_class: "".to_string(),
+    seq: 0,
+    overwrite: true,
+    linked_parent_dir: String::new(),
+    source_file_path: String::new(),
   }
 }